@@ -0,0 +1,978 @@
+//! Zip and tar archives: creating them, listing and extracting their members.
+//!
+//! Everything here does blocking I/O and is meant to run on a worker thread
+//! through the operations panel.
+
+use crate::{operations::OperationContext, templates};
+use gtk4::{gio, glib};
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, Read, Seek, Write},
+    os::unix::fs::{PermissionsExt, symlink},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    pub const ALL: [ArchiveFormat; 5] = [
+        ArchiveFormat::Zip,
+        ArchiveFormat::Tar,
+        ArchiveFormat::TarGz,
+        ArchiveFormat::TarXz,
+        ArchiveFormat::TarZst,
+    ];
+
+    /// Extension given to new archives, with its leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => ".zip",
+            ArchiveFormat::Tar => ".tar",
+            ArchiveFormat::TarGz => ".tar.gz",
+            ArchiveFormat::TarXz => ".tar.xz",
+            ArchiveFormat::TarZst => ".tar.zst",
+        }
+    }
+
+    /// Recognizes an archive by its name.
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        let format = if name.ends_with(".zip") {
+            ArchiveFormat::Zip
+        } else if name.ends_with(".tar") {
+            ArchiveFormat::Tar
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            ArchiveFormat::TarGz
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            ArchiveFormat::TarXz
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            ArchiveFormat::TarZst
+        } else {
+            return None;
+        };
+        Some(format)
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    ArchiveFormat::from_path(path).is_some()
+}
+
+/// The archive name without its archive extension, e.g. "photos" for
+/// "photos.tar.gz".
+pub fn archive_stem(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let lower = name.to_lowercase();
+    [".tar.gz", ".tar.xz", ".tar.zst", ".tgz", ".txz", ".tzst", ".zip", ".tar"]
+        .iter()
+        .find(|extension| lower.ends_with(*extension) && lower.len() > extension.len())
+        .map(|extension| name[..name.len() - extension.len()].to_string())
+        .unwrap_or(name)
+}
+
+/// A member of an archive, as found in its index.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Path inside the archive, `/`-separated and without leading or trailing
+    /// slashes.
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Modification time in seconds since the epoch, 0 when unknown.
+    pub modified: i64,
+}
+
+/// What to do with a member whose destination already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    /// Extracts next to the existing file as "name (2).ext".
+    KeepBoth,
+}
+
+/// Turns a member name into a path relative to the destination, or `None`
+/// when the name is absolute or climbs out of it with `..`.
+pub fn safe_member_path(name: &str) -> Option<PathBuf> {
+    if name.starts_with('/') || name.contains('\0') {
+        return None;
+    }
+
+    let mut path = PathBuf::new();
+    for part in name.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            _ => path.push(part),
+        }
+    }
+
+    // A drive letter or the like, which `push` would treat as a root
+    let relative = path.components().all(|c| matches!(c, Component::Normal(_)));
+    (relative && !path.as_os_str().is_empty()).then_some(path)
+}
+
+/// Whether a symlink extracted at `member` pointing to `target` stays inside
+/// the destination.
+fn symlink_stays_inside(member: &Path, target: &str) -> bool {
+    if target.is_empty() || target.starts_with('/') {
+        return false;
+    }
+
+    let mut depth = member.components().count() - 1;
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if depth == 0 => return false,
+            ".." => depth -= 1,
+            _ => depth += 1,
+        }
+    }
+    true
+}
+
+/// Lists the members of `archive`. Unsafe member names are left out.
+pub fn list_entries(archive: &Path) -> io::Result<Vec<ArchiveEntry>> {
+    let format = ArchiveFormat::from_path(archive).ok_or_else(|| not_an_archive(archive))?;
+    let mut entries = Vec::new();
+
+    if format == ArchiveFormat::Zip {
+        let mut zip = zip::ZipArchive::new(fs::File::open(archive)?).map_err(io::Error::other)?;
+        for i in 0..zip.len() {
+            let member = zip.by_index(i).map_err(io::Error::other)?;
+            if let Some(path) = safe_member_path(member.name()) {
+                entries.push(ArchiveEntry {
+                    path: path_string(&path),
+                    is_dir: member.is_dir(),
+                    size: member.size(),
+                    modified: member.last_modified().map(zip_time_to_unix).unwrap_or(0),
+                });
+            }
+        }
+    } else {
+        let mut tar = tar::Archive::new(tar_reader(archive, format, None)?);
+        for member in tar.entries()? {
+            let member = member?;
+            if let Some(path) = safe_member_path(&String::from_utf8_lossy(&member.path_bytes())) {
+                let header = member.header();
+                entries.push(ArchiveEntry {
+                    path: path_string(&path),
+                    is_dir: header.entry_type().is_dir(),
+                    size: header.size().unwrap_or(0),
+                    modified: header.mtime().unwrap_or(0) as i64,
+                });
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Outcome of an extraction that went through.
+#[derive(Debug, Default)]
+pub struct ExtractReport {
+    /// Where the members ended up: the destination, or the single top-level
+    /// member when extracting here.
+    pub extracted: Vec<PathBuf>,
+    /// Members left out, with the reason.
+    pub skipped: Vec<String>,
+}
+
+/// Extracts `archive` next to itself. A single top-level member lands
+/// directly beside the archive, anything else goes into a new folder named
+/// after it.
+pub fn extract_here(archive: &Path, context: &OperationContext) -> io::Result<ExtractReport> {
+    let parent = archive.parent().ok_or_else(|| not_an_archive(archive))?;
+    let entries = list_entries(archive)?;
+
+    let top_level: BTreeSet<&str> =
+        entries.iter().filter_map(|entry| entry.path.split('/').next()).collect();
+    let single = match top_level.iter().next() {
+        Some(name) if top_level.len() == 1 && fs::symlink_metadata(parent.join(name)).is_err() => {
+            Some(parent.join(name))
+        }
+        _ => None,
+    };
+
+    match single {
+        Some(extracted) => {
+            let mut report = extract(archive, parent, ConflictPolicy::Skip, context)?;
+            report.extracted = vec![extracted];
+            Ok(report)
+        }
+        None => {
+            let destination = unique_path(&parent.join(archive_stem(archive)));
+            fs::create_dir(&destination)?;
+            let mut report = extract(archive, &destination, ConflictPolicy::Skip, context)?;
+            report.extracted = vec![destination];
+            Ok(report)
+        }
+    }
+}
+
+/// Members of `archive`, folders aside, that already exist in `destination`.
+pub fn conflicts(archive: &Path, destination: &Path) -> io::Result<Vec<String>> {
+    Ok(list_entries(archive)?
+        .into_iter()
+        .filter(|entry| !entry.is_dir)
+        .filter(|entry| fs::symlink_metadata(destination.join(&entry.path)).is_ok())
+        .map(|entry| entry.path)
+        .collect())
+}
+
+/// Extracts every member of `archive` into `destination`, which must exist.
+///
+/// Members with absolute names or `..` components are skipped, as are
+/// symlinks pointing outside `destination` and members that would be written
+/// through a symlink.
+pub fn extract(
+    archive: &Path,
+    destination: &Path,
+    policy: ConflictPolicy,
+    context: &OperationContext,
+) -> io::Result<ExtractReport> {
+    extract_entries(archive, destination, policy, Some(context), None)
+}
+
+/// Extracts the single `member` of `archive`, with everything below it when
+/// it is a folder, into `destination`. Returns the extracted file, renamed
+/// to "name (2)" and so on when the name is taken.
+pub fn extract_member(
+    archive: &Path,
+    member: &str,
+    destination: &Path,
+    context: Option<&OperationContext>,
+) -> io::Result<PathBuf> {
+    let name = member.rsplit('/').next().filter(|name| !name.is_empty());
+    let name = name.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no member given"))?;
+    let target = unique_path(&destination.join(name));
+    let renamed = PathBuf::from(target.file_name().unwrap_or_default());
+
+    let report = extract_entries(
+        archive,
+        destination,
+        ConflictPolicy::Skip,
+        context,
+        Some((member.to_string(), renamed)),
+    )?;
+
+    if fs::symlink_metadata(&target).is_err() {
+        let reason = report.skipped.into_iter().next();
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            reason.unwrap_or_else(|| format!("\"{}\" is not in the archive", member)),
+        ));
+    }
+    Ok(target)
+}
+
+fn extract_entries(
+    archive: &Path,
+    destination: &Path,
+    policy: ConflictPolicy,
+    context: Option<&OperationContext>,
+    member: Option<(String, PathBuf)>,
+) -> io::Result<ExtractReport> {
+    let format = ArchiveFormat::from_path(archive).ok_or_else(|| not_an_archive(archive))?;
+    let mut extractor = Extractor {
+        root: destination.to_path_buf(),
+        policy,
+        context,
+        member,
+        report: ExtractReport { extracted: vec![destination.to_path_buf()], skipped: Vec::new() },
+    };
+
+    if format == ArchiveFormat::Zip {
+        let mut zip = zip::ZipArchive::new(fs::File::open(archive)?).map_err(io::Error::other)?;
+        if let Some(context) = context {
+            context.set_total(zip.len() as u64);
+        }
+
+        for i in 0..zip.len() {
+            if context.is_some_and(OperationContext::is_cancelled) {
+                return Err(cancelled());
+            }
+            let mut member = zip.by_index(i).map_err(io::Error::other)?;
+            let name = member.name().to_string();
+            if let Some(context) = context {
+                context.advance(1, &name);
+            }
+
+            let Some(path) = extractor.target_path(&name) else {
+                continue;
+            };
+            let mode = member.unix_mode();
+            let modified = member.last_modified().map(zip_time_to_unix);
+
+            if member.is_dir() {
+                extractor.dir(&path)?;
+            } else if member.is_symlink() {
+                let mut target = String::new();
+                member.read_to_string(&mut target)?;
+                extractor.symlink(&path, &target)?;
+            } else {
+                extractor.file(&path, &mut member, mode, modified)?;
+            }
+        }
+    } else {
+        if let Some(context) = context {
+            context.set_total(fs::metadata(archive)?.len());
+        }
+        let mut tar = tar::Archive::new(tar_reader(archive, format, context)?);
+
+        for member in tar.entries()? {
+            let mut member = member?;
+            let name = String::from_utf8_lossy(&member.path_bytes()).into_owned();
+            let Some(path) = extractor.target_path(&name) else {
+                continue;
+            };
+
+            let header = member.header();
+            let entry_type = header.entry_type();
+            let mode = header.mode().ok();
+            let modified = header.mtime().ok().map(|t| t as i64);
+
+            if entry_type.is_dir() {
+                extractor.dir(&path)?;
+            } else if entry_type.is_symlink() {
+                let target = member
+                    .link_name_bytes()
+                    .map(|target| String::from_utf8_lossy(&target).into_owned())
+                    .unwrap_or_default();
+                extractor.symlink(&path, &target)?;
+            } else if entry_type.is_file() || entry_type == tar::EntryType::Continuous {
+                extractor.file(&path, &mut member, mode, modified)?;
+            } else {
+                // Hard links, devices and fifos have no business in a download
+                extractor.skip(&name, "unsupported member type");
+            }
+        }
+    }
+
+    Ok(extractor.report)
+}
+
+struct Extractor<'a> {
+    root: PathBuf,
+    policy: ConflictPolicy,
+    context: Option<&'a OperationContext>,
+    /// When extracting a single member: its path and the name it gets.
+    member: Option<(String, PathBuf)>,
+    report: ExtractReport,
+}
+
+impl Extractor<'_> {
+    fn skip(&mut self, name: &str, reason: &str) {
+        self.report.skipped.push(format!("{}: {}", name, reason));
+    }
+
+    /// Where the member called `name` goes, relative to the destination, or
+    /// `None` when it is left out.
+    fn target_path(&mut self, name: &str) -> Option<PathBuf> {
+        let path = safe_member_path(name);
+        let Some((member, renamed)) = &self.member else {
+            if path.is_none() && !name.trim_matches('/').is_empty() {
+                self.skip(name, "path points outside the destination");
+            }
+            return path;
+        };
+
+        let path = path_string(&path?);
+        if path == *member {
+            return Some(renamed.clone());
+        }
+        let rest = path.strip_prefix(member.as_str())?.strip_prefix('/')?;
+        Some(renamed.join(rest))
+    }
+
+    /// The destination of `path`, once its parent folders exist and none of
+    /// them is a symlink.
+    fn prepare(&self, path: &Path) -> io::Result<PathBuf> {
+        let mut current = self.root.clone();
+        if let Some(parent) = path.parent() {
+            for component in parent.components() {
+                current.push(component);
+                match fs::symlink_metadata(&current) {
+                    Ok(meta) if meta.file_type().is_symlink() => {
+                        return Err(io::Error::other("path goes through a symbolic link"));
+                    }
+                    Ok(meta) if !meta.is_dir() => {
+                        return Err(io::Error::other("a parent folder is a file"));
+                    }
+                    Ok(_) => {}
+                    Err(_) => fs::create_dir(&current)?,
+                }
+            }
+        }
+        Ok(self.root.join(path))
+    }
+
+    /// Applies the conflict policy, `None` meaning the member is skipped.
+    fn resolve(&mut self, path: &Path, target: PathBuf) -> io::Result<Option<PathBuf>> {
+        let Ok(existing) = fs::symlink_metadata(&target) else {
+            return Ok(Some(target));
+        };
+
+        match self.policy {
+            ConflictPolicy::Skip => {
+                self.skip(&path_string(path), "already exists");
+                Ok(None)
+            }
+            ConflictPolicy::Overwrite if existing.is_dir() => {
+                self.skip(&path_string(path), "a folder with that name exists");
+                Ok(None)
+            }
+            ConflictPolicy::Overwrite => {
+                fs::remove_file(&target)?;
+                Ok(Some(target))
+            }
+            ConflictPolicy::KeepBoth => Ok(Some(unique_path(&target))),
+        }
+    }
+
+    fn dir(&mut self, path: &Path) -> io::Result<()> {
+        let target = match self.prepare(path) {
+            Ok(target) => target,
+            Err(e) => {
+                self.skip(&path_string(path), &e.to_string());
+                return Ok(());
+            }
+        };
+
+        match fs::symlink_metadata(&target) {
+            Ok(meta) if meta.is_dir() => Ok(()),
+            Ok(_) => {
+                self.skip(&path_string(path), "a file with that name exists");
+                Ok(())
+            }
+            Err(_) => fs::create_dir(&target),
+        }
+    }
+
+    fn symlink(&mut self, path: &Path, link_target: &str) -> io::Result<()> {
+        if !symlink_stays_inside(path, link_target) {
+            self.skip(&path_string(path), "symbolic link points outside the destination");
+            return Ok(());
+        }
+        let target = match self.prepare(path) {
+            Ok(target) => target,
+            Err(e) => {
+                self.skip(&path_string(path), &e.to_string());
+                return Ok(());
+            }
+        };
+
+        if let Some(target) = self.resolve(path, target)? {
+            symlink(link_target, target)?;
+        }
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        path: &Path,
+        reader: &mut dyn Read,
+        mode: Option<u32>,
+        modified: Option<i64>,
+    ) -> io::Result<()> {
+        let target = match self.prepare(path) {
+            Ok(target) => target,
+            Err(e) => {
+                self.skip(&path_string(path), &e.to_string());
+                return Ok(());
+            }
+        };
+        let Some(target) = self.resolve(path, target)? else {
+            return Ok(());
+        };
+
+        // `create_new` never follows a symlink that showed up meanwhile
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&target)?;
+        let mut reader =
+            CancellableReader { inner: reader, context: self.context, progress: false };
+        if let Err(e) = io::copy(&mut reader, &mut file) {
+            drop(file);
+            let _ = fs::remove_file(&target);
+            return Err(e);
+        }
+
+        if let Some(mode) = mode {
+            // Only permission bits, no setuid and the like
+            let _ = file.set_permissions(fs::Permissions::from_mode(mode & 0o777));
+        }
+        if let Some(modified) = modified.filter(|&t| t > 0) {
+            let _ =
+                file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(modified as u64));
+        }
+        Ok(())
+    }
+}
+
+/// Creates `target` from `files`, which all live in the same folder.
+/// A partially written archive is removed on failure or cancellation.
+pub fn compress(
+    files: &[PathBuf],
+    target: &Path,
+    format: ArchiveFormat,
+    context: &OperationContext,
+) -> io::Result<()> {
+    let mut members = Vec::new();
+    for file in files {
+        let name = file.file_name().ok_or_else(|| io::Error::other("invalid file name"))?;
+        collect_members(file, PathBuf::from(name), &mut members, context)?;
+    }
+    context.set_total(members.iter().map(|member| member.size).sum());
+
+    let output = fs::OpenOptions::new().write(true).create_new(true).open(target)?;
+    let result = match format {
+        ArchiveFormat::Zip => write_zip(output, &members, context),
+        ArchiveFormat::Tar => write_tar(output, &members, context).and_then(|mut w| w.flush()),
+        ArchiveFormat::TarGz => {
+            let encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+            write_tar(encoder, &members, context).and_then(|encoder| encoder.finish().map(drop))
+        }
+        ArchiveFormat::TarXz => {
+            let encoder = xz2::write::XzEncoder::new(output, 6);
+            write_tar(encoder, &members, context).and_then(|encoder| encoder.finish().map(drop))
+        }
+        ArchiveFormat::TarZst => zstd::Encoder::new(output, 0)
+            .and_then(|encoder| write_tar(encoder, &members, context))
+            .and_then(|encoder| encoder.finish().map(drop)),
+    };
+
+    if result.is_err() {
+        let _ = fs::remove_file(target);
+    }
+    result
+}
+
+struct Member {
+    source: PathBuf,
+    /// Name inside the archive.
+    name: PathBuf,
+    kind: MemberKind,
+    size: u64,
+}
+
+enum MemberKind {
+    File,
+    Dir,
+    Symlink(PathBuf),
+}
+
+/// Walks `source` without following symlinks.
+fn collect_members(
+    source: &Path,
+    name: PathBuf,
+    members: &mut Vec<Member>,
+    context: &OperationContext,
+) -> io::Result<()> {
+    if context.is_cancelled() {
+        return Err(cancelled());
+    }
+
+    let meta = fs::symlink_metadata(source)?;
+    let file_type = meta.file_type();
+
+    if file_type.is_symlink() {
+        let target = fs::read_link(source)?;
+        members.push(Member {
+            source: source.into(),
+            name,
+            kind: MemberKind::Symlink(target),
+            size: 0,
+        });
+    } else if file_type.is_dir() {
+        members.push(Member {
+            source: source.into(),
+            name: name.clone(),
+            kind: MemberKind::Dir,
+            size: 0,
+        });
+
+        let mut children: Vec<_> = fs::read_dir(source)?.collect::<io::Result<_>>()?;
+        children.sort_by_key(|child| child.file_name());
+        for child in children {
+            collect_members(&child.path(), name.join(child.file_name()), members, context)?;
+        }
+    } else if file_type.is_file() {
+        members.push(Member {
+            source: source.into(),
+            name,
+            kind: MemberKind::File,
+            size: meta.len(),
+        });
+    }
+    // Sockets, fifos and devices can't be archived meaningfully
+    Ok(())
+}
+
+fn write_tar<W: Write>(writer: W, members: &[Member], context: &OperationContext) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+
+    for member in members {
+        if context.is_cancelled() {
+            return Err(cancelled());
+        }
+
+        match member.kind {
+            MemberKind::File => {
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(&fs::metadata(&member.source)?);
+                let file = fs::File::open(&member.source)?;
+                let reader =
+                    CancellableReader { inner: file, context: Some(context), progress: true };
+                builder.append_data(&mut header, &member.name, reader)?;
+            }
+            _ => builder.append_path_with_name(&member.source, &member.name)?,
+        }
+    }
+
+    builder.into_inner()
+}
+
+fn write_zip(output: fs::File, members: &[Member], context: &OperationContext) -> io::Result<()> {
+    let mut zip = zip::ZipWriter::new(output);
+
+    for member in members {
+        if context.is_cancelled() {
+            return Err(cancelled());
+        }
+
+        let meta = fs::symlink_metadata(&member.source)?;
+        let mut options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .unix_permissions(meta.permissions().mode() & 0o777)
+            .large_file(member.size >= u32::MAX as u64);
+        if let Some(time) = meta.modified().ok().and_then(unix_to_zip_time) {
+            options = options.last_modified_time(time);
+        }
+
+        let name = path_string(&member.name);
+        match &member.kind {
+            MemberKind::Dir => zip.add_directory(name, options).map_err(io::Error::other)?,
+            MemberKind::Symlink(target) => zip
+                .add_symlink(name, target.to_string_lossy(), options)
+                .map_err(io::Error::other)?,
+            MemberKind::File => {
+                zip.start_file(name, options).map_err(io::Error::other)?;
+                let file = fs::File::open(&member.source)?;
+                io::copy(
+                    &mut CancellableReader { inner: file, context: Some(context), progress: true },
+                    &mut zip,
+                )?;
+            }
+        }
+    }
+
+    zip.finish().map_err(io::Error::other)?;
+    Ok(())
+}
+
+/// Reads through `inner`, failing once the operation is cancelled and
+/// counting the bytes read as progress when `progress` is set.
+struct CancellableReader<'a, R> {
+    inner: R,
+    context: Option<&'a OperationContext>,
+    progress: bool,
+}
+
+impl<R: Read> Read for CancellableReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(context) = self.context else {
+            return self.inner.read(buf);
+        };
+        if context.is_cancelled() {
+            return Err(cancelled());
+        }
+        let read = self.inner.read(buf)?;
+        if self.progress {
+            context.advance(read as u64, "");
+        }
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for CancellableReader<'_, R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// The decompressed tar stream of `archive`. With a context, reading it
+/// reports the compressed bytes consumed.
+fn tar_reader<'a>(
+    archive: &Path,
+    format: ArchiveFormat,
+    context: Option<&'a OperationContext>,
+) -> io::Result<Box<dyn Read + 'a>> {
+    let file = fs::File::open(archive)?;
+    let file: Box<dyn Read + 'a> =
+        Box::new(CancellableReader { inner: file, context, progress: true });
+
+    Ok(match format {
+        ArchiveFormat::TarGz => Box::new(flate2::read::MultiGzDecoder::new(file)),
+        ArchiveFormat::TarXz => Box::new(xz2::read::XzDecoder::new_multi_decoder(file)),
+        ArchiveFormat::TarZst => Box::new(zstd::Decoder::new(file)?),
+        _ => file,
+    })
+}
+
+/// `path`, or `path (2)`, `path (3)`, ... with the extension kept last.
+pub fn unique_path(path: &Path) -> PathBuf {
+    if fs::symlink_metadata(path).is_err() {
+        return path.to_path_buf();
+    }
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    let name = templates::unique_name(
+        &gio::File::for_path(parent),
+        &name.to_string_lossy(),
+        path.is_dir(),
+    );
+    parent.join(name)
+}
+
+fn path_string(path: &Path) -> String {
+    path.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+fn zip_time_to_unix(time: zip::DateTime) -> i64 {
+    glib::DateTime::from_local(
+        time.year() as i32,
+        time.month() as i32,
+        time.day() as i32,
+        time.hour() as i32,
+        time.minute() as i32,
+        time.second() as f64,
+    )
+    .map(|date| date.to_unix())
+    .unwrap_or(0)
+}
+
+fn unix_to_zip_time(time: SystemTime) -> Option<zip::DateTime> {
+    let seconds = time.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+    let date = glib::DateTime::from_unix_local(seconds as i64).ok()?;
+    zip::DateTime::from_date_and_time(
+        date.year() as u16,
+        date.month() as u8,
+        date.day_of_month() as u8,
+        date.hour() as u8,
+        date.minute() as u8,
+        date.second() as u8,
+    )
+    .ok()
+}
+
+const CANCELLED: &str = "Operation was cancelled";
+
+/// Whether `error` comes from the operation being cancelled.
+pub fn is_cancelled(error: &io::Error) -> bool {
+    error.to_string() == CANCELLED
+}
+
+// Not `Interrupted`, which `io::copy` would simply retry
+fn cancelled() -> io::Error {
+    io::Error::other(CANCELLED)
+}
+
+fn not_an_archive(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{} is not a supported archive", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gtk4::prelude::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("axfm-archive-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a tar of `members`, given as name, type and content (the
+    /// target for symlinks). Names are stored as they are, unchecked.
+    fn write_test_tar(path: &Path, members: &[(&str, tar::EntryType, &str)]) {
+        let mut builder = tar::Builder::new(fs::File::create(path).unwrap());
+        for &(name, entry_type, data) in members {
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(entry_type);
+            header.set_mode(0o644);
+            if entry_type.is_symlink() {
+                header.set_link_name(data).unwrap();
+                header.set_size(0);
+                header.set_cksum();
+                builder.append(&header, io::empty()).unwrap();
+            } else {
+                header.set_size(data.len() as u64);
+                header.set_cksum();
+                builder.append(&header, data.as_bytes()).unwrap();
+            }
+        }
+        builder.finish().unwrap();
+    }
+
+    fn extract_test_tar(
+        dir: &Path,
+        members: &[(&str, tar::EntryType, &str)],
+    ) -> (PathBuf, ExtractReport) {
+        let archive = dir.join("test.tar");
+        write_test_tar(&archive, members);
+        let destination = dir.join("destination");
+        fs::create_dir_all(&destination).unwrap();
+        let report =
+            extract_entries(&archive, &destination, ConflictPolicy::Skip, None, None).unwrap();
+        (destination, report)
+    }
+
+    fn exists(path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok()
+    }
+
+    #[test]
+    fn member_names_stay_relative() {
+        assert_eq!(safe_member_path("a/b.txt"), Some(PathBuf::from("a/b.txt")));
+        assert_eq!(safe_member_path("./a//b"), Some(PathBuf::from("a/b")));
+        assert_eq!(safe_member_path("a/"), Some(PathBuf::from("a")));
+
+        assert_eq!(safe_member_path("../x"), None);
+        assert_eq!(safe_member_path("a/../../x"), None);
+        assert_eq!(safe_member_path("a/.."), None);
+        assert_eq!(safe_member_path("/etc/passwd"), None);
+        assert_eq!(safe_member_path("a\0b"), None);
+        assert_eq!(safe_member_path("./"), None);
+        assert_eq!(safe_member_path(""), None);
+    }
+
+    #[test]
+    fn symlinks_must_point_inside() {
+        assert!(symlink_stays_inside(Path::new("a"), "b"));
+        assert!(symlink_stays_inside(Path::new("a/b"), "../c"));
+        assert!(symlink_stays_inside(Path::new("a/b"), "./c/../d"));
+
+        assert!(!symlink_stays_inside(Path::new("a"), "../.."));
+        assert!(!symlink_stays_inside(Path::new("a"), ".."));
+        assert!(!symlink_stays_inside(Path::new("a/b"), "../../x"));
+        assert!(!symlink_stays_inside(Path::new("a/b"), "c/../../../x"));
+        assert!(!symlink_stays_inside(Path::new("a"), "/etc/passwd"));
+        assert!(!symlink_stays_inside(Path::new("a"), ""));
+    }
+
+    #[test]
+    fn escaping_members_are_skipped() {
+        let dir = temp_dir("escaping");
+        let absolute = dir.join("absolute.txt");
+        let (destination, report) = extract_test_tar(
+            &dir,
+            &[
+                ("../climbed.txt", tar::EntryType::Regular, "x"),
+                (absolute.to_str().unwrap(), tar::EntryType::Regular, "x"),
+                ("a", tar::EntryType::Symlink, "../.."),
+                ("d/b", tar::EntryType::Symlink, "../../x"),
+                ("inside", tar::EntryType::Symlink, "d"),
+                ("kept.txt", tar::EntryType::Regular, "kept"),
+            ],
+        );
+
+        assert!(!exists(&dir.join("climbed.txt")));
+        assert!(!exists(&absolute));
+        assert!(!exists(&destination.join("a")));
+        assert!(!exists(&destination.join("d/b")));
+        assert!(exists(&destination.join("inside")));
+        assert_eq!(fs::read_to_string(destination.join("kept.txt")).unwrap(), "kept");
+        assert_eq!(report.skipped.len(), 4, "{:?}", report.skipped);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nothing_is_written_through_a_symlinked_parent() {
+        let dir = temp_dir("symlinked-parent");
+        let outside = dir.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::create_dir_all(dir.join("destination")).unwrap();
+        // Already in the destination, pointing out of it
+        symlink(&outside, dir.join("destination/escape")).unwrap();
+
+        let (destination, report) = extract_test_tar(
+            &dir,
+            &[
+                ("escape/owned.txt", tar::EntryType::Regular, "x"),
+                ("sub/", tar::EntryType::Directory, ""),
+                // Inside the destination, but still not followed
+                ("link", tar::EntryType::Symlink, "sub"),
+                ("link/inner.txt", tar::EntryType::Regular, "x"),
+            ],
+        );
+
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+        assert!(!exists(&destination.join("sub/inner.txt")));
+        assert_eq!(report.skipped.len(), 2, "{:?}", report.skipped);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_compression_leaves_nothing() {
+        let dir = temp_dir("cancel");
+        let source = dir.join("big");
+        fs::write(&source, vec![0u8; 16 << 20]).unwrap();
+
+        for (format, name) in [
+            (ArchiveFormat::TarGz, "big.tar.gz"),
+            (ArchiveFormat::TarXz, "big.tar.xz"),
+            (ArchiveFormat::TarZst, "big.tar.zst"),
+            (ArchiveFormat::Zip, "big.zip"),
+        ] {
+            let target = dir.join(name);
+            let context = OperationContext::new();
+            let result = std::thread::scope(|scope| {
+                // Cancels as soon as the archive is being written
+                scope.spawn(|| {
+                    let started = std::time::Instant::now();
+                    while !exists(&target) && started.elapsed() < Duration::from_secs(10) {
+                        std::thread::yield_now();
+                    }
+                    context.cancellable().cancel();
+                });
+                compress(std::slice::from_ref(&source), &target, format, &context)
+            });
+            assert!(result.is_err_and(|e| is_cancelled(&e)), "{} was finished", name);
+            assert!(!exists(&target), "{} was left behind", name);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unique_paths_keep_the_extension() {
+        let dir = temp_dir("unique");
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::write(dir.join("notes (2).txt"), "").unwrap();
+        fs::create_dir(dir.join("photos.2024")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.join("broken")).unwrap();
+
+        assert_eq!(unique_path(&dir.join("other.txt")), dir.join("other.txt"));
+        assert_eq!(unique_path(&dir.join("notes.txt")), dir.join("notes (3).txt"));
+        assert_eq!(unique_path(&dir.join("photos.2024")), dir.join("photos.2024 (2)"));
+        assert_eq!(unique_path(&dir.join("broken")), dir.join("broken (2)"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,362 @@
+//! Archives browsed like folders.
+//!
+//! A path going through an archive, like `/home/me/photos.zip/2023/beach.jpg`,
+//! names a member of that archive. Listing such a folder reads the archive
+//! index on a worker thread, kept until the archive changes; nothing is
+//! extracted until a member is opened, copied or dragged out.
+
+use crate::{
+    archive::{self, ArchiveEntry},
+    file_operations,
+    fm_window::FmWindow,
+    models::file_item::FileItem,
+    operations::OperationContext,
+};
+use gtk4::{gdk, gio, glib, prelude::*, subclass::prelude::*};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::Command,
+    rc::Rc,
+    time::SystemTime,
+};
+
+/// How many archive indexes are kept in memory.
+const MAX_CACHED_INDEXES: usize = 4;
+
+/// A folder or member inside an archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveLocation {
+    pub archive: PathBuf,
+    /// Path inside the archive, `/`-separated, empty for the archive itself.
+    pub member: String,
+}
+
+impl ArchiveLocation {
+    /// Finds the archive `path` goes through, if any.
+    pub fn from_path(path: &Path) -> Option<ArchiveLocation> {
+        // Checking the name first keeps ordinary folders cheap
+        let archive = path
+            .ancestors()
+            .find(|ancestor| archive::is_archive(ancestor) && ancestor.is_file())?;
+        let member = path.strip_prefix(archive).ok()?;
+
+        Some(ArchiveLocation {
+            archive: archive.to_path_buf(),
+            member: member
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+        })
+    }
+
+    pub fn from_file(file: &gio::File) -> Option<ArchiveLocation> {
+        Self::from_path(&file.path()?)
+    }
+
+    /// The member's path as shown in the file list and path bar.
+    pub fn path(&self) -> PathBuf {
+        if self.member.is_empty() { self.archive.clone() } else { self.archive.join(&self.member) }
+    }
+}
+
+struct CachedIndex {
+    archive: PathBuf,
+    /// Modification time and size of the archive when it was read.
+    stamp: (Option<SystemTime>, u64),
+    entries: Rc<Vec<ArchiveEntry>>,
+}
+
+thread_local! {
+    static INDEXES: RefCell<Vec<CachedIndex>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Modification time and size of `archive`, telling when its index is stale.
+type Stamp = (Option<SystemTime>, u64);
+
+fn stamp(archive: &Path) -> io::Result<Stamp> {
+    let meta = fs::metadata(archive)?;
+    Ok((meta.modified().ok(), meta.len()))
+}
+
+fn cached_index(archive: &Path, stamp: Stamp) -> Option<Rc<Vec<ArchiveEntry>>> {
+    INDEXES.with_borrow(|indexes| {
+        indexes
+            .iter()
+            .find(|index| index.archive == archive && index.stamp == stamp)
+            .map(|index| index.entries.clone())
+    })
+}
+
+fn cache_index(archive: &Path, stamp: Stamp, entries: Vec<ArchiveEntry>) -> Rc<Vec<ArchiveEntry>> {
+    let entries = Rc::new(entries);
+    INDEXES.with_borrow_mut(|indexes| {
+        indexes.retain(|index| index.archive != archive);
+        if indexes.len() >= MAX_CACHED_INDEXES {
+            indexes.remove(0);
+        }
+        indexes.push(CachedIndex {
+            archive: archive.to_path_buf(),
+            stamp,
+            entries: entries.clone(),
+        });
+    });
+    entries
+}
+
+/// The index of `archive` as last listed, even if it changed since.
+fn listed_index(archive: &Path) -> Option<Rc<Vec<ArchiveEntry>>> {
+    INDEXES.with_borrow(|indexes| {
+        indexes.iter().find(|index| index.archive == archive).map(|index| index.entries.clone())
+    })
+}
+
+/// Lists the members directly inside the folder at `location` and hands
+/// them to `on_listed`. Folders that only show up in the path of other
+/// members are listed too. A cached index is used right away, otherwise the
+/// archive is read on a worker thread as big archives take a while.
+pub fn list_folder(
+    location: &ArchiveLocation,
+    show_hidden: bool,
+    on_listed: impl FnOnce(io::Result<Vec<FileItem>>) + 'static,
+) {
+    let stamp = match stamp(&location.archive) {
+        Ok(stamp) => stamp,
+        Err(e) => return on_listed(Err(e)),
+    };
+    if let Some(entries) = cached_index(&location.archive, stamp) {
+        on_listed(Ok(folder_items(location, &entries, show_hidden)));
+        return;
+    }
+
+    let location = location.clone();
+    glib::spawn_future_local(async move {
+        let archive = location.archive.clone();
+        let read = gio::spawn_blocking(move || archive::list_entries(&archive)).await;
+        let result = match read {
+            Ok(Ok(entries)) => {
+                let entries = cache_index(&location.archive, stamp, entries);
+                Ok(folder_items(&location, &entries, show_hidden))
+            }
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(io::Error::other("reading the archive failed")),
+        };
+        on_listed(result);
+    });
+}
+
+fn folder_items(
+    location: &ArchiveLocation,
+    entries: &[ArchiveEntry],
+    show_hidden: bool,
+) -> Vec<FileItem> {
+    let prefix =
+        if location.member.is_empty() { String::new() } else { format!("{}/", location.member) };
+
+    // Name to entry, `None` for folders without an entry of their own
+    let mut children: BTreeMap<&str, Option<&ArchiveEntry>> = BTreeMap::new();
+    for entry in entries {
+        let Some(rest) = entry.path.strip_prefix(&prefix) else {
+            continue;
+        };
+        match rest.split_once('/') {
+            Some((name, _)) => {
+                children.entry(name).or_insert(None);
+            }
+            None if !rest.is_empty() => {
+                children.insert(rest, Some(entry));
+            }
+            None => {}
+        }
+    }
+
+    children
+        .into_iter()
+        .filter(|(name, _)| show_hidden || !name.starts_with('.'))
+        .map(|(name, entry)| {
+            let member = ArchiveLocation {
+                archive: location.archive.clone(),
+                member: format!("{}{}", prefix, name),
+            };
+            file_item(&member, name, entry)
+        })
+        .collect()
+}
+
+/// The member `path` points to, when it is inside an archive that was
+/// listed. The archive is not read again, this is called from the UI.
+/// Folders without an entry of their own are made up.
+pub fn member_entry(path: &Path) -> Option<ArchiveEntry> {
+    let location = ArchiveLocation::from_path(path)?;
+    if location.member.is_empty() {
+        return None;
+    }
+
+    let entries = listed_index(&location.archive)?;
+    let folder_prefix = format!("{}/", location.member);
+    entries.iter().rev().find(|entry| entry.path == location.member).cloned().or_else(|| {
+        entries.iter().any(|entry| entry.path.starts_with(&folder_prefix)).then(|| ArchiveEntry {
+            path: location.member.clone(),
+            is_dir: true,
+            size: 0,
+            modified: 0,
+        })
+    })
+}
+
+fn file_item(location: &ArchiveLocation, name: &str, entry: Option<&ArchiveEntry>) -> FileItem {
+    let is_dir = entry.is_none_or(|entry| entry.is_dir);
+    let content_type = if is_dir {
+        glib::GString::from("inode/directory")
+    } else {
+        gio::content_type_guess(Some(name), None).0
+    };
+
+    let item = FileItem::new(
+        location.path().display().to_string(),
+        name.to_string(),
+        entry.map(|entry| entry.size).unwrap_or(0),
+        entry.map(|entry| entry.modified).unwrap_or(0),
+        gio::content_type_get_description(&content_type).to_string(),
+        is_dir,
+        Some(gio::content_type_get_icon(&content_type)),
+    );
+    item.set_archive_member(true);
+    item.set_content_type(content_type.as_str());
+    item
+}
+
+/// Extracts the member at `location` into a new temporary folder, for
+/// handing it to other applications. The copy is left for the system to
+/// clean up, as the application may read it at any time.
+pub fn extract_to_temp(
+    location: &ArchiveLocation,
+    context: Option<&OperationContext>,
+) -> io::Result<PathBuf> {
+    let temp = glib::mkdtemp(glib::tmp_dir().join("axfm-XXXXXX"))
+        .ok_or_else(|| io::Error::other("could not create a temporary folder"))?;
+    archive::extract_member(&location.archive, &location.member, &temp, context)
+}
+
+/// Extracts the member at `path` in the background and opens it with its
+/// default application.
+pub fn open_member(fm_window: &FmWindow, path: &Path) {
+    let Some(location) = ArchiveLocation::from_path(path) else {
+        return;
+    };
+    let name = location.member.rsplit('/').next().unwrap_or_default().to_string();
+
+    fm_window.operations.run(
+        &format!("Extracting \"{}\"", name),
+        move |context| extract_to_temp(&location, Some(context)),
+        glib::clone!(
+            #[weak(rename_to = window)]
+            fm_window.window,
+            move |result: io::Result<PathBuf>| match result {
+                Ok(extracted) => {
+                    if let Err(err) = Command::new("xdg-open").arg(&extracted).spawn() {
+                        eprintln!("Failed to open file '{}': {}", extracted.display(), err);
+                    }
+                }
+                Err(e) if archive::is_cancelled(&e) => {}
+                Err(e) => file_operations::show_errors(
+                    window.upcast_ref(),
+                    "The file could not be extracted",
+                    &[e.to_string()],
+                ),
+            }
+        ),
+    );
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default)]
+    pub struct MemberContent {
+        pub(super) location: RefCell<Option<ArchiveLocation>>,
+        /// The extracted copy, shared by the reads of a drag.
+        pub(super) extracted: RefCell<Option<PathBuf>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for MemberContent {
+        const NAME: &'static str = "AxFMMemberContent";
+        type Type = super::MemberContent;
+        type ParentType = gdk::ContentProvider;
+    }
+
+    impl ObjectImpl for MemberContent {}
+
+    impl ContentProviderImpl for MemberContent {
+        fn formats(&self) -> gdk::ContentFormats {
+            gdk::ContentFormats::new(&[URI_LIST_MIME, TEXT_MIME])
+        }
+
+        fn write_mime_type_future(
+            &self,
+            mime_type: &str,
+            stream: &gio::OutputStream,
+            io_priority: glib::Priority,
+        ) -> Pin<Box<dyn Future<Output = Result<(), glib::Error>> + 'static>> {
+            let content = self.obj().clone();
+            let stream = stream.clone();
+            let uri_list = mime_type == URI_LIST_MIME;
+            Box::pin(async move {
+                let uri = gio::File::for_path(content.extract().await?).uri();
+                let text = if uri_list { format!("{}\r\n", uri) } else { uri.to_string() };
+                match stream.write_all_future(text, io_priority).await {
+                    Ok((_, _, None)) => Ok(()),
+                    Ok((_, _, Some(e))) | Err((_, e)) => Err(e),
+                }
+            })
+        }
+    }
+}
+
+const URI_LIST_MIME: &str = "text/uri-list";
+const TEXT_MIME: &str = "text/plain;charset=utf-8";
+
+glib::wrapper! {
+    /// Drag content for an archive member. The member is extracted on a
+    /// worker thread when the drop reads it, not when the drag starts.
+    pub struct MemberContent(ObjectSubclass<imp::MemberContent>)
+        @extends gdk::ContentProvider;
+}
+
+impl MemberContent {
+    pub fn new(location: ArchiveLocation) -> Self {
+        let content: Self = glib::Object::new();
+        content.imp().location.replace(Some(location));
+        content
+    }
+
+    async fn extract(&self) -> Result<PathBuf, glib::Error> {
+        if let Some(extracted) = self.imp().extracted.borrow().clone() {
+            return Ok(extracted);
+        }
+        let Some(location) = self.imp().location.borrow().clone() else {
+            return Err(glib::Error::new(gio::IOErrorEnum::NotFound, "No archive member"));
+        };
+
+        let name = location.path();
+        let extracted = match gio::spawn_blocking(move || extract_to_temp(&location, None)).await {
+            Ok(Ok(extracted)) => extracted,
+            Ok(Err(e)) => {
+                eprintln!("Failed to extract {}: {}", name.display(), e);
+                return Err(glib::Error::new(gio::IOErrorEnum::Failed, &e.to_string()));
+            }
+            Err(_) => {
+                return Err(glib::Error::new(gio::IOErrorEnum::Failed, "Extraction failed"));
+            }
+        };
+        self.imp().extracted.replace(Some(extracted.clone()));
+        Ok(extracted)
+    }
+}
@@ -0,0 +1,787 @@
+//! Renaming files: rules that compute new names for several files at once,
+//! a preview that checks them, and the renaming itself.
+
+use crate::operations::OperationContext;
+use gtk4::{gio, glib, prelude::*};
+
+/// Longest file name most file systems accept, in bytes.
+const MAX_NAME_LENGTH: usize = 255;
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// `(old, new)` pairs of files.
+pub type Renames = Vec<(gio::File, gio::File)>;
+
+/// A file to rename, with the metadata its tokens can refer to.
+pub struct RenameSource {
+    pub file: gio::File,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<glib::DateTime>,
+}
+
+impl RenameSource {
+    pub fn query(file: &gio::File) -> Option<Self> {
+        let info = file
+            .query_info(
+                "standard::name,standard::type,standard::size,time::modified",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                gio::Cancellable::NONE,
+            )
+            .ok()?;
+
+        Some(Self {
+            file: file.clone(),
+            name: info.name().to_string_lossy().into_owned(),
+            is_dir: info.file_type() == gio::FileType::Directory,
+            size: info.size().max(0) as u64,
+            modified: info.modification_date_time(),
+        })
+    }
+
+    fn split_name(&self) -> (&str, Option<&str>) {
+        split_extension(&self.name, self.is_dir)
+    }
+}
+
+/// Splits a name into stem and extension. Folders and dot files have no
+/// extension.
+pub fn split_extension(name: &str, is_dir: bool) -> (&str, Option<&str>) {
+    if is_dir {
+        return (name, None);
+    }
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+        _ => (name, None),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseChange {
+    Lower,
+    Upper,
+    Title,
+    Sentence,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertPosition {
+    Start,
+    End,
+    /// Character offset, clamped to the length of the name.
+    At(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenameRule {
+    /// Replaces every match. With `regex`, the replacement can refer to
+    /// groups as `\1` or `\g<name>`.
+    Replace {
+        find: String,
+        replacement: String,
+        regex: bool,
+        case_sensitive: bool,
+    },
+    Insert {
+        text: String,
+        position: InsertPosition,
+    },
+    /// Removes `count` characters starting `from` characters into the name,
+    /// counted from its end when `from_end` is set.
+    Remove {
+        from: usize,
+        count: usize,
+        from_end: bool,
+    },
+    ChangeCase(CaseChange),
+    /// Builds the whole name from a pattern of text and tokens.
+    Pattern(String),
+}
+
+/// The `{n}` token: `start`, then `start + step`, ... in selection order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counter {
+    pub start: u64,
+    pub step: u64,
+    pub padding: usize,
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self { start: 1, step: 1, padding: 1 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenameOptions {
+    pub rule: RenameRule,
+    pub counter: Counter,
+    /// Whether the rule also sees the extension, instead of the stem only.
+    pub include_extension: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreviewStatus {
+    Unchanged,
+    Renamed,
+    Invalid(String),
+    Collision(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct PreviewRow {
+    pub old_name: String,
+    pub new_name: String,
+    pub status: PreviewStatus,
+}
+
+impl PreviewRow {
+    pub fn is_error(&self) -> bool {
+        matches!(self.status, PreviewStatus::Invalid(_) | PreviewStatus::Collision(_))
+    }
+}
+
+/// Computes the new name of every source and flags the ones that can't be
+/// applied. Fails when the rule itself is invalid, e.g. a broken regex.
+pub fn preview(
+    sources: &[RenameSource],
+    options: &RenameOptions,
+) -> Result<Vec<PreviewRow>, String> {
+    let regex = match &options.rule {
+        RenameRule::Replace { find, regex, case_sensitive, .. } if !find.is_empty() => {
+            let pattern =
+                if *regex { find.clone() } else { glib::Regex::escape_string(find).into() };
+            let flags = if *case_sensitive {
+                glib::RegexCompileFlags::empty()
+            } else {
+                glib::RegexCompileFlags::CASELESS
+            };
+            glib::Regex::new(&pattern, flags, glib::RegexMatchFlags::empty())
+                .map_err(|e| e.message().to_string())?
+        }
+        _ => None,
+    };
+
+    let mut rows = Vec::with_capacity(sources.len());
+    for (index, source) in sources.iter().enumerate() {
+        let new_name = new_name(source, index, options, regex.as_ref())?;
+        let status = if new_name == source.name {
+            PreviewStatus::Unchanged
+        } else {
+            match validate_name(&new_name) {
+                Some(problem) => PreviewStatus::Invalid(problem),
+                None => PreviewStatus::Renamed,
+            }
+        };
+        rows.push(PreviewRow { old_name: source.name.clone(), new_name, status });
+    }
+
+    flag_collisions(sources, &mut rows);
+    Ok(rows)
+}
+
+/// The renames a preview stands for, or `None` when any row is in error.
+pub fn planned_renames(sources: &[RenameSource], rows: &[PreviewRow]) -> Option<Renames> {
+    if rows.iter().any(PreviewRow::is_error) {
+        return None;
+    }
+
+    let renames: Vec<_> = sources
+        .iter()
+        .zip(rows)
+        .filter(|(_, row)| row.status == PreviewStatus::Renamed)
+        .filter_map(|(source, row)| {
+            let parent = source.file.parent()?;
+            Some((source.file.clone(), parent.child(&row.new_name)))
+        })
+        .collect();
+
+    (!renames.is_empty()).then_some(renames)
+}
+
+fn new_name(
+    source: &RenameSource,
+    index: usize,
+    options: &RenameOptions,
+    regex: Option<&glib::Regex>,
+) -> Result<String, String> {
+    let (stem, extension) = source.split_name();
+    let (target, extension) = if options.include_extension || extension.is_none() {
+        (source.name.as_str(), None)
+    } else {
+        (stem, extension)
+    };
+
+    let expand = |text: &str| expand_tokens(text, source, index, options.counter);
+
+    let renamed = match &options.rule {
+        RenameRule::Replace { replacement, regex: use_groups, .. } => match regex {
+            Some(regex) => {
+                let replacement = expand(replacement)?;
+                let result = if *use_groups {
+                    regex.replace(target, 0, replacement.as_str(), glib::RegexMatchFlags::empty())
+                } else {
+                    regex.replace_literal(
+                        target,
+                        0,
+                        replacement.as_str(),
+                        glib::RegexMatchFlags::empty(),
+                    )
+                };
+                result.map_err(|e| e.message().to_string())?.to_string()
+            }
+            None => target.to_string(),
+        },
+        RenameRule::Insert { text, position } => {
+            let text = expand(text)?;
+            let mut chars: Vec<char> = target.chars().collect();
+            let at = match position {
+                InsertPosition::Start => 0,
+                InsertPosition::End => chars.len(),
+                InsertPosition::At(at) => (*at).min(chars.len()),
+            };
+            chars.splice(at..at, text.chars());
+            chars.into_iter().collect()
+        }
+        RenameRule::Remove { from, count, from_end } => {
+            let mut chars: Vec<char> = target.chars().collect();
+            let len = chars.len();
+            let (start, end) = if *from_end {
+                let end = len.saturating_sub(*from);
+                (end.saturating_sub(*count), end)
+            } else {
+                let start = (*from).min(len);
+                (start, start.saturating_add(*count).min(len))
+            };
+            chars.drain(start..end);
+            chars.into_iter().collect()
+        }
+        RenameRule::ChangeCase(case) => change_case(target, *case),
+        RenameRule::Pattern(pattern) => expand(pattern)?,
+    };
+
+    Ok(match extension {
+        Some(extension) => format!("{}.{}", renamed, extension),
+        None => renamed,
+    })
+}
+
+/// Expands `{token}` and `{token:argument}` placeholders. `{{` stands for a
+/// literal brace.
+fn expand_tokens(
+    text: &str,
+    source: &RenameSource,
+    index: usize,
+    counter: Counter,
+) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        rest = &rest[open + 1..];
+
+        if let Some(after) = rest.strip_prefix('{') {
+            result.push('{');
+            rest = after;
+            continue;
+        }
+
+        let close = rest.find('}').ok_or_else(|| "Unclosed \"{\" in the pattern".to_string())?;
+        let token = &rest[..close];
+        rest = &rest[close + 1..];
+
+        let (name, argument) = match token.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (token, None),
+        };
+
+        let (stem, extension) = source.split_name();
+        let value = match name {
+            "n" => {
+                let padding = match argument {
+                    Some(argument) => {
+                        argument.parse().map_err(|_| format!("Invalid padding in {{{}}}", token))?
+                    }
+                    None => counter.padding,
+                };
+                let value = counter.start + counter.step * index as u64;
+                format!("{:0padding$}", value, padding = padding)
+            }
+            "name" => stem.to_string(),
+            "ext" => extension.unwrap_or_default().to_string(),
+            "date" => source
+                .modified
+                .as_ref()
+                .and_then(|date| date.format(argument.unwrap_or(DEFAULT_DATE_FORMAT)).ok())
+                .map(|date| date.to_string())
+                .unwrap_or_default(),
+            "size" => source.size.to_string(),
+            "parent" => source
+                .file
+                .parent()
+                .and_then(|parent| parent.basename())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            _ => return Err(format!("Unknown token {{{}}}", name)),
+        };
+        result.push_str(&value);
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn change_case(text: &str, case: CaseChange) -> String {
+    match case {
+        CaseChange::Lower => text.to_lowercase(),
+        CaseChange::Upper => text.to_uppercase(),
+        CaseChange::Title => {
+            let mut result = String::with_capacity(text.len());
+            let mut word_start = true;
+            for c in text.chars() {
+                if word_start {
+                    result.extend(c.to_uppercase());
+                } else {
+                    result.extend(c.to_lowercase());
+                }
+                word_start = c.is_whitespace() || matches!(c, '_' | '-' | '.' | '(' | '[');
+            }
+            result
+        }
+        CaseChange::Sentence => {
+            let mut chars = text.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect()
+                }
+                None => String::new(),
+            }
+        }
+    }
+}
+
+/// Describes what is wrong with `name` as a file name, if anything.
+pub fn validate_name(name: &str) -> Option<String> {
+    if name.is_empty() {
+        Some("The name is empty".to_string())
+    } else if name == "." || name == ".." {
+        Some(format!("\"{}\" is reserved", name))
+    } else if name.contains('/') {
+        Some("Names can't contain \"/\"".to_string())
+    } else if name.contains('\0') {
+        Some("Names can't contain NUL characters".to_string())
+    } else if name.len() > MAX_NAME_LENGTH {
+        Some(format!("The name is longer than {} bytes", MAX_NAME_LENGTH))
+    } else {
+        None
+    }
+}
+
+/// Flags new names given to more than one file, and names already taken by
+/// a file that isn't part of the batch.
+fn flag_collisions(sources: &[RenameSource], rows: &mut [PreviewRow]) {
+    // Sources can come from several folders, as search results do
+    let parents: Vec<Option<gio::File>> = sources.iter().map(|s| s.file.parent()).collect();
+    let same_folder = |i: usize, j: usize| match (&parents[i], &parents[j]) {
+        (Some(a), Some(b)) => a.equal(b),
+        _ => false,
+    };
+
+    for i in 0..rows.len() {
+        if rows[i].is_error() {
+            continue;
+        }
+
+        let duplicate = rows
+            .iter()
+            .enumerate()
+            .any(|(j, other)| j != i && other.new_name == rows[i].new_name && same_folder(i, j));
+        if duplicate {
+            rows[i].status =
+                PreviewStatus::Collision("Another file gets the same name".to_string());
+            continue;
+        }
+
+        if rows[i].status != PreviewStatus::Renamed {
+            continue;
+        }
+
+        // Names of files in the batch are free once those are renamed, and
+        // case-only renames find the file itself on case-insensitive drives
+        let new_name = &rows[i].new_name;
+        if sources.iter().enumerate().any(|(j, s)| &s.name == new_name && same_folder(i, j))
+            || new_name.to_lowercase() == rows[i].old_name.to_lowercase()
+        {
+            continue;
+        }
+
+        let taken = parents[i]
+            .as_ref()
+            .is_some_and(|parent| parent.child(new_name).query_exists(gio::Cancellable::NONE));
+        if taken {
+            rows[i].status =
+                PreviewStatus::Collision("A file with this name already exists".to_string());
+        }
+    }
+}
+
+/// Renames each `(from, to)` pair. A target may be the current name of
+/// another file in the batch, so swaps and case-only renames work. When a
+/// rename fails, the ones already done are reverted.
+pub fn rename_files(
+    renames: &[(gio::File, gio::File)],
+    context: &OperationContext,
+) -> Result<(), String> {
+    let cancellable = Some(context.cancellable());
+    context.set_total(renames.len() as u64);
+
+    // Moves done so far, to revert them on failure
+    let mut done: Vec<(gio::File, gio::File)> = Vec::new();
+    let mut current: Vec<gio::File> = renames.iter().map(|(from, _)| from.clone()).collect();
+
+    // Files taking the new name of another one in the batch step aside
+    // first, as do case-only renames
+    for (index, (from, to)) in renames.iter().enumerate() {
+        let blocked = renames.iter().any(|(_, other_to)| other_to.equal(from))
+            || display_name(from).to_lowercase() == display_name(to).to_lowercase();
+        if !blocked {
+            continue;
+        }
+
+        let Some(parent) = from.parent() else {
+            continue;
+        };
+        let temp = parent.child(format!(".{}.rename-{}", display_name(from), index));
+        if let Err(e) = move_file(from, &temp, cancellable) {
+            revert(&done);
+            return Err(format!("{}: {}", display_name(from), e));
+        }
+        done.push((from.clone(), temp.clone()));
+        current[index] = temp;
+    }
+
+    for (index, (from, to)) in renames.iter().enumerate() {
+        if let Err(e) = move_file(&current[index], to, cancellable) {
+            revert(&done);
+            return Err(format!("{} → {}: {}", display_name(from), display_name(to), e));
+        }
+        done.push((current[index].clone(), to.clone()));
+        context.advance(1, &display_name(to));
+    }
+
+    Ok(())
+}
+
+/// Renames a single file in its folder. Case-only renames go through a
+/// temporary name, as case-insensitive drives see the new name as taken.
+pub fn rename_file(file: &gio::File, new_name: &str) -> Result<gio::File, glib::Error> {
+    let parent = file.parent().ok_or_else(|| {
+        glib::Error::new(gio::IOErrorEnum::InvalidArgument, "The file has no parent folder")
+    })?;
+    let target = parent.child(new_name);
+
+    if display_name(file).to_lowercase() != new_name.to_lowercase() {
+        move_file(file, &target, None)?;
+        return Ok(target);
+    }
+
+    let temp = parent.child(format!(".{}.rename", display_name(file)));
+    move_file(file, &temp, None)?;
+    if let Err(e) = move_file(&temp, &target, None) {
+        revert(&[(file.clone(), temp)]);
+        return Err(e);
+    }
+    Ok(target)
+}
+
+fn move_file(
+    from: &gio::File,
+    to: &gio::File,
+    cancellable: Option<&gio::Cancellable>,
+) -> Result<(), glib::Error> {
+    from.move_(to, gio::FileCopyFlags::NOFOLLOW_SYMLINKS, cancellable, None)
+}
+
+fn revert(done: &[(gio::File, gio::File)]) {
+    for (from, to) in done.iter().rev() {
+        if let Err(e) = move_file(to, from, None) {
+            eprintln!("Failed to restore {}: {}", from.parse_name(), e);
+        }
+    }
+}
+
+fn display_name(file: &gio::File) -> String {
+    file.basename().map(|n| n.display().to_string()).unwrap_or_else(|| file.uri().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(name: &str) -> RenameSource {
+        RenameSource {
+            file: gio::File::for_path(format!("/tmp/photos/{}", name)),
+            name: name.to_string(),
+            is_dir: false,
+            size: 2048,
+            modified: glib::DateTime::from_utc(2024, 3, 9, 12, 0, 0.0).ok(),
+        }
+    }
+
+    fn rename_with(rule: RenameRule, names: &[&str]) -> Vec<String> {
+        let options = RenameOptions { rule, counter: Counter::default(), include_extension: false };
+        let sources: Vec<_> = names.iter().map(|name| source(name)).collect();
+        preview(&sources, &options).unwrap().into_iter().map(|row| row.new_name).collect()
+    }
+
+    fn expand(text: &str, index: usize, counter: Counter) -> Result<String, String> {
+        expand_tokens(text, &source("beach.jpg"), index, counter)
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("axfm-rename-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn extensions_split_at_the_last_dot() {
+        assert_eq!(split_extension("archive.tar.gz", false), ("archive.tar", Some("gz")));
+        assert_eq!(split_extension(".bashrc", false), (".bashrc", None));
+        assert_eq!(split_extension("noext", false), ("noext", None));
+        assert_eq!(split_extension("folder.d", true), ("folder.d", None));
+        assert_eq!(split_extension("photo.été", false), ("photo", Some("été")));
+    }
+
+    #[test]
+    fn tokens_expand() {
+        let counter = Counter::default();
+        assert_eq!(expand("{name}_{n}.{ext}", 0, counter).unwrap(), "beach_1.jpg");
+        assert_eq!(expand("{parent}-{size}", 0, counter).unwrap(), "photos-2048");
+        assert_eq!(expand("{date}", 0, counter).unwrap(), "2024-03-09");
+        assert_eq!(expand("{date:%d.%m}", 0, counter).unwrap(), "09.03");
+        assert_eq!(expand("{{n} {{", 0, counter).unwrap(), "{n} {");
+
+        assert_eq!(expand("{nope}", 0, counter).unwrap_err(), "Unknown token {nope}");
+        assert_eq!(expand("{name", 0, counter).unwrap_err(), "Unclosed \"{\" in the pattern");
+        assert_eq!(expand("{n:x}", 0, counter).unwrap_err(), "Invalid padding in {n:x}");
+    }
+
+    #[test]
+    fn counters_step_and_pad() {
+        let counter = Counter { start: 5, step: 10, padding: 3 };
+        assert_eq!(expand("{n}", 0, counter).unwrap(), "005");
+        assert_eq!(expand("{n}", 2, counter).unwrap(), "025");
+        assert_eq!(expand("{n:1}", 2, counter).unwrap(), "25");
+        assert_eq!(expand("{n:2}", 200, counter).unwrap(), "2005");
+
+        let names = rename_with(RenameRule::Pattern("img-{n}".to_string()), &["b.jpg", "a.jpg"]);
+        assert_eq!(names, ["img-1.jpg", "img-2.jpg"]);
+    }
+
+    #[test]
+    fn case_rules() {
+        assert_eq!(
+            change_case("hello wORLD-foo_bar (x)", CaseChange::Title),
+            "Hello World-Foo_Bar (X)"
+        );
+        assert_eq!(change_case("hELLO World", CaseChange::Sentence), "Hello world");
+        assert_eq!(change_case("ÀÉÎ", CaseChange::Lower), "àéî");
+        assert_eq!(change_case("straße", CaseChange::Upper), "STRASSE");
+        assert_eq!(change_case("éclair ünd", CaseChange::Title), "Éclair Ünd");
+        assert_eq!(change_case("", CaseChange::Sentence), "");
+
+        let names = rename_with(RenameRule::ChangeCase(CaseChange::Upper), &["notes.txt"]);
+        assert_eq!(names, ["NOTES.txt"]);
+    }
+
+    #[test]
+    fn remove_ranges_are_clamped() {
+        let remove = |from, count, from_end| {
+            rename_with(RenameRule::Remove { from, count, from_end }, &["abcdef.txt"]).remove(0)
+        };
+        assert_eq!(remove(0, 2, false), "cdef.txt");
+        assert_eq!(remove(0, 2, true), "abcd.txt");
+        assert_eq!(remove(4, 10, false), "abcd.txt");
+        assert_eq!(remove(10, 2, false), "abcdef.txt");
+        assert_eq!(remove(4, 10, true), "cdef.txt");
+        assert_eq!(remove(10, 3, true), "abcdef.txt");
+    }
+
+    #[test]
+    fn multibyte_names() {
+        let names = rename_with(
+            RenameRule::Remove { from: 1, count: 2, from_end: false },
+            &["日本語ファイル.txt"],
+        );
+        assert_eq!(names, ["日ファイル.txt"]);
+
+        let names = rename_with(
+            RenameRule::Insert { text: "XX".to_string(), position: InsertPosition::At(2) },
+            &["héllo.txt"],
+        );
+        assert_eq!(names, ["héXXllo.txt"]);
+
+        let names = rename_with(
+            RenameRule::Replace {
+                find: "É".to_string(),
+                replacement: "e".to_string(),
+                regex: false,
+                case_sensitive: false,
+            },
+            &["café.txt"],
+        );
+        assert_eq!(names, ["cafe.txt"]);
+    }
+
+    #[test]
+    fn replace_with_groups() {
+        let names = rename_with(
+            RenameRule::Replace {
+                find: r"IMG_(\d+)".to_string(),
+                replacement: r"photo-\1".to_string(),
+                regex: true,
+                case_sensitive: true,
+            },
+            &["IMG_0042.jpg", "img_0043.jpg"],
+        );
+        assert_eq!(names, ["photo-0042.jpg", "img_0043.jpg"]);
+    }
+
+    #[test]
+    fn collisions() {
+        let dir = temp_dir("collisions");
+        for name in ["A.txt", "a.txt", "readme.md", "x.txt", "taken.txt", "1.txt", "2.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let source =
+            |name: &str| RenameSource { file: gio::File::for_path(dir.join(name)), ..source(name) };
+        let statuses = |rule: RenameRule, names: &[&str]| {
+            let options =
+                RenameOptions { rule, counter: Counter::default(), include_extension: true };
+            let sources: Vec<_> = names.iter().map(|name| source(name)).collect();
+            preview(&sources, &options)
+                .unwrap()
+                .into_iter()
+                .map(|row| row.status)
+                .collect::<Vec<_>>()
+        };
+        let same = PreviewStatus::Collision("Another file gets the same name".to_string());
+        let exists = PreviewStatus::Collision("A file with this name already exists".to_string());
+
+        // Only differing in case, both end up the same
+        let lower = RenameRule::ChangeCase(CaseChange::Lower);
+        assert_eq!(statuses(lower, &["A.txt", "a.txt"]), [same.clone(), same]);
+
+        // A case-only rename finds the file itself, which is not a collision
+        let upper = RenameRule::ChangeCase(CaseChange::Upper);
+        assert_eq!(statuses(upper, &["readme.md"]), [PreviewStatus::Renamed]);
+
+        let to_taken = RenameRule::Pattern("taken.txt".to_string());
+        assert_eq!(statuses(to_taken, &["x.txt"]), [exists]);
+
+        // Swapping names within the batch is fine
+        let swap = RenameRule::Pattern("{n}.txt".to_string());
+        let renamed = PreviewStatus::Renamed;
+        assert_eq!(statuses(swap, &["2.txt", "1.txt"]), [renamed.clone(), renamed]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn collisions_are_per_folder() {
+        let dir = temp_dir("folders");
+        for name in ["one/a.txt", "one/b.txt", "two/b.txt", "two/c.txt"] {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let source = |name: &str| RenameSource {
+            file: gio::File::for_path(dir.join(name)),
+            ..source(name.rsplit('/').next().unwrap())
+        };
+        let statuses = |pattern: &str, names: &[&str]| {
+            let options = RenameOptions {
+                rule: RenameRule::Pattern(pattern.to_string()),
+                counter: Counter::default(),
+                include_extension: true,
+            };
+            let sources: Vec<_> = names.iter().map(|name| source(name)).collect();
+            preview(&sources, &options)
+                .unwrap()
+                .into_iter()
+                .map(|row| row.status)
+                .collect::<Vec<_>>()
+        };
+        let renamed = PreviewStatus::Renamed;
+        let exists = PreviewStatus::Collision("A file with this name already exists".to_string());
+
+        // The same new name in two folders
+        assert_eq!(statuses("new.txt", &["one/a.txt", "two/c.txt"]), [renamed.clone(), renamed]);
+
+        // two/b.txt being in the batch doesn't free one/b.txt
+        assert_eq!(statuses("b.txt", &["one/a.txt", "two/b.txt"])[0], exists);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn rename_in(dir: &std::path::Path, pairs: &[(&str, &str)]) -> Result<(), String> {
+        let renames: Vec<_> = pairs
+            .iter()
+            .map(|(from, to)| {
+                (gio::File::for_path(dir.join(from)), gio::File::for_path(dir.join(to)))
+            })
+            .collect();
+        rename_files(&renames, &OperationContext::new())
+    }
+
+    fn read(dir: &std::path::Path, name: &str) -> String {
+        std::fs::read_to_string(dir.join(name)).unwrap()
+    }
+
+    #[test]
+    fn renames_can_swap_names() {
+        let dir = temp_dir("swap");
+        std::fs::write(dir.join("a"), "first").unwrap();
+        std::fs::write(dir.join("b"), "second").unwrap();
+
+        rename_in(&dir, &[("a", "b"), ("b", "a")]).unwrap();
+        assert_eq!(read(&dir, "a"), "second");
+        assert_eq!(read(&dir, "b"), "first");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn renames_can_shift_names() {
+        let dir = temp_dir("shift");
+        std::fs::write(dir.join("1.txt"), "one").unwrap();
+        std::fs::write(dir.join("2.txt"), "two").unwrap();
+
+        rename_in(&dir, &[("1.txt", "2.txt"), ("2.txt", "3.txt")]).unwrap();
+        assert!(!dir.join("1.txt").exists());
+        assert_eq!(read(&dir, "2.txt"), "one");
+        assert_eq!(read(&dir, "3.txt"), "two");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_renames_are_reverted() {
+        let dir = temp_dir("revert");
+        std::fs::write(dir.join("a"), "first").unwrap();
+        std::fs::write(dir.join("b"), "second").unwrap();
+
+        // The second one has nothing to rename
+        assert!(rename_in(&dir, &[("a", "c"), ("missing", "d")]).is_err());
+        assert_eq!(read(&dir, "a"), "first");
+        assert!(!dir.join("c").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
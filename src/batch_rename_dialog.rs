@@ -0,0 +1,444 @@
+use crate::{
+    batch_rename::{
+        self, CaseChange, Counter, InsertPosition, PreviewRow, PreviewStatus, RenameOptions,
+        RenameRule, RenameSource, Renames,
+    },
+    file_operations,
+    fm_window::FmWindow,
+    undo::{UndoAction, UndoEntry},
+};
+use gtk4::{
+    Box as GtkBox, CheckButton, Dialog, DropDown, Entry, Grid, Label, ListBox, Orientation,
+    ResponseType, ScrolledWindow, SpinButton, Stack, gio, glib, prelude::*,
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// Stack page names, in the order of the mode drop-down.
+const MODES: &[(&str, &str)] = &[
+    ("replace", "Find and Replace"),
+    ("insert", "Insert Text"),
+    ("remove", "Remove Text"),
+    ("case", "Change Case"),
+    ("pattern", "Rename Using Pattern"),
+];
+
+const CASES: &[CaseChange] =
+    &[CaseChange::Lower, CaseChange::Upper, CaseChange::Title, CaseChange::Sentence];
+
+const TOKENS_HINT: &str = concat!(
+    "Tokens: {n} counter, {n:3} counter padded to 3 digits, {name} original name, ",
+    "{ext} extension, {date} or {date:%Y%m%d} modification date, {size} size in bytes, ",
+    "{parent} folder name. With regular expressions, \\1 or \\g<name> insert a group."
+);
+
+/// The inputs of the dialog, read again whenever one of them changes.
+#[derive(Clone)]
+struct Controls {
+    mode: DropDown,
+    find: Entry,
+    replacement: Entry,
+    use_regex: CheckButton,
+    match_case: CheckButton,
+    insert_text: Entry,
+    insert_position: DropDown,
+    insert_offset: SpinButton,
+    remove_from: SpinButton,
+    remove_count: SpinButton,
+    remove_from_end: DropDown,
+    case: DropDown,
+    pattern: Entry,
+    counter_start: SpinButton,
+    counter_step: SpinButton,
+    counter_padding: SpinButton,
+    include_extension: CheckButton,
+}
+
+impl Controls {
+    fn new() -> Self {
+        let mode_names: Vec<&str> = MODES.iter().map(|(_, label)| *label).collect();
+
+        Self {
+            mode: DropDown::from_strings(&mode_names),
+            find: Entry::new(),
+            replacement: Entry::new(),
+            use_regex: CheckButton::with_label("Regular expression"),
+            match_case: CheckButton::with_label("Match case"),
+            insert_text: Entry::new(),
+            insert_position: DropDown::from_strings(&["At the start", "At the end", "At position"]),
+            insert_offset: SpinButton::with_range(0.0, 255.0, 1.0),
+            remove_from: SpinButton::with_range(0.0, 255.0, 1.0),
+            remove_count: SpinButton::with_range(0.0, 255.0, 1.0),
+            remove_from_end: DropDown::from_strings(&["From the start", "From the end"]),
+            case: DropDown::from_strings(&[
+                "lowercase",
+                "UPPERCASE",
+                "Title Case",
+                "Sentence case",
+            ]),
+            pattern: Entry::new(),
+            counter_start: SpinButton::with_range(0.0, 1_000_000.0, 1.0),
+            counter_step: SpinButton::with_range(1.0, 1000.0, 1.0),
+            counter_padding: SpinButton::with_range(1.0, 10.0, 1.0),
+            include_extension: CheckButton::with_label("Include extension"),
+        }
+    }
+
+    fn options(&self) -> RenameOptions {
+        let spin = |button: &SpinButton| button.value_as_int().max(0) as usize;
+
+        let rule = match MODES.get(self.mode.selected() as usize).map(|(name, _)| *name) {
+            Some("insert") => RenameRule::Insert {
+                text: self.insert_text.text().to_string(),
+                position: match self.insert_position.selected() {
+                    0 => InsertPosition::Start,
+                    1 => InsertPosition::End,
+                    _ => InsertPosition::At(spin(&self.insert_offset)),
+                },
+            },
+            Some("remove") => RenameRule::Remove {
+                from: spin(&self.remove_from),
+                count: spin(&self.remove_count),
+                from_end: self.remove_from_end.selected() == 1,
+            },
+            Some("case") => RenameRule::ChangeCase(
+                CASES.get(self.case.selected() as usize).copied().unwrap_or(CaseChange::Lower),
+            ),
+            Some("pattern") => RenameRule::Pattern(self.pattern.text().to_string()),
+            _ => RenameRule::Replace {
+                find: self.find.text().to_string(),
+                replacement: self.replacement.text().to_string(),
+                regex: self.use_regex.is_active(),
+                case_sensitive: self.match_case.is_active(),
+            },
+        };
+
+        RenameOptions {
+            rule,
+            counter: Counter {
+                start: self.counter_start.value_as_int().max(0) as u64,
+                step: self.counter_step.value_as_int().max(1) as u64,
+                padding: spin(&self.counter_padding),
+            },
+            include_extension: self.include_extension.is_active(),
+        }
+    }
+
+    fn connect_changed(&self, on_change: impl Fn() + Clone + 'static) {
+        for entry in [&self.find, &self.replacement, &self.insert_text, &self.pattern] {
+            let on_change = on_change.clone();
+            entry.connect_changed(move |_| on_change());
+        }
+        for check in [&self.use_regex, &self.match_case, &self.include_extension] {
+            let on_change = on_change.clone();
+            check.connect_toggled(move |_| on_change());
+        }
+        for spin in [
+            &self.insert_offset,
+            &self.remove_from,
+            &self.remove_count,
+            &self.counter_start,
+            &self.counter_step,
+            &self.counter_padding,
+        ] {
+            let on_change = on_change.clone();
+            spin.connect_value_changed(move |_| on_change());
+        }
+        for drop_down in [&self.mode, &self.insert_position, &self.remove_from_end, &self.case] {
+            let on_change = on_change.clone();
+            drop_down.connect_selected_notify(move |_| on_change());
+        }
+    }
+}
+
+/// Opens the batch rename dialog for `files`, which are renamed in the
+/// order given when counters are used.
+pub fn show_batch_rename_dialog(fm_window: &FmWindow, files: Vec<gio::File>) {
+    let sources: Rc<Vec<RenameSource>> =
+        Rc::new(files.iter().filter_map(RenameSource::query).collect());
+    if sources.is_empty() {
+        return;
+    }
+
+    let dialog = Dialog::builder()
+        .title(format!("Rename {} Items", sources.len()))
+        .transient_for(&fm_window.window)
+        .modal(true)
+        .default_width(680)
+        .default_height(560)
+        .build();
+
+    let content = dialog.content_area();
+    content.set_spacing(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+
+    let controls = Controls::new();
+    controls.pattern.set_text("{name}_{n}");
+    controls.counter_start.set_value(1.0);
+    controls.remove_count.set_value(1.0);
+    controls.insert_offset.set_sensitive(false);
+
+    // Rule settings, one stack page per mode
+    let stack = Stack::new();
+    stack.add_named(
+        &labeled_grid(&[
+            ("Find:", controls.find.upcast_ref()),
+            ("Replace with:", controls.replacement.upcast_ref()),
+            ("", checks_row(&[&controls.use_regex, &controls.match_case]).upcast_ref()),
+        ]),
+        Some("replace"),
+    );
+    stack.add_named(
+        &labeled_grid(&[
+            ("Text:", controls.insert_text.upcast_ref()),
+            ("Insert:", controls.insert_position.upcast_ref()),
+            ("Position:", controls.insert_offset.upcast_ref()),
+        ]),
+        Some("insert"),
+    );
+    stack.add_named(
+        &labeled_grid(&[
+            ("Characters:", controls.remove_count.upcast_ref()),
+            ("Starting at:", controls.remove_from.upcast_ref()),
+            ("Counted:", controls.remove_from_end.upcast_ref()),
+        ]),
+        Some("remove"),
+    );
+    stack.add_named(&labeled_grid(&[("Change to:", controls.case.upcast_ref())]), Some("case"));
+    stack.add_named(&labeled_grid(&[("Pattern:", controls.pattern.upcast_ref())]), Some("pattern"));
+
+    controls.mode.connect_selected_notify(glib::clone!(
+        #[weak]
+        stack,
+        move |mode| {
+            if let Some((name, _)) = MODES.get(mode.selected() as usize) {
+                stack.set_visible_child_name(name);
+            }
+        }
+    ));
+    controls.insert_position.connect_selected_notify(glib::clone!(
+        #[weak(rename_to = offset)]
+        controls.insert_offset,
+        move |position| offset.set_sensitive(position.selected() == 2)
+    ));
+
+    let counter_row = GtkBox::new(Orientation::Horizontal, 6);
+    for (label, spin) in [
+        ("Counter starts at", &controls.counter_start),
+        ("step", &controls.counter_step),
+        ("digits", &controls.counter_padding),
+    ] {
+        counter_row.append(&Label::new(Some(label)));
+        counter_row.append(spin);
+    }
+
+    let hint = Label::new(Some(TOKENS_HINT));
+    hint.set_wrap(true);
+    hint.set_xalign(0.0);
+    hint.add_css_class("dim-label");
+
+    content.append(&labeled_grid(&[("Rename by:", controls.mode.upcast_ref())]));
+    content.append(&stack);
+    content.append(&counter_row);
+    content.append(&controls.include_extension);
+    content.append(&hint);
+
+    // Live preview
+    let preview_list = ListBox::new();
+    preview_list.set_selection_mode(gtk4::SelectionMode::None);
+    let scrolled = ScrolledWindow::builder().vexpand(true).child(&preview_list).build();
+    let summary = Label::new(None);
+    summary.set_xalign(0.0);
+    content.append(&scrolled);
+    content.append(&summary);
+
+    dialog.add_button("Cancel", ResponseType::Cancel);
+    let rename_button = dialog.add_button("Rename", ResponseType::Accept);
+    rename_button.add_css_class("suggested-action");
+
+    let planned: Rc<RefCell<Option<Renames>>> = Rc::new(RefCell::new(None));
+
+    // The handlers of the controls refer to the controls, the slot is
+    // emptied when the dialog closes so they can be freed
+    let controls_slot = Rc::new(RefCell::new(Some(controls.clone())));
+
+    let refresh = glib::clone!(
+        #[strong]
+        controls_slot,
+        #[strong]
+        sources,
+        #[strong]
+        planned,
+        #[weak]
+        preview_list,
+        #[weak]
+        summary,
+        #[weak]
+        rename_button,
+        move || {
+            let Some(options) = controls_slot.borrow().as_ref().map(Controls::options) else {
+                return;
+            };
+            let result = batch_rename::preview(&sources, &options);
+            let renames =
+                result.as_ref().ok().and_then(|rows| batch_rename::planned_renames(&sources, rows));
+
+            show_preview(&preview_list, &summary, &result);
+            rename_button.set_sensitive(renames.is_some());
+            planned.replace(renames);
+        }
+    );
+    controls.connect_changed(refresh.clone());
+    refresh();
+
+    dialog.connect_response(glib::clone!(
+        #[strong]
+        fm_window,
+        move |dialog, response| {
+            if response == ResponseType::Accept {
+                let Some(renames) = planned.borrow_mut().take() else {
+                    return;
+                };
+                apply_renames(&fm_window, renames);
+            }
+            controls_slot.take();
+            dialog.close();
+        }
+    ));
+
+    dialog.present();
+}
+
+fn apply_renames(fm_window: &FmWindow, renames: Renames) {
+    let label = match renames.len() {
+        1 => "Rename 1 item".to_string(),
+        count => format!("Rename {} items", count),
+    };
+    let work_renames = renames.clone();
+
+    fm_window.operations.run(
+        &format!("Renaming {} items", renames.len()),
+        move |context| batch_rename::rename_files(&work_renames, context),
+        glib::clone!(
+            #[strong]
+            fm_window,
+            move |result: Result<(), String>| {
+                fm_window.reload();
+                match result {
+                    Ok(()) => {
+                        let renamed: Vec<_> = renames.iter().map(|(_, new)| new.clone()).collect();
+                        fm_window
+                            .fmstate
+                            .borrow_mut()
+                            .push_undo(UndoEntry { label, action: UndoAction::Rename(renames) });
+                        fm_window.select_files(&renamed);
+                    }
+                    Err(e) => file_operations::show_errors(
+                        fm_window.window.upcast_ref(),
+                        "The files could not be renamed",
+                        &[e, "No file was renamed.".to_string()],
+                    ),
+                }
+            }
+        ),
+    );
+}
+
+fn show_preview(list: &ListBox, summary: &Label, result: &Result<Vec<PreviewRow>, String>) {
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(message) => {
+            summary.set_text(&format!("Invalid rule: {}", message));
+            summary.add_css_class("error");
+            return;
+        }
+    };
+
+    for row in rows {
+        list.append(&preview_row(row));
+    }
+
+    let errors = rows.iter().filter(|row| row.is_error()).count();
+    let renamed = rows.iter().filter(|row| row.status == PreviewStatus::Renamed).count();
+    if errors > 0 {
+        summary.set_text(&format!("{} of {} new names can't be used", errors, rows.len()));
+        summary.add_css_class("error");
+    } else {
+        summary.set_text(&format!("{} of {} items will be renamed", renamed, rows.len()));
+        summary.remove_css_class("error");
+    }
+}
+
+fn preview_row(row: &PreviewRow) -> GtkBox {
+    let hbox = GtkBox::new(Orientation::Horizontal, 8);
+    hbox.set_margin_start(6);
+    hbox.set_margin_end(6);
+    hbox.set_margin_top(3);
+    hbox.set_margin_bottom(3);
+
+    let name_label = |text: &str| {
+        let label = Label::new(Some(text));
+        label.set_xalign(0.0);
+        label.set_hexpand(true);
+        label.set_width_chars(20);
+        label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+        label
+    };
+    let old_label = name_label(&row.old_name);
+    let new_label = name_label(&row.new_name);
+
+    let (icon, tooltip) = match &row.status {
+        PreviewStatus::Unchanged => {
+            hbox.add_css_class("dim-label");
+            (None, None)
+        }
+        PreviewStatus::Renamed => (None, None),
+        PreviewStatus::Invalid(problem) | PreviewStatus::Collision(problem) => {
+            new_label.add_css_class("error");
+            (Some("dialog-warning-symbolic"), Some(problem.as_str()))
+        }
+    };
+
+    let status = gtk4::Image::new();
+    status.set_icon_name(icon);
+    status.set_pixel_size(16);
+    status.set_tooltip_text(tooltip);
+    new_label.set_tooltip_text(tooltip);
+
+    hbox.append(&old_label);
+    hbox.append(&Label::new(Some("→")));
+    hbox.append(&new_label);
+    hbox.append(&status);
+    hbox
+}
+
+fn labeled_grid(rows: &[(&str, &gtk4::Widget)]) -> Grid {
+    let grid = Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+
+    for (row, (label, widget)) in rows.iter().enumerate() {
+        let label = Label::new(Some(label));
+        label.set_xalign(0.0);
+        label.set_width_chars(12);
+        widget.set_hexpand(true);
+        grid.attach(&label, 0, row as i32, 1, 1);
+        grid.attach(*widget, 1, row as i32, 1, 1);
+    }
+
+    grid
+}
+
+fn checks_row(checks: &[&CheckButton]) -> GtkBox {
+    let hbox = GtkBox::new(Orientation::Horizontal, 12);
+    for check in checks {
+        hbox.append(*check);
+    }
+    hbox
+}
@@ -1,19 +1,62 @@
-use crate::state::FmState;
-use gtk4::{
-    Box as GtkBox, Button, Dialog, Label, ListBox, ListBoxRow, Orientation, ResponseType,
-    ScrolledWindow, StringList, Window, gio, glib, prelude::*,
+use crate::utils::json::{self, JsonError, JsonValue};
+use gtk4::{gio, glib, prelude::*};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
 };
-use std::{cell::RefCell, path::Path, rc::Rc};
 
-#[derive(Debug, Clone)]
+pub const DEFAULT_ICON: &str = "starred";
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Bookmark {
     pub name: String,
     pub path: String,
+    /// Icon picked by the user, `DEFAULT_ICON` when unset.
+    pub icon: Option<String>,
+    /// Collapsible sidebar folder the bookmark is shown in.
+    pub group: Option<String>,
+}
+
+/// A search pinned in the sidebar. The criteria are kept as they were
+/// typed, so relative dates like "this week" move along with the calendar.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SavedSearch {
+    /// Shown in the sidebar, unique among saved searches.
+    pub name: String,
+    /// Folder searched, a local path.
+    pub root: String,
+    pub query: String,
+    /// Key of the `search::NameMode`.
+    pub name_mode: String,
+    pub content: String,
+    pub min_size: String,
+    pub max_size: String,
+    pub modified_after: String,
+    pub modified_before: String,
+    pub content_type: String,
+}
+
+impl SavedSearch {
+    pub fn root_file(&self) -> gio::File {
+        gio::File::for_path(&self.root)
+    }
+}
+
+/// Everything stored in the bookmarks file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookmarksFile {
+    pub bookmarks: Vec<Bookmark>,
+    pub collapsed_groups: Vec<String>,
+    pub saved_searches: Vec<SavedSearch>,
 }
 
 impl Bookmark {
     pub fn new(name: String, path: String) -> Self {
-        Self { name, path }
+        Self { name, path, icon: None, group: None }
+    }
+
+    pub fn icon_name(&self) -> &str {
+        self.icon.as_deref().unwrap_or(DEFAULT_ICON)
     }
 
     pub fn from_file(file: &gio::File) -> Self {
@@ -25,11 +68,11 @@ impl Bookmark {
         let path =
             file.path().map(|p| p.display().to_string()).unwrap_or_else(|| file.uri().to_string());
 
-        Self { name, path }
+        Self::new(name, path)
     }
 
     pub fn to_gio_file(&self) -> gio::File {
-        if Path::new(&self.path).exists() {
+        if Path::new(&self.path).is_absolute() {
             gio::File::for_path(&self.path)
         } else {
             gio::File::for_uri(&self.path)
@@ -37,273 +80,625 @@ impl Bookmark {
     }
 }
 
-// Manual JSON serialization (no serde dependency)
-pub fn save_bookmarks(bookmarks: &[Bookmark]) -> Result<(), std::io::Error> {
-    let config_dir = std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        format!("{}/.config", home)
-    });
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+const BACKUP_FILE: &str = "bookmarks.json.bak";
+const CORRUPT_FILE: &str = "bookmarks.json.corrupt";
+
+/// Schema version written by this build. Files from before versioning have
+/// no `version` member and are treated as version 0.
+const SCHEMA_VERSION: u64 = 3;
+
+#[derive(Debug)]
+pub enum BookmarksError {
+    Io { path: PathBuf, source: std::io::Error },
+    Parse { path: PathBuf, source: JsonError },
+    Schema { path: PathBuf, message: String },
+    UnsupportedVersion { path: PathBuf, version: u64 },
+    Write { path: PathBuf, message: String },
+}
+
+impl fmt::Display for BookmarksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookmarksError::Io { path, source } => {
+                write!(f, "Could not read {}: {}", path.display(), source)
+            }
+            BookmarksError::Parse { path, source } => {
+                write!(f, "{} is not valid JSON ({})", path.display(), source)
+            }
+            BookmarksError::Schema { path, message } => {
+                write!(f, "{} has an unexpected layout: {}", path.display(), message)
+            }
+            BookmarksError::UnsupportedVersion { path, version } => write!(
+                f,
+                "{} was written by a newer version of AxFM (format version {})",
+                path.display(),
+                version
+            ),
+            BookmarksError::Write { path, message } => {
+                write!(f, "Could not save {}: {}", path.display(), message)
+            }
+        }
+    }
+}
 
-    let config_path = format!("{}/axfm", config_dir);
-    std::fs::create_dir_all(&config_path)?;
+impl std::error::Error for BookmarksError {}
 
-    let file_path = format!("{}/bookmarks.json", config_path);
+/// Why bookmarks could not be loaded, and what was done about it.
+#[derive(Debug)]
+pub struct LoadFailure {
+    pub error: BookmarksError,
+    /// Where the unreadable file was moved, so that saving doesn't overwrite it.
+    pub kept_as: Option<PathBuf>,
+    pub restored_from_backup: bool,
+}
 
-    // Build JSON manually
-    let mut json = String::from("{\"bookmarks\":[");
-    for (i, bm) in bookmarks.iter().enumerate() {
-        if i > 0 {
-            json.push(',');
+impl fmt::Display for LoadFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        if let Some(path) = &self.kept_as {
+            write!(f, "\n\nThe damaged file was kept as {}.", path.display())?;
+        }
+        if self.restored_from_backup {
+            write!(f, "\nBookmarks were restored from the last backup.")
+        } else if matches!(self.error, BookmarksError::UnsupportedVersion { .. }) {
+            write!(f, "\nThe sidebar starts without bookmarks, and changes to them are not saved.")
+        } else {
+            write!(f, "\nThe sidebar starts without bookmarks.")
         }
-        // Escape quotes and backslashes in name and path
-        let name = bm.name.replace('\\', "\\\\").replace('"', "\\\"");
-        let path = bm.path.replace('\\', "\\\\").replace('"', "\\\"");
-        json.push_str(&format!("{{\"name\":\"{}\",\"path\":\"{}\"}}", name, path));
     }
-    json.push_str("]}");
+}
 
-    std::fs::write(&file_path, json)?;
-    Ok(())
+pub fn bookmarks_dir() -> PathBuf {
+    crate::utils::FMSettings::config_dir()
 }
 
-pub fn load_bookmarks() -> Vec<Bookmark> {
-    match load_bookmarks_impl() {
-        Ok(bookmarks) => bookmarks,
-        Err(e) => {
-            eprintln!("Warning: Could not load bookmarks: {}", e);
-            Vec::new() // Start with empty bookmarks
+/// Loads the bookmarks file, migrating older formats. A corrupt file is moved
+/// aside and replaced by the backup when there is a usable one. A file from
+/// a newer version is left alone, and nothing is loaded.
+pub fn load_bookmarks() -> (BookmarksFile, Option<LoadFailure>) {
+    load_bookmarks_from(&bookmarks_dir())
+}
+
+/// Writes the bookmarks atomically, keeping the previous file as a backup.
+/// A file written by a newer version is not replaced.
+pub fn save_bookmarks(file: &BookmarksFile) -> Result<(), BookmarksError> {
+    save_bookmarks_to(&bookmarks_dir(), file)
+}
+
+/// Orders bookmarks the way the sidebar shows them: ungrouped ones first,
+/// then each group, in order of first appearance. The order within a group
+/// is kept.
+pub fn normalize_order(bookmarks: &mut [Bookmark]) {
+    let mut groups: Vec<String> = Vec::new();
+    for group in bookmarks.iter().filter_map(|b| b.group.as_ref()) {
+        if !groups.contains(group) {
+            groups.push(group.clone());
         }
     }
-}
 
-fn load_bookmarks_impl() -> Result<Vec<Bookmark>, Box<dyn std::error::Error>> {
-    let config_dir = std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        format!("{}/.config", home)
+    bookmarks.sort_by_key(|b| match &b.group {
+        None => 0,
+        Some(group) => 1 + groups.iter().position(|g| g == group).unwrap_or(0),
     });
+}
 
-    let file_path = format!("{}/axfm/bookmarks.json", config_dir);
-
-    if !Path::new(&file_path).exists() {
-        return Ok(Vec::new()); // First run, no bookmarks yet
+/// Moves the bookmark at `from` before the one at `before` (or to the end)
+/// and into `group`.
+pub fn move_bookmark(
+    bookmarks: &mut Vec<Bookmark>,
+    from: usize,
+    before: Option<usize>,
+    group: Option<String>,
+) {
+    if from >= bookmarks.len() {
+        return;
     }
 
-    let content = std::fs::read_to_string(&file_path)?;
+    let mut bookmark = bookmarks.remove(from);
+    bookmark.group = group;
 
-    // Simple manual JSON parsing
-    parse_bookmarks_json(&content)
+    let to = match before {
+        Some(before) if before > from => before - 1,
+        Some(before) => before,
+        None => bookmarks.len(),
+    };
+    bookmarks.insert(to.min(bookmarks.len()), bookmark);
+    normalize_order(bookmarks);
 }
 
-fn parse_bookmarks_json(json: &str) -> Result<Vec<Bookmark>, Box<dyn std::error::Error>> {
-    let mut bookmarks = Vec::new();
+fn load_bookmarks_from(dir: &Path) -> (BookmarksFile, Option<LoadFailure>) {
+    let path = dir.join(BOOKMARKS_FILE);
 
-    // Find "bookmarks" array
-    let start = json.find("[").ok_or("Invalid JSON: missing array start")?;
-    let end = json.rfind("]").ok_or("Invalid JSON: missing array end")?;
+    let error = match read_bookmarks_file(&path) {
+        Ok(None) => return (BookmarksFile::default(), None), // First run, no bookmarks yet
+        Ok(Some((file, version))) => {
+            if version < SCHEMA_VERSION {
+                // The old file stays around as the backup
+                if let Err(e) = save_bookmarks_to(dir, &file) {
+                    eprintln!("Failed to migrate bookmarks: {}", e);
+                }
+            }
+            return (file, None);
+        }
+        // The file may be fine, it just can't be read right now or is for a
+        // newer version: leave it alone
+        Err(error @ (BookmarksError::Io { .. } | BookmarksError::UnsupportedVersion { .. })) => {
+            return (
+                BookmarksFile::default(),
+                Some(LoadFailure { error, kept_as: None, restored_from_backup: false }),
+            );
+        }
+        Err(error) => error,
+    };
 
-    let array_content = &json[start + 1..end];
+    let corrupt_path = dir.join(CORRUPT_FILE);
+    let kept_as = match std::fs::rename(&path, &corrupt_path) {
+        Ok(()) => Some(corrupt_path),
+        Err(e) => {
+            eprintln!("Failed to move {} aside: {}", path.display(), e);
+            None
+        }
+    };
+
+    let backup = match read_bookmarks_file(&dir.join(BACKUP_FILE)) {
+        Ok(Some((file, _))) if kept_as.is_some() => Some(file),
+        _ => None,
+    };
+
+    let restored_from_backup = match &backup {
+        Some(file) => match write_bookmarks_file(&path, file) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{}", e);
+                false
+            }
+        },
+        None => false,
+    };
 
-    if array_content.trim().is_empty() {
-        return Ok(bookmarks);
+    let failure = LoadFailure { error, kept_as, restored_from_backup };
+    (backup.unwrap_or_default(), Some(failure))
+}
+
+fn save_bookmarks_to(dir: &Path, file: &BookmarksFile) -> Result<(), BookmarksError> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| BookmarksError::Write { path: dir.to_path_buf(), message: e.to_string() })?;
+
+    let path = dir.join(BOOKMARKS_FILE);
+
+    // Only a file that still loads is worth keeping as a backup
+    match read_bookmarks_file(&path) {
+        Ok(Some(_)) => {
+            let backup_path = dir.join(BACKUP_FILE);
+            std::fs::copy(&path, &backup_path)
+                .map_err(|e| BookmarksError::Write { path: backup_path, message: e.to_string() })?;
+        }
+        // The newer version that wrote it still needs it
+        Err(error @ BookmarksError::UnsupportedVersion { .. }) => return Err(error),
+        _ => {}
     }
 
-    // Split by object boundaries (simple parsing)
-    let mut depth = 0;
-    let mut current_obj = String::new();
+    write_bookmarks_file(&path, file)
+}
 
-    for ch in array_content.chars() {
-        match ch {
-            '{' => {
-                depth += 1;
-                current_obj.push(ch);
-            }
-            '}' => {
-                depth -= 1;
-                current_obj.push(ch);
-                if depth == 0 && !current_obj.trim().is_empty() {
-                    if let Ok(bookmark) = parse_bookmark_object(&current_obj) {
-                        bookmarks.push(bookmark);
-                    }
-                    current_obj.clear();
-                }
+/// Returns the file contents and the schema version they were stored with,
+/// or `None` when the file doesn't exist.
+fn read_bookmarks_file(path: &Path) -> Result<Option<(BookmarksFile, u64)>, BookmarksError> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+            let message = "the file is not valid UTF-8".to_string();
+            return Err(BookmarksError::Schema { path: path.to_path_buf(), message });
+        }
+        Err(source) => return Err(BookmarksError::Io { path: path.to_path_buf(), source }),
+    };
+
+    let json = json::parse(&content)
+        .map_err(|source| BookmarksError::Parse { path: path.to_path_buf(), source })?;
+
+    decode_bookmarks(&json).map(Some).map_err(|e| match e {
+        DecodeError::Schema(message) => {
+            BookmarksError::Schema { path: path.to_path_buf(), message }
+        }
+        DecodeError::UnsupportedVersion(version) => {
+            BookmarksError::UnsupportedVersion { path: path.to_path_buf(), version }
+        }
+    })
+}
+
+fn write_bookmarks_file(path: &Path, file: &BookmarksFile) -> Result<(), BookmarksError> {
+    // glib writes to a temporary file and renames it over the old one
+    glib::file_set_contents(path, encode_bookmarks(file).to_pretty_string().as_bytes())
+        .map_err(|e| BookmarksError::Write { path: path.to_path_buf(), message: e.to_string() })
+}
+
+#[derive(Debug, PartialEq)]
+enum DecodeError {
+    Schema(String),
+    UnsupportedVersion(u64),
+}
+
+fn encode_bookmarks(file: &BookmarksFile) -> JsonValue {
+    let string = |s: &str| JsonValue::String(s.to_string());
+
+    let entries = file
+        .bookmarks
+        .iter()
+        .map(|bm| {
+            let mut members = vec![
+                ("name".to_string(), string(&bm.name)),
+                ("path".to_string(), string(&bm.path)),
+            ];
+            if let Some(icon) = &bm.icon {
+                members.push(("icon".to_string(), string(icon)));
             }
-            _ => {
-                if depth > 0 {
-                    current_obj.push(ch);
-                }
+            if let Some(group) = &bm.group {
+                members.push(("group".to_string(), string(group)));
             }
-        }
+            JsonValue::Object(members)
+        })
+        .collect();
+
+    let collapsed = file.collapsed_groups.iter().map(|g| string(g)).collect();
+
+    let searches = file
+        .saved_searches
+        .iter()
+        .map(|search| {
+            let members = saved_search_members(search)
+                .into_iter()
+                .filter(|(key, value)| matches!(*key, "name" | "root") || !value.is_empty())
+                .map(|(key, value)| (key.to_string(), string(value)))
+                .collect();
+            JsonValue::Object(members)
+        })
+        .collect();
+
+    JsonValue::Object(vec![
+        ("version".to_string(), JsonValue::Number(SCHEMA_VERSION as f64)),
+        ("bookmarks".to_string(), JsonValue::Array(entries)),
+        ("collapsed-groups".to_string(), JsonValue::Array(collapsed)),
+        ("saved-searches".to_string(), JsonValue::Array(searches)),
+    ])
+}
+
+/// Members of a saved search in the file, by key. Empty ones are left out.
+fn saved_search_members(search: &SavedSearch) -> [(&'static str, &String); 10] {
+    [
+        ("name", &search.name),
+        ("root", &search.root),
+        ("query", &search.query),
+        ("name-mode", &search.name_mode),
+        ("content", &search.content),
+        ("min-size", &search.min_size),
+        ("max-size", &search.max_size),
+        ("modified-after", &search.modified_after),
+        ("modified-before", &search.modified_before),
+        ("content-type", &search.content_type),
+    ]
+}
+
+/// Decodes every supported schema version. Versions 0 and 1 share the same
+/// layout, version 1 only adds the `version` member. Version 2 adds the
+/// optional `icon` and `group` members and the `collapsed-groups` list, and
+/// version 3 the `saved-searches` list.
+fn decode_bookmarks(json: &JsonValue) -> Result<(BookmarksFile, u64), DecodeError> {
+    if !matches!(json, JsonValue::Object(_)) {
+        return Err(DecodeError::Schema("the top level is not an object".to_string()));
     }
 
-    Ok(bookmarks)
+    let version = match json.get("version") {
+        None => 0,
+        Some(value) => value.as_u64().ok_or_else(|| {
+            DecodeError::Schema("\"version\" is not a non-negative integer".to_string())
+        })?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    let entries = json
+        .get("bookmarks")
+        .and_then(JsonValue::as_array)
+        .ok_or_else(|| DecodeError::Schema("missing \"bookmarks\" list".to_string()))?;
+
+    let invalid = |index: usize, key: &str| {
+        DecodeError::Schema(format!("bookmark {}: missing or invalid \"{}\"", index + 1, key))
+    };
+    let field = |entry: &JsonValue, index: usize, key: &str| {
+        entry
+            .get(key)
+            .and_then(JsonValue::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| invalid(index, key))
+    };
+    let optional_field = |entry: &JsonValue, index: usize, key: &str| match entry.get(key) {
+        None => Ok(None),
+        Some(value) => {
+            value.as_str().map(|s| Some(s.to_string())).ok_or_else(|| invalid(index, key))
+        }
+    };
+
+    let mut bookmarks = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            Ok(Bookmark {
+                name: field(entry, index, "name")?,
+                path: field(entry, index, "path")?,
+                icon: optional_field(entry, index, "icon")?,
+                group: optional_field(entry, index, "group")?,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    normalize_order(&mut bookmarks);
+
+    let collapsed_groups = match json.get("collapsed-groups") {
+        None => Vec::new(),
+        Some(value) => value
+            .as_array()
+            .and_then(|groups| groups.iter().map(|g| g.as_str().map(str::to_string)).collect())
+            .ok_or_else(|| {
+                DecodeError::Schema("\"collapsed-groups\" is not a list of names".to_string())
+            })?,
+    };
+
+    let saved_searches = match json.get("saved-searches") {
+        None => Vec::new(),
+        Some(value) => value
+            .as_array()
+            .ok_or_else(|| DecodeError::Schema("\"saved-searches\" is not a list".to_string()))?
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| decode_saved_search(entry, index))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    Ok((BookmarksFile { bookmarks, collapsed_groups, saved_searches }, version))
 }
 
-fn parse_bookmark_object(obj: &str) -> Result<Bookmark, Box<dyn std::error::Error>> {
-    let name = extract_json_field(obj, "name")?;
-    let path = extract_json_field(obj, "path")?;
-    Ok(Bookmark::new(name, path))
+fn decode_saved_search(entry: &JsonValue, index: usize) -> Result<SavedSearch, DecodeError> {
+    let mut search = SavedSearch::default();
+    let keys = saved_search_members(&search).map(|(key, _)| key);
+    let fields = [
+        &mut search.name,
+        &mut search.root,
+        &mut search.query,
+        &mut search.name_mode,
+        &mut search.content,
+        &mut search.min_size,
+        &mut search.max_size,
+        &mut search.modified_after,
+        &mut search.modified_before,
+        &mut search.content_type,
+    ];
+    for (key, field) in keys.into_iter().zip(fields) {
+        let invalid = || {
+            DecodeError::Schema(format!(
+                "saved search {}: missing or invalid \"{}\"",
+                index + 1,
+                key
+            ))
+        };
+        *field = match entry.get(key) {
+            None if matches!(key, "name" | "root") => return Err(invalid()),
+            None => String::new(),
+            Some(value) => value.as_str().ok_or_else(invalid)?.to_string(),
+        };
+    }
+    Ok(search)
 }
 
-fn extract_json_field(obj: &str, field: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let pattern = format!("\"{}\":", field);
-    let start = obj.find(&pattern).ok_or(format!("Field '{}' not found", field))? + pattern.len();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> BookmarksFile {
+        let mut work = Bookmark::new("Projects".to_string(), "/home/user/Projects".to_string());
+        work.icon = Some("applications-development".to_string());
+        work.group = Some("Work".to_string());
+
+        BookmarksFile {
+            bookmarks: vec![
+                Bookmark::new("Quote \" and \\ slash".to_string(), "/tmp/a\\b".to_string()),
+                Bookmark::new("Line\nbreak\ttab".to_string(), "/tmp/line".to_string()),
+                Bookmark::new("Ünïcödé 📁".to_string(), "sftp://host/srv/données".to_string()),
+                work,
+            ],
+            collapsed_groups: vec!["Work".to_string()],
+            saved_searches: vec![SavedSearch {
+                name: "Recent PDFs".to_string(),
+                root: "/home/user/Documents".to_string(),
+                modified_after: "this week".to_string(),
+                content_type: "application/pdf".to_string(),
+                ..SavedSearch::default()
+            }],
+        }
+    }
 
-    let after_colon = &obj[start..].trim_start();
-    if !after_colon.starts_with('"') {
-        return Err("Expected quoted string".into());
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("axfm-bookmarks-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
     }
 
-    let mut value = String::new();
-    let mut escaped = false;
-    let mut chars = after_colon.chars().skip(1);
-
-    while let Some(ch) = chars.next() {
-        if escaped {
-            match ch {
-                'n' => value.push('\n'),
-                't' => value.push('\t'),
-                'r' => value.push('\r'),
-                '\\' => value.push('\\'),
-                '"' => value.push('"'),
-                _ => {
-                    value.push('\\');
-                    value.push(ch);
-                }
-            }
-            escaped = false;
-        } else if ch == '\\' {
-            escaped = true;
-        } else if ch == '"' {
-            break;
-        } else {
-            value.push(ch);
-        }
+    fn names(bookmarks: &[Bookmark]) -> Vec<&str> {
+        bookmarks.iter().map(|b| b.name.as_str()).collect()
     }
 
-    Ok(value)
-}
+    #[test]
+    fn encode_decode_round_trip() {
+        let text = encode_bookmarks(&sample()).to_pretty_string();
+        let decoded = decode_bookmarks(&json::parse(&text).unwrap()).unwrap();
+        assert_eq!(decoded, (sample(), SCHEMA_VERSION));
+    }
 
-pub fn show_manage_bookmarks_dialog(
-    parent: &Window,
-    fmstate: Rc<RefCell<FmState>>,
-    sidebar_list: &StringList,
-) {
-    let dialog = Dialog::builder()
-        .title("Manage Bookmarks")
-        .transient_for(parent)
-        .modal(true)
-        .default_width(400)
-        .default_height(300)
-        .build();
-
-    let content = dialog.content_area();
-
-    let scrolled = ScrolledWindow::builder().vexpand(true).hexpand(true).build();
-
-    let list_box = ListBox::new();
-    list_box.set_selection_mode(gtk4::SelectionMode::None);
-
-    // Populate with current bookmarks
-    let bookmarks = fmstate.borrow().bookmarks.clone();
-    for (index, bookmark) in bookmarks.iter().enumerate() {
-        let row = create_bookmark_row(
-            bookmark,
-            index,
-            fmstate.clone(),
-            sidebar_list.clone(),
-            dialog.clone(),
+    #[test]
+    fn empty_list_round_trip() {
+        let text = encode_bookmarks(&BookmarksFile::default()).to_pretty_string();
+        let decoded = decode_bookmarks(&json::parse(&text).unwrap()).unwrap();
+        assert_eq!(decoded, (BookmarksFile::default(), SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn decodes_unversioned_format() {
+        let legacy = r#"{"bookmarks":[{"name":"Aé\n","path":"/a"},{"name":"B","path":"/b"}]}"#;
+        let (file, version) = decode_bookmarks(&json::parse(legacy).unwrap()).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(file.bookmarks[0], Bookmark::new("Aé\n".to_string(), "/a".to_string()));
+        assert_eq!(file.bookmarks.len(), 2);
+        assert!(file.collapsed_groups.is_empty());
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let json = json::parse(r#"{"version": 99, "bookmarks": []}"#).unwrap();
+        assert_eq!(decode_bookmarks(&json), Err(DecodeError::UnsupportedVersion(99)));
+    }
+
+    #[test]
+    fn reports_invalid_entries_instead_of_dropping_them() {
+        let json =
+            json::parse(r#"{"version":1,"bookmarks":[{"name":"A","path":"/a"},{"name":"B"}]}"#)
+                .unwrap();
+        assert_eq!(
+            decode_bookmarks(&json),
+            Err(DecodeError::Schema("bookmark 2: missing or invalid \"path\"".to_string()))
+        );
+
+        let json = json::parse(r#"{"version":2,"bookmarks":[{"name":"A","path":"/a","icon":1}]}"#)
+            .unwrap();
+        assert_eq!(
+            decode_bookmarks(&json),
+            Err(DecodeError::Schema("bookmark 1: missing or invalid \"icon\"".to_string()))
         );
-        list_box.append(&row);
     }
 
-    if bookmarks.is_empty() {
-        let empty_label = Label::new(Some("No bookmarks yet"));
-        empty_label.set_margin_top(20);
-        empty_label.set_margin_bottom(20);
-        empty_label.add_css_class("dim-label");
-        list_box.append(&empty_label);
+    #[test]
+    fn reports_invalid_saved_searches() {
+        let json = json::parse(
+            r#"{"version":3,"bookmarks":[],"saved-searches":[{"name":"A","root":"/a"},{"name":"B"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            decode_bookmarks(&json),
+            Err(DecodeError::Schema("saved search 2: missing or invalid \"root\"".to_string()))
+        );
     }
 
-    scrolled.set_child(Some(&list_box));
-    content.append(&scrolled);
+    #[test]
+    fn moves_bookmarks_between_positions_and_groups() {
+        let mut bookmarks: Vec<Bookmark> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|n| Bookmark::new(n.to_string(), format!("/{}", n)))
+            .collect();
 
-    dialog.add_button("Close", ResponseType::Close);
+        move_bookmark(&mut bookmarks, 0, Some(3), None);
+        assert_eq!(names(&bookmarks), ["b", "c", "a", "d"]);
 
-    dialog.connect_response(move |dialog, response| {
-        if response == ResponseType::Close {
-            dialog.close();
-        }
-    });
+        move_bookmark(&mut bookmarks, 3, Some(0), None);
+        assert_eq!(names(&bookmarks), ["d", "b", "c", "a"]);
 
-    dialog.present();
-}
+        // Grouped bookmarks come after ungrouped ones
+        move_bookmark(&mut bookmarks, 0, None, Some("G".to_string()));
+        assert_eq!(names(&bookmarks), ["b", "c", "a", "d"]);
+        move_bookmark(&mut bookmarks, 0, Some(3), Some("G".to_string()));
+        assert_eq!(names(&bookmarks), ["c", "a", "b", "d"]);
+    }
 
-fn create_bookmark_row(
-    bookmark: &Bookmark,
-    index: usize,
-    fmstate: Rc<RefCell<FmState>>,
-    sidebar_list: StringList,
-    dialog: Dialog,
-) -> ListBoxRow {
-    let row = ListBoxRow::new();
-
-    let hbox = GtkBox::new(Orientation::Horizontal, 12);
-    hbox.set_margin_start(12);
-    hbox.set_margin_end(12);
-    hbox.set_margin_top(6);
-    hbox.set_margin_bottom(6);
-
-    // Icon
-    let icon = gtk4::Image::from_icon_name("starred");
-    icon.set_pixel_size(24);
-
-    // Labels box
-    let vbox = GtkBox::new(Orientation::Vertical, 2);
-    vbox.set_hexpand(true);
-
-    let name_label = Label::new(Some(&bookmark.name));
-    name_label.set_xalign(0.0);
-    name_label.add_css_class("heading");
-
-    let path_label = Label::new(Some(&bookmark.path));
-    path_label.set_xalign(0.0);
-    path_label.add_css_class("dim-label");
-    path_label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
-
-    vbox.append(&name_label);
-    vbox.append(&path_label);
-
-    // Delete button
-    let delete_button = Button::builder()
-        .icon_name("user-trash-symbolic")
-        .tooltip_text("Remove bookmark")
-        .valign(gtk4::Align::Center)
-        .build();
-
-    delete_button.connect_clicked(glib::clone!(
-        #[strong]
-        fmstate,
-        #[strong]
-        sidebar_list,
-        #[weak]
-        dialog,
-        move |_| {
-            if let Err(e) = fmstate.borrow_mut().remove_bookmark(index) {
-                eprintln!("Failed to remove bookmark: {}", e);
-            } else {
-                // Refresh sidebar
-                crate::sidebar::refresh_sidebar(&sidebar_list, &fmstate);
-                // Close and reopen dialog to refresh list
-                dialog.close();
-                dialog.destroy();
-            }
-        }
-    ));
+    #[test]
+    fn save_load_round_trip() {
+        let dir = temp_dir("round-trip");
+        save_bookmarks_to(&dir, &sample()).unwrap();
+
+        let (file, failure) = load_bookmarks_from(&dir);
+        assert!(failure.is_none());
+        assert_eq!(file, sample());
+        assert!(!dir.join(BACKUP_FILE).exists());
 
-    hbox.append(&icon);
-    hbox.append(&vbox);
-    hbox.append(&delete_button);
+        save_bookmarks_to(&dir, &BookmarksFile::default()).unwrap();
+        let (backup, _) = read_bookmarks_file(&dir.join(BACKUP_FILE)).unwrap().unwrap();
+        assert_eq!(backup, sample());
 
-    row.set_child(Some(&hbox));
-    row
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn migrates_older_files() {
+        let dir = temp_dir("migrate");
+        let legacy = r#"{"bookmarks":[{"name":"Docs","path":"/home/user/Docs"}]}"#;
+        std::fs::write(dir.join(BOOKMARKS_FILE), legacy).unwrap();
+
+        let (file, failure) = load_bookmarks_from(&dir);
+        assert!(failure.is_none());
+        assert_eq!(file.bookmarks.len(), 1);
+
+        let (_, version) = read_bookmarks_file(&dir.join(BOOKMARKS_FILE)).unwrap().unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        assert_eq!(std::fs::read_to_string(dir.join(BACKUP_FILE)).unwrap(), legacy);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupt_file_is_kept_and_backup_restored() {
+        let dir = temp_dir("corrupt");
+        save_bookmarks_to(&dir, &sample()).unwrap();
+        save_bookmarks_to(&dir, &sample()).unwrap();
+        std::fs::write(dir.join(BOOKMARKS_FILE), "{\"bookmarks\": [").unwrap();
+
+        let (file, failure) = load_bookmarks_from(&dir);
+        let failure = failure.expect("corruption should be reported");
+        assert!(matches!(failure.error, BookmarksError::Parse { .. }));
+        assert!(failure.restored_from_backup);
+        assert_eq!(failure.kept_as, Some(dir.join(CORRUPT_FILE)));
+        assert_eq!(file, sample());
+
+        let (file, failure) = load_bookmarks_from(&dir);
+        assert!(failure.is_none());
+        assert_eq!(file, sample());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn newer_file_is_left_alone() {
+        let dir = temp_dir("newer");
+        save_bookmarks_to(&dir, &sample()).unwrap();
+        save_bookmarks_to(&dir, &sample()).unwrap();
+        let newer = r#"{"version": 99, "bookmarks": []}"#;
+        std::fs::write(dir.join(BOOKMARKS_FILE), newer).unwrap();
+
+        let (file, failure) = load_bookmarks_from(&dir);
+        let failure = failure.expect("the newer version should be reported");
+        assert!(matches!(failure.error, BookmarksError::UnsupportedVersion { version: 99, .. }));
+        assert!(!failure.restored_from_backup);
+        assert_eq!(failure.kept_as, None);
+        assert!(file.bookmarks.is_empty());
+        assert!(!dir.join(CORRUPT_FILE).exists());
+
+        let saved = save_bookmarks_to(&dir, &sample());
+        assert!(matches!(saved, Err(BookmarksError::UnsupportedVersion { .. })));
+        assert_eq!(std::fs::read_to_string(dir.join(BOOKMARKS_FILE)).unwrap(), newer);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupt_file_without_backup() {
+        let dir = temp_dir("no-backup");
+        std::fs::write(dir.join(BOOKMARKS_FILE), "not json").unwrap();
+
+        let (file, failure) = load_bookmarks_from(&dir);
+        let failure = failure.expect("corruption should be reported");
+        assert!(!failure.restored_from_backup);
+        assert!(file.bookmarks.is_empty());
+        assert!(!dir.join(BOOKMARKS_FILE).exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,353 @@
+use crate::{
+    bookmarks::{Bookmark, BookmarksError},
+    state::FmState,
+};
+use gtk4::{
+    Box as GtkBox, Button, Dialog, Entry, Grid, Label, ListBox, ListBoxRow, MenuButton,
+    Orientation, Popover, ResponseType, ScrolledWindow, Window, gdk, gio, glib, prelude::*,
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// Icons offered for bookmarks, the first one is the default.
+const BOOKMARK_ICONS: &[&str] = &[
+    "starred",
+    "folder",
+    "user-home",
+    "user-desktop",
+    "folder-documents",
+    "folder-download",
+    "folder-music",
+    "folder-pictures",
+    "folder-videos",
+    "folder-remote",
+    "folder-publicshare",
+    "folder-templates",
+    "drive-harddisk",
+    "network-server",
+    "applications-development",
+    "emblem-important",
+];
+
+pub fn show_manage_bookmarks_dialog(
+    parent: &Window,
+    fmstate: Rc<RefCell<FmState>>,
+    sidebar_list: &gio::ListStore,
+) {
+    let dialog = Dialog::builder()
+        .title("Manage Bookmarks")
+        .transient_for(parent)
+        .modal(true)
+        .default_width(560)
+        .default_height(360)
+        .build();
+
+    let content = dialog.content_area();
+
+    let scrolled = ScrolledWindow::builder().vexpand(true).hexpand(true).build();
+
+    let list_box = ListBox::new();
+    list_box.set_selection_mode(gtk4::SelectionMode::None);
+    populate_rows(&list_box, &fmstate, sidebar_list);
+
+    scrolled.set_child(Some(&list_box));
+    content.append(&scrolled);
+
+    let hint = Label::new(Some(concat!(
+        "Drag bookmarks to reorder them. Bookmarks sharing a group name ",
+        "are shown in a collapsible folder."
+    )));
+    hint.set_wrap(true);
+    hint.set_xalign(0.0);
+    hint.set_margin_start(12);
+    hint.set_margin_end(12);
+    hint.set_margin_top(6);
+    hint.add_css_class("dim-label");
+    content.append(&hint);
+
+    dialog.add_button("Close", ResponseType::Close);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Close {
+            dialog.close();
+        }
+    });
+
+    dialog.present();
+}
+
+fn populate_rows(
+    list_box: &ListBox,
+    fmstate: &Rc<RefCell<FmState>>,
+    sidebar_list: &gio::ListStore,
+) {
+    while let Some(child) = list_box.first_child() {
+        list_box.remove(&child);
+    }
+
+    let bookmarks = fmstate.borrow().bookmarks.clone();
+    for bookmark in bookmarks.iter() {
+        let row = create_bookmark_row(bookmark, list_box, fmstate, sidebar_list);
+        list_box.append(&row);
+    }
+
+    if bookmarks.is_empty() {
+        let empty_label = Label::new(Some("No bookmarks yet"));
+        empty_label.set_margin_top(20);
+        empty_label.set_margin_bottom(20);
+        empty_label.add_css_class("dim-label");
+        list_box.append(&empty_label);
+    }
+}
+
+/// Reports the outcome of a change, then refreshes the sidebar and the rows.
+fn finish_change(
+    result: Result<(), BookmarksError>,
+    list_box: &ListBox,
+    fmstate: &Rc<RefCell<FmState>>,
+    sidebar_list: &gio::ListStore,
+) {
+    if let Err(e) = result {
+        eprintln!("Failed to update bookmarks: {}", e);
+    }
+    crate::sidebar::refresh_sidebar(sidebar_list, fmstate);
+
+    // The rows are rebuilt once the signal that triggered the change returns
+    glib::idle_add_local_once(glib::clone!(
+        #[weak]
+        list_box,
+        #[weak]
+        sidebar_list,
+        #[strong]
+        fmstate,
+        move || populate_rows(&list_box, &fmstate, &sidebar_list)
+    ));
+}
+
+fn create_bookmark_row(
+    bookmark: &Bookmark,
+    list_box: &ListBox,
+    fmstate: &Rc<RefCell<FmState>>,
+    sidebar_list: &gio::ListStore,
+) -> ListBoxRow {
+    let row = ListBoxRow::new();
+
+    let hbox = GtkBox::new(Orientation::Horizontal, 12);
+    hbox.set_margin_start(12);
+    hbox.set_margin_end(12);
+    hbox.set_margin_top(6);
+    hbox.set_margin_bottom(6);
+
+    let handle = gtk4::Image::from_icon_name("list-drag-handle-symbolic");
+    handle.add_css_class("dim-label");
+
+    // Icon picker
+    let icon_button = MenuButton::new();
+    icon_button.set_valign(gtk4::Align::Center);
+    icon_button.set_tooltip_text(Some("Change icon"));
+    icon_button.set_icon_name(bookmark.icon_name());
+    icon_button.set_popover(Some(&create_icon_popover(bookmark, list_box, fmstate, sidebar_list)));
+
+    // Name and location
+    let vbox = GtkBox::new(Orientation::Vertical, 2);
+    vbox.set_hexpand(true);
+
+    let name_entry = Entry::new();
+    name_entry.set_text(&bookmark.name);
+    name_entry.set_tooltip_text(Some("Bookmark name"));
+
+    let path_label = Label::new(Some(&bookmark.path));
+    path_label.set_xalign(0.0);
+    path_label.add_css_class("dim-label");
+    path_label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+
+    vbox.append(&name_entry);
+    vbox.append(&path_label);
+
+    let commit_name = glib::clone!(
+        #[weak]
+        list_box,
+        #[strong]
+        fmstate,
+        #[strong]
+        sidebar_list,
+        #[strong(rename_to = path)]
+        bookmark.path,
+        #[strong(rename_to = original)]
+        bookmark.name,
+        move |entry: &Entry| {
+            let name = entry.text().trim().to_string();
+            if name.is_empty() {
+                entry.add_css_class("error");
+                return;
+            }
+            entry.remove_css_class("error");
+            if name == original {
+                return;
+            }
+
+            let result = fmstate.borrow_mut().update_bookmark(&path, |b| b.name = name);
+            finish_change(result, &list_box, &fmstate, &sidebar_list);
+        }
+    );
+    connect_commit(&name_entry, commit_name);
+
+    // Group
+    let group_entry = Entry::new();
+    group_entry.set_text(bookmark.group.as_deref().unwrap_or(""));
+    group_entry.set_placeholder_text(Some("No group"));
+    group_entry.set_width_chars(10);
+    group_entry.set_valign(gtk4::Align::Center);
+    group_entry.set_tooltip_text(Some("Sidebar folder"));
+
+    let commit_group = glib::clone!(
+        #[weak]
+        list_box,
+        #[strong]
+        fmstate,
+        #[strong]
+        sidebar_list,
+        #[strong(rename_to = path)]
+        bookmark.path,
+        #[strong(rename_to = original)]
+        bookmark.group,
+        move |entry: &Entry| {
+            let text = entry.text().trim().to_string();
+            let group = (!text.is_empty()).then_some(text);
+            if group == original {
+                return;
+            }
+
+            let result = fmstate.borrow_mut().move_bookmark(&path, None, group);
+            finish_change(result, &list_box, &fmstate, &sidebar_list);
+        }
+    );
+    connect_commit(&group_entry, commit_group);
+
+    // Delete button
+    let delete_button = Button::builder()
+        .icon_name("user-trash-symbolic")
+        .tooltip_text("Remove bookmark")
+        .valign(gtk4::Align::Center)
+        .build();
+
+    delete_button.connect_clicked(glib::clone!(
+        #[weak]
+        list_box,
+        #[strong]
+        fmstate,
+        #[strong]
+        sidebar_list,
+        #[strong(rename_to = path)]
+        bookmark.path,
+        move |_| {
+            let index = fmstate.borrow().bookmarks.iter().position(|b| b.path == path);
+            if let Some(index) = index {
+                let result = fmstate.borrow_mut().remove_bookmark(index);
+                finish_change(result, &list_box, &fmstate, &sidebar_list);
+            }
+        }
+    ));
+
+    // Reordering: drop a row onto another to move it there
+    let drag_source = gtk4::DragSource::new();
+    drag_source.set_actions(gdk::DragAction::MOVE);
+    drag_source.set_content(Some(&gdk::ContentProvider::for_value(&bookmark.path.to_value())));
+    row.add_controller(drag_source);
+
+    let drop_target = gtk4::DropTarget::new(String::static_type(), gdk::DragAction::MOVE);
+    drop_target.connect_drop(glib::clone!(
+        #[weak]
+        list_box,
+        #[strong]
+        fmstate,
+        #[strong]
+        sidebar_list,
+        #[strong(rename_to = path)]
+        bookmark.path,
+        #[strong(rename_to = group)]
+        bookmark.group,
+        #[upgrade_or]
+        false,
+        move |_, value, _, _| {
+            let Ok(dragged) = value.get::<String>() else {
+                return false;
+            };
+            if dragged == path {
+                return false;
+            }
+
+            let result = fmstate.borrow_mut().move_bookmark(&dragged, Some(&path), group.clone());
+            finish_change(result, &list_box, &fmstate, &sidebar_list);
+            true
+        }
+    ));
+    row.add_controller(drop_target);
+
+    hbox.append(&handle);
+    hbox.append(&icon_button);
+    hbox.append(&vbox);
+    hbox.append(&group_entry);
+    hbox.append(&delete_button);
+
+    row.set_child(Some(&hbox));
+    row
+}
+
+fn create_icon_popover(
+    bookmark: &Bookmark,
+    list_box: &ListBox,
+    fmstate: &Rc<RefCell<FmState>>,
+    sidebar_list: &gio::ListStore,
+) -> Popover {
+    let popover = Popover::new();
+    let grid = Grid::new();
+    grid.set_row_spacing(4);
+    grid.set_column_spacing(4);
+
+    for (i, icon_name) in BOOKMARK_ICONS.iter().enumerate() {
+        let button = Button::from_icon_name(icon_name);
+        button.add_css_class("flat");
+        if *icon_name == bookmark.icon_name() {
+            button.add_css_class("suggested-action");
+        }
+
+        button.connect_clicked(glib::clone!(
+            #[weak]
+            list_box,
+            #[weak]
+            popover,
+            #[strong]
+            fmstate,
+            #[strong]
+            sidebar_list,
+            #[strong(rename_to = path)]
+            bookmark.path,
+            move |_| {
+                popover.popdown();
+
+                // The default icon is stored as no icon at all
+                let icon = (*icon_name != BOOKMARK_ICONS[0]).then(|| icon_name.to_string());
+                let result = fmstate.borrow_mut().update_bookmark(&path, |b| b.icon = icon);
+                finish_change(result, &list_box, &fmstate, &sidebar_list);
+            }
+        ));
+
+        grid.attach(&button, (i % 4) as i32, (i / 4) as i32, 1, 1);
+    }
+
+    popover.set_child(Some(&grid));
+    popover
+}
+
+/// Calls `commit` when Enter is pressed or the entry loses focus.
+fn connect_commit(entry: &Entry, commit: impl Fn(&Entry) + Clone + 'static) {
+    entry.connect_activate(commit.clone());
+
+    let focus = gtk4::EventControllerFocus::new();
+    focus.connect_leave(glib::clone!(
+        #[weak]
+        entry,
+        move |_| commit(&entry)
+    ));
+    entry.add_controller(focus);
+}
@@ -0,0 +1,186 @@
+//! Command-line handling: opening locations in new or existing windows and
+//! the `axfm config` subcommand.
+
+use crate::{fm_window::FmWindow, utils::FMSettings};
+use gtk4::{Application, gio, glib, prelude::*};
+use std::{ops::ControlFlow, path::PathBuf};
+
+const CONFIG_USAGE: &str = "Usage: axfm config list
+       axfm config get KEY
+       axfm config set KEY VALUE";
+
+pub fn add_main_options(app: &Application) {
+    app.add_main_option(
+        "new-window",
+        glib::Char::from(b'w'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Open a new window",
+        None,
+    );
+    app.add_main_option(
+        "tab",
+        glib::Char::from(b't'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Open the first location in the current window",
+        None,
+    );
+    app.add_main_option(
+        "select",
+        glib::Char::from(b's'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Filename,
+        "Reveal and highlight FILE in its folder",
+        Some("FILE"),
+    );
+
+    app.set_option_context_parameter_string(Some("[LOCATION…]"));
+    app.set_option_context_summary(Some(
+        "Manage settings with:\n  axfm config list|get KEY|set KEY VALUE",
+    ));
+
+    // Reject conflicting flags in the calling process, before any window opens
+    app.connect_handle_local_options(|_, options| {
+        if options.contains("new-window") && options.contains("tab") {
+            eprintln!("--new-window and --tab cannot be used together");
+            return ControlFlow::Break(glib::ExitCode::FAILURE);
+        }
+        ControlFlow::Continue(())
+    });
+}
+
+pub fn handle_command_line(
+    app: &Application,
+    command_line: &gio::ApplicationCommandLine,
+) -> glib::ExitCode {
+    let options = command_line.options_dict();
+    let new_window = options.contains("new-window");
+    let in_current_window = options.contains("tab");
+    let select = options
+        .lookup::<PathBuf>("select")
+        .ok()
+        .flatten()
+        .map(|path| command_line.create_file_for_arg(path));
+
+    // Parsed options are already stripped, only the locations remain
+    let locations: Vec<gio::File> = command_line
+        .arguments()
+        .iter()
+        .skip(1)
+        .map(|arg| command_line.create_file_for_arg(arg))
+        .collect();
+
+    if in_current_window {
+        if let Some(fm_window) = FmWindow::active(app) {
+            // Without tabs the current window takes the first location, and
+            // the others get windows of their own
+            if let Some((first, others)) = locations.split_first() {
+                fm_window.open_location(first);
+                for location in others {
+                    open_file_in_new_window(app, location);
+                }
+            }
+            if let Some(file) = &select {
+                fm_window.reveal(file);
+            }
+            fm_window.window.present();
+            return glib::ExitCode::SUCCESS;
+        }
+    }
+
+    let windows: Vec<FmWindow> =
+        locations.iter().filter_map(|location| open_file_in_new_window(app, location)).collect();
+
+    if let Some(file) = &select {
+        let parent = file.parent();
+        let shows_parent = |fm_window: &FmWindow| {
+            parent.as_ref().is_some_and(|p| fm_window.fmstate.borrow().current_path.equal(p))
+        };
+
+        // A window just opened at the file's folder reveals it, otherwise
+        // the current one does unless new windows were asked for
+        if let Some(fm_window) = windows.iter().find(|w| shows_parent(w)) {
+            fm_window.select_file(file);
+            fm_window.window.present();
+        } else {
+            let fm_window = match FmWindow::active(app) {
+                Some(fm_window) if !new_window && locations.is_empty() => fm_window,
+                _ => crate::build_fm(app, parent.as_ref().unwrap_or(file)),
+            };
+            fm_window.reveal(file);
+            fm_window.window.present();
+        }
+    } else if locations.is_empty() {
+        if new_window {
+            crate::build_fm(app, &gio::File::for_path(glib::home_dir()));
+        } else {
+            app.activate();
+        }
+    }
+
+    glib::ExitCode::SUCCESS
+}
+
+/// Opens a folder in a new window. Regular files are revealed in their
+/// parent folder instead. Returns the new window.
+pub fn open_file_in_new_window(app: &Application, file: &gio::File) -> Option<FmWindow> {
+    let is_dir = file.query_file_type(gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE)
+        == gio::FileType::Directory;
+
+    if is_dir {
+        Some(crate::build_fm(app, file))
+    } else if let Some(parent) = file.parent() {
+        let fm_window = crate::build_fm(app, &parent);
+        fm_window.select_file(file);
+        Some(fm_window)
+    } else {
+        eprintln!("Cannot open location: {}", file.uri());
+        None
+    }
+}
+
+/// Whether `args` run the `axfm config` subcommand. A file or folder named
+/// `config` in the working directory is opened instead, like any location.
+pub fn is_config_command(args: &[String]) -> bool {
+    args.get(1).map(String::as_str) == Some("config")
+        && std::fs::symlink_metadata("config").is_err()
+}
+
+/// Runs `axfm config <list|get|set>` and returns the process exit code.
+pub fn run_config_command(args: &[String]) -> glib::ExitCode {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let settings = match FMSettings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}", e);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
+    let result = match args.as_slice() {
+        ["list"] => {
+            for (key, value) in settings.entries() {
+                println!("{}={}", key, value);
+            }
+            Ok(())
+        }
+        ["get", key] => settings.value_of(key).map(|value| println!("{}", value)),
+        ["set", key, value] => {
+            settings.with_value(key, value).and_then(|new_settings| new_settings.save())
+        }
+        _ => {
+            eprintln!("{}", CONFIG_USAGE);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(()) => glib::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            glib::ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Dialog asking for the name and format of a new archive.
+
+use crate::{
+    archive::ArchiveFormat,
+    batch_rename::{self, split_extension},
+    file_operations,
+    fm_window::FmWindow,
+};
+use gtk4::{Dialog, DropDown, Entry, Label, Orientation, ResponseType, gio, glib, prelude::*};
+
+const FORMAT_LABELS: [&str; 5] = [
+    "Zip (.zip) — opens everywhere",
+    "Tar (.tar) — no compression",
+    "Tar Gzip (.tar.gz)",
+    "Tar XZ (.tar.xz) — smallest, slowest",
+    "Tar Zstandard (.tar.zst) — fast",
+];
+
+/// Asks where to put the archive of `files`, then creates it in the
+/// background. `files` all belong to the current folder.
+pub fn show_compress_dialog(fm_window: &FmWindow, files: Vec<gio::File>) {
+    let Some(dir) = files.first().and_then(|file| file.parent()) else {
+        return;
+    };
+
+    let default_name = match files.as_slice() {
+        [file] => {
+            let name =
+                file.basename().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let is_dir = file
+                .query_file_type(gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE)
+                == gio::FileType::Directory;
+            split_extension(&name, is_dir).0.to_string()
+        }
+        _ => "Archive".to_string(),
+    };
+
+    let dialog = Dialog::builder()
+        .title(match files.len() {
+            1 => "Compress".to_string(),
+            count => format!("Compress {} Items", count),
+        })
+        .transient_for(&fm_window.window)
+        .modal(true)
+        .default_width(420)
+        .build();
+
+    let content = dialog.content_area();
+    content.set_orientation(Orientation::Vertical);
+    content.set_spacing(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+
+    let name_entry = Entry::new();
+    name_entry.set_text(&default_name);
+    name_entry.set_activates_default(true);
+    let format = DropDown::from_strings(&FORMAT_LABELS);
+    let problem = Label::new(None);
+    problem.set_xalign(0.0);
+    problem.add_css_class("error");
+    problem.set_visible(false);
+
+    let name_label = Label::new(Some("Archive name:"));
+    name_label.set_xalign(0.0);
+
+    content.append(&name_label);
+    content.append(&name_entry);
+    content.append(&format);
+    content.append(&problem);
+
+    dialog.add_button("Cancel", ResponseType::Cancel);
+    let create_button = dialog.add_button("Create", ResponseType::Accept);
+    create_button.add_css_class("suggested-action");
+    dialog.set_default_response(ResponseType::Accept);
+
+    // The target name, or why it can't be used
+    let target = glib::clone!(
+        #[strong]
+        dir,
+        #[weak]
+        name_entry,
+        #[weak]
+        format,
+        #[upgrade_or]
+        Err(String::new()),
+        move || -> Result<(gio::File, ArchiveFormat), String> {
+            let format = ArchiveFormat::ALL
+                .get(format.selected() as usize)
+                .copied()
+                .unwrap_or(ArchiveFormat::Zip);
+            let name = format!("{}{}", name_entry.text().trim(), format.extension());
+            if name_entry.text().trim().is_empty() {
+                return Err("Enter a name for the archive".to_string());
+            }
+            if let Some(problem) = batch_rename::validate_name(&name) {
+                return Err(problem);
+            }
+            let file = dir.child(&name);
+            if file.query_exists(gio::Cancellable::NONE) {
+                return Err(format!("\"{}\" already exists", name));
+            }
+            Ok((file, format))
+        }
+    );
+
+    let refresh = glib::clone!(
+        #[strong]
+        target,
+        #[weak]
+        problem,
+        #[weak]
+        create_button,
+        move || {
+            let result = target();
+            if let Err(text) = &result {
+                problem.set_text(text);
+            }
+            problem.set_visible(result.as_ref().is_err_and(|text| !text.is_empty()));
+            create_button.set_sensitive(result.is_ok());
+        }
+    );
+    name_entry.connect_changed(glib::clone!(
+        #[strong]
+        refresh,
+        move |_| refresh()
+    ));
+    format.connect_selected_notify(glib::clone!(
+        #[strong]
+        refresh,
+        move |_| refresh()
+    ));
+    refresh();
+
+    dialog.connect_response(glib::clone!(
+        #[strong]
+        fm_window,
+        move |dialog, response| {
+            if response == ResponseType::Accept {
+                let Ok((archive, format)) = target() else {
+                    return;
+                };
+                file_operations::compress(&fm_window, files.clone(), archive, format);
+            }
+            dialog.close();
+        }
+    ));
+
+    dialog.present();
+    name_entry.grab_focus();
+}
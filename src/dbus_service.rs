@@ -0,0 +1,187 @@
+//! `org.freedesktop.FileManager1` D-Bus interface.
+//!
+//! Other applications use it for "Show in folder" style actions. It is served
+//! on the same session bus connection as the `org.filemanager.axfm`
+//! application, by the primary instance only.
+
+use crate::fm_window::FmWindow;
+use gtk4::{Application, gio, gio::prelude::DBusMethodCall, glib, prelude::*};
+
+const BUS_NAME: &str = "org.freedesktop.FileManager1";
+const OBJECT_PATH: &str = "/org/freedesktop/FileManager1";
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="org.freedesktop.FileManager1">
+    <method name="ShowFolders">
+      <arg type="as" name="URIs" direction="in"/>
+      <arg type="s" name="StartupId" direction="in"/>
+    </method>
+    <method name="ShowItems">
+      <arg type="as" name="URIs" direction="in"/>
+      <arg type="s" name="StartupId" direction="in"/>
+    </method>
+    <method name="ShowItemProperties">
+      <arg type="as" name="URIs" direction="in"/>
+      <arg type="s" name="StartupId" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+#[derive(Debug)]
+enum FileManagerCall {
+    ShowFolders { uris: Vec<String>, startup_id: String },
+    ShowItems { uris: Vec<String>, startup_id: String },
+    ShowItemProperties { uris: Vec<String>, startup_id: String },
+}
+
+impl DBusMethodCall for FileManagerCall {
+    fn parse_call(
+        _obj_path: &str,
+        _interface: Option<&str>,
+        method: &str,
+        params: glib::Variant,
+    ) -> Result<Self, glib::Error> {
+        let (uris, startup_id) = params.get::<(Vec<String>, String)>().ok_or_else(|| {
+            glib::Error::new(
+                gio::DBusError::InvalidArgs,
+                "Expected a list of URIs and a startup id",
+            )
+        })?;
+
+        match method {
+            "ShowFolders" => Ok(FileManagerCall::ShowFolders { uris, startup_id }),
+            "ShowItems" => Ok(FileManagerCall::ShowItems { uris, startup_id }),
+            "ShowItemProperties" => Ok(FileManagerCall::ShowItemProperties { uris, startup_id }),
+            _ => Err(glib::Error::new(
+                gio::DBusError::UnknownMethod,
+                &format!("Unknown method {}", method),
+            )),
+        }
+    }
+}
+
+/// Exports the interface and requests the well-known name. Called on startup
+/// of the primary instance.
+pub fn register(app: &Application) {
+    let Some(connection) = app.dbus_connection() else {
+        return;
+    };
+
+    let interface_info = match gio::DBusNodeInfo::for_xml(INTERFACE_XML)
+        .map(|node| node.lookup_interface(BUS_NAME))
+    {
+        Ok(Some(info)) => info,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("Invalid D-Bus interface description: {}", e);
+            return;
+        }
+    };
+
+    let registration = connection
+        .register_object(OBJECT_PATH, &interface_info)
+        .typed_method_call::<FileManagerCall>()
+        .invoke_and_return(glib::clone!(
+            #[weak]
+            app,
+            #[upgrade_or]
+            Err(glib::Error::new(gio::DBusError::Failed, "Application is shutting down")),
+            move |_, _, call| {
+                handle_call(&app, call);
+                Ok(None)
+            }
+        ))
+        .build();
+
+    if let Err(e) = registration {
+        eprintln!("Failed to export {}: {}", BUS_NAME, e);
+        return;
+    }
+
+    // Another file manager may already own the name; it can be taken over later
+    gio::bus_own_name_on_connection(
+        &connection,
+        BUS_NAME,
+        gio::BusNameOwnerFlags::ALLOW_REPLACEMENT,
+        |_, _| {},
+        |_, name| eprintln!("D-Bus name {} is owned by another application", name),
+    );
+}
+
+fn handle_call(app: &Application, call: FileManagerCall) {
+    match call {
+        FileManagerCall::ShowFolders { uris, startup_id } => {
+            for (index, uri) in uris.iter().enumerate() {
+                let folder = gio::File::for_uri(uri);
+                let fm_window = window_for_location(app, index == 0, &folder);
+                present(&fm_window, &startup_id);
+            }
+        }
+        FileManagerCall::ShowItems { uris, startup_id } => {
+            for (index, (parent, items)) in group_by_parent(&uris).into_iter().enumerate() {
+                let fm_window = window_for_location(app, index == 0, &parent);
+                fm_window.select_files(&items);
+                present(&fm_window, &startup_id);
+            }
+        }
+        FileManagerCall::ShowItemProperties { uris, startup_id } => {
+            for (index, (parent, items)) in group_by_parent(&uris).into_iter().enumerate() {
+                let fm_window = window_for_location(app, index == 0, &parent);
+                present(&fm_window, &startup_id);
+
+                for item in &items {
+                    fm_window.select_file(item);
+
+                    let Some(path) = item.path() else {
+                        eprintln!("Properties are only available for local files: {}", item.uri());
+                        continue;
+                    };
+                    let size_units = fm_window.fmstate.borrow().settings.size_units;
+                    crate::properties_dialog::show_properties_dialog(
+                        fm_window.window.upcast_ref::<gtk4::Window>(),
+                        &path.display().to_string(),
+                        size_units,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Reuses the active window for the first location of a request, and opens
+/// new windows for the others.
+fn window_for_location(app: &Application, reuse: bool, location: &gio::File) -> FmWindow {
+    match FmWindow::active(app).filter(|_| reuse) {
+        Some(fm_window) => {
+            fm_window.open_location(location);
+            fm_window
+        }
+        None => crate::build_fm(app, location),
+    }
+}
+
+fn present(fm_window: &FmWindow, startup_id: &str) {
+    if !startup_id.is_empty() {
+        fm_window.window.set_startup_id(startup_id);
+    }
+    fm_window.window.present();
+}
+
+/// Groups item URIs by their parent folder, keeping the order of the request.
+fn group_by_parent(uris: &[String]) -> Vec<(gio::File, Vec<gio::File>)> {
+    let mut groups: Vec<(gio::File, Vec<gio::File>)> = Vec::new();
+
+    for uri in uris {
+        let file = gio::File::for_uri(uri);
+        let parent = file.parent().unwrap_or_else(|| file.clone());
+
+        match groups.iter_mut().find(|(folder, _)| folder.equal(&parent)) {
+            Some((_, items)) => items.push(file),
+            None => groups.push((parent, vec![file])),
+        }
+    }
+
+    groups
+}
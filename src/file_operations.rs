@@ -0,0 +1,501 @@
+//! File operations that run in the background: permanent deletion,
+//! compressing and extracting archives.
+
+use crate::{
+    archive::{self, ArchiveFormat, ConflictPolicy, ExtractReport},
+    archive_folder::ArchiveLocation,
+    fm_window::FmWindow,
+    footer_bar::format_size,
+    operations::OperationContext,
+    utils::SizeUnits,
+};
+use gtk4::{gio, glib, prelude::*};
+use std::{io, path::PathBuf};
+
+/// How many names the confirmation dialog lists before summarizing.
+const MAX_LISTED_ITEMS: usize = 10;
+
+/// Deletes `files` without going through the trash, after asking the user
+/// when `confirm` is set. `on_done` runs once the deletion has finished.
+pub fn delete_permanently<F: Fn() + 'static>(
+    fm_window: &FmWindow,
+    files: Vec<gio::File>,
+    confirm: bool,
+    on_done: F,
+) {
+    if files.is_empty() {
+        return;
+    }
+
+    if !confirm {
+        start_delete(fm_window, files, on_done);
+        return;
+    }
+
+    let title = match files.as_slice() {
+        [file] => format!("Permanently delete \"{}\"?", display_name(file)),
+        _ => format!("Permanently delete {} items?", files.len()),
+    };
+
+    let dialog = gtk4::MessageDialog::builder()
+        .transient_for(&fm_window.window)
+        .modal(true)
+        .message_type(gtk4::MessageType::Warning)
+        .text(title)
+        .secondary_text(confirmation_details(&files, None))
+        .build();
+
+    dialog.add_button("Cancel", gtk4::ResponseType::Cancel);
+    let delete_button = dialog.add_button("Delete Permanently", gtk4::ResponseType::Accept);
+    delete_button.add_css_class("destructive-action");
+    dialog.set_default_response(gtk4::ResponseType::Cancel);
+
+    // Measuring a large tree takes a while, the total is filled in when known
+    let size_units = fm_window.fmstate.borrow().settings.size_units;
+    let measured_files = files.clone();
+    glib::spawn_future_local(glib::clone!(
+        #[weak]
+        dialog,
+        async move {
+            let listed = measured_files.clone();
+            if let Ok(total) = gio::spawn_blocking(move || measure(&measured_files, None)).await {
+                dialog.set_secondary_text(Some(&confirmation_details(
+                    &listed,
+                    Some(describe_total(total, size_units)),
+                )));
+            }
+        }
+    ));
+
+    let fm_window = fm_window.clone();
+    let on_done = std::cell::RefCell::new(Some(on_done));
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        if response == gtk4::ResponseType::Accept
+            && let Some(on_done) = on_done.borrow_mut().take()
+        {
+            start_delete(&fm_window, files.clone(), on_done);
+        }
+    });
+
+    dialog.present();
+}
+
+fn start_delete<F: Fn() + 'static>(fm_window: &FmWindow, files: Vec<gio::File>, on_done: F) {
+    let title = match files.as_slice() {
+        [file] => format!("Deleting \"{}\"", display_name(file)),
+        _ => format!("Deleting {} items", files.len()),
+    };
+
+    fm_window.operations.run(
+        &title,
+        move |context| {
+            context.set_total(measure(&files, Some(context)).items);
+
+            let mut errors = Vec::new();
+            for file in &files {
+                if context.is_cancelled() {
+                    break;
+                }
+                if let Err(e) = delete_recursive(file, context) {
+                    if e.matches(gio::IOErrorEnum::Cancelled) {
+                        break;
+                    }
+                    errors.push(format!("{}: {}", display_name(file), e));
+                }
+            }
+            errors
+        },
+        glib::clone!(
+            #[weak(rename_to = window)]
+            fm_window.window,
+            move |errors: Vec<String>| {
+                on_done();
+                if !errors.is_empty() {
+                    show_errors(window.upcast_ref(), "Some items could not be deleted", &errors);
+                }
+            }
+        ),
+    );
+}
+
+/// Creates `archive` from `files` in the background, then selects it.
+pub fn compress(
+    fm_window: &FmWindow,
+    files: Vec<gio::File>,
+    archive: gio::File,
+    format: ArchiveFormat,
+) {
+    let (Some(target), Some(paths)) = (archive.path(), local_paths(&files)) else {
+        show_errors(
+            fm_window.window.upcast_ref(),
+            "The archive could not be created",
+            &["Only local files can be compressed.".to_string()],
+        );
+        return;
+    };
+
+    fm_window.operations.run(
+        &format!("Compressing to \"{}\"", display_name(&archive)),
+        move |context| archive::compress(&paths, &target, format, context),
+        glib::clone!(
+            #[strong]
+            fm_window,
+            move |result: io::Result<()>| {
+                fm_window.reload();
+                match result {
+                    Ok(()) => {
+                        fm_window.select_file(&archive);
+                    }
+                    Err(e) if archive::is_cancelled(&e) => {}
+                    Err(e) => show_errors(
+                        fm_window.window.upcast_ref(),
+                        "The archive could not be created",
+                        &[e.to_string()],
+                    ),
+                }
+            }
+        ),
+    );
+}
+
+/// Extracts `archive` next to itself, into a new folder unless it holds a
+/// single item.
+pub fn extract_here(fm_window: &FmWindow, archive: gio::File) {
+    let Some(path) = archive.path() else {
+        return;
+    };
+
+    fm_window.operations.run(
+        &format!("Extracting \"{}\"", display_name(&archive)),
+        move |context| archive::extract_here(&path, context),
+        glib::clone!(
+            #[strong]
+            fm_window,
+            move |result| extraction_done(&fm_window, result)
+        ),
+    );
+}
+
+/// Asks for a folder, then extracts `archive` into it. Files that already
+/// exist there are only touched once the user has said what to do with them.
+pub fn extract_to(fm_window: &FmWindow, archive: gio::File) {
+    choose_folder(
+        fm_window,
+        archive.parent(),
+        glib::clone!(
+            #[strong]
+            fm_window,
+            move |destination| check_conflicts(&fm_window, archive.clone(), destination)
+        ),
+    );
+}
+
+/// Asks for a folder, then extracts the archive member at `location` into
+/// it, under a new name if it is taken.
+pub fn extract_member_to(fm_window: &FmWindow, location: ArchiveLocation) {
+    let start = location.archive.parent().map(gio::File::for_path);
+
+    choose_folder(
+        fm_window,
+        start,
+        glib::clone!(
+            #[strong]
+            fm_window,
+            move |destination: gio::File| {
+                let Some(destination) = destination.path() else {
+                    return;
+                };
+                let location = location.clone();
+                let name = location.member.rsplit('/').next().unwrap_or_default().to_string();
+
+                fm_window.operations.run(
+                    &format!("Extracting \"{}\"", name),
+                    move |context| {
+                        archive::extract_member(
+                            &location.archive,
+                            &location.member,
+                            &destination,
+                            Some(context),
+                        )
+                        .map(|extracted| ExtractReport {
+                            extracted: vec![extracted],
+                            skipped: Vec::new(),
+                        })
+                    },
+                    glib::clone!(
+                        #[strong]
+                        fm_window,
+                        move |result| extraction_done(&fm_window, result)
+                    ),
+                );
+            }
+        ),
+    );
+}
+
+fn choose_folder<F: Fn(gio::File) + 'static>(
+    fm_window: &FmWindow,
+    start: Option<gio::File>,
+    on_chosen: F,
+) {
+    let chooser = gtk4::FileChooserDialog::new(
+        Some("Extract To"),
+        Some(&fm_window.window),
+        gtk4::FileChooserAction::SelectFolder,
+        &[("Cancel", gtk4::ResponseType::Cancel), ("Extract", gtk4::ResponseType::Accept)],
+    );
+    chooser.set_modal(true);
+    if let Some(start) = start {
+        let _ = chooser.set_current_folder(Some(&start));
+    }
+
+    chooser.connect_response(move |chooser, response| {
+        let destination = chooser.file();
+        chooser.close();
+        if response == gtk4::ResponseType::Accept
+            && let Some(destination) = destination
+        {
+            on_chosen(destination);
+        }
+    });
+
+    chooser.present();
+}
+
+/// Looks for members that would overwrite something in `destination`, and
+/// asks about them before extracting.
+fn check_conflicts(fm_window: &FmWindow, archive: gio::File, destination: gio::File) {
+    let (Some(archive_path), Some(destination_path)) = (archive.path(), destination.path()) else {
+        show_errors(
+            fm_window.window.upcast_ref(),
+            "The archive could not be extracted",
+            &["Archives can only be extracted to local folders.".to_string()],
+        );
+        return;
+    };
+
+    fm_window.operations.run(
+        &format!("Reading \"{}\"", display_name(&archive)),
+        move |_| archive::conflicts(&archive_path, &destination_path),
+        glib::clone!(
+            #[strong]
+            fm_window,
+            move |result: io::Result<Vec<String>>| match result {
+                Ok(conflicts) if conflicts.is_empty() => {
+                    start_extract(&fm_window, archive, destination, ConflictPolicy::Skip)
+                }
+                Ok(conflicts) => ask_conflict_policy(&fm_window, archive, destination, &conflicts),
+                Err(e) => show_errors(
+                    fm_window.window.upcast_ref(),
+                    "The archive could not be extracted",
+                    &[e.to_string()],
+                ),
+            }
+        ),
+    );
+}
+
+fn ask_conflict_policy(
+    fm_window: &FmWindow,
+    archive: gio::File,
+    destination: gio::File,
+    conflicts: &[String],
+) {
+    let title = match conflicts {
+        [name] => format!("\"{}\" already exists in \"{}\"", name, display_name(&destination)),
+        _ => {
+            format!("{} items already exist in \"{}\"", conflicts.len(), display_name(&destination))
+        }
+    };
+    let mut details: Vec<String> =
+        conflicts.iter().take(MAX_LISTED_ITEMS).map(|name| format!("• {}", name)).collect();
+    if conflicts.len() > MAX_LISTED_ITEMS {
+        details.push(format!("…and {} more", conflicts.len() - MAX_LISTED_ITEMS));
+    }
+
+    let dialog = gtk4::MessageDialog::builder()
+        .transient_for(&fm_window.window)
+        .modal(true)
+        .message_type(gtk4::MessageType::Question)
+        .text(title)
+        .secondary_text(details.join("\n"))
+        .build();
+
+    dialog.add_button("Cancel", gtk4::ResponseType::Cancel);
+    dialog.add_button("Skip Existing", gtk4::ResponseType::Other(0));
+    dialog.add_button("Keep Both", gtk4::ResponseType::Other(1));
+    let replace_button = dialog.add_button("Replace", gtk4::ResponseType::Other(2));
+    replace_button.add_css_class("destructive-action");
+    dialog.set_default_response(gtk4::ResponseType::Cancel);
+
+    dialog.connect_response(glib::clone!(
+        #[strong]
+        fm_window,
+        move |dialog, response| {
+            dialog.close();
+            let policy = match response {
+                gtk4::ResponseType::Other(0) => ConflictPolicy::Skip,
+                gtk4::ResponseType::Other(1) => ConflictPolicy::KeepBoth,
+                gtk4::ResponseType::Other(2) => ConflictPolicy::Overwrite,
+                _ => return,
+            };
+            start_extract(&fm_window, archive.clone(), destination.clone(), policy);
+        }
+    ));
+
+    dialog.present();
+}
+
+fn start_extract(
+    fm_window: &FmWindow,
+    archive: gio::File,
+    destination: gio::File,
+    policy: ConflictPolicy,
+) {
+    let (Some(archive_path), Some(destination_path)) = (archive.path(), destination.path()) else {
+        return;
+    };
+
+    fm_window.operations.run(
+        &format!("Extracting \"{}\"", display_name(&archive)),
+        move |context| archive::extract(&archive_path, &destination_path, policy, context),
+        glib::clone!(
+            #[strong]
+            fm_window,
+            move |result| extraction_done(&fm_window, result)
+        ),
+    );
+}
+
+fn extraction_done(fm_window: &FmWindow, result: io::Result<ExtractReport>) {
+    fm_window.reload();
+    match result {
+        Ok(report) => {
+            let extracted: Vec<_> = report.extracted.iter().map(gio::File::for_path).collect();
+            fm_window.select_files(&extracted);
+            if !report.skipped.is_empty() {
+                show_errors(
+                    fm_window.window.upcast_ref(),
+                    "Some items were not extracted",
+                    &report.skipped,
+                );
+            }
+        }
+        Err(e) if archive::is_cancelled(&e) => {}
+        Err(e) => show_errors(
+            fm_window.window.upcast_ref(),
+            "The archive could not be extracted",
+            &[e.to_string()],
+        ),
+    }
+}
+
+fn local_paths(files: &[gio::File]) -> Option<Vec<PathBuf>> {
+    files.iter().map(|file| file.path()).collect()
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Total {
+    pub bytes: u64,
+    pub items: u64,
+}
+
+/// Counts the files and bytes below `files`, without following symlinks.
+pub fn measure(files: &[gio::File], context: Option<&OperationContext>) -> Total {
+    let mut total = Total::default();
+    for file in files {
+        measure_file(file, context, &mut total);
+    }
+    total
+}
+
+fn measure_file(file: &gio::File, context: Option<&OperationContext>, total: &mut Total) {
+    let cancellable = context.map(|c| c.cancellable());
+    let Ok(info) = file.query_info(
+        "standard::type,standard::size",
+        gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+        cancellable,
+    ) else {
+        return;
+    };
+
+    total.items += 1;
+    total.bytes += info.size().max(0) as u64;
+
+    if info.file_type() == gio::FileType::Directory {
+        for child in children(file, cancellable) {
+            measure_file(&child, context, total);
+        }
+    }
+}
+
+fn children(dir: &gio::File, cancellable: Option<&gio::Cancellable>) -> Vec<gio::File> {
+    let Ok(enumerator) = dir.enumerate_children(
+        "standard::name",
+        gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+        cancellable,
+    ) else {
+        return Vec::new();
+    };
+
+    let mut children = Vec::new();
+    while let Ok(Some(info)) = enumerator.next_file(cancellable) {
+        children.push(dir.child(info.name()));
+    }
+    children
+}
+
+/// Deletes a file or a whole directory tree. Symlinks are removed, never
+/// followed.
+fn delete_recursive(file: &gio::File, context: &OperationContext) -> Result<(), glib::Error> {
+    let cancellable = Some(context.cancellable());
+
+    let file_type = file.query_file_type(gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS, cancellable);
+    if file_type == gio::FileType::Directory {
+        for child in children(file, cancellable) {
+            delete_recursive(&child, context)?;
+        }
+    }
+
+    file.delete(cancellable)?;
+    context.advance(1, &display_name(file));
+    Ok(())
+}
+
+fn confirmation_details(files: &[gio::File], total: Option<String>) -> String {
+    let mut details: Vec<String> =
+        files.iter().take(MAX_LISTED_ITEMS).map(|f| format!("• {}", display_name(f))).collect();
+    if files.len() > MAX_LISTED_ITEMS {
+        details.push(format!("…and {} more", files.len() - MAX_LISTED_ITEMS));
+    }
+
+    details.push(String::new());
+    details.push(total.unwrap_or_else(|| "Calculating size…".to_string()));
+    details.push("This cannot be undone.".to_string());
+    details.join("\n")
+}
+
+fn describe_total(total: Total, size_units: SizeUnits) -> String {
+    let items =
+        if total.items == 1 { "1 item".to_string() } else { format!("{} items", total.items) };
+    format!("Total: {}, {}", format_size(total.bytes, size_units), items)
+}
+
+fn display_name(file: &gio::File) -> String {
+    file.basename().map(|n| n.display().to_string()).unwrap_or_else(|| file.uri().to_string())
+}
+
+pub fn show_errors(parent: &gtk4::Window, title: &str, errors: &[String]) {
+    let dialog = gtk4::MessageDialog::builder()
+        .transient_for(parent)
+        .modal(true)
+        .message_type(gtk4::MessageType::Error)
+        .buttons(gtk4::ButtonsType::Close)
+        .text(title)
+        .secondary_text(errors.join("\n"))
+        .build();
+
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.present();
+}
@@ -1,87 +1,342 @@
-use crate::{models::file_item::FileItem, sorters, state::FmState, utils::WidgetDataExt};
+use crate::{
+    archive_folder::{self, ArchiveLocation},
+    fm_window::FmWindow,
+    links,
+    models::file_item::FileItem,
+    sorters,
+    state::FmState,
+    thumbnails::{self, ThumbnailSize},
+    utils::{
+        ClickActivation, ColumnLayout, FMSettings, FileView, ListColumn, SizeUnits, SortColumn,
+        SortOrder, WidgetDataExt,
+    },
+};
 use gtk4::{
-    ColumnView, ColumnViewColumn, DragIcon, DragSource, EventControllerMotion, ScrolledWindow,
-    SignalListItemFactory, SingleSelection, SortListModel, gdk, gio, gio::ThemedIcon, glib,
-    prelude::*,
+    ColumnView, ColumnViewColumn, DragIcon, DragSource, EventControllerMotion, FilterListModel,
+    GestureClick, GridView, MultiSelection, ScrolledWindow, SignalListItemFactory, SortListModel,
+    Stack, gdk, gio, gio::ThemedIcon, glib, prelude::*,
 };
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    collections::HashMap,
     path::{Path, PathBuf},
     rc::Rc,
+    time::Duration,
 };
 
+const LIST_COLUMNS_KEY: &str = "list-columns";
+const ICON_VIEW_KEY: &str = "icon-view";
+
+/// Pages of the stack holding the two views.
+const LIST_PAGE: &str = "list";
+const ICON_PAGE: &str = "icons";
+
+/// The columns of the list by what they show, kept on the column view so
+/// they are found without going through their titles.
+#[derive(Clone)]
+struct ListColumns {
+    name: ColumnViewColumn,
+    location: ColumnViewColumn,
+    configurable: Vec<(ListColumn, ColumnViewColumn)>,
+}
+
+/// Builds the views of the files: a list with columns, and icons. Both
+/// show the same items and selection; the column view stands for them in
+/// the rest of the window.
 pub fn build_files_panel(
     fmstate: Rc<RefCell<FmState>>,
-) -> (ScrolledWindow, gio::ListStore, ColumnView, SingleSelection) {
+) -> (Stack, gio::ListStore, ColumnView, MultiSelection) {
     let file_store = gio::ListStore::new::<FileItem>();
 
-    // Create sorter based on settings
-    let folders_first = fmstate.borrow().settings.folders_first;
-    let sorter = sorters::create_name_sorter(folders_first);
+    // Folders first, then the column headers, then the name for ties. The
+    // column view keeps the columns clicked before as secondary keys.
+    let folders_first = Rc::new(Cell::new(fmstate.borrow().settings.folders_first));
+    let folders_sorter = sorters::create_folders_first_sorter(folders_first.clone());
+    let sorter = gtk4::MultiSorter::new();
+    sorter.append(folders_sorter.clone());
 
     let sort_model = SortListModel::new(Some(file_store.clone()), Some(sorter.clone()));
-    let selection_model = SingleSelection::new(Some(sort_model.clone()));
+    // Without a filter every item passes, the filter bar sets one while in use
+    let filter_model = FilterListModel::new(Some(sort_model.clone()), None::<gtk4::Filter>);
+    let selection_model = MultiSelection::new(Some(filter_model));
 
     let column_view = ColumnView::new(Some(selection_model.clone()));
 
+    let icon_view = GridView::new(
+        Some(selection_model.clone()),
+        Some(create_name_column_factory(fmstate.clone(), true)),
+    );
+    icon_view.set_max_columns(32);
+    // Opening from the icons goes through the same handler as the list
+    icon_view.connect_activate(glib::clone!(
+        #[weak]
+        column_view,
+        move |_, position| column_view.emit_by_name::<()>("activate", &[&position])
+    ));
+    column_view.set_typed_data(ICON_VIEW_KEY, icon_view.clone());
+
     // Name Column
-    let name_factory = create_name_column_factory(fmstate.clone());
+    let name_factory = create_name_column_factory(fmstate.clone(), false);
     let name_column = ColumnViewColumn::new(Some("Name"), Some(name_factory));
     name_column.set_expand(true);
-    name_column.set_sorter(Some(&sorters::create_name_sorter(folders_first)));
+    name_column.set_header_menu(Some(&create_header_menu(None)));
+    name_column.set_sorter(Some(&sorters::create_name_sorter()));
     column_view.append_column(&name_column);
 
-    // Size Column
-    let size_factory = create_size_column_factory();
-    let size_column = ColumnViewColumn::new(Some("Size"), Some(size_factory));
-    size_column.set_fixed_width(100);
-    size_column.set_sorter(Some(&sorters::create_size_sorter(folders_first)));
-    column_view.append_column(&size_column);
-
-    // Modified Column
-    let modified_factory = create_modified_column_factory();
-    let modified_column = ColumnViewColumn::new(Some("Modified"), Some(modified_factory));
-    modified_column.set_fixed_width(150);
-    modified_column.set_sorter(Some(&sorters::create_date_sorter(folders_first)));
-    column_view.append_column(&modified_column);
-
-    // Type Column
-    let type_factory = create_type_column_factory();
-    let type_column = ColumnViewColumn::new(Some("Type"), Some(type_factory));
-    type_column.set_fixed_width(120);
-    type_column.set_sorter(Some(&sorters::create_type_sorter(folders_first)));
-    column_view.append_column(&type_column);
-
-    // Connect the column view sorter to the sort model
-    column_view.sorter().unwrap().connect_changed(glib::clone!(
+    // Location Column, only shown for search results
+    let location_factory = create_location_column_factory();
+    let location_column = ColumnViewColumn::new(Some("Location"), Some(location_factory));
+    location_column.set_fixed_width(200);
+    location_column.set_resizable(true);
+    location_column.set_visible(false);
+    location_column.set_sorter(Some(&sorters::create_location_sorter()));
+    column_view.append_column(&location_column);
+
+    // The other columns, those left out of the layout hidden
+    let cell_format = Rc::new(RefCell::new(CellFormat::from_settings(&fmstate.borrow().settings)));
+    let mut configurable = Vec::new();
+    for kind in ListColumn::ALL {
+        let column = create_column(kind, &cell_format);
+        column_view.append_column(&column);
+        configurable.push((kind, column));
+    }
+    column_view.set_typed_data(
+        LIST_COLUMNS_KEY,
+        ListColumns { name: name_column.clone(), location: location_column.clone(), configurable },
+    );
+    column_view.track_widget_cleanup();
+    apply_column_layout(&column_view, &fmstate.borrow().settings.columns);
+    add_column_actions(&column_view, &fmstate);
+    track_column_layout(&column_view, &fmstate);
+
+    if let Some(column_sorter) = column_view.sorter() {
+        sorter.append(column_sorter);
+    }
+    sorter.append(sorters::create_name_sorter());
+
+    // The sort from the settings, applied again only when it changes there so
+    // clicks on the headers are kept meanwhile
+    let default_sort = {
+        let settings = &fmstate.borrow().settings;
+        (settings.sort_column, settings.sort_order)
+    };
+    apply_default_sort(&column_view, default_sort);
+    let default_sort = Rc::new(Cell::new(default_sort));
+
+    let list_scroll = ScrolledWindow::builder().child(&column_view).build();
+    let icon_scroll = ScrolledWindow::builder().child(&icon_view).build();
+    let files_view = Stack::builder().vexpand(true).hexpand(true).build();
+    files_view.add_named(&list_scroll, Some(LIST_PAGE));
+    files_view.add_named(&icon_scroll, Some(ICON_PAGE));
+
+    // Like the sort, the view from the settings is applied when the window
+    // opens and when it changes there
+    let file_view = fmstate.borrow().settings.file_view;
+    show_file_view(&files_view, file_view);
+    let file_view = Rc::new(Cell::new(file_view));
+
+    apply_click_activation(&column_view, fmstate.borrow().settings.click_activation);
+    fmstate.borrow_mut().connect_settings_changed(glib::clone!(
+        #[weak]
+        column_view,
         #[weak]
-        sort_model,
-        move |sorter, _| {
-            sort_model.set_sorter(Some(sorter));
+        files_view,
+        #[strong]
+        cell_format,
+        move |state| {
+            apply_click_activation(&column_view, state.settings.click_activation);
+            if file_view.get() != state.settings.file_view {
+                file_view.set(state.settings.file_view);
+                show_file_view(&files_view, state.settings.file_view);
+            }
+            cell_format.replace(CellFormat::from_settings(&state.settings));
+            // The settings follow the columns shown, so they only differ when
+            // the layout was changed elsewhere (the settings file)
+            if state.settings.columns != column_layout(&column_view) {
+                apply_column_layout(&column_view, &state.settings.columns);
+            }
+
+            if folders_first.get() != state.settings.folders_first {
+                folders_first.set(state.settings.folders_first);
+                folders_sorter.changed(gtk4::SorterChange::Different);
+            }
+            let sort = (state.settings.sort_column, state.settings.sort_order);
+            if default_sort.get() != sort {
+                default_sort.set(sort);
+                apply_default_sort(&column_view, sort);
+            }
         }
     ));
 
-    let scroll = ScrolledWindow::builder().child(&column_view).vexpand(true).hexpand(true).build();
+    (files_view, file_store, column_view, selection_model)
+}
+
+fn show_file_view(files_view: &Stack, file_view: FileView) {
+    files_view.set_visible_child_name(match file_view {
+        FileView::IconView => ICON_PAGE,
+        FileView::ListView => LIST_PAGE,
+    });
+}
+
+/// The stack holding the list and the icons.
+pub fn files_view(column_view: &ColumnView) -> Option<Stack> {
+    column_view.ancestor(Stack::static_type()).and_downcast()
+}
+
+fn icon_view(column_view: &ColumnView) -> Option<GridView> {
+    column_view.get_typed_data::<GridView>(ICON_VIEW_KEY)
+}
+
+/// The view the files are shown in, the list or the icons.
+pub fn shown_view(column_view: &ColumnView) -> gtk4::Widget {
+    let shows_icons = files_view(column_view)
+        .is_some_and(|files_view| files_view.visible_child_name().as_deref() == Some(ICON_PAGE));
+    match icon_view(column_view) {
+        Some(icon_view) if shows_icons => icon_view.upcast(),
+        _ => column_view.clone().upcast(),
+    }
+}
+
+/// Formatting options read by the cell factories. Kept apart from `FmState`
+/// because cells can be bound while the state is mutably borrowed.
+struct CellFormat {
+    size_units: SizeUnits,
+    date_format: String,
+}
+
+impl CellFormat {
+    fn from_settings(settings: &FMSettings) -> Self {
+        Self { size_units: settings.size_units, date_format: settings.date_format.clone() }
+    }
+}
+
+/// Sorts the list by the column and direction chosen in the settings.
+fn apply_default_sort(column_view: &ColumnView, (column, order): (SortColumn, SortOrder)) {
+    let column = match column {
+        SortColumn::Name => list_columns(column_view).map(|columns| columns.name),
+        SortColumn::Size => find_column(column_view, ListColumn::Size),
+        SortColumn::ModifiedDate => find_column(column_view, ListColumn::Modified),
+        SortColumn::Type => find_column(column_view, ListColumn::Type),
+    };
+    let order = match order {
+        SortOrder::Ascending => gtk4::SortType::Ascending,
+        SortOrder::Descending => gtk4::SortType::Descending,
+    };
+    column_view.sort_by_column(column.as_ref(), order);
+}
 
-    (scroll, file_store, column_view, selection_model)
+fn apply_click_activation(column_view: &ColumnView, activation: ClickActivation) {
+    let single = activation == ClickActivation::Single;
+    column_view.set_single_click_activate(single);
+    if let Some(icon_view) = icon_view(column_view) {
+        icon_view.set_single_click_activate(single);
+    }
 }
 
-fn create_name_column_factory(fmstate: Rc<RefCell<FmState>>) -> SignalListItemFactory {
+/// Cells showing the icon and name of files, on a row for the list or
+/// the name below a large icon with `icons`.
+fn create_name_column_factory(fmstate: Rc<RefCell<FmState>>, icons: bool) -> SignalListItemFactory {
     let factory = SignalListItemFactory::new();
 
     factory.connect_setup(glib::clone!(
         #[strong]
         fmstate,
         move |_, item| {
-            let hbox = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+            let orientation =
+                if icons { gtk4::Orientation::Vertical } else { gtk4::Orientation::Horizontal };
+            let hbox = gtk4::Box::new(orientation, 6);
             let icon = gtk4::Image::new();
-            icon.set_pixel_size(24);
+            icon.set_pixel_size(if icons { 64 } else { 24 });
+            // Small arrow in the corner of symbolic links
+            let link_emblem = gtk4::Image::from_icon_name("emblem-symbolic-link");
+            link_emblem.set_pixel_size(12);
+            link_emblem.set_halign(gtk4::Align::End);
+            link_emblem.set_valign(gtk4::Align::End);
+            link_emblem.set_visible(false);
+            let icon_overlay = gtk4::Overlay::new();
+            icon_overlay.set_child(Some(&icon));
+            icon_overlay.add_overlay(&link_emblem);
             let label = gtk4::Label::new(None);
-            label.set_xalign(0.0);
-            hbox.append(&icon);
+            if icons {
+                label.set_wrap(true);
+                label.set_wrap_mode(gtk4::pango::WrapMode::WordChar);
+                label.set_lines(2);
+                label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+                label.set_justify(gtk4::Justification::Center);
+                label.set_max_width_chars(14);
+            } else {
+                label.set_xalign(0.0);
+            }
+            // Shown instead of the label while renaming in place
+            let rename_entry = gtk4::Entry::new();
+            rename_entry.set_hexpand(true);
+            rename_entry.set_visible(false);
+            hbox.append(&icon_overlay);
             hbox.append(&label);
+            hbox.append(&rename_entry);
+            hbox.set_flag("name-cell", true);
             item.set_child(Some(&hbox));
 
+            // A second, slow click on a selected item renames it
+            let slow_click = Rc::new(Cell::new(0u32));
+            let click = GestureClick::new();
+            click.set_button(1);
+            click.connect_pressed(glib::clone!(
+                #[weak]
+                item,
+                #[weak]
+                hbox,
+                #[strong]
+                slow_click,
+                move |gesture, n_press, _, _| {
+                    slow_click.set(slow_click.get().wrapping_add(1));
+
+                    let modifiers = gesture.current_event_state();
+                    let single_click_activate = hbox
+                        .ancestor(ColumnView::static_type())
+                        .and_downcast::<ColumnView>()
+                        .map(|view| view.is_single_click_activate())
+                        .or_else(|| {
+                            hbox.ancestor(GridView::static_type())
+                                .and_downcast::<GridView>()
+                                .map(|view| view.is_single_click_activate())
+                        })
+                        .unwrap_or(false);
+                    if n_press != 1
+                        || !item.is_selected()
+                        || single_click_activate
+                        || modifiers.intersects(
+                            gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::SHIFT_MASK,
+                        )
+                    {
+                        return;
+                    }
+
+                    let pending = slow_click.get();
+                    let delay = gtk4::Settings::default()
+                        .map(|settings| settings.gtk_double_click_time())
+                        .unwrap_or(400)
+                        .max(0) as u64;
+                    glib::timeout_add_local_once(
+                        Duration::from_millis(delay + 100),
+                        glib::clone!(
+                            #[weak]
+                            item,
+                            #[weak]
+                            hbox,
+                            #[strong]
+                            slow_click,
+                            move || {
+                                if slow_click.get() == pending && item.is_selected() {
+                                    crate::inline_rename::start_rename_in_cell(&hbox);
+                                }
+                            }
+                        ),
+                    );
+                }
+            ));
+            hbox.add_controller(click);
+
             // Setup hover detection
             let motion = EventControllerMotion::new();
 
@@ -115,26 +370,38 @@ fn create_name_column_factory(fmstate: Rc<RefCell<FmState>>) -> SignalListItemFa
 
             // Setup drag
             let drag_source = DragSource::new();
-            drag_source.set_actions(gdk::DragAction::COPY);
+            drag_source.set_actions(gdk::DragAction::COPY | gdk::DragAction::LINK);
 
             drag_source.connect_prepare(glib::clone!(
                 #[strong]
                 fmstate,
                 move |_, _, _| {
-                    if let Some(file) = &fmstate.borrow().hovered_file {
-                        let uri = gtk4::gio::File::for_path(file).uri();
-                        Some(gdk::ContentProvider::for_value(&uri.to_value()))
-                    } else {
-                        None
+                    let file = fmstate.borrow().hovered_file.clone()?;
+
+                    // Archive members are handed out as a temporary copy
+                    let location = ArchiveLocation::from_path(Path::new(&file))
+                        .filter(|location| !location.member.is_empty());
+                    if let Some(location) = location {
+                        return Some(archive_folder::MemberContent::new(location).upcast());
                     }
+
+                    let uri = gtk4::gio::File::for_path(&file).uri();
+                    Some(gdk::ContentProvider::for_value(&uri.to_value()))
                 }
             ));
 
             drag_source.connect_drag_begin(glib::clone!(
                 #[weak]
                 icon,
+                #[strong]
+                slow_click,
                 move |_, drag| {
-                    if let Some(gicon) = icon.gicon() {
+                    // Dragging is not a click
+                    slow_click.set(slow_click.get().wrapping_add(1));
+
+                    if let Some(thumbnail) = icon.paintable() {
+                        DragIcon::set_from_paintable(drag, &thumbnail, 0, 0);
+                    } else if let Some(gicon) = icon.gicon() {
                         let paintable = gtk4::IconTheme::default().lookup_by_gicon(
                             &gicon,
                             24,
@@ -166,8 +433,16 @@ fn create_name_column_factory(fmstate: Rc<RefCell<FmState>>) -> SignalListItemFa
         fmstate,
         move |_, item| {
             let hbox = item.child().and_downcast::<gtk4::Box>().unwrap();
-            let icon = hbox.first_child().and_downcast::<gtk4::Image>().unwrap();
-            let label = hbox.last_child().and_downcast::<gtk4::Label>().unwrap();
+            let icon_overlay = hbox.first_child().and_downcast::<gtk4::Overlay>().unwrap();
+            let icon = icon_overlay.child().and_downcast::<gtk4::Image>().unwrap();
+            let link_emblem = icon_overlay.last_child().and_downcast::<gtk4::Image>().unwrap();
+            let label = icon_overlay.next_sibling().and_downcast::<gtk4::Label>().unwrap();
+
+            // A recycled cell may still be renaming its previous file
+            if let Some(entry) = hbox.last_child().filter(|child| child.is::<gtk4::Entry>()) {
+                entry.set_visible(false);
+                label.set_visible(true);
+            }
 
             if let Some(obj) = item.item() {
                 if let Some(file_item) = obj.downcast_ref::<FileItem>() {
@@ -179,6 +454,21 @@ fn create_name_column_factory(fmstate: Rc<RefCell<FmState>>) -> SignalListItemFa
                         icon.set_icon_name(Some("gtk-missing-image"));
                     }
 
+                    link_emblem.set_visible(file_item.is_symlink());
+                    let tooltip = file_item.symlink_target().map(|target| {
+                        if file_item.is_broken_link() {
+                            format!("Broken link to {}", target)
+                        } else {
+                            format!("Link to {}", target)
+                        }
+                    });
+                    hbox.set_tooltip_text(tooltip.as_deref());
+                    if file_item.is_broken_link() {
+                        label.add_css_class("broken-link");
+                    } else {
+                        label.remove_css_class("broken-link");
+                    }
+
                     // Add drop target for directories
                     let is_dir = file_item.is_directory();
                     let file_path = file_item.path();
@@ -187,9 +477,40 @@ fn create_name_column_factory(fmstate: Rc<RefCell<FmState>>) -> SignalListItemFa
                     hbox.set_flag("is-dir", is_dir);
                     hbox.track_widget_cleanup();
 
-                    if is_dir {
-                        let drop_target =
-                            gtk4::DropTarget::new(String::static_type(), gdk::DragAction::COPY);
+                    // The thumbnail replaces the icon if the row still shows the file
+                    let shows_file = glib::clone!(
+                        #[weak]
+                        hbox,
+                        #[strong]
+                        file_path,
+                        #[upgrade_or]
+                        false,
+                        move || {
+                            hbox.get_typed_data::<glib::GString>("file-path")
+                                .is_some_and(|path| path.as_str() == file_path)
+                        }
+                    );
+                    thumbnails::request(
+                        file_item,
+                        ThumbnailSize::Normal,
+                        shows_file.clone(),
+                        glib::clone!(
+                            #[weak]
+                            icon,
+                            move |texture| {
+                                if shows_file() {
+                                    icon.set_paintable(Some(texture));
+                                }
+                            }
+                        ),
+                    );
+
+                    // Archives are read-only
+                    if is_dir && !file_item.archive_member() {
+                        let drop_target = gtk4::DropTarget::new(
+                            String::static_type(),
+                            gdk::DragAction::COPY | gdk::DragAction::LINK,
+                        );
 
                         drop_target.connect_drop(glib::clone!(
                             #[strong]
@@ -201,6 +522,26 @@ fn create_name_column_factory(fmstate: Rc<RefCell<FmState>>) -> SignalListItemFa
                                     {
                                         if let Ok(uri) = value.get::<glib::GString>() {
                                             let src_file = gio::File::for_uri(&uri);
+
+                                            // Shift+Ctrl drags ask for a link
+                                            let link = drop_target.drop().is_some_and(|drop| {
+                                                drop.actions() == gdk::DragAction::LINK
+                                            });
+                                            if link {
+                                                let fm_window = target_widget
+                                                    .root()
+                                                    .and_downcast::<gtk4::Window>()
+                                                    .and_then(|w| FmWindow::from_window(&w));
+                                                if let Some(fm_window) = fm_window {
+                                                    links::link_into(
+                                                        &fm_window,
+                                                        &gio::File::for_path(target_path.as_str()),
+                                                        &[src_file],
+                                                    );
+                                                }
+                                                return true;
+                                            }
+
                                             let mut dest_path = PathBuf::from(&target_path);
 
                                             let src_filename = src_file
@@ -242,21 +583,51 @@ fn create_name_column_factory(fmstate: Rc<RefCell<FmState>>) -> SignalListItemFa
     factory
 }
 
-fn create_size_column_factory() -> SignalListItemFactory {
+fn create_location_column_factory() -> SignalListItemFactory {
     let factory = SignalListItemFactory::new();
 
     factory.connect_setup(|_, item| {
         let label = gtk4::Label::new(None);
-        label.set_xalign(1.0); // Right align
+        label.set_xalign(0.0);
+        label.set_ellipsize(gtk4::pango::EllipsizeMode::Start);
         item.set_child(Some(&label));
     });
 
     factory.connect_bind(|_, item| {
         let label = item.child().and_downcast::<gtk4::Label>().unwrap();
 
+        if let Some(file_item) = item.item().and_downcast::<FileItem>() {
+            let location = file_item.location();
+            label.set_text(&location);
+            label.set_tooltip_text(Some(&location));
+        }
+    });
+
+    factory
+}
+
+/// Shows or hides the column telling where search results are.
+pub fn set_location_column_visible(column_view: &ColumnView, visible: bool) {
+    if let Some(columns) = list_columns(column_view) {
+        columns.location.set_visible(visible);
+    }
+}
+
+fn create_size_column_factory(cell_format: Rc<RefCell<CellFormat>>) -> SignalListItemFactory {
+    let factory = SignalListItemFactory::new();
+
+    factory.connect_setup(|_, item| {
+        let label = gtk4::Label::new(None);
+        label.set_xalign(1.0); // Right align
+        item.set_child(Some(&label));
+    });
+
+    factory.connect_bind(move |_, item| {
+        let label = item.child().and_downcast::<gtk4::Label>().unwrap();
+
         if let Some(obj) = item.item() {
             if let Some(file_item) = obj.downcast_ref::<FileItem>() {
-                label.set_text(&file_item.format_size());
+                label.set_text(&file_item.format_size(cell_format.borrow().size_units));
             }
         }
     });
@@ -264,7 +635,7 @@ fn create_size_column_factory() -> SignalListItemFactory {
     factory
 }
 
-fn create_modified_column_factory() -> SignalListItemFactory {
+fn create_modified_column_factory(cell_format: Rc<RefCell<CellFormat>>) -> SignalListItemFactory {
     let factory = SignalListItemFactory::new();
 
     factory.connect_setup(|_, item| {
@@ -273,12 +644,12 @@ fn create_modified_column_factory() -> SignalListItemFactory {
         item.set_child(Some(&label));
     });
 
-    factory.connect_bind(|_, item| {
+    factory.connect_bind(move |_, item| {
         let label = item.child().and_downcast::<gtk4::Label>().unwrap();
 
         if let Some(obj) = item.item() {
             if let Some(file_item) = obj.downcast_ref::<FileItem>() {
-                label.set_text(&file_item.format_modified());
+                label.set_text(&file_item.format_modified(&cell_format.borrow().date_format));
             }
         }
     });
@@ -308,9 +679,399 @@ fn create_type_column_factory() -> SignalListItemFactory {
     factory
 }
 
+/// Label cells showing `text` for each item.
+fn create_label_column_factory(
+    cell_format: &Rc<RefCell<CellFormat>>,
+    xalign: f32,
+    monospace: bool,
+    text: impl Fn(&FileItem, &CellFormat) -> String + 'static,
+) -> SignalListItemFactory {
+    let factory = SignalListItemFactory::new();
+
+    factory.connect_setup(move |_, item| {
+        let label = gtk4::Label::new(None);
+        label.set_xalign(xalign);
+        label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        if monospace {
+            label.add_css_class("monospace");
+        }
+        item.set_child(Some(&label));
+    });
+
+    factory.connect_bind(glib::clone!(
+        #[strong]
+        cell_format,
+        move |_, item| {
+            let label = item.child().and_downcast::<gtk4::Label>().unwrap();
+
+            if let Some(file_item) = item.item().and_downcast::<FileItem>() {
+                label.set_text(&text(&file_item, &cell_format.borrow()));
+            }
+        }
+    ));
+
+    factory
+}
+
+fn create_column(kind: ListColumn, cell_format: &Rc<RefCell<CellFormat>>) -> ColumnViewColumn {
+    // Numbers shown only when known
+    let count = |value: u64| if value == 0 { String::new() } else { value.to_string() };
+
+    let factory = match kind {
+        ListColumn::Size => create_size_column_factory(cell_format.clone()),
+        ListColumn::Modified => create_modified_column_factory(cell_format.clone()),
+        ListColumn::Type => create_type_column_factory(),
+        ListColumn::Permissions => {
+            create_label_column_factory(cell_format, 0.0, true, |item, _| item.format_permissions())
+        }
+        ListColumn::Owner => {
+            create_label_column_factory(cell_format, 0.0, false, |item, _| item.owner())
+        }
+        ListColumn::Group => {
+            create_label_column_factory(cell_format, 0.0, false, |item, _| item.group())
+        }
+        ListColumn::Created => {
+            create_label_column_factory(cell_format, 0.0, false, |item, format| {
+                item.format_created(&format.date_format)
+            })
+        }
+        ListColumn::Accessed => {
+            create_label_column_factory(cell_format, 0.0, false, |item, format| {
+                item.format_accessed(&format.date_format)
+            })
+        }
+        ListColumn::Extension => {
+            create_label_column_factory(cell_format, 0.0, false, |item, _| item.extension())
+        }
+        ListColumn::MimeType => {
+            create_label_column_factory(cell_format, 0.0, false, |item, _| item.content_type())
+        }
+        ListColumn::LinkTarget => {
+            create_label_column_factory(cell_format, 0.0, false, |item, _| {
+                item.symlink_target().unwrap_or_default()
+            })
+        }
+        ListColumn::Inode => {
+            create_label_column_factory(cell_format, 1.0, false, move |item, _| count(item.inode()))
+        }
+        ListColumn::Links => {
+            create_label_column_factory(cell_format, 1.0, false, move |item, _| {
+                count(item.nlink() as u64)
+            })
+        }
+    };
+
+    let sorter = match kind {
+        ListColumn::Size => sorters::create_size_sorter(),
+        ListColumn::Modified => sorters::create_date_sorter(),
+        ListColumn::Type => sorters::create_type_sorter(),
+        ListColumn::Permissions => sorters::create_permissions_sorter(),
+        ListColumn::Owner => sorters::create_owner_sorter(),
+        ListColumn::Group => sorters::create_group_sorter(),
+        ListColumn::Created => sorters::create_created_sorter(),
+        ListColumn::Accessed => sorters::create_accessed_sorter(),
+        ListColumn::Extension => sorters::create_extension_sorter(),
+        ListColumn::MimeType => sorters::create_mime_type_sorter(),
+        ListColumn::LinkTarget => sorters::create_link_target_sorter(),
+        ListColumn::Inode => sorters::create_inode_sorter(),
+        ListColumn::Links => sorters::create_links_sorter(),
+    };
+
+    let column = ColumnViewColumn::new(Some(kind.title()), Some(factory));
+    column.set_fixed_width(kind.default_width());
+    column.set_resizable(true);
+    column.set_sorter(Some(&sorter));
+    column.set_header_menu(Some(&create_header_menu(Some(kind))));
+    column
+}
+
+/// Menu of a column header: moving the column, and which columns to show.
+fn create_header_menu(kind: Option<ListColumn>) -> gio::Menu {
+    let menu = gio::Menu::new();
+
+    if let Some(kind) = kind {
+        let moves = gio::Menu::new();
+        for (label, action) in
+            [("Move Left", "columns.move-left"), ("Move Right", "columns.move-right")]
+        {
+            let item = gio::MenuItem::new(Some(label), None);
+            item.set_action_and_target_value(Some(action), Some(&kind.as_str().to_variant()));
+            moves.append_item(&item);
+        }
+        menu.append_section(None, &moves);
+    }
+
+    let shown = gio::Menu::new();
+    for kind in ListColumn::ALL {
+        shown.append(Some(kind.title()), Some(&format!("columns.show-{}", kind.as_str())));
+    }
+    menu.append_section(Some("Columns"), &shown);
+
+    menu
+}
+
+/// Adds the actions of the header menus, saving the layout as it changes.
+fn add_column_actions(column_view: &ColumnView, fmstate: &Rc<RefCell<FmState>>) {
+    let save = glib::clone!(
+        #[weak]
+        column_view,
+        #[strong]
+        fmstate,
+        move || {
+            let mut fmstate_mut = fmstate.borrow_mut();
+            fmstate_mut.settings.columns = column_layout(&column_view);
+            fmstate_mut.save_settings();
+        }
+    );
+
+    let actions = gio::SimpleActionGroup::new();
+    for kind in ListColumn::ALL {
+        let Some(column) = find_column(column_view, kind) else {
+            continue;
+        };
+        let action = gio::SimpleAction::new_stateful(
+            &format!("show-{}", kind.as_str()),
+            None,
+            &column.is_visible().to_variant(),
+        );
+        action.connect_activate(glib::clone!(
+            #[weak]
+            column,
+            #[strong]
+            save,
+            move |_, _| {
+                column.set_visible(!column.is_visible());
+                save();
+            }
+        ));
+        // Also follows the settings file
+        column.connect_visible_notify(glib::clone!(
+            #[weak]
+            action,
+            move |column| action.set_state(&column.is_visible().to_variant())
+        ));
+        actions.add_action(&action);
+    }
+
+    for (name, step) in [("move-left", -1), ("move-right", 1)] {
+        let action = gio::SimpleAction::new(name, Some(glib::VariantTy::STRING));
+        action.connect_activate(glib::clone!(
+            #[weak]
+            column_view,
+            #[strong]
+            save,
+            move |_, parameter| {
+                let kind = parameter.and_then(|p| p.str()).and_then(ListColumn::parse);
+                if let Some(kind) = kind {
+                    move_column(&column_view, kind, step);
+                    save();
+                }
+            }
+        ));
+        actions.add_action(&action);
+    }
+
+    column_view.insert_action_group("columns", Some(&actions));
+}
+
+/// Keeps `settings.columns` following the columns as they are resized,
+/// dragged or hidden, so settings applied later carry the layout shown.
+fn track_column_layout(column_view: &ColumnView, fmstate: &Rc<RefCell<FmState>>) {
+    let update = glib::clone!(
+        #[weak]
+        column_view,
+        #[strong]
+        fmstate,
+        move || {
+            // Busy while settings are applied, and those bring their layout
+            if let Ok(mut fmstate_mut) = fmstate.try_borrow_mut() {
+                fmstate_mut.settings.columns = column_layout(&column_view);
+            }
+        }
+    );
+
+    column_view.columns().connect_items_changed(glib::clone!(
+        #[strong]
+        update,
+        move |_, _, _, _| update()
+    ));
+    for column in columns(column_view) {
+        column.connect_fixed_width_notify(glib::clone!(
+            #[strong]
+            update,
+            move |_| update()
+        ));
+        column.connect_visible_notify(glib::clone!(
+            #[strong]
+            update,
+            move |_| update()
+        ));
+    }
+}
+
+fn columns(column_view: &ColumnView) -> Vec<ColumnViewColumn> {
+    let columns = column_view.columns();
+    (0..columns.n_items()).filter_map(|i| columns.item(i).and_downcast()).collect()
+}
+
+fn list_columns(column_view: &ColumnView) -> Option<ListColumns> {
+    column_view.get_typed_data::<ListColumns>(LIST_COLUMNS_KEY)
+}
+
+/// Which of the configurable columns `column` is, `None` for the name and
+/// location.
+fn column_kind(column_view: &ColumnView, column: &ColumnViewColumn) -> Option<ListColumn> {
+    list_columns(column_view)?
+        .configurable
+        .into_iter()
+        .find_map(|(kind, configurable)| (&configurable == column).then_some(kind))
+}
+
+fn find_column(column_view: &ColumnView, kind: ListColumn) -> Option<ColumnViewColumn> {
+    list_columns(column_view)?
+        .configurable
+        .into_iter()
+        .find_map(|(configurable, column)| (configurable == kind).then_some(column))
+}
+
+/// Moves a column past the next column shown on its left (`step` -1) or
+/// right (1). The name and location columns stay in front.
+fn move_column(column_view: &ColumnView, kind: ListColumn, step: i32) {
+    let columns = columns(column_view);
+    let Some(index) =
+        columns.iter().position(|column| column_kind(column_view, column) == Some(kind))
+    else {
+        return;
+    };
+    let movable =
+        |i: &usize| columns[*i].is_visible() && column_kind(column_view, &columns[*i]).is_some();
+    let target = if step < 0 {
+        (0..index).rev().find(movable)
+    } else {
+        (index + 1..columns.len()).find(movable)
+    };
+    // Moving right, the target shifts left once the column is taken out
+    if let Some(target) = target {
+        column_view.insert_column(target as u32, &columns[index]);
+    }
+}
+
+/// Shows the columns of `layout` after the name, in its order and with its
+/// widths, and hides the others.
+fn apply_column_layout(column_view: &ColumnView, layout: &[ColumnLayout]) {
+    let mut order: Vec<ColumnViewColumn> = list_columns(column_view)
+        .map(|columns| vec![columns.name, columns.location])
+        .unwrap_or_default();
+    for layout in layout {
+        if let Some(column) = find_column(column_view, layout.column) {
+            column.set_fixed_width(layout.width);
+            column.set_visible(true);
+            order.push(column);
+        }
+    }
+    for kind in ListColumn::ALL {
+        if !layout.iter().any(|layout| layout.column == kind)
+            && let Some(column) = find_column(column_view, kind)
+        {
+            column.set_visible(false);
+            order.push(column);
+        }
+    }
+
+    for (position, column) in order.iter().enumerate() {
+        if column_view.columns().item(position as u32).as_ref() != Some(column.upcast_ref()) {
+            column_view.insert_column(position as u32, column);
+        }
+    }
+}
+
+/// The columns shown after the name, as laid out now, including headers
+/// dragged around and resized.
+pub fn column_layout(column_view: &ColumnView) -> Vec<ColumnLayout> {
+    columns(column_view)
+        .iter()
+        .filter(|column| column.is_visible())
+        .filter_map(|column| {
+            let kind = column_kind(column_view, column)?;
+            let width =
+                if column.fixed_width() > 0 { column.fixed_width() } else { kind.default_width() };
+            Some(ColumnLayout { column: kind, width })
+        })
+        .collect()
+}
+
+/// Returns the selected items, in the order they are listed.
+pub fn selected_items(selection: &MultiSelection) -> Vec<FileItem> {
+    let selected = selection.selection();
+    (0..selected.size())
+        .filter_map(|i| selection.item(selected.nth(i as u32)).and_downcast::<FileItem>())
+        .collect()
+}
+
+/// Returns the files selected in the listing.
+pub fn selected_files(selection: &MultiSelection) -> Vec<gio::File> {
+    selected_items(selection).iter().map(|item| item.file()).collect()
+}
+
+/// Scrolls the column view so the row at `position` is visible.
+pub fn scroll_to_position(column_view: &ColumnView, position: u32) {
+    let view = shown_view(column_view);
+    if view.is::<GridView>() {
+        let _ = view.activate_action("list.scroll-to-item", Some(&position.to_variant()));
+        return;
+    }
+
+    // The rows live in an internal list view which owns the scroll action
+    let mut child = column_view.first_child();
+    while let Some(widget) = child {
+        if widget.is::<gtk4::ListView>() {
+            let _ = widget.activate_action("list.scroll-to-item", Some(&position.to_variant()));
+            return;
+        }
+        child = widget.next_sibling();
+    }
+}
+
+thread_local! {
+    /// Archives still being read, by the address of the store they fill.
+    static PENDING_LISTINGS: RefCell<HashMap<usize, gio::Cancellable>> =
+        RefCell::new(HashMap::new());
+}
+
 pub fn populate_files_list(file_store: &gio::ListStore, dir: &gio::File, show_hidden: &bool) {
     file_store.remove_all();
 
+    // A folder opened meanwhile replaces an archive not read yet
+    let store_id = file_store.as_ptr() as usize;
+    if let Some(pending) = PENDING_LISTINGS.with_borrow_mut(|pending| pending.remove(&store_id)) {
+        pending.cancel();
+    }
+
+    if let Some(location) = ArchiveLocation::from_file(dir) {
+        let cancellable = gio::Cancellable::new();
+        PENDING_LISTINGS.with_borrow_mut(|pending| pending.insert(store_id, cancellable.clone()));
+        let archive = location.archive.clone();
+        archive_folder::list_folder(
+            &location,
+            *show_hidden,
+            glib::clone!(
+                #[weak]
+                file_store,
+                move |result| {
+                    if cancellable.is_cancelled() {
+                        return;
+                    }
+                    PENDING_LISTINGS.with_borrow_mut(|pending| pending.remove(&store_id));
+                    match result {
+                        Ok(items) => file_store.splice(0, 0, &items),
+                        Err(e) => eprintln!("Failed to read archive {}: {}", archive.display(), e),
+                    }
+                }
+            ),
+        );
+        return;
+    }
+
     if let Ok(enumerator) =
         dir.enumerate_children("*", gio::FileQueryInfoFlags::NONE, None::<&gio::Cancellable>)
     {
@@ -0,0 +1,230 @@
+//! Filter bar above the file list, narrowing the listing to the names
+//! matching what is typed. Unlike the search bar it only looks at the items
+//! already listed.
+
+use crate::{files_panel, fm_window::FmWindow, models::file_item::FileItem, search};
+use gtk4::{
+    Box as GtkBox, CheckButton, CustomFilter, FilterChange, FilterListModel, MultiSelection,
+    Orientation, SearchEntry, gio, glib, prelude::*,
+};
+use std::{cell::RefCell, rc::Rc};
+
+enum Pattern {
+    /// Part of the name, matching case.
+    Text(String),
+    /// Part of the name, lowercase.
+    Lowercase(String),
+    /// Shell wildcards matching the whole name.
+    Wildcards(glib::Regex),
+}
+
+impl Pattern {
+    fn new(text: &str, wildcards: bool, match_case: bool) -> Option<Pattern> {
+        if text.is_empty() {
+            return None;
+        }
+        if !wildcards {
+            return Some(if match_case {
+                Pattern::Text(text.to_string())
+            } else {
+                Pattern::Lowercase(text.to_lowercase())
+            });
+        }
+
+        let flags = if match_case {
+            glib::RegexCompileFlags::empty()
+        } else {
+            glib::RegexCompileFlags::CASELESS
+        };
+        let regex =
+            glib::Regex::new(&search::glob_to_regex(text), flags, glib::RegexMatchFlags::empty());
+        regex.ok().flatten().map(Pattern::Wildcards)
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Pattern::Text(text) => name.contains(text.as_str()),
+            Pattern::Lowercase(text) => name.to_lowercase().contains(text.as_str()),
+            Pattern::Wildcards(regex) => regex
+                .match_(glib::GString::from(name).as_gstr(), glib::RegexMatchFlags::empty())
+                .is_some(),
+        }
+    }
+}
+
+/// The filter on the listing, apart from the widgets setting it so the
+/// handlers on those can hold it without keeping them alive.
+#[derive(Clone)]
+struct Filtering {
+    filter_model: FilterListModel,
+    filter: CustomFilter,
+    pattern: Rc<RefCell<Option<Pattern>>>,
+}
+
+impl Filtering {
+    /// Applies the pattern typed, taking the filter off when there is none.
+    fn update(&self, entry: &SearchEntry, wildcards: &CheckButton, match_case: &CheckButton) {
+        let pattern = Pattern::new(&entry.text(), wildcards.is_active(), match_case.is_active());
+        let active = pattern.is_some();
+        self.pattern.replace(pattern);
+
+        if !active {
+            self.filter_model.set_filter(None::<&gtk4::Filter>);
+        } else if self.filter_model.filter().is_none() {
+            self.filter_model.set_filter(Some(&self.filter));
+        } else {
+            self.filter.changed(FilterChange::Different);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FilterBar {
+    bar: gtk4::SearchBar,
+    entry: SearchEntry,
+    wildcards: CheckButton,
+    match_case: CheckButton,
+    filtering: Filtering,
+}
+
+impl FilterBar {
+    /// Builds the bar for the listing shown through `files_selection`.
+    pub fn new(files_selection: &MultiSelection) -> Self {
+        let filter_model = files_selection.model().and_downcast::<FilterListModel>().unwrap();
+
+        let pattern: Rc<RefCell<Option<Pattern>>> = Rc::new(RefCell::new(None));
+        let filter = CustomFilter::new(glib::clone!(
+            #[strong]
+            pattern,
+            move |item| {
+                let Some(item) = item.downcast_ref::<FileItem>() else {
+                    return false;
+                };
+                pattern
+                    .borrow()
+                    .as_ref()
+                    .is_none_or(|pattern| pattern.matches(&item.display_name()))
+            }
+        ));
+
+        let entry = SearchEntry::new();
+        entry.set_hexpand(true);
+        entry.set_placeholder_text(Some("Filter the names in this folder"));
+        let wildcards = CheckButton::with_label("Wildcards");
+        wildcards.set_tooltip_text(Some("Match the whole name, * and ? standing for any text"));
+        let match_case = CheckButton::with_label("Match case");
+
+        let row = GtkBox::new(Orientation::Horizontal, 6);
+        row.append(&entry);
+        row.append(&wildcards);
+        row.append(&match_case);
+
+        let bar = gtk4::SearchBar::new();
+        bar.set_child(Some(&row));
+        bar.connect_entry(&entry);
+        bar.set_show_close_button(true);
+
+        let filtering = Filtering { filter_model, filter, pattern };
+        Self { bar, entry, wildcards, match_case, filtering }
+    }
+
+    pub fn widget(&self) -> &gtk4::SearchBar {
+        &self.bar
+    }
+
+    /// Opens the bar, or moves the focus back to it.
+    pub fn open(&self) {
+        self.bar.set_search_mode(true);
+        self.entry.grab_focus();
+    }
+
+    /// Filters the listing of `fm_window` as the pattern changes, and adds
+    /// the `win.filter` action opening the bar with Ctrl+I.
+    pub fn connect(&self, fm_window: &FmWindow) {
+        self.entry.connect_search_changed(glib::clone!(
+            #[weak(rename_to = wildcards)]
+            self.wildcards,
+            #[weak(rename_to = match_case)]
+            self.match_case,
+            #[strong(rename_to = filtering)]
+            self.filtering,
+            move |entry| filtering.update(entry, &wildcards, &match_case)
+        ));
+        for toggle in [&self.wildcards, &self.match_case] {
+            toggle.connect_toggled(glib::clone!(
+                #[weak(rename_to = entry)]
+                self.entry,
+                #[weak(rename_to = wildcards)]
+                self.wildcards,
+                #[weak(rename_to = match_case)]
+                self.match_case,
+                #[strong(rename_to = filtering)]
+                self.filtering,
+                move |_| filtering.update(&entry, &wildcards, &match_case)
+            ));
+        }
+
+        // Enter goes to the first item left
+        self.entry.connect_activate(glib::clone!(
+            #[weak(rename_to = files_selection)]
+            fm_window.files_selection,
+            #[weak(rename_to = column_view)]
+            fm_window.column_view,
+            move |_| {
+                if files_selection.n_items() > 0 {
+                    files_selection.select_item(0, true);
+                    files_panel::scroll_to_position(&column_view, 0);
+                }
+                files_panel::shown_view(&column_view).grab_focus();
+            }
+        ));
+
+        self.bar.connect_search_mode_enabled_notify(glib::clone!(
+            #[weak(rename_to = entry)]
+            self.entry,
+            #[weak(rename_to = wildcards)]
+            self.wildcards,
+            #[weak(rename_to = match_case)]
+            self.match_case,
+            #[strong(rename_to = filtering)]
+            self.filtering,
+            #[weak(rename_to = column_view)]
+            fm_window.column_view,
+            move |bar| {
+                if bar.is_search_mode() {
+                    return;
+                }
+                entry.set_text("");
+                filtering.update(&entry, &wildcards, &match_case);
+                files_panel::shown_view(&column_view).grab_focus();
+            }
+        ));
+
+        let filter_action = gio::SimpleAction::new("filter", None);
+        filter_action.connect_activate(glib::clone!(
+            #[weak(rename_to = bar)]
+            self.bar,
+            #[weak(rename_to = entry)]
+            self.entry,
+            move |_, _| {
+                bar.set_search_mode(true);
+                entry.grab_focus();
+            }
+        ));
+        fm_window.window.add_action(&filter_action);
+
+        let shortcuts = gtk4::ShortcutController::new();
+        shortcuts.add_shortcut(gtk4::Shortcut::new(
+            gtk4::ShortcutTrigger::parse_string("<Control>i"),
+            Some(gtk4::NamedAction::new("win.filter")),
+        ));
+        fm_window.window.add_controller(shortcuts);
+
+        // A filter belongs to the folder it was typed in
+        fm_window.fmstate.borrow_mut().connect_path_changed(glib::clone!(
+            #[weak(rename_to = bar)]
+            self.bar,
+            move |_| bar.set_search_mode(false)
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+//! Handle to a file manager window, so it can be driven from outside the
+//! widget callbacks (command line, D-Bus requests, ...).
+
+use crate::{
+    files_panel, filter_bar::FilterBar, models::file_item::FileItem, operations::OperationsPanel,
+    preview_pane::PreviewPane, search_bar::SearchBar, state::FmState, utils::WidgetDataExt,
+};
+use gtk4::{
+    Application, ApplicationWindow, ColumnView, MultiSelection, SingleSelection, gio, prelude::*,
+};
+use std::{cell::RefCell, rc::Rc};
+
+const WINDOW_DATA_KEY: &str = "fm-window";
+
+#[derive(Clone)]
+pub struct FmWindow {
+    pub window: ApplicationWindow,
+    pub fmstate: Rc<RefCell<FmState>>,
+    pub file_store: gio::ListStore,
+    pub column_view: ColumnView,
+    pub files_selection: MultiSelection,
+    pub sidebar_selection: SingleSelection,
+    pub operations: OperationsPanel,
+    pub search_bar: SearchBar,
+    pub filter_bar: FilterBar,
+    pub preview_pane: PreviewPane,
+}
+
+impl FmWindow {
+    /// Attaches the handle to its window so it can be found again later.
+    pub fn register(&self) {
+        self.window.set_typed_data(WINDOW_DATA_KEY, self.clone());
+        self.window.track_widget_cleanup();
+    }
+
+    pub fn from_window(window: &gtk4::Window) -> Option<FmWindow> {
+        window.get_typed_data::<FmWindow>(WINDOW_DATA_KEY)
+    }
+
+    /// Returns the most recently focused file manager window.
+    pub fn active(app: &Application) -> Option<FmWindow> {
+        app.active_window().and_then(|window| Self::from_window(&window))
+    }
+
+    /// Lists the current folder again, after its contents changed. Search
+    /// results are refreshed instead.
+    pub fn reload(&self) {
+        if self.search_bar.showing_results() {
+            self.search_bar.refresh_results(&self.file_store);
+            return;
+        }
+
+        let fmstate = self.fmstate.borrow();
+        files_panel::populate_files_list(
+            &self.file_store,
+            &fmstate.current_path,
+            &fmstate.settings.show_hidden,
+        );
+    }
+
+    pub fn open_location(&self, dir: &gio::File) {
+        let mut fmstate_mut = self.fmstate.borrow_mut();
+
+        files_panel::populate_files_list(&self.file_store, dir, &fmstate_mut.settings.show_hidden);
+        fmstate_mut.set_path(dir.clone());
+        fmstate_mut.update_history(dir.clone());
+        self.sidebar_selection.unselect_all();
+    }
+
+    /// Opens the folder containing `file` and highlights it.
+    pub fn reveal(&self, file: &gio::File) {
+        match file.parent() {
+            Some(parent) => {
+                self.open_location(&parent);
+                self.select_file(file);
+            }
+            None => self.open_location(file),
+        }
+    }
+
+    /// Selects `file` in the current listing and scrolls it into view.
+    /// Returns `false` when the file is not part of the listing.
+    pub fn select_file(&self, file: &gio::File) -> bool {
+        self.select_files(std::slice::from_ref(file))
+    }
+
+    /// Replaces the selection with `files` and scrolls the first one into
+    /// view. Returns `false` when none of them is part of the listing.
+    pub fn select_files(&self, files: &[gio::File]) -> bool {
+        let targets: Vec<String> = files
+            .iter()
+            .map(|f| {
+                f.path().map(|p| p.display().to_string()).unwrap_or_else(|| f.uri().to_string())
+            })
+            .collect();
+
+        let positions: Vec<u32> = (0..self.files_selection.n_items())
+            .filter(|&i| {
+                self.files_selection
+                    .item(i)
+                    .and_downcast::<FileItem>()
+                    .is_some_and(|item| targets.contains(&item.path()))
+            })
+            .collect();
+
+        let Some(&first) = positions.first() else {
+            return false;
+        };
+
+        self.files_selection.unselect_all();
+        for position in positions {
+            self.files_selection.select_item(position, false);
+        }
+        files_panel::scroll_to_position(&self.column_view, first);
+        files_panel::shown_view(&self.column_view).grab_focus();
+        true
+    }
+}
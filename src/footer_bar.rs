@@ -1,3 +1,4 @@
+use crate::{models::file_item::FileItem, utils::SizeUnits};
 use gtk4::{Box as GtkBox, Label, Orientation, gio, prelude::*};
 use std::path::Path;
 use sysinfo::Disks;
@@ -58,12 +59,18 @@ pub fn update_disk_space(label: &Label, path: &gio::File) {
     }
 }
 
-pub fn update_item_count(label: &Label, count: usize) {
-    let text = if count == 1 { "1 item".to_string() } else { format!("{} items", count) };
+/// Shows how many items are listed, as "N of M items" while a filter hides
+/// some of the `total`.
+pub fn update_item_count(label: &Label, shown: usize, total: usize) {
+    let text = match (shown, total) {
+        (shown, total) if shown != total => format!("{} of {} items", shown, total),
+        (_, 1) => "1 item".to_string(),
+        (_, total) => format!("{} items", total),
+    };
     label.set_text(&text);
 }
 
-pub fn update_selection_info(label: &Label, file: &gio::File) {
+pub fn update_selection_info(label: &Label, file: &gio::File, units: SizeUnits) {
     if let Ok(info) = file.query_info(
         "standard::size,standard::type,standard::content-type",
         gio::FileQueryInfoFlags::NONE,
@@ -75,7 +82,7 @@ pub fn update_selection_info(label: &Label, file: &gio::File) {
             label.set_text("Directory");
         } else {
             let size = info.size();
-            let size_str = format_size(size as u64);
+            let size_str = format_size(size as u64, units);
 
             // Get file type description
             let type_desc = get_file_type_description(file);
@@ -87,6 +94,26 @@ pub fn update_selection_info(label: &Label, file: &gio::File) {
     }
 }
 
+/// Like `update_selection_info`, from what the listing knows about the item.
+pub fn update_item_info(label: &Label, item: &FileItem, units: SizeUnits) {
+    if item.is_directory() {
+        label.set_text("Directory");
+    } else {
+        label.set_text(&format!("{} - {}", item.mime_type(), format_size(item.size(), units)));
+    }
+}
+
+pub fn update_multi_selection_info(label: &Label, items: &[FileItem], units: SizeUnits) {
+    let size: u64 = items.iter().filter(|item| !item.is_directory()).map(|item| item.size()).sum();
+    let folders = items.iter().filter(|item| item.is_directory()).count();
+
+    let mut text = format!("{} items selected", items.len());
+    if folders < items.len() {
+        text.push_str(&format!(" - {}", format_size(size, units)));
+    }
+    label.set_text(&text);
+}
+
 pub fn update_default_app(label: &Label, file: &gio::File) {
     // Only show default app for regular files, not directories
     if let Ok(info) = file.query_info(
@@ -214,20 +241,18 @@ pub fn get_default_app(file: &gio::File) -> Option<String> {
     None
 }
 
-pub fn format_size(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-    const TB: u64 = GB * 1024;
-
-    if bytes >= TB {
-        format!("{:.2} TB", bytes as f64 / TB as f64)
-    } else if bytes >= GB {
-        format!("{:.2} GB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.2} MB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.2} KB", bytes as f64 / KB as f64)
+pub fn format_size(bytes: u64, units: SizeUnits) -> String {
+    let suffixes = units.suffixes();
+    let mut value = bytes as f64;
+    let mut unit_idx = 0;
+
+    while value >= units.base() && unit_idx < suffixes.len() - 1 {
+        value /= units.base();
+        unit_idx += 1;
+    }
+
+    if unit_idx > 0 {
+        format!("{:.2} {}", value, suffixes[unit_idx])
     } else if bytes == 1 {
         "1 byte".to_string()
     } else {
@@ -0,0 +1,408 @@
+//! Folders visited, ranked by how often and how recently.
+//!
+//! Every folder opened is recorded with a visit count and the time of the
+//! last visit. The ranking feeds the Ctrl+P folder palette and the path bar
+//! completion. Folders deleted since are dropped from time to time, when the
+//! palette opens. The database is shared by all windows and kept in the user
+//! data folder.
+
+use crate::utils::json::{self, JsonValue};
+use gtk4::{gio, glib, prelude::*};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+const DATABASE_FILE: &str = "folder-history.json";
+const SCHEMA_VERSION: u64 = 1;
+
+/// The least used folders are forgotten beyond this many.
+const MAX_FOLDERS: usize = 1000;
+
+/// Changes are written this long after the last one.
+const SAVE_DELAY_SECS: u32 = 5;
+
+/// Deleted folders are looked for at most this often.
+const PRUNE_INTERVAL_SECS: i64 = 600;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Visits {
+    path: PathBuf,
+    count: u32,
+    /// Unix time of the last visit.
+    last: i64,
+}
+
+impl Visits {
+    /// Visits weighted by how long ago the last one was.
+    fn score(&self, now: i64) -> f64 {
+        let age = now - self.last;
+        let weight = match age {
+            age if age < 3600 => 4.0,
+            age if age < 86400 => 2.0,
+            age if age < 7 * 86400 => 0.5,
+            _ => 0.25,
+        };
+        self.count as f64 * weight
+    }
+}
+
+#[derive(Default)]
+struct Database {
+    folders: Vec<Visits>,
+    save_timer: Option<glib::SourceId>,
+    /// Unix time deleted folders were last looked for.
+    last_prune: i64,
+}
+
+thread_local! {
+    static DATABASE: RefCell<Option<Database>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` on the database, reading it from disk the first time.
+fn with_database<T>(f: impl FnOnce(&mut Database) -> T) -> T {
+    DATABASE.with_borrow_mut(|database| {
+        let database = database.get_or_insert_with(|| Database {
+            folders: load().unwrap_or_else(|e| {
+                eprintln!("Failed to read the folder history: {}", e);
+                Vec::new()
+            }),
+            save_timer: None,
+            last_prune: 0,
+        });
+        f(database)
+    })
+}
+
+/// Counts a visit to `folder`. Only folders on this computer are kept.
+pub fn record(folder: &gio::File) {
+    let Some(path) = folder.path() else {
+        return;
+    };
+    let now = glib::real_time() / 1_000_000;
+
+    with_database(|database| {
+        match database.folders.iter_mut().find(|visits| visits.path == path) {
+            Some(visits) => {
+                visits.count = visits.count.saturating_add(1);
+                visits.last = now;
+            }
+            None => database.folders.push(Visits { path, count: 1, last: now }),
+        }
+        forget_least_used(&mut database.folders, MAX_FOLDERS, now);
+    });
+    schedule_save();
+}
+
+/// Keeps the `max` best ranked of `folders`.
+fn forget_least_used(folders: &mut Vec<Visits>, max: usize, now: i64) {
+    if folders.len() > max {
+        folders.sort_by(|a, b| b.score(now).total_cmp(&a.score(now)));
+        folders.truncate(max);
+    }
+}
+
+/// The folders visited, best ranked first.
+pub fn ranked() -> Vec<PathBuf> {
+    let now = glib::real_time() / 1_000_000;
+
+    let mut folders = with_database(|database| database.folders.clone());
+    folders.sort_by(|a, b| b.score(now).total_cmp(&a.score(now)));
+    folders.into_iter().map(|visits| visits.path).collect()
+}
+
+/// Drops the folders deleted since they were visited, unless that was done
+/// recently. They are looked for on a worker thread, as a stale network
+/// mount can block for a long while.
+pub fn prune() {
+    let now = glib::real_time() / 1_000_000;
+    let paths = with_database(|database| {
+        if now - database.last_prune < PRUNE_INTERVAL_SECS {
+            return None;
+        }
+        database.last_prune = now;
+        Some(database.folders.iter().map(|visits| visits.path.clone()).collect::<Vec<_>>())
+    });
+    let Some(paths) = paths else {
+        return;
+    };
+
+    glib::spawn_future_local(async move {
+        let deleted = gio::spawn_blocking(move || {
+            paths.into_iter().filter(|path| is_deleted(path)).collect::<HashSet<_>>()
+        })
+        .await;
+        let Ok(deleted) = deleted else {
+            return;
+        };
+        if deleted.is_empty() {
+            return;
+        }
+        with_database(|database| database.folders.retain(|visits| !deleted.contains(&visits.path)));
+        schedule_save();
+    });
+}
+
+/// Whether `folder` was deleted, rather than out of reach. It must be
+/// missing from a parent that is there and not empty, which tells the drive
+/// it was on is mounted: the mount point of a drive taken out is empty.
+fn is_deleted(folder: &Path) -> bool {
+    let missing = matches!(
+        std::fs::metadata(folder),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound
+    );
+    missing
+        && folder
+            .parent()
+            .and_then(|parent| std::fs::read_dir(parent).ok())
+            .is_some_and(|mut entries| entries.next().is_some())
+}
+
+/// The folders of `ranked` matching `query`, at most `limit` of them. Each
+/// word of the query must be found in the path, its letters in order but not
+/// necessarily together, ignoring case. Folders whose own name contains the
+/// last word come first, otherwise the ranking is kept.
+pub fn find(ranked: &[PathBuf], query: &str, limit: usize) -> Vec<PathBuf> {
+    let query = query.to_lowercase();
+    let words: Vec<&str> = query.split_whitespace().collect();
+    let Some(last_word) = words.last().copied() else {
+        return ranked.iter().take(limit).cloned().collect();
+    };
+
+    let (mut named, mut others): (Vec<PathBuf>, Vec<PathBuf>) = ranked
+        .iter()
+        .filter(|path| {
+            let path = path.to_string_lossy().to_lowercase();
+            words.iter().all(|word| fuzzy_contains(&path, word))
+        })
+        .cloned()
+        .partition(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().to_lowercase().contains(last_word))
+        });
+
+    named.append(&mut others);
+    named.truncate(limit);
+    named
+}
+
+/// Whether the letters of `word` appear in `text` in the same order.
+fn fuzzy_contains(text: &str, word: &str) -> bool {
+    let mut rest = text.chars();
+    word.chars().all(|c| rest.any(|t| t == c))
+}
+
+/// Saves the changes not saved yet, for when the application quits.
+pub fn save_pending() {
+    let pending = DATABASE.with_borrow_mut(|database| {
+        database.as_mut().and_then(|database| database.save_timer.take())
+    });
+    if let Some(timer) = pending {
+        timer.remove();
+        save();
+    }
+}
+
+fn schedule_save() {
+    DATABASE.with_borrow_mut(|database| {
+        let Some(database) = database.as_mut().filter(|database| database.save_timer.is_none())
+        else {
+            return;
+        };
+        database.save_timer = Some(glib::timeout_add_seconds_local_once(SAVE_DELAY_SECS, || {
+            DATABASE.with_borrow_mut(|database| {
+                if let Some(database) = database.as_mut() {
+                    database.save_timer = None;
+                }
+            });
+            save();
+        }));
+    });
+}
+
+fn database_path() -> PathBuf {
+    glib::user_data_dir().join("axfm").join(DATABASE_FILE)
+}
+
+fn save() {
+    let text = DATABASE.with_borrow(|database| {
+        database.as_ref().map(|database| encode(&database.folders).to_pretty_string())
+    });
+    let Some(text) = text else {
+        return;
+    };
+
+    let path = database_path();
+    let result =
+        path.parent().map_or(Ok(()), std::fs::create_dir_all).map_err(|e| e.to_string()).and_then(
+            |()| {
+                // glib writes to a temporary file and renames it over the old one
+                glib::file_set_contents(&path, text.as_bytes()).map_err(|e| e.to_string())
+            },
+        );
+    if let Err(e) = result {
+        eprintln!("Failed to save the folder history to {}: {}", path.display(), e);
+    }
+}
+
+fn load() -> Result<Vec<Visits>, String> {
+    let path = database_path();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let json = json::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    decode(&json).ok_or_else(|| format!("{} has an unexpected layout", path.display()))
+}
+
+fn encode(folders: &[Visits]) -> JsonValue {
+    let folders = folders
+        .iter()
+        .map(|visits| {
+            JsonValue::Object(vec![
+                ("path".to_string(), JsonValue::String(visits.path.display().to_string())),
+                ("visits".to_string(), JsonValue::Number(visits.count as f64)),
+                ("last-visit".to_string(), JsonValue::Number(visits.last as f64)),
+            ])
+        })
+        .collect();
+
+    JsonValue::Object(vec![
+        ("version".to_string(), JsonValue::Number(SCHEMA_VERSION as f64)),
+        ("folders".to_string(), JsonValue::Array(folders)),
+    ])
+}
+
+/// Reads the folders saved, skipping the entries that don't decode so one
+/// bad entry doesn't cost the whole history.
+fn decode(json: &JsonValue) -> Option<Vec<Visits>> {
+    if json.get("version")?.as_u64()? > SCHEMA_VERSION {
+        return None;
+    }
+
+    let folders = json.get("folders")?.as_array()?;
+    Some(
+        folders
+            .iter()
+            .filter_map(|entry| {
+                Some(Visits {
+                    path: Path::new(entry.get("path")?.as_str()?).to_path_buf(),
+                    count: entry.get("visits")?.as_u64()?.min(u32::MAX as u64) as u32,
+                    last: entry.get("last-visit")?.as_u64()? as i64,
+                })
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn visits(path: &str, count: u32, age: i64) -> Visits {
+        Visits { path: PathBuf::from(path), count, last: NOW - age }
+    }
+
+    fn paths(list: &[&str]) -> Vec<PathBuf> {
+        list.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn scores_decay_with_age() {
+        assert_eq!(visits("/a", 3, 0).score(NOW), 12.0);
+        assert_eq!(visits("/a", 3, 3599).score(NOW), 12.0);
+        assert_eq!(visits("/a", 3, 3600).score(NOW), 6.0);
+        assert_eq!(visits("/a", 3, 86400).score(NOW), 1.5);
+        assert_eq!(visits("/a", 3, 7 * 86400).score(NOW), 0.75);
+        assert_eq!(visits("/a", 3, 365 * 86400).score(NOW), 0.75);
+
+        // A folder visited often long ago still beats one seen once today
+        assert!(visits("/a", 10, 30 * 86400).score(NOW) > visits("/b", 1, 7200).score(NOW));
+    }
+
+    #[test]
+    fn finds_folders_by_words() {
+        let ranked = paths(&["/home/me/src/axfm", "/home/me/Music", "/srv/music-backup"]);
+
+        assert_eq!(find(&ranked, "", 2), paths(&["/home/me/src/axfm", "/home/me/Music"]));
+        assert_eq!(find(&ranked, "AXFM", 10), paths(&["/home/me/src/axfm"]));
+        // Letters in order, not necessarily together
+        assert_eq!(find(&ranked, "hmsrc", 10), paths(&["/home/me/src/axfm"]));
+        assert_eq!(find(&ranked, "src me", 10), paths(&["/home/me/src/axfm"]));
+        assert!(find(&ranked, "xyz", 10).is_empty());
+    }
+
+    #[test]
+    fn folders_named_after_the_query_come_first() {
+        let ranked = paths(&["/music/old/photos", "/home/me/music", "/srv/music"]);
+
+        assert_eq!(
+            find(&ranked, "music", 10),
+            paths(&["/home/me/music", "/srv/music", "/music/old/photos"])
+        );
+        assert_eq!(find(&ranked, "music", 1), paths(&["/home/me/music"]));
+    }
+
+    #[test]
+    fn least_used_folders_are_forgotten() {
+        let mut folders = vec![
+            visits("/old", 5, 30 * 86400),
+            visits("/today", 2, 60),
+            visits("/yesterday", 3, 2 * 3600),
+        ];
+
+        forget_least_used(&mut folders, 3, NOW);
+        assert_eq!(folders.len(), 3);
+
+        forget_least_used(&mut folders, 2, NOW);
+        let kept: Vec<&str> = folders.iter().map(|v| v.path.to_str().unwrap()).collect();
+        assert_eq!(kept, ["/today", "/yesterday"]);
+    }
+
+    #[test]
+    fn only_deleted_folders_are_pruned() {
+        let dir = std::env::temp_dir().join(format!("axfm-frecency-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("kept")).unwrap();
+
+        assert!(!is_deleted(&dir.join("kept")));
+        assert!(is_deleted(&dir.join("gone")));
+        // An empty parent may be the mount point of a drive taken out
+        assert!(!is_deleted(&dir.join("kept").join("gone")));
+        assert!(!is_deleted(&dir.join("missing").join("gone")));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn round_trip() {
+        let folders = vec![visits("/a", 1, 0), visits("/b c", 42, 86400)];
+        assert_eq!(decode(&encode(&folders)), Some(folders));
+    }
+
+    #[test]
+    fn bad_entries_are_skipped() {
+        let json = json::parse(
+            r#"{"version": 1, "folders": [
+                {"path": "/a", "visits": 2, "last-visit": 100},
+                {"path": "/b", "visits": "many", "last-visit": 100},
+                {"visits": 1, "last-visit": 100},
+                {"path": "/c", "visits": 1, "last-visit": 200}
+            ]}"#,
+        )
+        .unwrap();
+
+        let folders = decode(&json).unwrap();
+        let kept: Vec<&str> = folders.iter().map(|v| v.path.to_str().unwrap()).collect();
+        assert_eq!(kept, ["/a", "/c"]);
+    }
+
+    #[test]
+    fn newer_versions_are_not_read() {
+        let json = json::parse(r#"{"version": 2, "folders": []}"#).unwrap();
+        assert_eq!(decode(&json), None);
+    }
+}
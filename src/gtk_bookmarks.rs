@@ -0,0 +1,260 @@
+//! The GTK bookmarks file (`~/.config/gtk-3.0/bookmarks`), shared by file
+//! choosers and other file managers.
+//!
+//! Each line holds a URI, optionally followed by a space and a custom label.
+//! Lines that aren't bookmarks are kept as they are when the file is written.
+
+use crate::bookmarks::{Bookmark, BookmarksError};
+use gtk4::{gio, glib, prelude::*};
+use std::path::PathBuf;
+
+pub fn gtk_bookmarks_path() -> PathBuf {
+    glib::user_config_dir().join("gtk-3.0").join("bookmarks")
+}
+
+/// Reads the GTK bookmarks. A missing file is the same as an empty one.
+pub fn load_gtk_bookmarks() -> Result<Vec<Bookmark>, BookmarksError> {
+    let path = gtk_bookmarks_path();
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => Ok(parse_gtk_bookmarks(&content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(source) => Err(BookmarksError::Io { path, source }),
+    }
+}
+
+pub fn save_gtk_bookmarks(bookmarks: &[Bookmark]) -> Result<(), BookmarksError> {
+    let path = gtk_bookmarks_path();
+    let existing = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(source) => return Err(BookmarksError::Io { path, source }),
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| BookmarksError::Write {
+            path: dir.to_path_buf(),
+            message: e.to_string(),
+        })?;
+    }
+
+    let content = format_gtk_bookmarks(bookmarks, &unknown_lines(&existing));
+    glib::file_set_contents(&path, content.as_bytes())
+        .map_err(|e| BookmarksError::Write { path, message: e.to_string() })
+}
+
+fn parse_gtk_bookmarks(content: &str) -> Vec<Bookmark> {
+    content.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<Bookmark> {
+    let line = line.trim_end();
+    let (uri, label) = match line.split_once(' ') {
+        Some((uri, label)) => (uri, Some(label.trim())),
+        None => (line, None),
+    };
+
+    glib::uri_parse_scheme(uri)?;
+
+    let mut bookmark = Bookmark::from_file(&gio::File::for_uri(uri));
+    if let Some(label) = label.filter(|l| !l.is_empty()) {
+        bookmark.name = label.to_string();
+    }
+    Some(bookmark)
+}
+
+/// The lines of `content` that aren't bookmarks, blank lines aside.
+fn unknown_lines(content: &str) -> Vec<&str> {
+    content.lines().filter(|line| !line.trim().is_empty() && parse_line(line).is_none()).collect()
+}
+
+/// Writes `bookmarks`, followed by the `unknown` lines of the file.
+fn format_gtk_bookmarks(bookmarks: &[Bookmark], unknown: &[&str]) -> String {
+    let mut content = String::new();
+
+    for bookmark in bookmarks {
+        let file = bookmark.to_gio_file();
+        content.push_str(&file.uri());
+
+        // Only custom names are written, like GTK does
+        if bookmark.name != Bookmark::from_file(&file).name {
+            content.push(' ');
+            content.push_str(&bookmark.name.replace('\n', " "));
+        }
+        content.push('\n');
+    }
+    for line in unknown {
+        content.push_str(line);
+        content.push('\n');
+    }
+
+    content
+}
+
+/// Three-way merge of our bookmarks with the GTK file. `previous` is the GTK
+/// list as last read or written, so that entries removed or renamed by
+/// another application can be told apart from ones it never had.
+pub fn merge_gtk_bookmarks(
+    own: &[Bookmark],
+    previous: &[Bookmark],
+    external: &[Bookmark],
+) -> Vec<Bookmark> {
+    let find = |list: &[Bookmark], path: &str| list.iter().find(|b| b.path == path).cloned();
+
+    let mut merged: Vec<Bookmark> = own
+        .iter()
+        .filter_map(|bookmark| {
+            match (find(previous, &bookmark.path), find(external, &bookmark.path)) {
+                (Some(_), None) => None,
+                (Some(before), Some(after)) if before.name != after.name => {
+                    Some(Bookmark { name: after.name, ..bookmark.clone() })
+                }
+                _ => Some(bookmark.clone()),
+            }
+        })
+        .collect();
+
+    for bookmark in external {
+        if find(&merged, &bookmark.path).is_none() {
+            merged.push(bookmark.clone());
+        }
+    }
+
+    merged
+}
+
+/// Whether both lists hold the same entries as far as the GTK file can tell,
+/// i.e. ignoring icons and groups.
+pub fn same_gtk_bookmarks(a: &[Bookmark], b: &[Bookmark]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.path == b.path && a.name == b.name)
+}
+
+/// Calls `on_change` with the new contents whenever the GTK bookmarks file
+/// is written.
+pub fn watch_gtk_bookmarks<F>(on_change: F) -> Option<gio::FileMonitor>
+where
+    F: Fn(Vec<Bookmark>) + 'static,
+{
+    let file = gio::File::for_path(gtk_bookmarks_path());
+    let monitor = match file.monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            eprintln!("Failed to watch GTK bookmarks: {}", e);
+            return None;
+        }
+    };
+
+    monitor.connect_changed(move |_, _, _, event| {
+        if matches!(event, gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::Created)
+        {
+            match load_gtk_bookmarks() {
+                Ok(bookmarks) => on_change(bookmarks),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    });
+
+    Some(monitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "file:///home/user/Music
+file:///tmp/My%20Stuff Stuff to sort
+sftp://host/srv Server
+# not a bookmark
+/home/user/no-scheme
+";
+
+    fn bookmark(name: &str, path: &str) -> Bookmark {
+        Bookmark::new(name.to_string(), path.to_string())
+    }
+
+    fn names(bookmarks: &[Bookmark]) -> Vec<&str> {
+        bookmarks.iter().map(|b| b.name.as_str()).collect()
+    }
+
+    #[test]
+    fn parse_reads_uris_and_labels() {
+        let bookmarks = parse_gtk_bookmarks(CONTENT);
+        assert_eq!(names(&bookmarks), ["Music", "Stuff to sort", "Server"]);
+        assert_eq!(bookmarks[0].path, "/home/user/Music");
+        assert_eq!(bookmarks[1].path, "/tmp/My Stuff");
+        assert_eq!(bookmarks[2].path, "sftp://host/srv");
+    }
+
+    #[test]
+    fn format_round_trip_keeps_unknown_lines() {
+        let bookmarks = parse_gtk_bookmarks(CONTENT);
+        let unknown = unknown_lines(CONTENT);
+        assert_eq!(unknown, ["# not a bookmark", "/home/user/no-scheme"]);
+
+        let written = format_gtk_bookmarks(&bookmarks, &unknown);
+        assert_eq!(written, CONTENT);
+        assert_eq!(parse_gtk_bookmarks(&written), bookmarks);
+    }
+
+    #[test]
+    fn only_custom_labels_are_written() {
+        let written = format_gtk_bookmarks(
+            &[bookmark("Music", "/home/user/Music"), bookmark("Two\nlines", "/tmp")],
+            &[],
+        );
+        assert_eq!(written, "file:///home/user/Music\nfile:///tmp Two lines\n");
+    }
+
+    #[test]
+    fn merge_follows_external_removals_and_renames() {
+        let mut own_a = bookmark("A", "/a");
+        own_a.group = Some("Work".to_string());
+        let own = [own_a, bookmark("B", "/b")];
+        let previous = [bookmark("A", "/a"), bookmark("B", "/b")];
+        let external = [bookmark("Renamed", "/a")];
+
+        let merged = merge_gtk_bookmarks(&own, &previous, &external);
+        assert_eq!(names(&merged), ["Renamed"]);
+        // What the GTK file can't hold is kept
+        assert_eq!(merged[0].group.as_deref(), Some("Work"));
+    }
+
+    #[test]
+    fn merge_keeps_own_changes() {
+        // Added and renamed here since the last sync
+        let own = [bookmark("Mine", "/a"), bookmark("New", "/new")];
+        let previous = [bookmark("A", "/a")];
+        let external = [bookmark("A", "/a")];
+
+        let merged = merge_gtk_bookmarks(&own, &previous, &external);
+        assert_eq!(names(&merged), ["Mine", "New"]);
+    }
+
+    #[test]
+    fn merge_appends_external_additions() {
+        let own = [bookmark("A", "/a")];
+        let previous = [bookmark("A", "/a")];
+        let external = [bookmark("A", "/a"), bookmark("Theirs", "/theirs")];
+
+        let merged = merge_gtk_bookmarks(&own, &previous, &external);
+        assert_eq!(names(&merged), ["A", "Theirs"]);
+    }
+
+    #[test]
+    fn merge_prefers_external_when_both_renamed() {
+        let own = [bookmark("Ours", "/a")];
+        let previous = [bookmark("A", "/a")];
+        let external = [bookmark("Theirs", "/a")];
+
+        assert_eq!(names(&merge_gtk_bookmarks(&own, &previous, &external)), ["Theirs"]);
+    }
+
+    #[test]
+    fn first_sync_merges_both_sides() {
+        let own = [bookmark("A", "/a")];
+        let external = [bookmark("B", "/b"), bookmark("Other name", "/a")];
+
+        let merged = merge_gtk_bookmarks(&own, &[], &external);
+        assert_eq!(names(&merged), ["A", "B"]);
+    }
+}
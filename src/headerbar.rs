@@ -1,7 +1,7 @@
 use crate::{files_panel, state::FmState};
 use gtk4::{
     Application, ApplicationWindow, Button, HeaderBar, MenuButton,
-    gio::{Menu, SimpleAction},
+    gio::{self, Menu, SimpleAction},
     glib,
     prelude::*,
 };
@@ -31,6 +31,10 @@ pub fn build_headerbar(fmstate: Rc<RefCell<FmState>>) -> HeaderBar {
     let edit_submenu = Menu::new();
     edit_submenu.append(Some("Undo"), Some("win.undo_history"));
     edit_submenu.append(Some("Redo"), Some("win.redo_history"));
+    edit_submenu.append(Some("Undo Rename"), Some("win.undo"));
+    edit_submenu.append(Some("Search"), Some("win.search"));
+    edit_submenu.append(Some("Filter"), Some("win.filter"));
+    edit_submenu.append(Some("Go to Folder"), Some("win.go_to_folder"));
     edit_submenu.append(Some("Manage Bookmarks"), Some("win.manage_bookmarks"));
     menu.append_submenu(Some("Edit"), &edit_submenu);
 
@@ -38,8 +42,11 @@ pub fn build_headerbar(fmstate: Rc<RefCell<FmState>>) -> HeaderBar {
     let view_submenu = Menu::new();
     view_submenu.append(Some("Show Hidden Files"), Some("win.show_hidden"));
     view_submenu.append(Some("Folders First"), Some("win.folders_first"));
+    view_submenu.append(Some("Preview Pane"), Some("win.show_preview"));
     menu.append_submenu(Some("View"), &view_submenu);
 
+    menu.append(Some("Preferences"), Some("win.preferences"));
+
     // Menu button
     let menu_button = MenuButton::new();
     menu_button.set_icon_name("open-menu-symbolic");
@@ -60,7 +67,7 @@ pub fn implement_actions(
     file_store: &gtk4::gio::ListStore,
     sidebar_selection: &gtk4::SingleSelection,
     headerbar: &HeaderBar,
-    sidebar_list: &gtk4::StringList,
+    sidebar_list: &gio::ListStore,
 ) {
     // Set initial window title
     let current_dir = fmstate.borrow().current_path.basename();
@@ -85,6 +92,7 @@ pub fn implement_actions(
 
             let mut fmstate_mut = fmstate.borrow_mut();
             fmstate_mut.settings.show_hidden = !current;
+            fmstate_mut.save_settings();
 
             files_panel::populate_files_list(
                 &file_store,
@@ -108,20 +116,35 @@ pub fn implement_actions(
             let current: bool = action.state().unwrap().get().unwrap();
             action.set_state(&(!current).into());
 
+            // Applied like a preference, so the file list sorts again
+            let mut settings = fmstate.borrow().settings.clone();
+            settings.folders_first = !current;
             let mut fmstate_mut = fmstate.borrow_mut();
-            fmstate_mut.settings.folders_first = !current;
-            // Sorting will be implemented in Phase 3
+            fmstate_mut.apply_settings(settings);
+            fmstate_mut.save_settings();
         }
     ));
 
     window.add_action(&folders_first_action);
 
+    // Keep the toggles in sync when the settings file is reloaded
+    fmstate.borrow_mut().connect_settings_changed(glib::clone!(
+        #[weak]
+        show_hidden_action,
+        #[weak]
+        folders_first_action,
+        move |state| {
+            show_hidden_action.set_state(&state.settings.show_hidden.into());
+            folders_first_action.set_state(&state.settings.folders_first.into());
+        }
+    ));
+
     let new_window_action = SimpleAction::new("open_new_window", None);
     new_window_action.connect_activate(glib::clone!(
         #[weak]
         app,
         move |_, _| {
-            crate::build_fm(&app);
+            crate::build_fm(&app, &gio::File::for_path(glib::home_dir()));
         }
     ));
     window.add_action(&new_window_action);
@@ -190,7 +213,7 @@ pub fn implement_actions(
         #[weak]
         sidebar_list,
         move |_, _| {
-            crate::bookmarks::show_manage_bookmarks_dialog(
+            crate::bookmarks_dialog::show_manage_bookmarks_dialog(
                 window.upcast_ref::<gtk4::Window>(),
                 fmstate.clone(),
                 &sidebar_list,
@@ -199,6 +222,22 @@ pub fn implement_actions(
     ));
     window.add_action(&manage_bookmarks_action);
 
+    // Preferences action
+    let preferences_action = SimpleAction::new("preferences", None);
+    preferences_action.connect_activate(glib::clone!(
+        #[weak]
+        window,
+        #[strong]
+        fmstate,
+        move |_, _| {
+            crate::preferences_dialog::show_preferences_dialog(
+                window.upcast_ref::<gtk4::Window>(),
+                fmstate.clone(),
+            );
+        }
+    ));
+    window.add_action(&preferences_action);
+
     // Update window title when path changes
     fmstate.borrow_mut().on_path_changed.push(Box::new(glib::clone!(
         #[weak]
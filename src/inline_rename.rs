@@ -0,0 +1,328 @@
+//! Renaming a file in place, in its cell of the name column.
+//!
+//! The name cell holds an icon, a label and a hidden entry. Editing swaps
+//! the label for the entry; Enter renames, Escape or leaving the entry with
+//! an unusable name gives up.
+
+use crate::{
+    batch_rename, file_operations, files_panel,
+    fm_window::FmWindow,
+    undo::{UndoAction, UndoEntry},
+    utils::WidgetDataExt,
+};
+use gtk4::{Box as GtkBox, Entry, Label, Popover, gdk, gio, glib, prelude::*};
+use std::{cell::RefCell, rc::Rc};
+
+/// Starts renaming `file`, which must be part of the current listing.
+pub fn start_rename(fm_window: &FmWindow, file: &gio::File) {
+    if !fm_window.select_file(file) {
+        return;
+    }
+
+    // The row may only get its widgets once it has been scrolled to
+    let path = file_path(file);
+    glib::idle_add_local_once(glib::clone!(
+        #[strong]
+        fm_window,
+        move || {
+            match find_name_cell(&files_panel::shown_view(&fm_window.column_view), &path) {
+                Some(cell) => begin_editing(&fm_window, &cell),
+                None => eprintln!("No cell found to rename {}", path),
+            }
+        }
+    ));
+}
+
+/// Starts renaming the file shown in the name `cell`.
+pub fn start_rename_in_cell(cell: &GtkBox) {
+    let fm_window = cell
+        .root()
+        .and_downcast::<gtk4::Window>()
+        .and_then(|window| FmWindow::from_window(&window));
+
+    if let Some(fm_window) = fm_window {
+        begin_editing(&fm_window, cell);
+    }
+}
+
+/// Looks for the name cell bound to `path` below `widget`.
+fn find_name_cell(widget: &gtk4::Widget, path: &str) -> Option<GtkBox> {
+    if let Some(cell) = widget.downcast_ref::<GtkBox>() {
+        let cell_path = cell.get_typed_data::<glib::GString>("file-path");
+        if cell_path.is_some_and(|p| p == path) && cell.get_flag("name-cell").unwrap_or(false) {
+            return Some(cell.clone());
+        }
+    }
+
+    let mut child = widget.first_child();
+    while let Some(current) = child {
+        if let Some(cell) = find_name_cell(&current, path) {
+            return Some(cell);
+        }
+        child = current.next_sibling();
+    }
+    None
+}
+
+/// One rename in progress, torn down by `finish`.
+struct Editing {
+    fm_window: FmWindow,
+    file: gio::File,
+    original: String,
+    is_dir: bool,
+    label: Label,
+    entry: Entry,
+    message: Popover,
+    message_label: Label,
+    /// Whether Enter was pressed once already with the extension changed.
+    extension_confirmed: bool,
+    handlers: Vec<glib::SignalHandlerId>,
+    controllers: Vec<gtk4::EventController>,
+}
+
+fn begin_editing(fm_window: &FmWindow, cell: &GtkBox) {
+    let Some(path) = cell.get_typed_data::<glib::GString>("file-path") else {
+        return;
+    };
+    let label = cell.first_child().and_then(|icon| icon.next_sibling()).and_downcast::<Label>();
+    let entry = cell.last_child().and_downcast::<Entry>();
+    let (Some(label), Some(entry)) = (label, entry) else {
+        return;
+    };
+    if WidgetExt::is_visible(&entry) {
+        return;
+    }
+
+    let file = gio::File::for_parse_name(&path);
+    let Some(original) = file.basename().map(|n| n.to_string_lossy().into_owned()) else {
+        return;
+    };
+    let is_dir = cell.get_flag("is-dir").unwrap_or(false);
+
+    // Everything but the extension is selected, so typing keeps it
+    let (stem, _) = batch_rename::split_extension(&original, is_dir);
+    entry.set_text(&original);
+    label.set_visible(false);
+    entry.set_visible(true);
+    entry.grab_focus();
+    entry.select_region(0, stem.chars().count() as i32);
+
+    let message_label = Label::new(None);
+    message_label.set_wrap(true);
+    message_label.set_max_width_chars(40);
+    let message = Popover::builder()
+        .child(&message_label)
+        .autohide(false)
+        .can_focus(false)
+        .position(gtk4::PositionType::Bottom)
+        .build();
+    message.set_parent(&entry);
+
+    let editing = Rc::new(RefCell::new(Some(Editing {
+        fm_window: fm_window.clone(),
+        file,
+        original,
+        is_dir,
+        label,
+        entry: entry.clone(),
+        message,
+        message_label,
+        extension_confirmed: false,
+        handlers: Vec::new(),
+        controllers: Vec::new(),
+    })));
+
+    let changed = entry.connect_changed(glib::clone!(
+        #[strong]
+        editing,
+        move |_| {
+            if let Some(editing) = editing.borrow_mut().as_mut() {
+                editing.extension_confirmed = false;
+                editing.show_check();
+            }
+        }
+    ));
+
+    let activate = entry.connect_activate(glib::clone!(
+        #[strong]
+        editing,
+        move |entry| {
+            let ready = match editing.borrow_mut().as_mut() {
+                Some(current) => current.confirm_commit(),
+                None => return,
+            };
+            if ready {
+                finish(&editing, true);
+            } else {
+                entry.error_bell();
+            }
+        }
+    ));
+
+    let keys = gtk4::EventControllerKey::new();
+    keys.connect_key_pressed(glib::clone!(
+        #[strong]
+        editing,
+        move |_, key, _, _| {
+            if key == gdk::Key::Escape {
+                finish(&editing, false);
+                glib::Propagation::Stop
+            } else {
+                glib::Propagation::Proceed
+            }
+        }
+    ));
+
+    // Clicking elsewhere keeps a usable name, and drops anything else
+    let focus = gtk4::EventControllerFocus::new();
+    focus.connect_leave(glib::clone!(
+        #[strong]
+        editing,
+        move |_| {
+            let usable = editing
+                .borrow()
+                .as_ref()
+                .is_some_and(|current| matches!(current.check(&current.entry.text()), Ok(None)));
+            finish(&editing, usable);
+        }
+    ));
+
+    entry.add_controller(keys.clone());
+    entry.add_controller(focus.clone());
+
+    if let Some(current) = editing.borrow_mut().as_mut() {
+        current.handlers = vec![changed, activate];
+        current.controllers = vec![keys.upcast(), focus.upcast()];
+    }
+}
+
+impl Editing {
+    /// `Err` when the name can't be used, `Ok(Some(warning))` when it can
+    /// but probably shouldn't.
+    fn check(&self, name: &str) -> Result<Option<String>, String> {
+        if name == self.original {
+            return Ok(None);
+        }
+        if let Some(problem) = batch_rename::validate_name(name) {
+            return Err(problem);
+        }
+
+        // A case-only rename finds the file itself on case-insensitive drives
+        let case_only = name.to_lowercase() == self.original.to_lowercase();
+        let taken = self
+            .file
+            .parent()
+            .is_some_and(|parent| parent.child(name).query_exists(gio::Cancellable::NONE));
+        if taken && !case_only {
+            return Err(format!("\"{}\" already exists", name));
+        }
+
+        let (_, old_extension) = batch_rename::split_extension(&self.original, self.is_dir);
+        let (_, new_extension) = batch_rename::split_extension(name, self.is_dir);
+        if old_extension != new_extension {
+            let describe = |extension: Option<&str>| match extension {
+                Some(extension) => format!("\".{}\"", extension),
+                None => "nothing".to_string(),
+            };
+            return Ok(Some(format!(
+                "Changing the extension from {} to {} may open the file with another \
+                 application, or not at all.",
+                describe(old_extension),
+                describe(new_extension)
+            )));
+        }
+
+        Ok(None)
+    }
+
+    fn show_check(&self) {
+        match self.check(&self.entry.text()) {
+            Ok(None) => {
+                self.entry.remove_css_class("error");
+                self.message.popdown();
+            }
+            Ok(Some(warning)) => {
+                self.entry.remove_css_class("error");
+                self.show_message(&warning);
+            }
+            Err(problem) => {
+                self.entry.add_css_class("error");
+                self.show_message(&problem);
+            }
+        }
+    }
+
+    fn show_message(&self, text: &str) {
+        self.message_label.set_text(text);
+        self.message.popup();
+    }
+
+    /// Whether Enter may rename. A changed extension needs a second Enter.
+    fn confirm_commit(&mut self) -> bool {
+        match self.check(&self.entry.text()) {
+            Ok(None) => true,
+            Ok(Some(warning)) if !self.extension_confirmed => {
+                self.extension_confirmed = true;
+                self.show_message(&format!("{}\nPress Enter again to rename.", warning));
+                false
+            }
+            Ok(Some(_)) => true,
+            Err(_) => false,
+        }
+    }
+}
+
+fn finish(editing: &Rc<RefCell<Option<Editing>>>, commit: bool) {
+    // Taken first: hiding the entry moves the focus, which calls back here
+    let Some(current) = editing.borrow_mut().take() else {
+        return;
+    };
+
+    for handler in current.handlers {
+        current.entry.disconnect(handler);
+    }
+    // The controllers may be the ones dispatching right now
+    let controllers = current.controllers;
+    glib::idle_add_local_once(glib::clone!(
+        #[weak(rename_to = entry)]
+        current.entry,
+        move || {
+            for controller in &controllers {
+                entry.remove_controller(controller);
+            }
+        }
+    ));
+    current.message.unparent();
+
+    let new_name = current.entry.text().to_string();
+    current.entry.remove_css_class("error");
+    current.entry.set_visible(false);
+    current.label.set_visible(true);
+    files_panel::shown_view(&current.fm_window.column_view).grab_focus();
+
+    if commit && new_name != current.original {
+        rename(&current.fm_window, &current.file, &new_name);
+    }
+}
+
+fn rename(fm_window: &FmWindow, file: &gio::File, new_name: &str) {
+    match batch_rename::rename_file(file, new_name) {
+        Ok(renamed) => {
+            fm_window.fmstate.borrow_mut().push_undo(UndoEntry {
+                label: format!("Rename \"{}\"", new_name),
+                action: UndoAction::Rename(vec![(file.clone(), renamed.clone())]),
+            });
+            fm_window.reload();
+            fm_window.select_file(&renamed);
+        }
+        Err(e) => file_operations::show_errors(
+            fm_window.window.upcast_ref(),
+            "The file could not be renamed",
+            &[e.to_string()],
+        ),
+    }
+}
+
+fn file_path(file: &gio::File) -> String {
+    file.path().map(|p| p.display().to_string()).unwrap_or_else(|| file.uri().to_string())
+}
@@ -0,0 +1,100 @@
+//! Symbolic links: creating them and following them to their target.
+
+use crate::{file_operations, fm_window::FmWindow, templates};
+use gtk4::{gio, glib, prelude::*};
+
+/// Creates a link to `target` in `dir`, named "Link to <name>" unless
+/// `name` is given. An existing file is never replaced.
+pub fn make_link(
+    dir: &gio::File,
+    target: &gio::File,
+    name: Option<&str>,
+) -> Result<gio::File, glib::Error> {
+    let target_name =
+        target.basename().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = name.map(str::to_string).unwrap_or_else(|| format!("Link to {}", target_name));
+
+    let link = dir.child(templates::unique_name(dir, &name, false));
+    let target_path = target.path().unwrap_or_else(|| target.uri().as_str().into());
+    link.make_symbolic_link(&target_path, gio::Cancellable::NONE)?;
+    Ok(link)
+}
+
+/// Makes a link to each of `targets` in the current folder, then selects
+/// them.
+pub fn make_links(fm_window: &FmWindow, targets: &[gio::File]) {
+    let dir = fm_window.fmstate.borrow().current_path.clone();
+    link_into(fm_window, &dir, targets);
+}
+
+/// Makes links to `targets` in `dir`, keeping their names when `dir` is
+/// another folder than theirs.
+pub fn link_into(fm_window: &FmWindow, dir: &gio::File, targets: &[gio::File]) {
+    let mut links = Vec::new();
+    let mut errors = Vec::new();
+
+    for target in targets {
+        let same_folder = target.parent().is_some_and(|parent| parent.equal(dir));
+        let name = target.basename().map(|n| n.to_string_lossy().into_owned());
+        let name = name.filter(|_| !same_folder);
+
+        match make_link(dir, target, name.as_deref()) {
+            Ok(link) => links.push(link),
+            Err(e) => errors.push(format!("{}: {}", target.parse_name(), e)),
+        }
+    }
+
+    fm_window.reload();
+    fm_window.select_files(&links);
+    if !errors.is_empty() {
+        file_operations::show_errors(
+            fm_window.window.upcast_ref(),
+            "Some links could not be created",
+            &errors,
+        );
+    }
+}
+
+/// What the link `file` points to, relative targets resolved against the
+/// folder of the link. `None` when `file` is not a link.
+pub fn link_target(file: &gio::File) -> Option<gio::File> {
+    let info = file
+        .query_info(
+            "standard::is-symlink,standard::symlink-target",
+            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+            gio::Cancellable::NONE,
+        )
+        .ok()?;
+    if !info.is_symlink() {
+        return None;
+    }
+
+    let target = info.symlink_target()?;
+    match file.parent() {
+        Some(parent) if target.is_relative() => Some(parent.resolve_relative_path(target)),
+        _ => Some(gio::File::for_path(target)),
+    }
+}
+
+/// Opens the folder containing the target of the link `file`, with the
+/// target selected.
+pub fn open_target_folder(fm_window: &FmWindow, file: &gio::File) {
+    let Some(target) = link_target(file) else {
+        return;
+    };
+
+    let folder_exists = target.parent().is_some_and(|parent| {
+        parent.query_file_type(gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE)
+            == gio::FileType::Directory
+    });
+    if !folder_exists {
+        file_operations::show_errors(
+            fm_window.window.upcast_ref(),
+            "The link target folder does not exist",
+            &[format!("The link points to {}", target.parse_name())],
+        );
+        return;
+    }
+
+    fm_window.reveal(&target);
+}
@@ -1,38 +1,111 @@
+mod archive;
+mod archive_folder;
+mod batch_rename;
+mod batch_rename_dialog;
 mod bookmarks;
+mod bookmarks_dialog;
+mod cli;
+mod compress_dialog;
+mod dbus_service;
+mod file_operations;
 mod files_panel;
+mod filter_bar;
+mod fm_window;
 mod footer_bar;
+mod frecency;
+mod gtk_bookmarks;
 mod headerbar;
+mod inline_rename;
+mod links;
 mod models;
+mod operations;
+mod palette_dialog;
 mod pathbar;
 mod popup_menu;
+mod preferences_dialog;
+mod preview_pane;
 mod properties_dialog;
+mod quick_look;
+mod search;
+mod search_bar;
+mod search_index;
 mod sidebar;
 mod sorters;
 mod state;
 mod style;
+mod templates;
+mod thumbnails;
+mod type_ahead;
+mod undo;
 mod utils;
 
-use crate::models::file_item::FileItem;
+use crate::{
+    fm_window::FmWindow,
+    models::{
+        file_item::FileItem,
+        sidebar_item::{SidebarItem, SidebarItemKind},
+    },
+};
 use gtk4::{
     Application, ApplicationWindow, Box as GtkBox, GestureClick, Orientation, Paned, gio, glib,
     prelude::*,
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 const APP_ID: &str = "org.filemanager.axfm";
 
-fn main() {
-    let app = Application::builder().application_id(APP_ID).build();
-    app.connect_activate(build_fm);
-    app.run();
+fn main() -> glib::ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+
+    // `axfm config ...` works on the settings file without starting the GUI
+    if cli::is_config_command(&args) {
+        return cli::run_config_command(&args[2..]);
+    }
+
+    let app = Application::builder()
+        .application_id(APP_ID)
+        .flags(gio::ApplicationFlags::HANDLES_COMMAND_LINE | gio::ApplicationFlags::HANDLES_OPEN)
+        .build();
+
+    cli::add_main_options(&app);
+
+    app.connect_startup(dbus_service::register);
+
+    app.connect_activate(|app| match FmWindow::active(app) {
+        Some(fm_window) => fm_window.window.present(),
+        None => {
+            build_fm(app, &gio::File::for_path(glib::home_dir()));
+        }
+    });
+    app.connect_open(|app, files, _| {
+        for file in files {
+            cli::open_file_in_new_window(app, file);
+        }
+    });
+    app.connect_command_line(cli::handle_command_line);
+    app.connect_shutdown(|_| {
+        search_index::save_pending();
+        frecency::save_pending();
+    });
+
+    app.run()
 }
 
-fn build_fm(app: &Application) {
+fn build_fm(app: &Application, location: &gio::File) -> FmWindow {
+    let (settings, settings_error) = match utils::FMSettings::load() {
+        Ok(settings) => (settings, None),
+        Err(e) => (utils::FMSettings::new(), Some(e)),
+    };
+    let (bookmarks, bookmarks_error) = bookmarks::load_bookmarks();
+
     let window = ApplicationWindow::builder()
         .application(app)
         .title("Ax File Manager")
-        .default_width(800)
-        .default_height(500)
+        .default_width(settings.window_width)
+        .default_height(settings.window_height)
         .build();
 
     style::load_css();
@@ -40,10 +113,9 @@ fn build_fm(app: &Application) {
     // where files will be shown
     let content_area = GtkBox::new(Orientation::Vertical, 0);
 
-    let home_path = gio::File::for_path(glib::home_dir());
-    let fmstate = Rc::new(RefCell::new(state::FmState::new(home_path.clone())));
+    let fmstate = Rc::new(RefCell::new(state::FmState::new(location.clone(), settings, bookmarks)));
 
-    let (files_scroll, file_store, column_view, files_selection) =
+    let (files_view, file_store, column_view, files_selection) =
         files_panel::build_files_panel(fmstate.clone());
     let (sidebar_box, sidebar_selection, sidebar_list) =
         sidebar::build_sidebar(fmstate.clone(), &file_store);
@@ -74,13 +146,11 @@ fn build_fm(app: &Application) {
         &sidebar_list,
     );
 
-    files_panel::populate_files_list(
-        &file_store,
-        &home_path,
-        &fmstate.borrow().settings.show_hidden,
-    );
+    files_panel::populate_files_list(&file_store, location, &fmstate.borrow().settings.show_hidden);
 
     sidebar_selection.connect_selected_notify(glib::clone!(
+        #[weak]
+        window,
         #[weak]
         file_store,
         #[weak]
@@ -93,37 +163,40 @@ fn build_fm(app: &Application) {
                 return;
             }
 
-            // Get the selected item text
-            let selected_text = if let Some(obj) = sidebar_list.item(idx) {
-                if let Some(str_obj) = obj.downcast_ref::<gtk4::StringObject>() {
-                    str_obj.string().to_string()
-                } else {
-                    return;
-                }
-            } else {
+            let Some(sidebar_item) = sidebar_list.item(idx).and_downcast::<SidebarItem>() else {
                 return;
             };
 
-            // Skip if it's a heading
-            if selected_text == "Places" || selected_text == "Bookmarks" {
+            // Headings have no target
+            if sidebar_item.kind() == SidebarItemKind::Heading {
                 sel.set_selected(gtk4::INVALID_LIST_POSITION);
                 return;
             }
 
-            // Find the file for this item (either in Places or Bookmarks)
-            let sidebar_items = sidebar::get_sidebar_items();
-            let target_file = if let Some((_, file)) =
-                sidebar_items.iter().find(|(name, _)| *name == selected_text)
+            // Group rows fold and unfold their bookmarks
+            if let Some(group) =
+                sidebar_item.group().filter(|_| sidebar_item.kind() == SidebarItemKind::Group)
             {
-                Some(file.clone())
-            } else {
-                // Check in bookmarks
-                let bookmarks = &fmstate.borrow().bookmarks;
-                bookmarks
-                    .iter()
-                    .find(|b| b.name == selected_text)
-                    .map(|b| gio::File::for_path(&b.path))
-            };
+                sel.set_selected(gtk4::INVALID_LIST_POSITION);
+                let result = fmstate.borrow_mut().toggle_bookmark_group(&group);
+                if let Err(e) = result {
+                    eprintln!("Failed to save bookmarks: {}", e);
+                }
+                sidebar::refresh_sidebar(&sidebar_list, &fmstate);
+                return;
+            }
+
+            if sidebar_item.kind() == SidebarItemKind::SavedSearch {
+                let saved = fmstate.borrow().saved_search(&sidebar_item.label());
+                if let (Some(saved), Some(fm_window)) =
+                    (saved, FmWindow::from_window(window.upcast_ref()))
+                {
+                    fm_window.search_bar.run_saved(&fm_window, &saved);
+                }
+                return;
+            }
+
+            let target_file = sidebar_item.file();
 
             if let Some(file) = target_file {
                 let mut fmstate_mut = fmstate.borrow_mut();
@@ -148,12 +221,14 @@ fn build_fm(app: &Application) {
         fmstate,
         move |widget| {
             let text = widget.text();
+            let path = std::path::Path::new(&text);
 
-            let file = if std::path::Path::new(&text).exists() {
-                gio::File::for_path(&text)
-            } else {
-                gio::File::for_uri(&text)
-            };
+            let file =
+                if path.exists() || archive_folder::ArchiveLocation::from_path(path).is_some() {
+                    gio::File::for_path(&text)
+                } else {
+                    gio::File::for_uri(&text)
+                };
 
             files_panel::populate_files_list(
                 &file_store,
@@ -182,13 +257,27 @@ fn build_fm(app: &Application) {
                     let file_path = file_item.path();
 
                     // Try local path first, otherwise fallback to URI
-                    let file = if std::path::Path::new(&file_path).exists() {
+                    let file = if std::path::Path::new(&file_path).exists()
+                        || file_item.archive_member()
+                    {
                         gio::File::for_path(&file_path)
                     } else {
                         gio::File::for_uri(&file_path)
                     };
 
-                    if file_item.is_directory() {
+                    if file_item.archive_member() && !file_item.is_directory() {
+                        let fm_window = cv
+                            .root()
+                            .and_downcast::<gtk4::Window>()
+                            .and_then(|window| FmWindow::from_window(&window));
+                        if let Some(fm_window) = fm_window {
+                            archive_folder::open_member(
+                                &fm_window,
+                                std::path::Path::new(&file_path),
+                            );
+                        }
+                    } else if file_item.is_directory() || archive::is_archive(file_path.as_ref()) {
+                        // Archives open like folders
                         files_panel::populate_files_list(
                             &file_store,
                             &file,
@@ -247,17 +336,24 @@ fn build_fm(app: &Application) {
         }
     ));
 
+    let search_bar = search_bar::SearchBar::new();
+    let filter_bar = filter_bar::FilterBar::new(&files_selection);
+
     // content area
     content_area.append(&path_bar);
-    content_area.append(&files_scroll);
+    content_area.append(search_bar.widget());
+    content_area.append(filter_bar.widget());
+    content_area.append(&files_view);
 
     // setup controllers
     content_area.add_controller(right_click);
 
+    let preview_pane = preview_pane::PreviewPane::new(&content_area);
+
     let paned = Paned::new(Orientation::Horizontal);
     paned.set_start_child(Some(&sidebar_box));
-    paned.set_end_child(Some(&content_area));
-    paned.set_position(200);
+    paned.set_end_child(Some(preview_pane.widget()));
+    paned.set_position(fmstate.borrow().settings.pane_position);
     paned.set_wide_handle(true);
     paned.set_resize_start_child(false);
     paned.set_shrink_start_child(false);
@@ -266,8 +362,11 @@ fn build_fm(app: &Application) {
     let (footer_bar, footer_components) = footer_bar::build_footer_bar();
 
     // Create main vertical box to hold paned and footer
+    let operations = operations::OperationsPanel::new();
+
     let main_vbox = GtkBox::new(Orientation::Vertical, 0);
     main_vbox.append(&paned);
+    main_vbox.append(operations.widget());
     main_vbox.append(&footer_bar);
 
     // Store labels in local variables for cloning
@@ -286,13 +385,18 @@ fn build_fm(app: &Application) {
         right_label,
         #[weak]
         file_store_path,
+        #[weak]
+        files_selection,
         move |new_path| {
             // Update disk space
             footer_bar::update_disk_space(&left_label, new_path);
 
             // Update item count based on what's actually displayed in the list
-            let count = file_store_path.n_items() as usize;
-            footer_bar::update_item_count(&center_label, count);
+            footer_bar::update_item_count(
+                &center_label,
+                files_selection.n_items() as usize,
+                file_store_path.n_items() as usize,
+            );
 
             // Clear selection info and default app
             right_label.set_text("");
@@ -304,48 +408,379 @@ fn build_fm(app: &Application) {
     let right_label_sel = footer_components.right_label.clone();
     let file_store_sel = file_store.clone();
 
+    // Selection changes can fire while the state is borrowed, so the footer
+    // keeps its own copy of the size units
+    let footer_units = Rc::new(Cell::new(fmstate.borrow().settings.size_units));
+    fmstate.borrow_mut().connect_settings_changed(glib::clone!(
+        #[strong]
+        footer_units,
+        move |state| footer_units.set(state.settings.size_units)
+    ));
+
     // Connect footer updates for selection changes
-    files_selection.connect_selected_notify(glib::clone!(
+    files_selection.connect_selection_changed(glib::clone!(
         #[weak]
         center_label_sel,
         #[weak]
         right_label_sel,
         #[weak]
         file_store_sel,
-        move |sel| {
-            let idx = sel.selected();
+        #[strong]
+        footer_units,
+        move |sel, _, _| {
+            let selected = files_panel::selected_items(sel);
+
+            match selected.as_slice() {
+                [] => {
+                    // No selection - show item count based on displayed items
+                    footer_bar::update_item_count(
+                        &center_label_sel,
+                        sel.n_items() as usize,
+                        file_store_sel.n_items() as usize,
+                    );
+                    right_label_sel.set_text("");
+                }
+                [file_item] if file_item.archive_member() => {
+                    footer_bar::update_item_info(&center_label_sel, file_item, footer_units.get());
+                    right_label_sel.set_text("");
+                }
+                [file_item] => {
+                    // Selection - show file info
+                    let file_path = file_item.path();
 
-            if idx == gtk4::INVALID_LIST_POSITION {
-                // No selection - show item count based on displayed items
-                let count = file_store_sel.n_items() as usize;
-                footer_bar::update_item_count(&center_label_sel, count);
-                right_label_sel.set_text("");
-            } else {
-                // Selection - show file info
-                if let Some(obj) = file_store_sel.item(idx) {
-                    if let Some(file_item) = obj.downcast_ref::<FileItem>() {
-                        let file_path = file_item.path();
-
-                        let file = if std::path::Path::new(&file_path).exists() {
-                            gio::File::for_path(&file_path)
-                        } else {
-                            gio::File::for_uri(&file_path)
-                        };
-
-                        footer_bar::update_selection_info(&center_label_sel, &file);
-                        footer_bar::update_default_app(&right_label_sel, &file);
-                    }
+                    let file = if std::path::Path::new(&file_path).exists() {
+                        gio::File::for_path(&file_path)
+                    } else {
+                        gio::File::for_uri(&file_path)
+                    };
+
+                    footer_bar::update_selection_info(&center_label_sel, &file, footer_units.get());
+                    footer_bar::update_default_app(&right_label_sel, &file);
+                }
+                items => {
+                    footer_bar::update_multi_selection_info(
+                        &center_label_sel,
+                        items,
+                        footer_units.get(),
+                    );
+                    right_label_sel.set_text("");
                 }
             }
         }
     ));
 
+    // Filtering and search results change the count without a new folder
+    files_selection.connect_items_changed(glib::clone!(
+        #[weak]
+        center_label_sel,
+        #[weak]
+        file_store_sel,
+        move |sel, _, _, _| {
+            if sel.selection().is_empty() {
+                footer_bar::update_item_count(
+                    &center_label_sel,
+                    sel.n_items() as usize,
+                    file_store_sel.n_items() as usize,
+                );
+            }
+        }
+    ));
+
     // Initialize footer with current state
     let current_path = fmstate.borrow().current_path.clone();
     footer_bar::update_disk_space(&footer_components.left_label, &current_path);
     let count = footer_bar::count_items(&current_path, fmstate.borrow().settings.show_hidden);
-    footer_bar::update_item_count(&footer_components.center_label, count);
+    footer_bar::update_item_count(&footer_components.center_label, count, count);
+
+    // List again when a setting changes which files are shown or how they
+    // read. Sorting and the columns follow the settings by themselves.
+    let listed_with = {
+        let settings = &fmstate.borrow().settings;
+        RefCell::new((settings.show_hidden, settings.date_format.clone(), settings.size_units))
+    };
+    fmstate.borrow_mut().connect_settings_changed(glib::clone!(
+        #[weak]
+        file_store,
+        #[strong]
+        search_bar,
+        move |state| {
+            let settings = &state.settings;
+            let wanted = (settings.show_hidden, settings.date_format.clone(), settings.size_units);
+            if *listed_with.borrow() == wanted {
+                return;
+            }
+            listed_with.replace(wanted);
+
+            if search_bar.showing_results() {
+                search_bar.refresh_results(&file_store);
+            } else {
+                files_panel::populate_files_list(
+                    &file_store,
+                    &state.current_path,
+                    &settings.show_hidden,
+                );
+            }
+        }
+    ));
+
+    // The index is shared by all windows, and follows the latest settings
+    search_index::configure(&fmstate.borrow().settings);
+    fmstate.borrow_mut().connect_settings_changed(|state| {
+        search_index::configure(&state.settings);
+    });
+
+    let settings_monitor = utils::watch_settings(glib::clone!(
+        #[weak]
+        window,
+        #[strong]
+        fmstate,
+        move |result| match result {
+            Ok(settings) => fmstate.borrow_mut().apply_settings(settings),
+            Err(e) => show_settings_error(window.upcast_ref::<gtk4::Window>(), &e),
+        }
+    ));
+    fmstate.borrow_mut().settings_monitor = settings_monitor;
+
+    // Share bookmarks with file choosers and other GTK applications
+    let sync_gtk_bookmarks = glib::clone!(
+        #[weak]
+        fmstate,
+        #[weak]
+        sidebar_list,
+        move |external: Vec<bookmarks::Bookmark>| {
+            let result = fmstate.borrow_mut().sync_gtk_bookmarks(external);
+            match result {
+                Ok(true) => sidebar::refresh_sidebar(&sidebar_list, &fmstate),
+                Ok(false) => {}
+                Err(e) => eprintln!("Failed to sync GTK bookmarks: {}", e),
+            }
+        }
+    );
+    match gtk_bookmarks::load_gtk_bookmarks() {
+        Ok(external) => sync_gtk_bookmarks(external),
+        Err(e) => eprintln!("{}", e),
+    }
+    // Turning the sharing on syncs right away, not at the next change
+    let sharing = Cell::new(fmstate.borrow().settings.sync_gtk_bookmarks);
+    let sync_when_enabled = sync_gtk_bookmarks.clone();
+    fmstate.borrow_mut().connect_settings_changed(move |state| {
+        let enabled = state.settings.sync_gtk_bookmarks;
+        if enabled && !sharing.get() {
+            // The state is borrowed while settings are applied
+            let sync = sync_when_enabled.clone();
+            glib::idle_add_local_once(move || match gtk_bookmarks::load_gtk_bookmarks() {
+                Ok(external) => sync(external),
+                Err(e) => eprintln!("{}", e),
+            });
+        }
+        sharing.set(enabled);
+    });
+    fmstate.borrow_mut().gtk_bookmarks_monitor =
+        gtk_bookmarks::watch_gtk_bookmarks(sync_gtk_bookmarks);
+
+    // Remember the window geometry for the next launch
+    window.connect_close_request(glib::clone!(
+        #[strong]
+        fmstate,
+        #[weak]
+        paned,
+        #[weak]
+        column_view,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |window| {
+            let mut fmstate_mut = fmstate.borrow_mut();
+            let (width, height) = window.default_size();
+            fmstate_mut.settings.window_width = width;
+            fmstate_mut.settings.window_height = height;
+            fmstate_mut.settings.pane_position = paned.position();
+            fmstate_mut.settings.columns = files_panel::column_layout(&column_view);
+            fmstate_mut.save_settings();
+
+            glib::Propagation::Proceed
+        }
+    ));
 
     window.set_child(Some(&main_vbox));
     window.present();
+
+    if let Some(e) = settings_error {
+        show_settings_error(window.upcast_ref::<gtk4::Window>(), &e);
+    }
+    if let Some(failure) = bookmarks_error {
+        show_error_dialog(
+            window.upcast_ref::<gtk4::Window>(),
+            "Bookmarks could not be loaded",
+            &failure.to_string(),
+        );
+    }
+
+    let fm_window = FmWindow {
+        window,
+        fmstate,
+        file_store,
+        column_view,
+        files_selection,
+        sidebar_selection,
+        operations,
+        search_bar,
+        filter_bar,
+        preview_pane,
+    };
+    fm_window.register();
+    add_file_actions(&fm_window);
+    fm_window.search_bar.connect(&fm_window);
+    fm_window.filter_bar.connect(&fm_window);
+    fm_window.preview_pane.connect(&fm_window);
+    type_ahead::attach(&fm_window);
+    quick_look::attach(&fm_window);
+
+    fm_window
+}
+
+/// Window actions working on the selected files, with their shortcuts.
+fn add_file_actions(fm_window: &FmWindow) {
+    let delete_action = gio::SimpleAction::new("delete_permanently", None);
+    delete_action.connect_activate(glib::clone!(
+        #[weak(rename_to = window)]
+        fm_window.window,
+        move |_, _| {
+            let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) else {
+                return;
+            };
+            let files = files_panel::selected_files(&fm_window.files_selection);
+            let confirm = fm_window.fmstate.borrow().settings.confirm_delete;
+            file_operations::delete_permanently(
+                &fm_window,
+                files,
+                confirm,
+                glib::clone!(
+                    #[strong]
+                    fm_window,
+                    move || fm_window.reload()
+                ),
+            );
+        }
+    ));
+    fm_window.window.add_action(&delete_action);
+
+    // One file is renamed in place, several in the batch rename dialog
+    let rename_action = gio::SimpleAction::new("rename", None);
+    rename_action.connect_activate(glib::clone!(
+        #[weak(rename_to = window)]
+        fm_window.window,
+        move |_, _| {
+            let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) else {
+                return;
+            };
+            let files = files_panel::selected_files(&fm_window.files_selection);
+            match files.as_slice() {
+                [] => {}
+                [file] => inline_rename::start_rename(&fm_window, file),
+                _ => batch_rename_dialog::show_batch_rename_dialog(&fm_window, files),
+            }
+        }
+    ));
+    fm_window.window.add_action(&rename_action);
+
+    // Documents created from the empty area menu, then named in place
+    let new_document_action = gio::SimpleAction::new("new_document", Some(glib::VariantTy::STRING));
+    new_document_action.connect_activate(glib::clone!(
+        #[weak(rename_to = window)]
+        fm_window.window,
+        move |_, parameter| {
+            let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) else {
+                return;
+            };
+            let template = parameter
+                .and_then(|p| p.str())
+                .filter(|path| !path.is_empty())
+                .map(gio::File::for_path);
+            let dir = fm_window.fmstate.borrow().current_path.clone();
+
+            match templates::create_document(&dir, template.as_ref()) {
+                Ok(file) => {
+                    fm_window.reload();
+                    inline_rename::start_rename(&fm_window, &file);
+                }
+                Err(e) => file_operations::show_errors(
+                    window.upcast_ref(),
+                    "The document could not be created",
+                    &[e.to_string()],
+                ),
+            }
+        }
+    ));
+    fm_window.window.add_action(&new_document_action);
+
+    let undo_action = gio::SimpleAction::new("undo", None);
+    undo_action.connect_activate(glib::clone!(
+        #[weak(rename_to = window)]
+        fm_window.window,
+        move |_, _| {
+            if let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) {
+                undo::undo_last(&fm_window);
+            }
+        }
+    ));
+    fm_window.window.add_action(&undo_action);
+
+    let palette_action = gio::SimpleAction::new("go_to_folder", None);
+    palette_action.connect_activate(glib::clone!(
+        #[weak(rename_to = window)]
+        fm_window.window,
+        move |_, _| {
+            if let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) {
+                palette_dialog::show_palette_dialog(&fm_window);
+            }
+        }
+    ));
+    fm_window.window.add_action(&palette_action);
+
+    let window_shortcuts = gtk4::ShortcutController::new();
+    window_shortcuts.add_shortcut(gtk4::Shortcut::new(
+        gtk4::ShortcutTrigger::parse_string("<Control>p"),
+        Some(gtk4::NamedAction::new("win.go_to_folder")),
+    ));
+    fm_window.window.add_controller(window_shortcuts);
+
+    // Only while the file list has the focus, so text entries keep the key
+    let shortcuts = gtk4::ShortcutController::new();
+    shortcuts.add_shortcut(gtk4::Shortcut::new(
+        gtk4::ShortcutTrigger::parse_string("<Shift>Delete"),
+        Some(gtk4::NamedAction::new("win.delete_permanently")),
+    ));
+    shortcuts.add_shortcut(gtk4::Shortcut::new(
+        gtk4::ShortcutTrigger::parse_string("F2"),
+        Some(gtk4::NamedAction::new("win.rename")),
+    ));
+    shortcuts.add_shortcut(gtk4::Shortcut::new(
+        gtk4::ShortcutTrigger::parse_string("<Control>z"),
+        Some(gtk4::NamedAction::new("win.undo")),
+    ));
+    if let Some(files_view) = files_panel::files_view(&fm_window.column_view) {
+        files_view.add_controller(shortcuts);
+    }
+}
+
+fn show_settings_error(parent: &gtk4::Window, error: &utils::SettingsError) {
+    show_error_dialog(
+        parent,
+        "Settings could not be loaded",
+        &format!("{}\n\nDefault settings are used until the file is fixed.", error),
+    );
+}
+
+fn show_error_dialog(parent: &gtk4::Window, title: &str, details: &str) {
+    let dialog = gtk4::MessageDialog::builder()
+        .transient_for(parent)
+        .modal(true)
+        .message_type(gtk4::MessageType::Error)
+        .buttons(gtk4::ButtonsType::Close)
+        .text(title)
+        .secondary_text(details)
+        .build();
+
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.present();
 }
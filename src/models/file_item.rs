@@ -1,3 +1,4 @@
+use crate::utils::SizeUnits;
 use gtk4::{gio, glib, prelude::*, subclass::prelude::*};
 use std::cell::RefCell;
 
@@ -7,20 +8,76 @@ mod imp {
     #[derive(Debug, Default, glib::Properties)]
     #[properties(wrapper_type = super::FileItem)]
     pub struct FileItem {
-        #[property(get, set)]
+        #[property(get, set = Self::set_path)]
         path: RefCell<String>,
-        #[property(get, set)]
+        #[property(get, set = Self::set_display_name)]
         display_name: RefCell<String>,
         #[property(get, set)]
         size: RefCell<u64>,
         #[property(get, set)]
         modified: RefCell<i64>,
+        /// Description of the content type, for display.
         #[property(get, set)]
         mime_type: RefCell<String>,
+        /// Content type like `image/png`, empty when unknown.
+        #[property(get, set)]
+        content_type: RefCell<String>,
         #[property(get, set)]
         is_directory: RefCell<bool>,
         #[property(get, set)]
         icon: RefCell<Option<gio::Icon>>,
+        /// Listed from an archive index rather than the file system.
+        #[property(get, set)]
+        archive_member: RefCell<bool>,
+        #[property(get, set)]
+        is_symlink: RefCell<bool>,
+        /// Where a symbolic link points, as stored in the link.
+        #[property(get, set)]
+        symlink_target: RefCell<Option<String>>,
+        /// A symbolic link whose target doesn't exist.
+        #[property(get, set)]
+        is_broken_link: RefCell<bool>,
+        /// Unix mode bits, with the file type. 0 when unknown.
+        #[property(get, set)]
+        mode: RefCell<u32>,
+        #[property(get, set)]
+        owner: RefCell<String>,
+        #[property(get, set)]
+        group: RefCell<String>,
+        /// Unix time, 0 when the file system doesn't record it.
+        #[property(get, set)]
+        created: RefCell<i64>,
+        /// Unix time of the last read, 0 when unknown.
+        #[property(get, set)]
+        accessed: RefCell<i64>,
+        #[property(get, set)]
+        inode: RefCell<u64>,
+        /// Number of hard links.
+        #[property(get, set)]
+        nlink: RefCell<u32>,
+        pub(super) sort_keys: RefCell<SortKeys>,
+    }
+
+    /// Keys the list sorts by, each made the first time it is needed.
+    #[derive(Debug, Default)]
+    pub struct SortKeys {
+        pub name: Option<glib::FilenameCollationKey>,
+        pub location: Option<glib::FilenameCollationKey>,
+        pub extension: Option<String>,
+    }
+
+    impl FileItem {
+        fn set_path(&self, path: String) {
+            self.path.replace(path);
+            self.sort_keys.borrow_mut().location = None;
+        }
+
+        fn set_display_name(&self, name: String) {
+            self.display_name.replace(name);
+            let mut keys = self.sort_keys.borrow_mut();
+            keys.name = None;
+            keys.extension = None;
+        }
     }
 
     #[glib::object_subclass]
@@ -61,7 +118,7 @@ impl FileItem {
     pub fn from_file(file: &gio::File, show_hidden: bool) -> Option<Self> {
         let info = file
             .query_info(
-                "standard::*,time::*",
+                "standard::*,time::*,unix::*,owner::*",
                 gio::FileQueryInfoFlags::NONE,
                 gio::Cancellable::NONE,
             )
@@ -81,54 +138,193 @@ impl FileItem {
 
         let modified = info.modification_date_time().map(|dt| dt.to_unix()).unwrap_or(0);
 
-        let mime_type = info
-            .content_type()
-            .map(|ct| gio::content_type_get_description(&ct).to_string())
-            .unwrap_or_else(|| String::from("Unknown"));
+        // Links are followed, so only a broken one is still a link here
+        let is_symlink = info.is_symlink();
+        let is_broken_link = is_symlink && info.file_type() == gio::FileType::SymbolicLink;
+
+        let mime_type = if is_broken_link {
+            String::from("Broken link")
+        } else {
+            info.content_type()
+                .map(|ct| gio::content_type_get_description(&ct).to_string())
+                .unwrap_or_else(|| String::from("Unknown"))
+        };
 
         let is_directory = info.file_type() == gio::FileType::Directory;
 
         let icon = info.icon();
 
-        Some(FileItem::new(path, display_name, size, modified, mime_type, is_directory, icon))
+        let item = FileItem::new(path, display_name, size, modified, mime_type, is_directory, icon);
+        item.set_is_symlink(is_symlink);
+        if let Some(target) = info.symlink_target() {
+            item.set_symlink_target(target.display().to_string());
+        }
+        item.set_is_broken_link(is_broken_link);
+        if let Some(content_type) = info.content_type() {
+            item.set_content_type(content_type.as_str());
+        }
+        item.set_mode(info.attribute_uint32(gio::FILE_ATTRIBUTE_UNIX_MODE));
+        item.set_owner(info.attribute_string(gio::FILE_ATTRIBUTE_OWNER_USER).unwrap_or_default());
+        item.set_group(info.attribute_string(gio::FILE_ATTRIBUTE_OWNER_GROUP).unwrap_or_default());
+        item.set_created(info.attribute_uint64(gio::FILE_ATTRIBUTE_TIME_CREATED) as i64);
+        item.set_accessed(info.attribute_uint64(gio::FILE_ATTRIBUTE_TIME_ACCESS) as i64);
+        item.set_inode(info.attribute_uint64(gio::FILE_ATTRIBUTE_UNIX_INODE));
+        item.set_nlink(info.attribute_uint32(gio::FILE_ATTRIBUTE_UNIX_NLINK));
+        Some(item)
+    }
+
+    /// The folder containing the item, with the home folder shortened to
+    /// `~`.
+    pub fn location(&self) -> String {
+        let path = self.path();
+        let parent = std::path::Path::new(&path).parent().unwrap_or(std::path::Path::new("/"));
+        match parent.strip_prefix(glib::home_dir()) {
+            Ok(rest) if rest.as_os_str().is_empty() => String::from("~"),
+            Ok(rest) => format!("~/{}", rest.display()),
+            Err(_) => parent.display().to_string(),
+        }
+    }
+
+    pub fn file(&self) -> gio::File {
+        gio::File::for_parse_name(&self.path())
     }
 
-    pub fn format_size(&self) -> String {
+    pub fn format_size(&self, units: SizeUnits) -> String {
         if self.is_directory() {
             return String::from("--");
         }
 
         let size = self.size();
-        const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+        let suffixes = units.suffixes();
         let mut size_f = size as f64;
         let mut unit_idx = 0;
 
-        while size_f >= 1024.0 && unit_idx < UNITS.len() - 1 {
-            size_f /= 1024.0;
+        while size_f >= units.base() && unit_idx < suffixes.len() - 1 {
+            size_f /= units.base();
             unit_idx += 1;
         }
 
         if unit_idx == 0 {
-            format!("{} {}", size, UNITS[unit_idx])
+            format!("{} {}", size, suffixes[unit_idx])
         } else {
-            format!("{:.1} {}", size_f, UNITS[unit_idx])
+            format!("{:.1} {}", size_f, suffixes[unit_idx])
         }
     }
 
-    pub fn format_modified(&self) -> String {
-        let timestamp = self.modified();
-        if timestamp == 0 {
-            return String::from("Unknown");
+    /// Orders by name the way people expect: numbers by their value, and
+    /// letters following the rules of the locale.
+    pub fn collate_name(&self, other: &FileItem) -> std::cmp::Ordering {
+        self.compare_sort_keys(
+            other,
+            |keys| &mut keys.name,
+            |item| glib::FilenameCollationKey::from(item.display_name()),
+        )
+    }
+
+    /// Orders by `location()`, like names.
+    pub fn collate_location(&self, other: &FileItem) -> std::cmp::Ordering {
+        self.compare_sort_keys(
+            other,
+            |keys| &mut keys.location,
+            |item| glib::FilenameCollationKey::from(item.location()),
+        )
+    }
+
+    /// Orders by `extension()`, ignoring case.
+    pub fn compare_extension(&self, other: &FileItem) -> std::cmp::Ordering {
+        self.compare_sort_keys(
+            other,
+            |keys| &mut keys.extension,
+            |item| item.extension().to_lowercase(),
+        )
+    }
+
+    /// Compares the key picked by `key` of both items, making it with
+    /// `make` where it isn't cached yet.
+    fn compare_sort_keys<K: Ord>(
+        &self,
+        other: &FileItem,
+        key: impl Fn(&mut imp::SortKeys) -> &mut Option<K>,
+        make: impl Fn(&FileItem) -> K,
+    ) -> std::cmp::Ordering {
+        if self == other {
+            return std::cmp::Ordering::Equal;
         }
+        let mut own = self.imp().sort_keys.borrow_mut();
+        let mut others = other.imp().sort_keys.borrow_mut();
+        let own = key(&mut own).get_or_insert_with(|| make(self));
+        let others = key(&mut others).get_or_insert_with(|| make(other));
+        Ord::cmp(own, others)
+    }
 
-        // For now, simple formatting - will use chrono in Phase 5
-        let dt = glib::DateTime::from_unix_local(timestamp).ok();
-        if let Some(dt) = dt {
-            dt.format("%Y-%m-%d %H:%M")
-                .unwrap_or_else(|_| glib::GString::from("Unknown"))
-                .to_string()
-        } else {
-            String::from("Unknown")
+    pub fn format_modified(&self, date_format: &str) -> String {
+        format_time(self.modified(), date_format)
+    }
+
+    pub fn format_created(&self, date_format: &str) -> String {
+        format_time(self.created(), date_format)
+    }
+
+    pub fn format_accessed(&self, date_format: &str) -> String {
+        format_time(self.accessed(), date_format)
+    }
+
+    /// The mode as `ls -l` shows it, like `drwxr-xr-x`. Empty when unknown.
+    pub fn format_permissions(&self) -> String {
+        let mode = self.mode();
+        if mode == 0 {
+            return String::new();
+        }
+
+        let kind = match mode & 0o170000 {
+            0o040000 => 'd',
+            0o120000 => 'l',
+            0o020000 => 'c',
+            0o060000 => 'b',
+            0o010000 => 'p',
+            0o140000 => 's',
+            _ => '-',
+        };
+        let mut text = String::from(kind);
+        // Owner, group and others, each with the special bit shown in its x
+        for (shift, special, set_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+            let bits = (mode >> shift) & 0o7;
+            text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            text.push(match (bits & 0o1 != 0, mode & special != 0) {
+                (true, true) => set_char,
+                (false, true) => set_char.to_ascii_uppercase(),
+                (true, false) => 'x',
+                (false, false) => '-',
+            });
+        }
+        text
+    }
+
+    /// What follows the last dot of the name, empty for folders and names
+    /// without one. The dot starting a hidden file's name doesn't count.
+    pub fn extension(&self) -> String {
+        if self.is_directory() {
+            return String::new();
+        }
+        let name = self.display_name();
+        match name.rfind('.') {
+            Some(dot) if dot > 0 && dot + 1 < name.len() => name[dot + 1..].to_string(),
+            _ => String::new(),
         }
     }
 }
+
+/// Formats a Unix time, "Unknown" when it's 0.
+fn format_time(timestamp: i64, date_format: &str) -> String {
+    if timestamp == 0 {
+        return String::from("Unknown");
+    }
+
+    let dt = glib::DateTime::from_unix_local(timestamp).ok();
+    if let Some(dt) = dt {
+        dt.format(date_format).unwrap_or_else(|_| glib::GString::from("Unknown")).to_string()
+    } else {
+        String::from("Unknown")
+    }
+}
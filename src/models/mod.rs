@@ -1 +1,2 @@
 pub mod file_item;
+pub mod sidebar_item;
@@ -0,0 +1,105 @@
+use crate::bookmarks::{Bookmark, SavedSearch};
+use gtk4::{gio, glib, prelude::*, subclass::prelude::*};
+use std::cell::RefCell;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "AxFMSidebarItemKind")]
+pub enum SidebarItemKind {
+    /// Section title, not selectable.
+    #[default]
+    Heading,
+    Place,
+    Bookmark,
+    /// Collapsible folder of bookmarks.
+    Group,
+    /// Search run again when opened, its file is the folder searched.
+    SavedSearch,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties)]
+    #[properties(wrapper_type = super::SidebarItem)]
+    pub struct SidebarItem {
+        #[property(get, set, builder(SidebarItemKind::default()))]
+        kind: RefCell<SidebarItemKind>,
+        #[property(get, set)]
+        label: RefCell<String>,
+        #[property(get, set)]
+        file: RefCell<Option<gio::File>>,
+        #[property(get, set)]
+        icon_name: RefCell<Option<String>>,
+        /// Group of a bookmark, or the name of a group row.
+        #[property(get, set)]
+        group: RefCell<Option<String>>,
+        #[property(get, set)]
+        expanded: RefCell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SidebarItem {
+        const NAME: &'static str = "AxFMSidebarItem";
+        type Type = super::SidebarItem;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for SidebarItem {}
+}
+
+glib::wrapper! {
+    pub struct SidebarItem(ObjectSubclass<imp::SidebarItem>);
+}
+
+impl SidebarItem {
+    pub fn heading(label: &str) -> Self {
+        glib::Object::builder()
+            .property("kind", SidebarItemKind::Heading)
+            .property("label", label)
+            .build()
+    }
+
+    pub fn group_row(name: &str, expanded: bool) -> Self {
+        glib::Object::builder()
+            .property("kind", SidebarItemKind::Group)
+            .property("label", name)
+            .property("group", name)
+            .property("expanded", expanded)
+            .build()
+    }
+
+    pub fn bookmark(bookmark: &Bookmark) -> Self {
+        glib::Object::builder()
+            .property("kind", SidebarItemKind::Bookmark)
+            .property("label", &bookmark.name)
+            .property("file", bookmark.to_gio_file())
+            .property("icon-name", bookmark.icon_name())
+            .property("group", bookmark.group.clone())
+            .build()
+    }
+
+    pub fn saved_search(search: &SavedSearch) -> Self {
+        glib::Object::builder()
+            .property("kind", SidebarItemKind::SavedSearch)
+            .property("label", &search.name)
+            .property("file", search.root_file())
+            .property("icon-name", "folder-saved-search")
+            .build()
+    }
+
+    pub fn new(kind: SidebarItemKind, label: &str, file: &gio::File, icon_name: &str) -> Self {
+        glib::Object::builder()
+            .property("kind", kind)
+            .property("label", label)
+            .property("file", file)
+            .property("icon-name", icon_name)
+            .build()
+    }
+
+    /// Local path or URI of the target, for tooltips.
+    pub fn location(&self) -> Option<String> {
+        self.file().map(|file| {
+            file.path().map(|p| p.display().to_string()).unwrap_or_else(|| file.uri().to_string())
+        })
+    }
+}
@@ -0,0 +1,202 @@
+//! Background file operations.
+//!
+//! The work runs on a worker thread and reports its progress through an
+//! `OperationContext`. Each running operation gets a row with a progress bar
+//! and a cancel button in the window's operations panel.
+
+use gtk4::{Box as GtkBox, Button, Label, Orientation, ProgressBar, gio, glib, prelude::*};
+use std::{
+    sync::{Arc, Mutex, mpsc},
+    time::Duration,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Default)]
+struct Progress {
+    done: u64,
+    total: u64,
+    current: String,
+}
+
+/// Handed to the worker so it can report progress and check for cancellation.
+#[derive(Clone)]
+pub struct OperationContext {
+    progress: Arc<Mutex<Progress>>,
+    cancellable: gio::Cancellable,
+}
+
+impl Default for OperationContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OperationContext {
+    pub fn new() -> Self {
+        Self {
+            progress: Arc::new(Mutex::new(Progress::default())),
+            cancellable: gio::Cancellable::new(),
+        }
+    }
+
+    pub fn cancellable(&self) -> &gio::Cancellable {
+        &self.cancellable
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellable.is_cancelled()
+    }
+
+    /// Sets the amount of work (bytes, items, ...) the progress bar tracks.
+    pub fn set_total(&self, total: u64) {
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.total = total;
+        }
+    }
+
+    /// Records `amount` units of work done while processing `current`.
+    pub fn advance(&self, amount: u64, current: &str) {
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.done += amount;
+            progress.current = current.to_string();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct OperationsPanel {
+    container: GtkBox,
+}
+
+impl Default for OperationsPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OperationsPanel {
+    pub fn new() -> Self {
+        let container = GtkBox::new(Orientation::Vertical, 4);
+        container.add_css_class("operations-panel");
+        container.set_visible(false);
+        Self { container }
+    }
+
+    pub fn widget(&self) -> &GtkBox {
+        &self.container
+    }
+
+    /// Runs `work` on a worker thread, then `on_done` with its result on the
+    /// main thread.
+    pub fn run<T, W, D>(&self, title: &str, work: W, on_done: D)
+    where
+        T: Send + 'static,
+        W: FnOnce(&OperationContext) -> T + Send + 'static,
+        D: FnOnce(T) + 'static,
+    {
+        self.run_streaming(
+            title,
+            move |context, _: &mpsc::Sender<()>| work(context),
+            |_| {},
+            on_done,
+        );
+    }
+
+    /// Like `run`, with `work` also sending partial results as it goes.
+    /// They are handed to `on_update` on the main thread, all of them before
+    /// `on_done`. Returns what cancels the operation.
+    pub fn run_streaming<T, U, W, P, D>(
+        &self,
+        title: &str,
+        work: W,
+        mut on_update: P,
+        on_done: D,
+    ) -> gio::Cancellable
+    where
+        T: Send + 'static,
+        U: Send + 'static,
+        W: FnOnce(&OperationContext, &mpsc::Sender<U>) -> T + Send + 'static,
+        P: FnMut(U) + 'static,
+        D: FnOnce(T) + 'static,
+    {
+        let context = OperationContext::new();
+
+        let row = GtkBox::new(Orientation::Horizontal, 8);
+        let labels = GtkBox::new(Orientation::Vertical, 2);
+        labels.set_hexpand(true);
+
+        let title_label = Label::new(Some(title));
+        title_label.set_xalign(0.0);
+        let detail_label = Label::new(None);
+        detail_label.set_xalign(0.0);
+        detail_label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+        detail_label.add_css_class("dim-label");
+
+        let progress_bar = ProgressBar::new();
+        labels.append(&title_label);
+        labels.append(&progress_bar);
+        labels.append(&detail_label);
+
+        let cancel_button = Button::builder()
+            .icon_name("process-stop-symbolic")
+            .tooltip_text("Cancel")
+            .valign(gtk4::Align::Center)
+            .build();
+        let cancellable = context.cancellable.clone();
+        cancel_button.connect_clicked(move |button| {
+            cancellable.cancel();
+            button.set_sensitive(false);
+        });
+
+        row.append(&labels);
+        row.append(&cancel_button);
+        self.container.append(&row);
+        self.container.set_visible(true);
+
+        let (sender, receiver) = mpsc::channel();
+        let (update_sender, updates) = mpsc::channel();
+        let worker_context = context.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(work(&worker_context, &update_sender));
+        });
+
+        let cancellable = context.cancellable.clone();
+        let container = self.container.clone();
+        let mut on_done = Some(on_done);
+        glib::timeout_add_local(POLL_INTERVAL, move || {
+            if let Ok(progress) = context.progress.lock() {
+                if progress.total > 0 {
+                    progress_bar.set_fraction(progress.done as f64 / progress.total as f64);
+                } else {
+                    progress_bar.pulse();
+                }
+                detail_label.set_text(&progress.current);
+            }
+
+            updates.try_iter().for_each(&mut on_update);
+
+            match receiver.try_recv() {
+                Ok(result) => {
+                    // Updates sent since the ones above
+                    updates.try_iter().for_each(&mut on_update);
+                    container.remove(&row);
+                    container.set_visible(container.first_child().is_some());
+                    if let Some(on_done) = on_done.take() {
+                        on_done(result);
+                    }
+                    glib::ControlFlow::Break
+                }
+                Err(mpsc::TryRecvError::Empty) => glib::ControlFlow::Continue,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    // The worker panicked
+                    container.remove(&row);
+                    container.set_visible(container.first_child().is_some());
+                    glib::ControlFlow::Break
+                }
+            }
+        });
+
+        cancellable
+    }
+}
@@ -0,0 +1,167 @@
+//! Ctrl+P palette jumping to one of the folders visited before, picked by
+//! typing a few letters of its path.
+
+use crate::{files_panel, fm_window::FmWindow, frecency};
+use gtk4::{
+    Box as GtkBox, Dialog, Label, ListBox, ListBoxRow, Orientation, ScrolledWindow, SearchEntry,
+    gdk, gio, glib, prelude::*,
+};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// How many folders the palette lists at most.
+const MAX_RESULTS: usize = 50;
+
+pub fn show_palette_dialog(fm_window: &FmWindow) {
+    let dialog = Dialog::builder()
+        .title("Go to Folder")
+        .transient_for(&fm_window.window)
+        .modal(true)
+        .default_width(560)
+        .default_height(420)
+        .build();
+
+    let content = dialog.content_area();
+    content.set_orientation(Orientation::Vertical);
+    content.set_spacing(6);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+
+    let entry = SearchEntry::new();
+    entry.set_placeholder_text(Some("Type part of a folder path"));
+    // Typing while a row has the focus still goes to the entry
+    entry.set_key_capture_widget(Some(&dialog));
+    let list_box = ListBox::new();
+    list_box.set_selection_mode(gtk4::SelectionMode::Browse);
+    let scrolled = ScrolledWindow::builder().child(&list_box).vexpand(true).build();
+    let hint = Label::new(Some("No visited folder matches"));
+    hint.add_css_class("dim-label");
+    hint.set_visible(false);
+
+    content.append(&entry);
+    content.append(&scrolled);
+    content.append(&hint);
+
+    // Ranked once, the typing only narrows it down
+    let ranked = frecency::ranked();
+    // Deleted folders go away for the next time
+    frecency::prune();
+    // The folders listed, in the order of the rows
+    let shown: Rc<RefCell<Vec<PathBuf>>> = Rc::default();
+    let fill = glib::clone!(
+        #[weak]
+        list_box,
+        #[weak]
+        hint,
+        #[strong]
+        shown,
+        move |query: &str| {
+            while let Some(child) = list_box.first_child() {
+                list_box.remove(&child);
+            }
+            let found = frecency::find(&ranked, query, MAX_RESULTS);
+            hint.set_visible(found.is_empty());
+            for path in &found {
+                list_box.append(&create_row(path));
+            }
+            list_box.select_row(list_box.row_at_index(0).as_ref());
+            shown.replace(found);
+        }
+    );
+    fill("");
+
+    entry.connect_search_changed(glib::clone!(
+        #[strong]
+        fill,
+        move |entry| fill(&entry.text())
+    ));
+
+    // Down moves from the entry into the list, which takes the arrows from there
+    let keys = gtk4::EventControllerKey::new();
+    keys.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    keys.connect_key_pressed(glib::clone!(
+        #[weak]
+        list_box,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, _| {
+            let step = match key {
+                gdk::Key::Down => 1,
+                gdk::Key::Up => -1,
+                _ => return glib::Propagation::Proceed,
+            };
+            let current = list_box.selected_row().map(|row| row.index()).unwrap_or(-1);
+            if let Some(row) = list_box.row_at_index((current + step).max(0)) {
+                list_box.select_row(Some(&row));
+                row.grab_focus();
+            }
+            glib::Propagation::Stop
+        }
+    ));
+    entry.add_controller(keys);
+
+    let go = glib::clone!(
+        #[weak]
+        dialog,
+        #[strong(rename_to = fm_window)]
+        fm_window.clone(),
+        #[strong]
+        shown,
+        move |row: Option<ListBoxRow>| {
+            let index = row.and_then(|row| usize::try_from(row.index()).ok());
+            let Some(path) = index.and_then(|index| shown.borrow().get(index).cloned()) else {
+                return;
+            };
+            dialog.close();
+            fm_window.open_location(&gio::File::for_path(path));
+            files_panel::shown_view(&fm_window.column_view).grab_focus();
+        }
+    );
+    entry.connect_activate(glib::clone!(
+        #[weak]
+        list_box,
+        #[strong]
+        go,
+        move |_| go(list_box.selected_row())
+    ));
+    list_box.connect_row_activated(move |_, row| go(Some(row.clone())));
+    entry.connect_stop_search(glib::clone!(
+        #[weak]
+        dialog,
+        move |_| dialog.close()
+    ));
+
+    dialog.present();
+    entry.grab_focus();
+}
+
+fn create_row(path: &Path) -> ListBoxRow {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string());
+
+    let name_label = Label::new(Some(&name));
+    name_label.set_xalign(0.0);
+    let path_label = Label::new(Some(&path.display().to_string()));
+    path_label.set_xalign(0.0);
+    path_label.set_ellipsize(gtk4::pango::EllipsizeMode::Start);
+    path_label.add_css_class("dim-label");
+
+    let vbox = GtkBox::new(Orientation::Vertical, 2);
+    vbox.set_margin_start(6);
+    vbox.set_margin_end(6);
+    vbox.set_margin_top(4);
+    vbox.set_margin_bottom(4);
+    vbox.append(&name_label);
+    vbox.append(&path_label);
+
+    let row = ListBoxRow::new();
+    row.set_child(Some(&vbox));
+    row
+}
@@ -1,5 +1,10 @@
-use crate::state::FmState;
+use crate::{frecency, state::FmState};
 use gtk4::{Entry, EntryCompletion, ListStore, gio, glib::Type, prelude::*};
+use std::collections::HashSet;
+
+/// How many of the folders visited most are offered, before the contents of
+/// the current folder.
+const COMPLETED_FOLDERS: usize = 20;
 
 pub fn build_pathbar(fmstate: &mut FmState) -> Entry {
     let pathbar = Entry::new();
@@ -9,28 +14,9 @@ pub fn build_pathbar(fmstate: &mut FmState) -> Entry {
     completion.set_inline_selection(true);
 
     let model = ListStore::new(&[Type::STRING]);
-
+    fill_completion(&model, &fmstate.current_path);
     let current_file = fmstate.current_path.clone();
 
-    // Populate entries
-    if let Ok(enumerator) = current_file.enumerate_children(
-        "*",
-        gio::FileQueryInfoFlags::NONE,
-        None::<&gio::Cancellable>,
-    ) {
-        while let Some(info) = enumerator.next_file(None::<&gio::Cancellable>).unwrap_or(None) {
-            let name = info.display_name();
-            let child_file = current_file.child(&name);
-            let full_path_str = child_file
-                .path()
-                .map(|p| p.display().to_string())
-                .unwrap_or_else(|| child_file.uri().to_string());
-
-            let iter = model.append();
-            model.set(&iter, &[(0, &full_path_str.to_value())]);
-        }
-    }
-
     completion.set_model(Some(&model));
     completion.set_text_column(0);
     pathbar.set_completion(Some(&completion));
@@ -51,27 +37,7 @@ pub fn build_pathbar(fmstate: &mut FmState) -> Entry {
                 .unwrap_or_else(|| new_file.uri().to_string());
             pathbar.set_text(&text);
 
-            model.clear();
-
-            if let Ok(enumerator) = new_file.enumerate_children(
-                "*",
-                gio::FileQueryInfoFlags::NONE,
-                None::<&gio::Cancellable>,
-            ) {
-                while let Some(info) =
-                    enumerator.next_file(None::<&gio::Cancellable>).unwrap_or(None)
-                {
-                    let name = info.display_name();
-                    let child_file = new_file.child(&name);
-                    let full_path_str = child_file
-                        .path()
-                        .map(|p| p.display().to_string())
-                        .unwrap_or_else(|| child_file.uri().to_string());
-
-                    let iter = model.append();
-                    model.set(&iter, &[(0, &full_path_str.to_value())]);
-                }
-            }
+            fill_completion(&model, new_file);
         }
     });
 
@@ -79,3 +45,33 @@ pub fn build_pathbar(fmstate: &mut FmState) -> Entry {
 
     pathbar
 }
+
+/// Offers the folders visited most, best ranked first, then the contents of
+/// `dir`.
+fn fill_completion(model: &ListStore, dir: &gio::File) {
+    model.clear();
+
+    let mut offered = HashSet::new();
+    for path in frecency::ranked().into_iter().take(COMPLETED_FOLDERS) {
+        let text = path.display().to_string();
+        model.set(&model.append(), &[(0, &text.to_value())]);
+        offered.insert(text);
+    }
+
+    if let Ok(enumerator) =
+        dir.enumerate_children("*", gio::FileQueryInfoFlags::NONE, None::<&gio::Cancellable>)
+    {
+        while let Some(info) = enumerator.next_file(None::<&gio::Cancellable>).unwrap_or(None) {
+            let name = info.display_name();
+            let child_file = dir.child(&name);
+            let full_path_str = child_file
+                .path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| child_file.uri().to_string());
+
+            if !offered.contains(&full_path_str) {
+                model.set(&model.append(), &[(0, &full_path_str.to_value())]);
+            }
+        }
+    }
+}
@@ -1,11 +1,28 @@
-use crate::files_panel;
 use crate::state::FmState;
+use crate::{
+    archive_folder::{self, ArchiveLocation},
+    file_operations, files_panel,
+    fm_window::FmWindow,
+    links,
+};
 use gtk4::{
     Box as GtkBox, ColumnView, Label, ListView, Popover, SignalListItemFactory, SingleSelection,
     StringList, gio, glib, prelude::*,
 };
 use std::path::PathBuf;
-use std::{cell::RefCell, env, path::Path, process::Command, rc::Rc};
+use std::{cell::RefCell, path::Path, process::Command, rc::Rc};
+
+/// Items only offered for archives.
+const ARCHIVE_ITEMS: &[&str] = &["Extract Here", "Extract To..."];
+
+/// Items offered for members of an archive opened as a folder.
+const ARCHIVE_MEMBER_ITEMS: &[&str] = &["Open File", "Copy", "Extract To..."];
+
+/// Items only offered for symbolic links.
+const LINK_ITEMS: &[&str] = &["Open Link Target Folder"];
+
+/// Items only offered for search results.
+const SEARCH_ITEMS: &[&str] = &["Open Containing Folder"];
 
 struct MenuItem<'a> {
     label: &'a str,
@@ -18,7 +35,7 @@ pub fn get_empty_right_click(
     content_area: &GtkBox,
     fmstate: Rc<RefCell<FmState>>,
     file_store: &gtk4::gio::ListStore,
-    sidebar_list: &StringList,
+    sidebar_list: &gio::ListStore,
 ) -> Popover {
     let popover = Popover::new();
     popover.set_parent(content_area);
@@ -30,6 +47,12 @@ pub fn get_empty_right_click(
             show_if_file: true,
             show_if_dir: true,
         }),
+        Rc::new(MenuItem {
+            label: "New Document",
+            icon_name: "document-new-symbolic",
+            show_if_file: true,
+            show_if_dir: true,
+        }),
         Rc::new(MenuItem {
             label: "Paste",
             icon_name: "edit-paste-symbolic",
@@ -116,6 +139,26 @@ pub fn get_empty_right_click(
                             state.settings.show_hidden,
                         );
                     }
+                    "New Document" => {
+                        // Templates can be nested, which needs a real menu
+                        let (_, rect) = popover.pointing_to();
+                        let parent = popover.parent().unwrap();
+                        let menu = gtk4::PopoverMenu::from_model(Some(
+                            &crate::templates::new_document_menu(),
+                        ));
+                        menu.set_parent(&parent);
+                        menu.set_pointing_to(Some(&rect));
+                        menu.set_has_arrow(false);
+                        menu.connect_closed(|menu| {
+                            // Unparenting right away would cancel the activated item
+                            glib::idle_add_local_once(glib::clone!(
+                                #[weak]
+                                menu,
+                                move || menu.unparent()
+                            ));
+                        });
+                        menu.popup();
+                    }
                     "Paste" => paste_function(fmstate.clone(), &file_store),
                     "Add to Bookmarks" => {
                         let current_path = fmstate.borrow().current_path.clone();
@@ -127,16 +170,12 @@ pub fn get_empty_right_click(
                         crate::sidebar::refresh_sidebar(&sidebar_list, &fmstate);
                     }
                     "Open Terminal Here" => {
-                        let terminal_cmd =
-                            env::var("TERMINAL").unwrap_or_else(|_| "xterm".to_string());
-                        let file = &fmstate.borrow().current_path;
+                        let fmstate_ref = fmstate.borrow();
+                        let terminal_cmd = &fmstate_ref.settings.terminal_command;
+                        let file = &fmstate_ref.current_path;
 
                         if let Some(local_path) = file.path() {
-                            if let Err(err) =
-                                Command::new(&terminal_cmd).current_dir(local_path).spawn()
-                            {
-                                eprintln!("Failed to open terminal '{}': {}", terminal_cmd, err);
-                            }
+                            open_terminal(terminal_cmd, local_path);
                         } else {
                             eprintln!(
                                 "Cannot open terminal: current path is virtual or remote: {}",
@@ -185,6 +224,12 @@ pub fn get_file_right_click(
                         show_if_file: true,
                         show_if_dir: false,
                     }),
+                    Rc::new(MenuItem {
+                        label: "Open Containing Folder",
+                        icon_name: "folder-open-symbolic",
+                        show_if_file: true,
+                        show_if_dir: true,
+                    }),
                     Rc::new(MenuItem {
                         label: "Cut",
                         icon_name: "edit-cut-symbolic",
@@ -209,12 +254,48 @@ pub fn get_file_right_click(
                         show_if_file: true,
                         show_if_dir: true,
                     }),
+                    Rc::new(MenuItem {
+                        label: "Delete Permanently",
+                        icon_name: "edit-delete-symbolic",
+                        show_if_file: true,
+                        show_if_dir: true,
+                    }),
                     Rc::new(MenuItem {
                         label: "Rename...",
                         icon_name: "document-edit-symbolic",
                         show_if_file: true,
                         show_if_dir: true,
                     }),
+                    Rc::new(MenuItem {
+                        label: "Make Link",
+                        icon_name: "insert-link-symbolic",
+                        show_if_file: true,
+                        show_if_dir: true,
+                    }),
+                    Rc::new(MenuItem {
+                        label: "Open Link Target Folder",
+                        icon_name: "folder-open-symbolic",
+                        show_if_file: true,
+                        show_if_dir: true,
+                    }),
+                    Rc::new(MenuItem {
+                        label: "Compress...",
+                        icon_name: "package-x-generic-symbolic",
+                        show_if_file: true,
+                        show_if_dir: true,
+                    }),
+                    Rc::new(MenuItem {
+                        label: "Extract Here",
+                        icon_name: "package-x-generic-symbolic",
+                        show_if_file: true,
+                        show_if_dir: false,
+                    }),
+                    Rc::new(MenuItem {
+                        label: "Extract To...",
+                        icon_name: "folder-symbolic",
+                        show_if_file: true,
+                        show_if_dir: false,
+                    }),
                     Rc::new(MenuItem {
                         label: "Open in Terminal",
                         icon_name: "utilities-terminal-symbolic",
@@ -230,12 +311,29 @@ pub fn get_file_right_click(
                 ];
 
                 let path = Path::new(path);
-                let is_file = path.is_file();
-                let is_dir = path.is_dir();
+                let member = archive_folder::member_entry(path);
+                let is_symlink = path.symlink_metadata().is_ok_and(|meta| meta.is_symlink());
+                // A broken link is neither, it gets the file items
+                let (is_file, is_dir) = match &member {
+                    Some(entry) => (!entry.is_dir, entry.is_dir),
+                    None => (path.is_file() || (is_symlink && !path.exists()), path.is_dir()),
+                };
+                let is_archive = member.is_none() && is_file && crate::archive::is_archive(path);
+                let is_search_result = popover
+                    .root()
+                    .and_downcast::<gtk4::Window>()
+                    .and_then(|window| FmWindow::from_window(&window))
+                    .is_some_and(|fm_window| fm_window.search_bar.showing_results());
 
                 let items_to_show: Vec<Rc<MenuItem>> = menu_items
                     .into_iter()
                     .filter(|item| (item.show_if_file && is_file) || (item.show_if_dir && is_dir))
+                    .filter(|item| match member {
+                        Some(_) => ARCHIVE_MEMBER_ITEMS.contains(&item.label),
+                        None => is_archive || !ARCHIVE_ITEMS.contains(&item.label),
+                    })
+                    .filter(|item| is_symlink || !LINK_ITEMS.contains(&item.label))
+                    .filter(|item| is_search_result || !SEARCH_ITEMS.contains(&item.label))
                     .collect();
                 let string_list: StringList = StringList::new(
                     &items_to_show.iter().map(|item| item.label).collect::<Vec<_>>(),
@@ -292,11 +390,33 @@ pub fn get_file_right_click(
 
                             match text.as_str() {
                                 "Open File" => {
-                                    if let Some(path) = &fmstate.borrow().popup_focused_file {
-                                        if let Err(err) = Command::new("xdg-open").arg(path).spawn()
-                                        {
-                                            eprintln!("Failed to open file '{}': {}", &path, err);
-                                        }
+                                    let focused = fmstate.borrow().popup_focused_file.clone();
+                                    let root = popover.root().unwrap();
+                                    let parent_window =
+                                        root.downcast_ref::<gtk4::Window>().unwrap();
+
+                                    // Archive members are opened from a temporary copy
+                                    if let (Some(path), Some(fm_window)) =
+                                        (&focused, FmWindow::from_window(parent_window))
+                                        && archive_folder::member_entry(Path::new(path)).is_some()
+                                    {
+                                        archive_folder::open_member(&fm_window, Path::new(path));
+                                    } else if let Some(path) = &focused
+                                        && let Err(err) = Command::new("xdg-open").arg(path).spawn()
+                                    {
+                                        eprintln!("Failed to open file '{}': {}", &path, err);
+                                    }
+                                }
+                                "Open Containing Folder" => {
+                                    let root = popover.root().unwrap();
+                                    let parent_window =
+                                        root.downcast_ref::<gtk4::Window>().unwrap();
+                                    let focused = fmstate.borrow().popup_focused_file.clone();
+
+                                    if let (Some(path), Some(fm_window)) =
+                                        (focused, FmWindow::from_window(parent_window))
+                                    {
+                                        fm_window.reveal(&gio::File::for_path(path));
                                     }
                                 }
                                 "Cut" => {
@@ -319,58 +439,149 @@ pub fn get_file_right_click(
                                 }
                                 "Paste" => paste_function(fmstate.clone(), &file_store),
                                 "Open in Terminal" => {
-                                    let terminal_cmd = env::var("TERMINAL")
-                                        .unwrap_or_else(|_| "xterm".to_string());
-                                    if let Some(path) = &fmstate.borrow().popup_focused_file {
-                                        if let Err(err) =
-                                            Command::new(&terminal_cmd).current_dir(path).spawn()
-                                        {
-                                            eprintln!(
-                                                "Failed to open terminal '{}': {}",
-                                                terminal_cmd, err
-                                            );
-                                        }
+                                    let fmstate_ref = fmstate.borrow();
+                                    let terminal_cmd = &fmstate_ref.settings.terminal_command;
+                                    if let Some(path) = &fmstate_ref.popup_focused_file {
+                                        open_terminal(terminal_cmd, path);
                                     }
                                 }
                                 "Move to Trash" => {
-                                    if let Some(path) = &fmstate.borrow().popup_focused_file {
+                                    let fmstate_ref = fmstate.borrow();
+                                    if let Some(path) = &fmstate_ref.popup_focused_file {
                                         let file = gio::File::for_path(path);
-                                        match file.trash(None::<&gio::Cancellable>) {
-                                            Ok(_) => {
-                                                let fmstate_ref = fmstate.borrow();
-                                                crate::files_panel::populate_files_list(
-                                                    &file_store,
-                                                    &fmstate_ref.current_path,
-                                                    &fmstate_ref.settings.show_hidden,
-                                                );
-                                            }
-                                            Err(e) => {
-                                                eprintln!("Error while moving to trash: {}", e)
-                                            }
+                                        if fmstate_ref.settings.confirm_trash {
+                                            let root = popover.root().unwrap();
+                                            let parent_window =
+                                                root.downcast_ref::<gtk4::Window>().unwrap();
+                                            let name = file
+                                                .basename()
+                                                .map(|n| n.display().to_string())
+                                                .unwrap_or_default();
+
+                                            confirm_dialog(
+                                                parent_window,
+                                                &format!("Move \"{}\" to the trash?", name),
+                                                "Move to Trash",
+                                                glib::clone!(
+                                                    #[strong]
+                                                    fmstate,
+                                                    #[weak]
+                                                    file_store,
+                                                    #[weak]
+                                                    parent_window,
+                                                    move || trash_file(
+                                                        &parent_window,
+                                                        &file,
+                                                        &fmstate,
+                                                        &file_store
+                                                    )
+                                                ),
+                                            );
+                                        } else {
+                                            drop(fmstate_ref);
+                                            let root = popover.root().unwrap();
+                                            let parent_window =
+                                                root.downcast_ref::<gtk4::Window>().unwrap();
+                                            trash_file(parent_window, &file, &fmstate, &file_store);
                                         }
                                     } else {
                                         eprintln!("Popup Focused File not found!");
                                     }
                                 }
-                                "Rename..." => {
-                                    let fmstate_brw = fmstate.borrow();
-                                    if let Some(path) = &fmstate_brw.popup_focused_file {
+                                "Delete Permanently" => {
+                                    let fmstate_ref = fmstate.borrow();
+                                    let root = popover.root().unwrap();
+                                    let parent_window =
+                                        root.downcast_ref::<gtk4::Window>().unwrap();
+
+                                    if let (Some(path), Some(fm_window)) = (
+                                        &fmstate_ref.popup_focused_file,
+                                        FmWindow::from_window(parent_window),
+                                    ) {
                                         let file = gio::File::for_path(path);
-                                        let current_path = &fmstate_brw.current_path;
-                                        let show_hidden = fmstate_brw.settings.show_hidden;
-                                        rename_file_dialog(
-                                            popover
-                                                .root()
-                                                .unwrap()
-                                                .downcast_ref::<gtk4::Window>()
-                                                .unwrap(),
-                                            &file,
-                                            &file_store,
-                                            &current_path,
-                                            show_hidden,
+                                        let confirm = fmstate_ref.settings.confirm_delete;
+                                        drop(fmstate_ref);
+                                        delete_and_reload(&fm_window, file, confirm);
+                                    }
+                                }
+                                "Rename..." => {
+                                    let root = popover.root().unwrap();
+                                    let parent_window =
+                                        root.downcast_ref::<gtk4::Window>().unwrap();
+
+                                    // Right-clicking inside a multiple selection renames all of it
+                                    match context_files(&fmstate, parent_window) {
+                                        Some((fm_window, files)) if files.len() > 1 => {
+                                            crate::batch_rename_dialog::show_batch_rename_dialog(
+                                                &fm_window, files,
+                                            );
+                                        }
+                                        Some((fm_window, files)) => {
+                                            crate::inline_rename::start_rename(
+                                                &fm_window, &files[0],
+                                            );
+                                        }
+                                        None => {}
+                                    }
+                                }
+                                "Make Link" => {
+                                    let root = popover.root().unwrap();
+                                    let parent_window =
+                                        root.downcast_ref::<gtk4::Window>().unwrap();
+
+                                    if let Some((fm_window, files)) =
+                                        context_files(&fmstate, parent_window)
+                                    {
+                                        links::make_links(&fm_window, &files);
+                                    }
+                                }
+                                "Open Link Target Folder" => {
+                                    let root = popover.root().unwrap();
+                                    let parent_window =
+                                        root.downcast_ref::<gtk4::Window>().unwrap();
+                                    let focused = fmstate.borrow().popup_focused_file.clone();
+
+                                    if let (Some(path), Some(fm_window)) =
+                                        (focused, FmWindow::from_window(parent_window))
+                                    {
+                                        let link = gio::File::for_path(path);
+                                        links::open_target_folder(&fm_window, &link);
+                                    }
+                                }
+                                "Compress..." => {
+                                    let root = popover.root().unwrap();
+                                    let parent_window =
+                                        root.downcast_ref::<gtk4::Window>().unwrap();
+
+                                    if let Some((fm_window, files)) =
+                                        context_files(&fmstate, parent_window)
+                                    {
+                                        crate::compress_dialog::show_compress_dialog(
+                                            &fm_window, files,
                                         );
                                     }
                                 }
+                                "Extract Here" | "Extract To..." => {
+                                    let root = popover.root().unwrap();
+                                    let parent_window =
+                                        root.downcast_ref::<gtk4::Window>().unwrap();
+                                    let focused = fmstate.borrow().popup_focused_file.clone();
+
+                                    if let (Some(path), Some(fm_window)) =
+                                        (focused, FmWindow::from_window(parent_window))
+                                    {
+                                        let member = ArchiveLocation::from_path(Path::new(&path))
+                                            .filter(|location| !location.member.is_empty());
+                                        let archive = gio::File::for_path(path);
+                                        if let Some(member) = member {
+                                            file_operations::extract_member_to(&fm_window, member);
+                                        } else if text == "Extract Here" {
+                                            file_operations::extract_here(&fm_window, archive);
+                                        } else {
+                                            file_operations::extract_to(&fm_window, archive);
+                                        }
+                                    }
+                                }
                                 "Properties" => {
                                     let fmstate_brw = fmstate.borrow();
                                     if let Some(path) = &fmstate_brw.popup_focused_file {
@@ -381,6 +592,7 @@ pub fn get_file_right_click(
                                         crate::properties_dialog::show_properties_dialog(
                                             parent_window,
                                             &path,
+                                            fmstate_brw.settings.size_units,
                                         );
                                     }
                                 }
@@ -403,65 +615,122 @@ pub fn get_file_right_click(
     popover
 }
 
-fn rename_file_dialog(
+/// The files the context menu acts on: the whole selection when it was
+/// opened on a selected file, the file it was opened on otherwise.
+fn context_files(
+    fmstate: &Rc<RefCell<FmState>>,
     parent_window: &gtk4::Window,
-    file_path: &gio::File,
-    file_store: &gtk4::gio::ListStore,
-    current_path: &gio::File,
-    show_hidden: bool,
-) {
-    let file_name: String = file_path
-        .basename()
-        .and_then(|name| name.to_str().map(|s| s.to_owned()))
-        .unwrap_or_default();
+) -> Option<(FmWindow, Vec<gio::File>)> {
+    let fm_window = FmWindow::from_window(parent_window)?;
+    let focused = gio::File::for_path(fmstate.borrow().popup_focused_file.as_deref()?);
+
+    let files = files_panel::selected_files(&fm_window.files_selection);
+    if files.iter().any(|f| f.equal(&focused)) {
+        Some((fm_window, files))
+    } else {
+        Some((fm_window, vec![focused]))
+    }
+}
 
-    let dialog = gtk4::Dialog::builder().title("Enter new file name").modal(true).build();
+/// Starts the terminal `command` in `dir`. The command may carry
+/// arguments, quoted as in a shell.
+fn open_terminal(command: &str, dir: impl AsRef<Path>) {
+    let argv = match glib::shell_parse_argv(command) {
+        Ok(argv) => argv,
+        Err(e) => {
+            eprintln!("Failed to open terminal '{}': {}", command, e);
+            return;
+        }
+    };
+    let Some((program, args)) = argv.split_first() else {
+        return;
+    };
+    if let Err(err) = Command::new(program).args(args).current_dir(dir).spawn() {
+        eprintln!("Failed to open terminal '{}': {}", command, err);
+    }
+}
 
-    let content_area = dialog.content_area();
-    let entry = gtk4::Entry::new();
-    entry.set_text(&file_name);
-    content_area.append(&entry);
+fn trash_file(
+    parent_window: &gtk4::Window,
+    file: &gio::File,
+    fmstate: &Rc<RefCell<FmState>>,
+    file_store: &gio::ListStore,
+) {
+    match file.trash(None::<&gio::Cancellable>) {
+        Ok(_) => {
+            let fmstate_ref = fmstate.borrow();
+            files_panel::populate_files_list(
+                file_store,
+                &fmstate_ref.current_path,
+                &fmstate_ref.settings.show_hidden,
+            );
+        }
+        Err(e) if e.matches(gio::IOErrorEnum::NotSupported) => {
+            let Some(fm_window) = FmWindow::from_window(parent_window) else {
+                return;
+            };
+            let name = file.basename().map(|n| n.display().to_string()).unwrap_or_default();
+
+            // The question doubles as the confirmation, whatever the settings say
+            confirm_dialog(
+                parent_window,
+                &format!(
+                    "\"{}\" can't be moved to the trash on this drive. Delete it permanently?",
+                    name
+                ),
+                "Delete Permanently",
+                glib::clone!(
+                    #[strong]
+                    file,
+                    move || delete_and_reload(&fm_window, file.clone(), false)
+                ),
+            );
+        }
+        Err(e) => {
+            eprintln!("Error while moving to trash: {}", e)
+        }
+    }
+}
 
-    let ok_button = dialog.add_button("OK", gtk4::ResponseType::Ok);
-    let cancel_button = dialog.add_button("Cancel", gtk4::ResponseType::Cancel);
+fn delete_and_reload(fm_window: &FmWindow, file: gio::File, confirm: bool) {
+    crate::file_operations::delete_permanently(
+        fm_window,
+        vec![file],
+        confirm,
+        glib::clone!(
+            #[strong]
+            fm_window,
+            move || fm_window.reload()
+        ),
+    );
+}
 
-    let file_clone = file_path.clone();
+/// Asks a yes/no question and runs `on_accept` if the user confirms.
+fn confirm_dialog<F: Fn() + 'static>(
+    parent_window: &gtk4::Window,
+    message: &str,
+    accept_label: &str,
+    on_accept: F,
+) {
+    let dialog = gtk4::MessageDialog::builder()
+        .transient_for(parent_window)
+        .modal(true)
+        .message_type(gtk4::MessageType::Question)
+        .text(message)
+        .build();
 
-    dialog.connect_response(glib::clone!(
-        #[weak]
-        file_store,
-        #[weak]
-        current_path,
-        move |dialog, response| {
-            if response == gtk4::ResponseType::Ok {
-                let new_name = entry.text();
-
-                if let Some(parent) = file_clone.parent() {
-                    let new_file = parent.child(&new_name);
-                    match file_clone.move_(
-                        &new_file,
-                        gio::FileCopyFlags::NONE,
-                        None::<&gio::Cancellable>,
-                        None,
-                    ) {
-                        Ok(_) => {
-                            files_panel::populate_files_list(
-                                &file_store,
-                                &current_path,
-                                &show_hidden,
-                            );
-                        }
-                        Err(err) => eprintln!("Failed to rename file: {}", err),
-                    }
-                } else {
-                    eprintln!("Cannot rename file without a parent directory");
-                }
-            }
-            dialog.close();
+    dialog.add_button("Cancel", gtk4::ResponseType::Cancel);
+    dialog.add_button(accept_label, gtk4::ResponseType::Accept);
+    dialog.set_default_response(gtk4::ResponseType::Cancel);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk4::ResponseType::Accept {
+            on_accept();
         }
-    ));
+        dialog.close();
+    });
 
-    dialog.show();
+    dialog.present();
 }
 
 fn new_folder_dialog(
@@ -543,6 +812,21 @@ fn get_selected_index(list_view: &gtk4::ListView) -> Option<u32> {
 fn paste_function(fmstate: Rc<RefCell<FmState>>, file_store: &gtk4::gio::ListStore) {
     let mut state = fmstate.borrow_mut();
     for src_path in &state.clipboard {
+        // Copied out of an archive: only that member is extracted
+        if let Some(location) =
+            ArchiveLocation::from_path(src_path).filter(|location| !location.member.is_empty())
+        {
+            let result = state.current_path.path().ok_or_else(|| {
+                std::io::Error::other("archive members can only be pasted into local folders")
+            });
+            if let Err(e) = result.and_then(|dest_dir| {
+                crate::archive::extract_member(&location.archive, &location.member, &dest_dir, None)
+            }) {
+                eprintln!("Failed to paste {}: {}", src_path.display(), e);
+            }
+            continue;
+        }
+
         let src_file = gio::File::for_path(src_path);
         let dest_file = state.current_path.child(src_path.file_name().unwrap().to_str().unwrap());
 
@@ -0,0 +1,369 @@
+use crate::{
+    search_index,
+    state::FmState,
+    utils::{self, ClickActivation, FMSettings, FileView, SizeUnits, SortColumn, SortOrder},
+};
+use gtk4::{
+    Box as GtkBox, Dialog, DropDown, Entry, Grid, Label, Orientation, ResponseType, Switch, Window,
+    glib, prelude::*,
+};
+use std::{cell::RefCell, rc::Rc};
+
+pub fn show_preferences_dialog(parent_window: &Window, fmstate: Rc<RefCell<FmState>>) {
+    let dialog = Dialog::builder()
+        .title("Preferences")
+        .transient_for(parent_window)
+        .modal(true)
+        .resizable(false)
+        .default_width(460)
+        .build();
+
+    let content = dialog.content_area();
+    let vbox = GtkBox::new(Orientation::Vertical, 12);
+    vbox.set_margin_start(20);
+    vbox.set_margin_end(20);
+    vbox.set_margin_top(20);
+    vbox.set_margin_bottom(20);
+
+    let settings = fmstate.borrow().settings.clone();
+
+    // View
+    let view_grid = add_section(&vbox, "View");
+    let mut row = 0;
+
+    let view_dropdown = DropDown::from_strings(&["Icons", "List"]);
+    view_dropdown.set_selected(match settings.file_view {
+        FileView::IconView => 0,
+        FileView::ListView => 1,
+    });
+    view_dropdown.connect_selected_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |dropdown| {
+            let file_view =
+                if dropdown.selected() == 0 { FileView::IconView } else { FileView::ListView };
+            update_settings(&fmstate, |s| s.file_view = file_view);
+        }
+    ));
+    add_row(&view_grid, row, "Default view:", &view_dropdown);
+    row += 1;
+
+    let sort_dropdown = DropDown::from_strings(&["Name", "Size", "Modified", "Type"]);
+    sort_dropdown.set_selected(match settings.sort_column {
+        SortColumn::Name => 0,
+        SortColumn::Size => 1,
+        SortColumn::ModifiedDate => 2,
+        SortColumn::Type => 3,
+    });
+    sort_dropdown.connect_selected_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |dropdown| {
+            let sort_column = match dropdown.selected() {
+                1 => SortColumn::Size,
+                2 => SortColumn::ModifiedDate,
+                3 => SortColumn::Type,
+                _ => SortColumn::Name,
+            };
+            update_settings(&fmstate, |s| s.sort_column = sort_column);
+        }
+    ));
+    add_row(&view_grid, row, "Sort by:", &sort_dropdown);
+    row += 1;
+
+    let order_dropdown = DropDown::from_strings(&["Ascending", "Descending"]);
+    order_dropdown.set_selected(match settings.sort_order {
+        SortOrder::Ascending => 0,
+        SortOrder::Descending => 1,
+    });
+    order_dropdown.connect_selected_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |dropdown| {
+            let sort_order =
+                if dropdown.selected() == 0 { SortOrder::Ascending } else { SortOrder::Descending };
+            update_settings(&fmstate, |s| s.sort_order = sort_order);
+        }
+    ));
+    add_row(&view_grid, row, "Sort order:", &order_dropdown);
+    row += 1;
+
+    let folders_first_switch = add_switch_row(&view_grid, row, "Folders first:");
+    folders_first_switch.set_active(settings.folders_first);
+    folders_first_switch.connect_active_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |switch| {
+            let active = switch.is_active();
+            update_settings(&fmstate, |s| s.folders_first = active);
+        }
+    ));
+    row += 1;
+
+    let hidden_switch = add_switch_row(&view_grid, row, "Show hidden files:");
+    hidden_switch.set_active(settings.show_hidden);
+    hidden_switch.connect_active_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |switch| {
+            let active = switch.is_active();
+            update_settings(&fmstate, |s| s.show_hidden = active);
+        }
+    ));
+    row += 1;
+
+    let date_entry = Entry::new();
+    date_entry.set_text(&settings.date_format);
+    date_entry.set_tooltip_text(Some("strftime format, e.g. %Y-%m-%d %H:%M"));
+    add_row(&view_grid, row, "Date format:", &date_entry);
+    row += 1;
+
+    let date_preview = Label::new(None);
+    date_preview.set_halign(gtk4::Align::Start);
+    date_preview.add_css_class("dim-label");
+    view_grid.attach(&date_preview, 1, row, 1, 1);
+    update_date_preview(&date_preview, &settings.date_format);
+    row += 1;
+
+    date_entry.connect_changed(glib::clone!(
+        #[strong]
+        fmstate,
+        #[weak]
+        date_preview,
+        move |entry| {
+            let text = entry.text();
+            if let Some(date_format) = crate::utils::parse_date_format(text.trim()) {
+                entry.remove_css_class("error");
+                update_date_preview(&date_preview, &date_format);
+                update_settings(&fmstate, |s| s.date_format = date_format);
+            } else {
+                entry.add_css_class("error");
+                date_preview.set_text("Invalid date format");
+            }
+        }
+    ));
+
+    let units_dropdown =
+        DropDown::from_strings(&["Binary (1 KiB = 1024 bytes)", "Decimal (1 kB = 1000 bytes)"]);
+    units_dropdown.set_selected(match settings.size_units {
+        SizeUnits::Iec => 0,
+        SizeUnits::Si => 1,
+    });
+    units_dropdown.connect_selected_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |dropdown| {
+            let size_units = if dropdown.selected() == 0 { SizeUnits::Iec } else { SizeUnits::Si };
+            update_settings(&fmstate, |s| s.size_units = size_units);
+        }
+    ));
+    add_row(&view_grid, row, "Size units:", &units_dropdown);
+
+    // Behavior
+    let behavior_grid = add_section(&vbox, "Behavior");
+    let mut row = 0;
+
+    let click_dropdown = DropDown::from_strings(&["Single click", "Double click"]);
+    click_dropdown.set_selected(match settings.click_activation {
+        ClickActivation::Single => 0,
+        ClickActivation::Double => 1,
+    });
+    click_dropdown.connect_selected_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |dropdown| {
+            let click_activation = if dropdown.selected() == 0 {
+                ClickActivation::Single
+            } else {
+                ClickActivation::Double
+            };
+            update_settings(&fmstate, |s| s.click_activation = click_activation);
+        }
+    ));
+    add_row(&behavior_grid, row, "Open items with:", &click_dropdown);
+    row += 1;
+
+    let terminal_entry = Entry::new();
+    terminal_entry.set_text(&settings.terminal_command);
+    terminal_entry
+        .set_tooltip_text(Some("A command with its arguments, e.g. kitty --single-instance"));
+    terminal_entry.connect_changed(glib::clone!(
+        #[strong]
+        fmstate,
+        move |entry| {
+            let command = entry.text().trim().to_string();
+            // Arguments are split as a shell would, quotes have to match
+            if glib::shell_parse_argv(&command).is_err() {
+                entry.add_css_class("error");
+            } else {
+                entry.remove_css_class("error");
+                update_settings(&fmstate, |s| s.terminal_command = command);
+            }
+        }
+    ));
+    add_row(&behavior_grid, row, "Terminal:", &terminal_entry);
+    row += 1;
+
+    let confirm_trash_switch = add_switch_row(&behavior_grid, row, "Confirm moving to trash:");
+    confirm_trash_switch.set_active(settings.confirm_trash);
+    confirm_trash_switch.connect_active_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |switch| {
+            let active = switch.is_active();
+            update_settings(&fmstate, |s| s.confirm_trash = active);
+        }
+    ));
+    row += 1;
+
+    let confirm_delete_switch = add_switch_row(&behavior_grid, row, "Confirm permanent deletion:");
+    confirm_delete_switch.set_active(settings.confirm_delete);
+    confirm_delete_switch.connect_active_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |switch| {
+            let active = switch.is_active();
+            update_settings(&fmstate, |s| s.confirm_delete = active);
+        }
+    ));
+    row += 1;
+
+    let gtk_bookmarks_switch =
+        add_switch_row(&behavior_grid, row, "Share bookmarks with GTK apps:");
+    gtk_bookmarks_switch.set_active(settings.sync_gtk_bookmarks);
+    gtk_bookmarks_switch.connect_active_notify(glib::clone!(
+        #[strong]
+        fmstate,
+        move |switch| {
+            let active = switch.is_active();
+            update_settings(&fmstate, |s| s.sync_gtk_bookmarks = active);
+        }
+    ));
+
+    // Search
+    let search_grid = add_section(&vbox, "Search");
+    let mut row = 0;
+
+    let index_switch = add_switch_row(&search_grid, row, "Index files for fast search:");
+    index_switch.set_active(settings.index_files);
+    row += 1;
+
+    let roots_entry = Entry::new();
+    roots_entry.set_text(&settings.index_roots.join("; "));
+    roots_entry.set_tooltip_text(Some("Folders to index, separated by ;"));
+    add_row(&search_grid, row, "Indexed folders:", &roots_entry);
+    row += 1;
+
+    let exclusions_entry = Entry::new();
+    exclusions_entry.set_text(&settings.index_exclusions.join("; "));
+    exclusions_entry.set_tooltip_text(Some(
+        "Names to leave out, wildcards allowed, or absolute paths, separated by ;",
+    ));
+    add_row(&search_grid, row, "Leave out:", &exclusions_entry);
+    row += 1;
+
+    let index_status = Label::new(Some(&search_index::status()));
+    index_status.set_halign(gtk4::Align::Start);
+    index_status.add_css_class("dim-label");
+    search_grid.attach(&index_status, 1, row, 1, 1);
+
+    // Changing the folders restarts the indexer, so they apply on Enter or
+    // when the dialog closes rather than on every key press
+    let apply_index_settings = glib::clone!(
+        #[strong]
+        fmstate,
+        #[weak]
+        index_switch,
+        #[weak]
+        roots_entry,
+        #[weak]
+        exclusions_entry,
+        #[weak]
+        index_status,
+        move || {
+            let index_files = index_switch.is_active();
+            let roots = utils::parse_list(&roots_entry.text()).unwrap_or_default();
+            let exclusions = utils::parse_list(&exclusions_entry.text()).unwrap_or_default();
+            update_settings(&fmstate, |s| {
+                s.index_files = index_files;
+                s.index_roots = roots;
+                s.index_exclusions = exclusions;
+            });
+            index_status.set_text(&search_index::status());
+        }
+    );
+    index_switch.connect_active_notify(glib::clone!(
+        #[strong]
+        apply_index_settings,
+        move |_| apply_index_settings()
+    ));
+    for entry in [&roots_entry, &exclusions_entry] {
+        entry.connect_activate(glib::clone!(
+            #[strong]
+            apply_index_settings,
+            move |_| apply_index_settings()
+        ));
+    }
+
+    content.append(&vbox);
+
+    dialog.add_button("Close", ResponseType::Close);
+
+    dialog.connect_response(move |dialog, _| {
+        apply_index_settings();
+        dialog.close();
+    });
+
+    dialog.present();
+}
+
+/// Applies a change to the settings, notifies listeners and saves the file.
+fn update_settings(fmstate: &Rc<RefCell<FmState>>, change: impl FnOnce(&mut FMSettings)) {
+    let mut settings = fmstate.borrow().settings.clone();
+    change(&mut settings);
+
+    let mut fmstate_mut = fmstate.borrow_mut();
+    fmstate_mut.apply_settings(settings);
+    fmstate_mut.save_settings();
+}
+
+fn update_date_preview(label: &Label, date_format: &str) {
+    let preview = glib::DateTime::now_local().ok().and_then(|now| now.format(date_format).ok());
+    match preview {
+        Some(text) => label.set_text(&format!("Example: {}", text)),
+        None => label.set_text(""),
+    }
+}
+
+fn add_section(vbox: &GtkBox, title: &str) -> Grid {
+    let heading = Label::new(Some(title));
+    heading.set_halign(gtk4::Align::Start);
+    heading.add_css_class("heading");
+    vbox.append(&heading);
+
+    let grid = Grid::new();
+    grid.set_row_spacing(8);
+    grid.set_column_spacing(15);
+    grid.set_margin_start(12);
+    vbox.append(&grid);
+
+    grid
+}
+
+fn add_row(grid: &Grid, row: i32, label_text: &str, widget: &impl IsA<gtk4::Widget>) {
+    let label = Label::new(Some(label_text));
+    label.set_halign(gtk4::Align::End);
+    label.add_css_class("dim-label");
+
+    widget.set_hexpand(true);
+
+    grid.attach(&label, 0, row, 1, 1);
+    grid.attach(widget, 1, row, 1, 1);
+}
+
+fn add_switch_row(grid: &Grid, row: i32, label_text: &str) -> Switch {
+    let switch = Switch::new();
+    switch.set_halign(gtk4::Align::Start);
+    add_row(grid, row, label_text, &switch);
+    switch
+}
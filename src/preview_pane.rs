@@ -0,0 +1,591 @@
+//! Preview pane on the right of the file list, showing the selected file:
+//! images scaled to fit, the start of text files, what a folder contains
+//! and the length of audio and video files. Other files get a large icon.
+//! The preview is built once the selection settles and its content read
+//! asynchronously, so moving through a folder never waits for it.
+
+use crate::{
+    files_panel, fm_window::FmWindow, footer_bar, models::file_item::FileItem, thumbnails,
+    utils::FMSettings,
+};
+use gtk4::{
+    Box as GtkBox, Grid, Image, Label, MediaFile, Orientation, Paned, Picture, PositionType,
+    ScrolledWindow, TextView,
+    gdk_pixbuf::{Colorspace, Pixbuf},
+    gio, glib,
+    prelude::*,
+};
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+    rc::Rc,
+    time::Duration,
+};
+
+/// The selection has to stay this long before it is previewed.
+const DELAY: Duration = Duration::from_millis(150);
+
+/// Images are read at most this large, the picture scales them down.
+const MAX_IMAGE_PIXELS: i32 = 1024;
+
+const MAX_TEXT_BYTES: usize = 32 * 1024;
+const MAX_TEXT_LINES: usize = 200;
+
+/// Folder entries listed, the others are only counted.
+const MAX_FOLDER_ENTRIES: usize = 200;
+
+const PANE_ICON_SIZE: i32 = 128;
+
+#[derive(Clone)]
+pub struct PreviewPane {
+    /// The file list with the pane on its right.
+    paned: Paned,
+    pane: ScrolledWindow,
+    preview: Preview,
+    timer: Rc<RefCell<Option<glib::SourceId>>>,
+    /// Whether the pane got its saved width since it was last shown.
+    placed: Rc<Cell<bool>>,
+}
+
+impl PreviewPane {
+    /// Puts the pane beside `content`, which holds the file list.
+    pub fn new(content: &impl IsA<gtk4::Widget>) -> Self {
+        let preview = Preview::new(PANE_ICON_SIZE);
+        let pane = ScrolledWindow::builder()
+            .child(preview.widget())
+            .hscrollbar_policy(gtk4::PolicyType::Never)
+            .width_request(200)
+            .visible(false)
+            .build();
+
+        let paned = Paned::new(Orientation::Horizontal);
+        paned.set_start_child(Some(content));
+        paned.set_end_child(Some(&pane));
+        paned.set_resize_end_child(false);
+        paned.set_shrink_end_child(false);
+        paned.set_wide_handle(true);
+
+        Self { paned, pane, preview, timer: Rc::default(), placed: Rc::default() }
+    }
+
+    pub fn widget(&self) -> &Paned {
+        &self.paned
+    }
+
+    /// Previews the selection of `fm_window` as it changes, and adds the
+    /// `win.show_preview` action toggling the pane with F11.
+    pub fn connect(&self, fm_window: &FmWindow) {
+        let fmstate = &fm_window.fmstate;
+
+        fm_window.files_selection.connect_selection_changed(glib::clone!(
+            #[strong(rename_to = preview)]
+            self,
+            #[weak(rename_to = window)]
+            fm_window.window,
+            move |_, _, _| preview.schedule(&window)
+        ));
+        // A new listing replaces the items without a selection change
+        fm_window.files_selection.connect_items_changed(glib::clone!(
+            #[strong(rename_to = preview)]
+            self,
+            #[weak(rename_to = window)]
+            fm_window.window,
+            move |_, _, _, _| preview.schedule(&window)
+        ));
+
+        // The pane keeps its width as the window is resized, and gets back
+        // the one it had when shown again
+        self.paned.connect_max_position_notify(glib::clone!(
+            #[strong(rename_to = preview)]
+            self,
+            #[strong]
+            fmstate,
+            move |paned| {
+                if preview.placed.get() || !preview.pane.is_visible() || paned.max_position() <= 0 {
+                    return;
+                }
+                let Ok(fmstate) = fmstate.try_borrow() else {
+                    return;
+                };
+                paned.set_position(paned.width() - fmstate.settings.preview_width);
+                preview.placed.set(true);
+            }
+        ));
+        self.paned.connect_position_notify(glib::clone!(
+            #[strong(rename_to = preview)]
+            self,
+            #[strong]
+            fmstate,
+            move |paned| {
+                if !preview.placed.get() || !preview.pane.is_visible() {
+                    return;
+                }
+                let width = paned.width() - paned.position();
+                if width > 0
+                    && let Ok(mut fmstate_mut) = fmstate.try_borrow_mut()
+                {
+                    fmstate_mut.settings.preview_width = width;
+                }
+            }
+        ));
+
+        let shown = fmstate.borrow().settings.show_preview;
+        self.set_shown(shown, &fm_window.window);
+
+        let show_action = gio::SimpleAction::new_stateful("show_preview", None, &shown.into());
+        show_action.connect_activate(glib::clone!(
+            #[strong(rename_to = preview)]
+            self,
+            #[strong]
+            fmstate,
+            #[weak(rename_to = window)]
+            fm_window.window,
+            move |action, _| {
+                let shown = !action.state().and_then(|s| s.get::<bool>()).unwrap_or(false);
+                action.set_state(&shown.into());
+                {
+                    let mut fmstate_mut = fmstate.borrow_mut();
+                    fmstate_mut.settings.show_preview = shown;
+                    fmstate_mut.save_settings();
+                }
+                preview.set_shown(shown, &window);
+            }
+        ));
+        fm_window.window.add_action(&show_action);
+
+        let shortcuts = gtk4::ShortcutController::new();
+        shortcuts.add_shortcut(gtk4::Shortcut::new(
+            gtk4::ShortcutTrigger::parse_string("F11"),
+            Some(gtk4::NamedAction::new("win.show_preview")),
+        ));
+        fm_window.window.add_controller(shortcuts);
+
+        // Follow the settings file when it is edited
+        fmstate.borrow_mut().connect_settings_changed(glib::clone!(
+            #[strong(rename_to = preview)]
+            self,
+            #[weak]
+            show_action,
+            #[weak(rename_to = window)]
+            fm_window.window,
+            move |state| {
+                let shown = state.settings.show_preview;
+                show_action.set_state(&shown.into());
+                if shown != preview.pane.is_visible() {
+                    // The state is borrowed, the preview reads it
+                    glib::idle_add_local_once(glib::clone!(
+                        #[strong]
+                        preview,
+                        #[weak]
+                        window,
+                        move || preview.set_shown(shown, &window)
+                    ));
+                }
+            }
+        ));
+    }
+
+    fn set_shown(&self, shown: bool, window: &gtk4::ApplicationWindow) {
+        self.placed.set(false);
+        self.pane.set_visible(shown);
+        if shown {
+            self.schedule(window);
+        } else {
+            self.preview.clear();
+        }
+    }
+
+    /// Previews the selection once it has not changed for a moment.
+    fn schedule(&self, window: &gtk4::ApplicationWindow) {
+        if !self.pane.is_visible() {
+            return;
+        }
+        if let Some(timer) = self.timer.take() {
+            timer.remove();
+        }
+        let timer = glib::timeout_add_local_once(
+            DELAY,
+            glib::clone!(
+                #[strong(rename_to = preview)]
+                self,
+                #[weak]
+                window,
+                move || {
+                    preview.timer.take();
+                    if let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) {
+                        preview.update(&fm_window);
+                    }
+                }
+            ),
+        );
+        self.timer.replace(Some(timer));
+    }
+
+    fn update(&self, fm_window: &FmWindow) {
+        let items = files_panel::selected_items(&fm_window.files_selection);
+        if items.is_empty() {
+            self.preview.clear();
+            let hint = Label::new(Some("Select a file to preview it"));
+            hint.add_css_class("dim-label");
+            hint.set_vexpand(true);
+            self.preview.widget().append(&hint);
+        } else {
+            self.preview.show(&items, &fm_window.fmstate.borrow().settings);
+        }
+    }
+}
+
+/// Shows files in a box, reading their content in the background. Used
+/// by the preview pane and the quick look popup.
+#[derive(Clone)]
+pub struct Preview {
+    body: GtkBox,
+    icon_size: i32,
+    /// Reads the content of the current preview.
+    task: Rc<RefCell<Option<glib::JoinHandle<()>>>>,
+    /// Kept while it finds out the length of the current file.
+    media: Rc<RefCell<Option<MediaFile>>>,
+}
+
+impl Preview {
+    /// Files without a picture of their content get an icon `icon_size`
+    /// pixels large.
+    pub fn new(icon_size: i32) -> Self {
+        let body = GtkBox::new(Orientation::Vertical, 12);
+        body.set_margin_start(12);
+        body.set_margin_end(12);
+        body.set_margin_top(12);
+        body.set_margin_bottom(12);
+
+        Self { body, icon_size, task: Rc::default(), media: Rc::default() }
+    }
+
+    pub fn widget(&self) -> &GtkBox {
+        &self.body
+    }
+
+    /// Stops reading the previous preview and empties the box.
+    fn clear(&self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.media.take();
+        while let Some(child) = self.body.first_child() {
+            self.body.remove(&child);
+        }
+    }
+
+    /// Previews `items`, the content of the file when there is only one.
+    pub fn show(&self, items: &[FileItem], settings: &FMSettings) {
+        self.clear();
+
+        match items {
+            [] => {}
+            [item] => self.show_item(item, settings),
+            items => {
+                let icon = Image::from_icon_name("edit-select-all");
+                icon.set_pixel_size(self.icon_size);
+                self.body.append(&icon);
+                self.body.append(&title_label(&format!("{} items selected", items.len())));
+
+                let units = settings.size_units;
+                let properties = properties_grid();
+                let files: Vec<&FileItem> = items.iter().filter(|i| !i.is_directory()).collect();
+                let folders = items.len() - files.len();
+                if folders > 0 {
+                    add_property(&properties, "Folders:", &folders.to_string());
+                }
+                if !files.is_empty() {
+                    add_property(&properties, "Files:", &files.len().to_string());
+                    let size = files.iter().map(|item| item.size()).sum();
+                    add_property(&properties, "Size:", &footer_bar::format_size(size, units));
+                }
+                self.body.append(&properties);
+            }
+        }
+    }
+
+    fn show_item(&self, item: &FileItem, settings: &FMSettings) {
+        let icon = Image::new();
+        icon.set_pixel_size(self.icon_size);
+        match item.icon() {
+            Some(gicon) => icon.set_from_gicon(&gicon),
+            None => icon.set_icon_name(Some("text-x-generic")),
+        }
+        self.body.append(&icon);
+        self.body.append(&title_label(&item.display_name()));
+
+        let properties = properties_grid();
+        add_property(&properties, "Type:", &item.mime_type());
+        if !item.is_directory() {
+            add_property(
+                &properties,
+                "Size:",
+                &footer_bar::format_size(item.size(), settings.size_units),
+            );
+        }
+        add_property(&properties, "Modified:", &item.format_modified(&settings.date_format));
+        add_property(&properties, "Location:", &item.location());
+        if let Some(target) = item.symlink_target() {
+            add_property(&properties, "Link target:", &target);
+        }
+        self.body.append(&properties);
+
+        // Archive members are not files that can be read
+        if item.archive_member() {
+            return;
+        }
+
+        let file = item.file();
+        let content_type = item.content_type();
+        if item.is_directory() {
+            let contents = GtkBox::new(Orientation::Vertical, 6);
+            self.body.append(&contents);
+            self.spawn(list_folder(file, contents, settings.show_hidden));
+        } else if content_type.starts_with("image/") {
+            self.spawn(load_image(PathBuf::from(item.path()), icon));
+        } else if gio::content_type_is_a(&content_type, "text/plain") {
+            let text = GtkBox::new(Orientation::Vertical, 0);
+            self.body.append(&text);
+            self.spawn(load_text(file, text));
+        } else {
+            if content_type.starts_with("audio/") || content_type.starts_with("video/") {
+                self.probe_media(&file, &properties);
+            }
+            // Thumbnailers draw videos, documents and the like
+            thumbnails::request(
+                item,
+                thumbnails::ThumbnailSize::Large,
+                glib::clone!(
+                    #[weak]
+                    icon,
+                    #[upgrade_or]
+                    false,
+                    move || icon.parent().is_some()
+                ),
+                glib::clone!(
+                    #[weak]
+                    icon,
+                    move |texture| {
+                        if icon.parent().is_some() {
+                            icon.set_paintable(Some(texture));
+                        }
+                    }
+                ),
+            );
+        }
+    }
+
+    fn spawn(&self, future: impl std::future::Future<Output = ()> + 'static) {
+        self.task.replace(Some(glib::spawn_future_local(future)));
+    }
+
+    /// Adds the length and size of the audio or video in `file` once the
+    /// media backend has read them.
+    fn probe_media(&self, file: &gio::File, properties: &Grid) {
+        let media = MediaFile::for_file(file);
+        media.connect_prepared_notify(glib::clone!(
+            #[weak]
+            properties,
+            move |media| {
+                if !media.is_prepared() {
+                    return;
+                }
+                if media.duration() > 0 {
+                    add_property(&properties, "Duration:", &format_duration(media.duration()));
+                }
+                if media.has_video() {
+                    let size =
+                        format!("{} × {}", media.intrinsic_width(), media.intrinsic_height());
+                    add_property(&properties, "Dimensions:", &size);
+                }
+                let audio = if media.has_audio() { "Yes" } else { "No" };
+                add_property(&properties, "Audio:", audio);
+            }
+        ));
+        self.media.replace(Some(media));
+    }
+}
+
+fn title_label(text: &str) -> Label {
+    let label = Label::new(None);
+    label.set_markup(&format!("<b>{}</b>", glib::markup_escape_text(text)));
+    label.set_wrap(true);
+    label.set_wrap_mode(gtk4::pango::WrapMode::WordChar);
+    label.set_justify(gtk4::Justification::Center);
+    label.set_selectable(true);
+    label
+}
+
+fn properties_grid() -> Grid {
+    let grid = Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    grid.set_halign(gtk4::Align::Center);
+    grid
+}
+
+/// Adds a row below the others.
+fn add_property(grid: &Grid, name: &str, value: &str) {
+    let name = Label::new(Some(name));
+    name.set_halign(gtk4::Align::End);
+    name.set_valign(gtk4::Align::Start);
+    name.add_css_class("dim-label");
+
+    let value = Label::new(Some(value));
+    value.set_halign(gtk4::Align::Start);
+    value.set_xalign(0.0);
+    value.set_selectable(true);
+    value.set_wrap(true);
+    value.set_wrap_mode(gtk4::pango::WrapMode::WordChar);
+
+    grid.attach_next_to(&name, None::<&gtk4::Widget>, PositionType::Bottom, 1, 1);
+    grid.attach_next_to(&value, Some(&name), PositionType::Right, 1, 1);
+}
+
+/// Formats microseconds as `h:mm:ss`, or `m:ss` under an hour.
+fn format_duration(microseconds: i64) -> String {
+    let seconds = microseconds / 1_000_000;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Replaces `icon` with the image at `path`, scaled down to fit the pane.
+async fn load_image(path: PathBuf, icon: Image) {
+    // A pixbuf can't leave the thread reading it, its pixels can
+    let loaded = gio::spawn_blocking(move || {
+        thumbnails::scale_image(&path, MAX_IMAGE_PIXELS).map(|pixbuf| {
+            let size = (pixbuf.width(), pixbuf.height(), pixbuf.rowstride());
+            (pixbuf.read_pixel_bytes(), pixbuf.has_alpha(), size)
+        })
+    })
+    .await;
+    let Ok(Ok((pixels, has_alpha, (width, height, rowstride)))) = loaded else {
+        return;
+    };
+    let pixbuf =
+        Pixbuf::from_bytes(&pixels, Colorspace::Rgb, has_alpha, 8, width, height, rowstride);
+    let Some(parent) = icon.parent().and_downcast::<GtkBox>() else {
+        return;
+    };
+
+    let picture = Picture::for_pixbuf(&pixbuf);
+    picture.set_can_shrink(true);
+    parent.insert_child_after(&picture, Some(&icon));
+    parent.remove(&icon);
+}
+
+/// Shows the first lines of the text file `file` in `container`. Files
+/// that turn out to be binary are left out.
+async fn load_text(file: gio::File, container: GtkBox) {
+    let Ok(stream) = file.read_future(glib::Priority::DEFAULT).await else {
+        return;
+    };
+    let mut data = Vec::new();
+    while data.len() < MAX_TEXT_BYTES {
+        match stream.read_bytes_future(MAX_TEXT_BYTES - data.len(), glib::Priority::DEFAULT).await {
+            Ok(bytes) if !bytes.is_empty() => data.extend_from_slice(&bytes),
+            Ok(_) => break,
+            Err(e) => {
+                eprintln!("Failed to read {} for its preview: {}", file.parse_name(), e);
+                return;
+            }
+        }
+    }
+    if data.contains(&0) {
+        return;
+    }
+
+    let text = String::from_utf8_lossy(&data);
+    let mut lines: Vec<&str> = text.lines().take(MAX_TEXT_LINES + 1).collect();
+    let truncated = lines.len() > MAX_TEXT_LINES || data.len() >= MAX_TEXT_BYTES;
+    lines.truncate(MAX_TEXT_LINES);
+    let mut shown = lines.join("\n");
+    if truncated {
+        // The last character may have been cut in half
+        shown = shown.trim_end_matches(char::REPLACEMENT_CHARACTER).to_string();
+        shown.push_str("\n…");
+    }
+
+    let view = TextView::new();
+    view.set_editable(false);
+    view.set_cursor_visible(false);
+    view.set_monospace(true);
+    view.set_wrap_mode(gtk4::WrapMode::WordChar);
+    view.buffer().set_text(&shown);
+    container.append(&view);
+}
+
+/// Lists what the folder `file` contains in `container`.
+async fn list_folder(file: gio::File, container: GtkBox, show_hidden: bool) {
+    let enumerator = match file
+        .enumerate_children_future(
+            "standard::display-name,standard::icon,standard::type",
+            gio::FileQueryInfoFlags::NONE,
+            glib::Priority::DEFAULT,
+        )
+        .await
+    {
+        Ok(enumerator) => enumerator,
+        Err(e) => {
+            let error = Label::new(Some(&e.to_string()));
+            error.add_css_class("dim-label");
+            error.set_wrap(true);
+            container.append(&error);
+            return;
+        }
+    };
+
+    let mut entries = Vec::new();
+    let mut count = 0;
+    loop {
+        let infos = match enumerator.next_files_future(100, glib::Priority::DEFAULT).await {
+            Ok(infos) if !infos.is_empty() => infos,
+            _ => break,
+        };
+        for info in infos {
+            let name = info.display_name().to_string();
+            if !show_hidden && name.starts_with('.') {
+                continue;
+            }
+            count += 1;
+            if entries.len() < MAX_FOLDER_ENTRIES {
+                entries.push((info.file_type() == gio::FileType::Directory, name, info.icon()));
+            }
+        }
+    }
+
+    let summary = match count {
+        0 => "Empty folder".to_string(),
+        1 => "1 item".to_string(),
+        count => format!("{} items", count),
+    };
+    let summary = Label::new(Some(&summary));
+    summary.add_css_class("dim-label");
+    container.append(&summary);
+
+    entries.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.to_lowercase().cmp(&b.1.to_lowercase())));
+    for (_, name, gicon) in &entries {
+        let row = GtkBox::new(Orientation::Horizontal, 6);
+        let icon = match gicon {
+            Some(gicon) => Image::from_gicon(gicon),
+            None => Image::from_icon_name("text-x-generic"),
+        };
+        let label = Label::new(Some(name));
+        label.set_xalign(0.0);
+        label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+        row.append(&icon);
+        row.append(&label);
+        container.append(&row);
+    }
+    if count > entries.len() {
+        let more = Label::new(Some(&format!("and {} more", count - entries.len())));
+        more.add_css_class("dim-label");
+        more.set_xalign(0.0);
+        container.append(&more);
+    }
+}
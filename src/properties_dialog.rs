@@ -1,9 +1,9 @@
-use crate::footer_bar;
+use crate::{footer_bar, utils::SizeUnits};
 use gtk4::{
     Box as GtkBox, Dialog, Grid, Image, Label, Orientation, ResponseType, Window, gio, prelude::*,
 };
 
-pub fn show_properties_dialog(parent_window: &Window, file_path: &str) {
+pub fn show_properties_dialog(parent_window: &Window, file_path: &str, size_units: SizeUnits) {
     let file = gio::File::for_path(file_path);
 
     // Create the dialog
@@ -27,6 +27,7 @@ pub fn show_properties_dialog(parent_window: &Window, file_path: &str) {
     // GIO query
     let query_attrs = "standard::icon,standard::size,standard::type,\
                        standard::content-type,standard::display-name,\
+                       standard::is-symlink,standard::symlink-target,\
                        time::modified,access::can-read,access::can-write";
 
     if let Ok(info) =
@@ -57,6 +58,18 @@ pub fn show_properties_dialog(parent_window: &Window, file_path: &str) {
         add_property_row(&grid, row, "Type:", &file_type);
         row += 1;
 
+        // Link target, a broken link being the only one still seen as a link
+        if info.is_symlink()
+            && let Some(target) = info.symlink_target()
+        {
+            let mut text = target.display().to_string();
+            if info.file_type() == gio::FileType::SymbolicLink {
+                text.push_str(" (broken)");
+            }
+            add_property_row(&grid, row, "Link target:", &text);
+            row += 1;
+        }
+
         // Location
         if let Some(parent) = file.parent() {
             if let Some(parent_path) = parent.path() {
@@ -69,7 +82,7 @@ pub fn show_properties_dialog(parent_window: &Window, file_path: &str) {
         let file_type_enum = info.file_type();
         if file_type_enum == gio::FileType::Regular {
             let size = info.size() as u64;
-            let formatted_size = footer_bar::format_size(size);
+            let formatted_size = footer_bar::format_size(size, size_units);
             add_property_row(&grid, row, "Size:", &formatted_size);
         } else if file_type_enum == gio::FileType::Directory {
             add_property_row(&grid, row, "Size:", "Folder");
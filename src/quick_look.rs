@@ -0,0 +1,126 @@
+//! Quick look: Space on a file in the list opens a large preview of it.
+//! The arrow keys go through the list in its sort order while the popup
+//! stays open, selecting each file on the way, and Space or Escape close it.
+
+use crate::{files_panel, fm_window::FmWindow, models::file_item::FileItem, preview_pane::Preview};
+use gtk4::{ScrolledWindow, gdk, glib, prelude::*};
+use std::{cell::Cell, rc::Rc};
+
+const ICON_SIZE: i32 = 256;
+
+/// Opens the popup with Space in the file list of `fm_window`.
+pub fn attach(fm_window: &FmWindow) {
+    // Caught before the rows, which select with Space
+    let keys = gtk4::EventControllerKey::new();
+    keys.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    keys.connect_key_pressed(glib::clone!(
+        #[weak(rename_to = window)]
+        fm_window.window,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, modifiers| {
+            let shortcut_modifiers = gdk::ModifierType::CONTROL_MASK
+                | gdk::ModifierType::ALT_MASK
+                | gdk::ModifierType::SUPER_MASK;
+            // Typing a space in a rename is not a preview
+            if key != gdk::Key::space
+                || modifiers.intersects(shortcut_modifiers)
+                || GtkWindowExt::focus(&window).is_some_and(|w| w.is::<gtk4::Text>())
+            {
+                return glib::Propagation::Proceed;
+            }
+            match FmWindow::from_window(window.upcast_ref()) {
+                Some(fm_window) => {
+                    show_quick_look(&fm_window);
+                    glib::Propagation::Stop
+                }
+                None => glib::Propagation::Proceed,
+            }
+        }
+    ));
+    fm_window.column_view.add_controller(keys);
+}
+
+/// Previews the first file selected in a popup over the window.
+pub fn show_quick_look(fm_window: &FmWindow) {
+    let selection = &fm_window.files_selection;
+    let selected = selection.selection();
+    if selected.is_empty() {
+        fm_window.column_view.error_bell();
+        return;
+    }
+
+    let popup = gtk4::Window::builder()
+        .transient_for(&fm_window.window)
+        .modal(true)
+        .default_width(900)
+        .default_height(700)
+        .build();
+    let preview = Preview::new(ICON_SIZE);
+    preview.widget().set_valign(gtk4::Align::Center);
+    let scrolled = ScrolledWindow::builder()
+        .child(preview.widget())
+        .hscrollbar_policy(gtk4::PolicyType::Never)
+        .build();
+    popup.set_child(Some(&scrolled));
+
+    // Where the popup is in the list, the list being in its sort order
+    let position = Rc::new(Cell::new(selected.minimum()));
+    let show = glib::clone!(
+        #[weak]
+        popup,
+        #[strong(rename_to = fm_window)]
+        fm_window.clone(),
+        #[strong]
+        position,
+        move || {
+            let Some(item) =
+                fm_window.files_selection.item(position.get()).and_downcast::<FileItem>()
+            else {
+                popup.close();
+                return;
+            };
+            popup.set_title(Some(&item.display_name()));
+            preview.show(std::slice::from_ref(&item), &fm_window.fmstate.borrow().settings);
+        }
+    );
+    show();
+
+    let keys = gtk4::EventControllerKey::new();
+    keys.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    keys.connect_key_pressed(glib::clone!(
+        #[weak]
+        popup,
+        #[strong(rename_to = fm_window)]
+        fm_window.clone(),
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, _| {
+            let step: i64 = match key {
+                gdk::Key::space | gdk::Key::Escape => {
+                    popup.close();
+                    return glib::Propagation::Stop;
+                }
+                gdk::Key::Right | gdk::Key::Down => 1,
+                gdk::Key::Left | gdk::Key::Up => -1,
+                _ => return glib::Propagation::Proceed,
+            };
+
+            let count = fm_window.files_selection.n_items() as i64;
+            let next = position.get() as i64 + step;
+            if next < 0 || next >= count {
+                popup.error_bell();
+                return glib::Propagation::Stop;
+            }
+            let next = next as u32;
+            position.set(next);
+            fm_window.files_selection.select_item(next, true);
+            files_panel::scroll_to_position(&fm_window.column_view, next);
+            show();
+            glib::Propagation::Stop
+        }
+    ));
+    popup.add_controller(keys);
+
+    popup.present();
+}
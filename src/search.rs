@@ -0,0 +1,512 @@
+//! Recursive search of a folder.
+//!
+//! The walk runs on a worker thread and sends each match as soon as it is
+//! found, so results show up while the search goes on. Folders covered by
+//! the search index skip the walk: the index gives the names, and only the
+//! files found are looked at.
+
+use crate::{operations::OperationContext, search_index, utils::SizeUnits};
+use gtk4::{gio, glib};
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::UNIX_EPOCH,
+};
+
+/// How much of a file is read at once when looking at its contents.
+const CONTENT_CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NameMode {
+    /// The name contains the text, ignoring case.
+    Contains,
+    /// The name starts with the text, ignoring case.
+    StartsWith,
+    /// The name has the characters of the text in the same order, with
+    /// anything in between, ignoring case.
+    Fuzzy,
+    /// Shell wildcards, `*` and `?`, matching the whole name.
+    Glob,
+    /// A regular expression found anywhere in the name.
+    Regex,
+}
+
+impl NameMode {
+    pub const ALL: [NameMode; 5] = [
+        NameMode::Contains,
+        NameMode::StartsWith,
+        NameMode::Fuzzy,
+        NameMode::Glob,
+        NameMode::Regex,
+    ];
+
+    /// Name under which the mode is stored in saved searches.
+    pub fn key(&self) -> &'static str {
+        match self {
+            NameMode::Contains => "contains",
+            NameMode::StartsWith => "starts-with",
+            NameMode::Fuzzy => "fuzzy",
+            NameMode::Glob => "glob",
+            NameMode::Regex => "regex",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<NameMode> {
+        NameMode::ALL.into_iter().find(|mode| mode.key() == key)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NameMode::Contains => "Name contains",
+            NameMode::StartsWith => "Name starts with",
+            NameMode::Fuzzy => "Name fuzzy",
+            NameMode::Glob => "Name matches",
+            NameMode::Regex => "Name regex",
+        }
+    }
+}
+
+/// File types offered by the search, as a label and a content type. A type
+/// ending in `/` stands for all types of that kind.
+pub const FILE_TYPES: [(&str, &str); 7] = [
+    ("Any type", ""),
+    ("Folders", "inode/directory"),
+    ("Text files", "text/plain"),
+    ("Images", "image/"),
+    ("Audio", "audio/"),
+    ("Video", "video/"),
+    ("PDF documents", "application/pdf"),
+];
+
+/// What the search looks for. Empty fields match everything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchCriteria {
+    pub name: String,
+    pub name_mode: NameMode,
+    /// Text the file contents must contain, ignoring ASCII case.
+    pub content: String,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Unix times bounding the modification date, both inclusive.
+    pub modified_after: Option<i64>,
+    pub modified_before: Option<i64>,
+    /// One of the content types of `FILE_TYPES`.
+    pub content_type: String,
+    pub show_hidden: bool,
+}
+
+impl SearchCriteria {
+    /// Whether nothing is asked for, in which case there is no search.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty()
+            && self.content.is_empty()
+            && self.min_size.is_none()
+            && self.max_size.is_none()
+            && self.modified_after.is_none()
+            && self.modified_before.is_none()
+            && self.content_type.is_empty()
+    }
+
+    /// Checks the name pattern, returning what is wrong with it.
+    pub fn validate(&self) -> Result<(), String> {
+        NameMatcher::new(&self.name, self.name_mode).map(|_| ())
+    }
+}
+
+enum NameMatcher {
+    Any,
+    // The texts are lowercase
+    Contains(String),
+    StartsWith(String),
+    Fuzzy(String),
+    Pattern(glib::Regex),
+}
+
+impl NameMatcher {
+    fn new(name: &str, mode: NameMode) -> Result<NameMatcher, String> {
+        if name.is_empty() {
+            return Ok(NameMatcher::Any);
+        }
+
+        let (pattern, flags) = match mode {
+            NameMode::Contains => return Ok(NameMatcher::Contains(name.to_lowercase())),
+            NameMode::StartsWith => return Ok(NameMatcher::StartsWith(name.to_lowercase())),
+            NameMode::Fuzzy => return Ok(NameMatcher::Fuzzy(name.to_lowercase())),
+            NameMode::Glob => (glob_to_regex(name), glib::RegexCompileFlags::CASELESS),
+            NameMode::Regex => (name.to_string(), glib::RegexCompileFlags::empty()),
+        };
+        match glib::Regex::new(&pattern, flags, glib::RegexMatchFlags::empty()) {
+            Ok(Some(regex)) => Ok(NameMatcher::Pattern(regex)),
+            Ok(None) => Err("Invalid pattern".to_string()),
+            Err(e) => Err(e.message().to_string()),
+        }
+    }
+
+    /// Whether `name`, also given in lowercase, matches.
+    fn matches(&self, name: &str, name_lower: &str) -> bool {
+        match self {
+            NameMatcher::Any => true,
+            NameMatcher::Contains(text) => name_lower.contains(text.as_str()),
+            NameMatcher::StartsWith(text) => name_lower.starts_with(text.as_str()),
+            NameMatcher::Fuzzy(text) => {
+                let mut rest = name_lower.chars();
+                text.chars().all(|c| rest.any(|n| n == c))
+            }
+            NameMatcher::Pattern(regex) => regex
+                .match_(glib::GString::from(name).as_gstr(), glib::RegexMatchFlags::empty())
+                .is_some(),
+        }
+    }
+}
+
+/// Turns shell wildcards into a regular expression matching whole names.
+pub fn glob_to_regex(glob: &str) -> String {
+    let mut pattern = String::from("^");
+    let mut literal = String::new();
+    for c in glob.chars() {
+        if c == '*' || c == '?' {
+            pattern.push_str(&glib::Regex::escape_string(&literal));
+            literal.clear();
+            pattern.push_str(if c == '*' { ".*" } else { "." });
+        } else {
+            literal.push(c);
+        }
+    }
+    pattern.push_str(&glib::Regex::escape_string(&literal));
+    pattern.push('$');
+    pattern
+}
+
+/// Sends every file under `root` matching `criteria` to `found`, taken from
+/// the search index when it covers `root`. Otherwise the folder is walked,
+/// skipping folders that can't be read and not following links to folders.
+/// Returns the number of matches.
+pub fn search(
+    root: &Path,
+    criteria: &SearchCriteria,
+    context: &OperationContext,
+    found: &mpsc::Sender<PathBuf>,
+) -> usize {
+    // Compiled here, a GRegex can't be sent between threads
+    let Ok(name_matcher) = NameMatcher::new(&criteria.name, criteria.name_mode) else {
+        return 0;
+    };
+    let content = criteria.content.to_ascii_lowercase().into_bytes();
+    let matches = |path: &Path, name: &str, meta: &Metadata| {
+        matches_metadata(name, meta, criteria)
+            && (content.is_empty() || (meta.is_file() && file_contains(path, &content, context)))
+    };
+
+    let mut count = 0;
+    if let Some(candidates) = search_index::find(root, criteria.show_hidden, |name, name_lower| {
+        name_matcher.matches(name, name_lower)
+    }) {
+        context.set_total(candidates.len() as u64);
+        for path in candidates {
+            if context.is_cancelled() {
+                break;
+            }
+            context.advance(1, &path.display().to_string());
+
+            // The index may be a little behind
+            let Ok(meta) = fs::metadata(&path).or_else(|_| fs::symlink_metadata(&path)) else {
+                continue;
+            };
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if matches(&path, &name, &meta) {
+                count += 1;
+                if found.send(path).is_err() {
+                    break;
+                }
+            }
+        }
+        return count;
+    }
+
+    let mut folders = vec![root.to_path_buf()];
+    while let Some(dir) = folders.pop() {
+        if context.is_cancelled() {
+            break;
+        }
+        context.advance(1, &dir.display().to_string());
+
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if context.is_cancelled() {
+                return count;
+            }
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !criteria.show_hidden && name.starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let is_link = entry.file_type().is_ok_and(|t| t.is_symlink());
+            // The target decides for links, unless it is missing
+            let Ok(meta) = fs::metadata(&path).or_else(|_| entry.metadata()) else {
+                continue;
+            };
+            if meta.is_dir() && !is_link {
+                folders.push(path.clone());
+            }
+
+            if name_matcher.matches(&name, &name.to_lowercase()) && matches(&path, &name, &meta) {
+                count += 1;
+                if found.send(path).is_err() {
+                    return count;
+                }
+            }
+        }
+    }
+    count
+}
+
+fn matches_metadata(name: &str, meta: &Metadata, criteria: &SearchCriteria) -> bool {
+    // Folders have no size of their own
+    let size = (!meta.is_dir()).then_some(meta.len());
+    if criteria.min_size.is_some_and(|min| size.is_none_or(|size| size < min))
+        || criteria.max_size.is_some_and(|max| size.is_none_or(|size| size > max))
+    {
+        return false;
+    }
+
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs() as i64)
+        .unwrap_or(0);
+    if criteria.modified_after.is_some_and(|after| modified < after)
+        || criteria.modified_before.is_some_and(|before| modified > before)
+    {
+        return false;
+    }
+
+    if criteria.content_type.is_empty() {
+        return true;
+    }
+    let content_type = if meta.is_dir() {
+        glib::GString::from("inode/directory")
+    } else {
+        gio::content_type_guess(Some(name), None).0
+    };
+    match criteria.content_type.strip_suffix('/') {
+        Some(kind) => content_type.split('/').next() == Some(kind),
+        None => gio::content_type_is_a(&content_type, &criteria.content_type),
+    }
+}
+
+/// Whether the file at `path` contains `needle`, which is lowercase.
+fn file_contains(path: &Path, needle: &[u8], context: &OperationContext) -> bool {
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+
+    // The tail of the previous chunk is kept, in case a match straddles both
+    let mut buffer = Vec::with_capacity(CONTENT_CHUNK + needle.len());
+    let mut chunk = vec![0; CONTENT_CHUNK];
+    loop {
+        if context.is_cancelled() {
+            return false;
+        }
+        let read = match file.read(&mut chunk) {
+            Ok(0) => return false,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return false,
+        };
+
+        buffer.extend(chunk[..read].iter().map(u8::to_ascii_lowercase));
+        if buffer.windows(needle.len()).any(|window| window == needle) {
+            return true;
+        }
+        let keep = buffer.len().min(needle.len() - 1);
+        buffer.drain(..buffer.len() - keep);
+    }
+}
+
+/// Reads a size like `200`, `10 MB` or `1.5G`. Bare unit letters follow
+/// `units`, while `kB`/`KiB` and the like say which base they use.
+pub fn parse_size(text: &str, units: SizeUnits) -> Option<u64> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let unit = unit.trim().to_lowercase();
+    let (prefix, base) = if let Some(prefix) = unit.strip_suffix("ib") {
+        (prefix, 1024.0)
+    } else if let Some(prefix) = unit.strip_suffix('b').filter(|prefix| !prefix.is_empty()) {
+        (prefix, 1000.0)
+    } else {
+        (unit.trim_end_matches('b'), units.base())
+    };
+    let exponent = match prefix {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => return None,
+    };
+    Some((number * f64::powi(base, exponent)) as u64)
+}
+
+/// Reads a `YYYY-MM-DD` date, or one relative to today: `today`,
+/// `yesterday`, `N days ago`, and `this week`, `this month` or `this year`
+/// for their first day. Gives the Unix time of the start of that day, or of
+/// its end with `end_of_day`, in local time.
+pub fn parse_date(text: &str, end_of_day: bool) -> Option<i64> {
+    let text = text.trim().to_lowercase();
+    let now = glib::DateTime::now_local().ok()?;
+    let (year, month, day) = now.ymd();
+    let today = glib::DateTime::from_local(year, month, day, 0, 0, 0.0).ok()?;
+    let start = match relative_day(&text, &today) {
+        Some(day) => day,
+        None => {
+            let mut parts = text.splitn(3, '-').map(|part| part.parse::<i32>().ok());
+            let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+            glib::DateTime::from_local(year, month, day, 0, 0, 0.0).ok()?
+        }
+    };
+
+    let time = if end_of_day { start.add_days(1).ok()?.add_seconds(-1.0).ok()? } else { start };
+    Some(time.to_unix())
+}
+
+/// The start of the day named by `text`, which is lowercase, when it is a
+/// date relative to `today`, itself the start of a day.
+fn relative_day(text: &str, today: &glib::DateTime) -> Option<glib::DateTime> {
+    let (year, month, _) = today.ymd();
+    match text {
+        "today" => Some(today.clone()),
+        "yesterday" => today.add_days(-1).ok(),
+        "this week" => today.add_days(1 - today.day_of_week()).ok(),
+        "this month" => glib::DateTime::from_local(year, month, 1, 0, 0, 0.0).ok(),
+        "this year" => glib::DateTime::from_local(year, 1, 1, 0, 0, 0.0).ok(),
+        _ => {
+            let days = text.strip_suffix("ago")?.trim_end();
+            let days = days.strip_suffix("days").or_else(|| days.strip_suffix("day"))?;
+            today.add_days(-days.trim().parse::<i32>().ok()?).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(year: i32, month: i32, day: i32) -> glib::DateTime {
+        glib::DateTime::from_local(year, month, day, 0, 0, 0.0).unwrap()
+    }
+
+    fn relative(text: &str, today: &glib::DateTime) -> Option<(i32, i32, i32)> {
+        relative_day(text, today).map(|date| date.ymd())
+    }
+
+    #[test]
+    fn days_relative_to_today() {
+        // A Thursday
+        let today = day(2024, 3, 14);
+        assert_eq!(relative("today", &today), Some((2024, 3, 14)));
+        assert_eq!(relative("yesterday", &today), Some((2024, 3, 13)));
+        assert_eq!(relative("this week", &today), Some((2024, 3, 11)));
+        assert_eq!(relative("this month", &today), Some((2024, 3, 1)));
+        assert_eq!(relative("this year", &today), Some((2024, 1, 1)));
+        assert_eq!(relative("1 day ago", &today), Some((2024, 3, 13)));
+        assert_eq!(relative("10 days ago", &today), Some((2024, 3, 4)));
+        assert_eq!(relative("3days ago", &today), Some((2024, 3, 11)));
+
+        assert_eq!(relative("tomorrow", &today), None);
+        assert_eq!(relative("some days ago", &today), None);
+        assert_eq!(relative("3 weeks ago", &today), None);
+        assert_eq!(relative("2024-03-14", &today), None);
+    }
+
+    #[test]
+    fn relative_days_cross_months_and_years() {
+        // Weeks start on Monday, Sunday being the last day
+        assert_eq!(relative("this week", &day(2024, 3, 17)), Some((2024, 3, 11)));
+        assert_eq!(relative("this week", &day(2024, 3, 11)), Some((2024, 3, 11)));
+        assert_eq!(relative("this week", &day(2024, 1, 3)), Some((2024, 1, 1)));
+        assert_eq!(relative("this week", &day(2023, 1, 1)), Some((2022, 12, 26)));
+        assert_eq!(relative("3 days ago", &day(2024, 3, 1)), Some((2024, 2, 27)));
+        assert_eq!(relative("yesterday", &day(2024, 1, 1)), Some((2023, 12, 31)));
+    }
+
+    #[test]
+    fn dates_cover_whole_days() {
+        let start = parse_date(" 2024-03-14 ", false).unwrap();
+        let end = parse_date("2024-03-14", true).unwrap();
+        assert_eq!(start, day(2024, 3, 14).to_unix());
+        assert_eq!(end, day(2024, 3, 15).to_unix() - 1);
+        assert_eq!(parse_date("Today", false), parse_date("today", false));
+        assert_eq!(parse_date("2024-03", false), None);
+        assert_eq!(parse_date("soon", false), None);
+    }
+
+    #[test]
+    fn sizes_with_units() {
+        assert_eq!(parse_size("10", SizeUnits::Iec), Some(10));
+        assert_eq!(parse_size(" 10 b ", SizeUnits::Si), Some(10));
+        // A bare prefix follows the units setting
+        assert_eq!(parse_size("1k", SizeUnits::Iec), Some(1024));
+        assert_eq!(parse_size("1k", SizeUnits::Si), Some(1000));
+        assert_eq!(parse_size("3 T", SizeUnits::Iec), Some(3 << 40));
+        // Suffixes say which units they are in
+        assert_eq!(parse_size("1kB", SizeUnits::Iec), Some(1000));
+        assert_eq!(parse_size("1KiB", SizeUnits::Si), Some(1024));
+        assert_eq!(parse_size("1.5 MB", SizeUnits::Iec), Some(1_500_000));
+        assert_eq!(parse_size("2 GiB", SizeUnits::Si), Some(2 << 30));
+        assert_eq!(parse_size("0.5m", SizeUnits::Iec), Some(512 * 1024));
+    }
+
+    #[test]
+    fn bad_sizes() {
+        assert_eq!(parse_size("", SizeUnits::Iec), None);
+        assert_eq!(parse_size("big", SizeUnits::Iec), None);
+        assert_eq!(parse_size("MB", SizeUnits::Iec), None);
+        assert_eq!(parse_size("-5", SizeUnits::Iec), None);
+        assert_eq!(parse_size("1.2.3", SizeUnits::Iec), None);
+        assert_eq!(parse_size("5 XB", SizeUnits::Iec), None);
+        assert_eq!(parse_size("5 kilobytes", SizeUnits::Iec), None);
+    }
+
+    fn glob_matches(glob: &str, name: &str) -> bool {
+        glib::Regex::match_simple(
+            glob_to_regex(glob),
+            name,
+            glib::RegexCompileFlags::empty(),
+            glib::RegexMatchFlags::empty(),
+        )
+    }
+
+    #[test]
+    fn globs_become_anchored_patterns() {
+        assert_eq!(glob_to_regex("*.txt"), "^.*\\.txt$");
+        assert_eq!(glob_to_regex("a?c"), "^a.c$");
+
+        assert!(glob_matches("*.txt", "notes.txt"));
+        assert!(!glob_matches("*.txt", "notes.txt.bak"));
+        assert!(!glob_matches("*.txt", "notes_txt"));
+        assert!(glob_matches("a?c", "abc"));
+        assert!(!glob_matches("a?c", "ac"));
+    }
+
+    #[test]
+    fn glob_literals_are_escaped() {
+        assert!(glob_matches("c++ notes*", "c++ notes.md"));
+        assert!(!glob_matches("c++ notes*", "ccc notes.md"));
+        assert!(glob_matches("photo (1).jpg", "photo (1).jpg"));
+        assert!(!glob_matches("photo (1).jpg", "photo 1.jpg"));
+        assert!(glob_matches("[draft] *", "[draft] plan.odt"));
+        assert!(!glob_matches("[draft] *", "d plan.odt"));
+        assert!(glob_matches("a.b", "a.b"));
+        assert!(!glob_matches("a.b", "axb"));
+        assert!(glob_matches("$HOME^{1}|x\\y", "$HOME^{1}|x\\y"));
+    }
+}
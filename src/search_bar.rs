@@ -0,0 +1,784 @@
+//! Search bar above the file list. While it is open, the list shows the
+//! files found under the current folder instead of the folder itself, and
+//! searches again as files there change.
+
+use crate::{
+    bookmarks::SavedSearch,
+    file_operations, files_panel,
+    fm_window::FmWindow,
+    models::file_item::FileItem,
+    search::{self, FILE_TYPES, NameMode, SearchCriteria},
+    search_index, sidebar,
+};
+use gtk4::{
+    Box as GtkBox, Button, DropDown, Entry, Grid, Label, MenuButton, Orientation, Popover,
+    SearchEntry, gio, glib, prelude::*,
+};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
+
+/// Changes arriving together are handled by a single search.
+const REFRESH_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct SearchState {
+    /// Counts the searches, so results arriving late from an earlier one
+    /// can be told apart.
+    generation: u32,
+    running: Option<gio::Cancellable>,
+    found: usize,
+    /// The file list holds search results rather than the current folder.
+    showing_results: bool,
+    /// What the results shown were searched with.
+    criteria: Option<SearchCriteria>,
+    /// The fields are being filled in from a saved search.
+    loading: bool,
+    /// Counts the searches started, which updating their results doesn't,
+    /// so the change handlers of an earlier one can tell they are done.
+    searches: u32,
+    /// Pending update of the results after files changed.
+    refresh_timer: Option<glib::SourceId>,
+    /// Watches the folder searched when the index doesn't cover it.
+    monitor: Option<gio::FileMonitor>,
+}
+
+#[derive(Clone)]
+pub struct SearchBar {
+    bar: gtk4::SearchBar,
+    entry: SearchEntry,
+    name_mode: DropDown,
+    content: Entry,
+    min_size: Entry,
+    max_size: Entry,
+    modified_after: Entry,
+    modified_before: Entry,
+    file_type: DropDown,
+    filters: MenuButton,
+    save: MenuButton,
+    save_name: Entry,
+    status: Label,
+    state: Rc<RefCell<SearchState>>,
+}
+
+impl Default for SearchBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchBar {
+    pub fn new() -> Self {
+        let name_modes: Vec<&str> = NameMode::ALL.iter().map(NameMode::label).collect();
+        let name_mode = DropDown::from_strings(&name_modes);
+
+        let entry = SearchEntry::new();
+        entry.set_hexpand(true);
+        entry.set_placeholder_text(Some("Search in this folder and below"));
+
+        let content = Entry::builder().placeholder_text("Text inside the file").build();
+        let min_size = Entry::builder().placeholder_text("e.g. 10 MB").width_chars(10).build();
+        let max_size = Entry::builder().placeholder_text("e.g. 2 GB").width_chars(10).build();
+        let date_entry = || {
+            Entry::builder()
+                .placeholder_text("YYYY-MM-DD")
+                .tooltip_text("A date, or today, yesterday, 3 days ago, this week, this month…")
+                .width_chars(10)
+                .build()
+        };
+        let modified_after = date_entry();
+        let modified_before = date_entry();
+        let type_labels: Vec<&str> = FILE_TYPES.iter().map(|(label, _)| *label).collect();
+        let file_type = DropDown::from_strings(&type_labels);
+
+        let grid = Grid::builder()
+            .row_spacing(6)
+            .column_spacing(6)
+            .margin_start(6)
+            .margin_end(6)
+            .margin_top(6)
+            .margin_bottom(6)
+            .build();
+        let add_row = |row: i32, title: &str, first: &gtk4::Widget, second: Option<&Entry>| {
+            let label = Label::new(Some(title));
+            label.set_xalign(0.0);
+            grid.attach(&label, 0, row, 1, 1);
+            match second {
+                Some(second) => {
+                    grid.attach(first, 1, row, 1, 1);
+                    grid.attach(&Label::new(Some("to")), 2, row, 1, 1);
+                    grid.attach(second, 3, row, 1, 1);
+                }
+                None => grid.attach(first, 1, row, 3, 1),
+            }
+        };
+        add_row(0, "Contains text:", content.upcast_ref(), None);
+        add_row(1, "Size:", min_size.upcast_ref(), Some(&max_size));
+        add_row(2, "Modified:", modified_after.upcast_ref(), Some(&modified_before));
+        add_row(3, "Type:", file_type.upcast_ref(), None);
+
+        let popover = Popover::new();
+        popover.set_child(Some(&grid));
+        let filters = MenuButton::builder()
+            .icon_name("preferences-system-symbolic")
+            .tooltip_text("Search Filters")
+            .popover(&popover)
+            .build();
+
+        let save_name = Entry::builder().placeholder_text("Name").width_chars(24).build();
+        let save_button = Button::with_label("Save");
+        save_button.add_css_class("suggested-action");
+        save_button.set_action_name(Some("win.save_search"));
+        let save_row = GtkBox::new(Orientation::Horizontal, 6);
+        save_row.set_margin_start(6);
+        save_row.set_margin_end(6);
+        save_row.set_margin_top(6);
+        save_row.set_margin_bottom(6);
+        save_row.append(&save_name);
+        save_row.append(&save_button);
+        let save_popover = Popover::new();
+        save_popover.set_child(Some(&save_row));
+        let save = MenuButton::builder()
+            .icon_name("document-save-symbolic")
+            .tooltip_text("Save Search in the Sidebar")
+            .popover(&save_popover)
+            .build();
+
+        let status = Label::new(None);
+        status.add_css_class("dim-label");
+
+        let row = GtkBox::new(Orientation::Horizontal, 6);
+        row.append(&name_mode);
+        row.append(&entry);
+        row.append(&filters);
+        row.append(&save);
+        row.append(&status);
+
+        let bar = gtk4::SearchBar::new();
+        bar.set_child(Some(&row));
+        bar.connect_entry(&entry);
+        bar.set_show_close_button(true);
+
+        Self {
+            bar,
+            entry,
+            name_mode,
+            content,
+            min_size,
+            max_size,
+            modified_after,
+            modified_before,
+            file_type,
+            filters,
+            save,
+            save_name,
+            status,
+            state: Rc::new(RefCell::new(SearchState::default())),
+        }
+    }
+
+    pub fn widget(&self) -> &gtk4::SearchBar {
+        &self.bar
+    }
+
+    pub fn showing_results(&self) -> bool {
+        self.state.borrow().showing_results
+    }
+
+    /// Opens the bar, or moves the focus back to it.
+    pub fn open(&self) {
+        self.bar.set_search_mode(true);
+        self.entry.grab_focus();
+    }
+
+    /// Makes the bar search in `fm_window` as the criteria change, and adds
+    /// the `win.search` action opening it with Ctrl+F.
+    pub fn connect(&self, fm_window: &FmWindow) {
+        let restart = glib::clone!(
+            #[strong(rename_to = search_bar)]
+            self,
+            #[weak(rename_to = window)]
+            fm_window.window,
+            move || {
+                if let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) {
+                    search_bar.restart(&fm_window);
+                }
+            }
+        );
+
+        self.entry.connect_search_changed(glib::clone!(
+            #[strong]
+            restart,
+            move |_| restart()
+        ));
+        self.name_mode.connect_selected_notify(glib::clone!(
+            #[strong]
+            restart,
+            move |_| restart()
+        ));
+        self.file_type.connect_selected_notify(glib::clone!(
+            #[strong]
+            restart,
+            move |_| restart()
+        ));
+        // Half-typed sizes and dates would only be errors, so the other
+        // filters apply on Enter or when the popover closes
+        for entry in [
+            &self.content,
+            &self.min_size,
+            &self.max_size,
+            &self.modified_after,
+            &self.modified_before,
+        ] {
+            entry.connect_activate(glib::clone!(
+                #[strong]
+                restart,
+                move |_| restart()
+            ));
+        }
+        if let Some(popover) = self.filters.popover() {
+            popover.connect_closed(glib::clone!(
+                #[strong]
+                restart,
+                move |_| restart()
+            ));
+        }
+
+        let search_action = gio::SimpleAction::new("search", None);
+        search_action.connect_activate(glib::clone!(
+            #[strong(rename_to = search_bar)]
+            self,
+            move |_, _| search_bar.open()
+        ));
+        fm_window.window.add_action(&search_action);
+
+        let save_action = gio::SimpleAction::new("save_search", None);
+        save_action.connect_activate(glib::clone!(
+            #[strong(rename_to = search_bar)]
+            self,
+            #[weak(rename_to = window)]
+            fm_window.window,
+            move |_, _| {
+                if let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) {
+                    search_bar.save(&fm_window);
+                }
+            }
+        ));
+        fm_window.window.add_action(&save_action);
+        self.save_name.connect_activate(|entry| {
+            let _ = entry.activate_action("win.save_search", None);
+        });
+        if let Some(popover) = self.save.popover() {
+            popover.connect_show(glib::clone!(
+                #[strong(rename_to = search_bar)]
+                self,
+                #[weak(rename_to = window)]
+                fm_window.window,
+                move |_| {
+                    if let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) {
+                        search_bar.save_name.set_text(&search_bar.default_name(&fm_window));
+                    }
+                }
+            ));
+        }
+
+        let shortcuts = gtk4::ShortcutController::new();
+        shortcuts.add_shortcut(gtk4::Shortcut::new(
+            gtk4::ShortcutTrigger::parse_string("<Control>f"),
+            Some(gtk4::NamedAction::new("win.search")),
+        ));
+        fm_window.window.add_controller(shortcuts);
+
+        self.bar.connect_search_mode_enabled_notify(glib::clone!(
+            #[strong(rename_to = search_bar)]
+            self,
+            #[weak(rename_to = window)]
+            fm_window.window,
+            move |bar| {
+                if bar.is_search_mode() {
+                    return;
+                }
+                search_bar.stop();
+                if search_bar.state.borrow().showing_results
+                    && let Some(fm_window) = FmWindow::from_window(window.upcast_ref())
+                {
+                    search_bar.show_folder(&fm_window);
+                }
+            }
+        ));
+
+        // Going elsewhere leaves the search, the new folder is already listed
+        fm_window.fmstate.borrow_mut().connect_path_changed(glib::clone!(
+            #[strong(rename_to = search_bar)]
+            self,
+            #[weak(rename_to = column_view)]
+            fm_window.column_view,
+            move |_| {
+                search_bar.stop();
+                search_bar.stop_following();
+                let mut state = search_bar.state.borrow_mut();
+                state.showing_results = false;
+                state.criteria = None;
+                drop(state);
+                files_panel::set_location_column_visible(&column_view, false);
+                search_bar.status.set_text("");
+                search_bar.bar.set_search_mode(false);
+            }
+        ));
+    }
+
+    /// Reads the criteria from the bar, or says what is wrong with them.
+    fn criteria(&self, fm_window: &FmWindow) -> Result<SearchCriteria, String> {
+        let fmstate = fm_window.fmstate.borrow();
+        let settings = &fmstate.settings;
+        let size = |entry: &Entry| {
+            let text = entry.text();
+            if text.trim().is_empty() {
+                return Ok(None);
+            }
+            search::parse_size(&text, settings.size_units)
+                .map(Some)
+                .ok_or_else(|| format!("\"{}\" is not a size", text))
+        };
+        let date = |entry: &Entry, end_of_day: bool| {
+            let text = entry.text();
+            if text.trim().is_empty() {
+                return Ok(None);
+            }
+            search::parse_date(&text, end_of_day)
+                .map(Some)
+                .ok_or_else(|| format!("\"{}\" is not a date, use YYYY-MM-DD", text))
+        };
+
+        let criteria = SearchCriteria {
+            name: self.entry.text().to_string(),
+            name_mode: self.selected_name_mode(),
+            content: self.content.text().to_string(),
+            min_size: size(&self.min_size)?,
+            max_size: size(&self.max_size)?,
+            modified_after: date(&self.modified_after, false)?,
+            modified_before: date(&self.modified_before, true)?,
+            content_type: self.selected_content_type(),
+            show_hidden: settings.show_hidden,
+        };
+        criteria.validate()?;
+        Ok(criteria)
+    }
+
+    fn selected_name_mode(&self) -> NameMode {
+        NameMode::ALL.get(self.name_mode.selected() as usize).copied().unwrap_or(NameMode::Contains)
+    }
+
+    fn selected_content_type(&self) -> String {
+        FILE_TYPES
+            .get(self.file_type.selected() as usize)
+            .map(|(_, content_type)| content_type.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Name offered when saving the search, from the text searched for and
+    /// the folder.
+    fn default_name(&self, fm_window: &FmWindow) -> String {
+        let folder = fm_window
+            .fmstate
+            .borrow()
+            .current_path
+            .basename()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let query = self.entry.text();
+        if query.is_empty() {
+            format!("Search in {}", folder)
+        } else {
+            format!("{} in {}", query, folder)
+        }
+    }
+
+    /// Pins the current search in the sidebar, under the name typed in the
+    /// save popover.
+    fn save(&self, fm_window: &FmWindow) {
+        let name = self.save_name.text().trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        self.save.popdown();
+
+        match self.criteria(fm_window) {
+            Ok(criteria) if criteria.is_empty() => {
+                self.set_status("Type something to search for before saving", true);
+                return;
+            }
+            Ok(_) => {}
+            Err(problem) => {
+                self.set_status(&problem, true);
+                return;
+            }
+        }
+        let Some(root) = fm_window.fmstate.borrow().current_path.path() else {
+            self.set_status("Only folders on this computer can be searched", true);
+            return;
+        };
+
+        // Sizes and dates are kept as typed, "this week" stays this week
+        let saved = SavedSearch {
+            name,
+            root: root.display().to_string(),
+            query: self.entry.text().to_string(),
+            name_mode: self.selected_name_mode().key().to_string(),
+            content: self.content.text().to_string(),
+            min_size: self.min_size.text().trim().to_string(),
+            max_size: self.max_size.text().trim().to_string(),
+            modified_after: self.modified_after.text().trim().to_string(),
+            modified_before: self.modified_before.text().trim().to_string(),
+            content_type: self.selected_content_type(),
+        };
+        let result = fm_window.fmstate.borrow_mut().add_saved_search(saved);
+        if let Err(e) = result {
+            file_operations::show_errors(
+                fm_window.window.upcast_ref(),
+                "The search could not be saved",
+                &[e.to_string()],
+            );
+        }
+        if let Some(sidebar_list) =
+            fm_window.sidebar_selection.model().and_downcast::<gio::ListStore>()
+        {
+            sidebar::refresh_sidebar(&sidebar_list, &fm_window.fmstate);
+        }
+    }
+
+    /// Fills the bar in from `saved`, without searching yet.
+    fn load(&self, saved: &SavedSearch) {
+        self.state.borrow_mut().loading = true;
+
+        let name_mode = NameMode::from_key(&saved.name_mode).unwrap_or(NameMode::Contains);
+        let name_mode = NameMode::ALL.iter().position(|mode| *mode == name_mode).unwrap_or(0);
+        let file_type = FILE_TYPES.iter().position(|(_, t)| *t == saved.content_type).unwrap_or(0);
+
+        self.entry.set_text(&saved.query);
+        self.name_mode.set_selected(name_mode as u32);
+        self.content.set_text(&saved.content);
+        self.min_size.set_text(&saved.min_size);
+        self.max_size.set_text(&saved.max_size);
+        self.modified_after.set_text(&saved.modified_after);
+        self.modified_before.set_text(&saved.modified_before);
+        self.file_type.set_selected(file_type as u32);
+
+        self.state.borrow_mut().loading = false;
+    }
+
+    /// Opens the folder of the saved search `saved` and runs it there again,
+    /// the results standing in for the folder.
+    pub fn run_saved(&self, fm_window: &FmWindow, saved: &SavedSearch) {
+        let root = saved.root_file();
+        let elsewhere = !fm_window.fmstate.borrow().current_path.equal(&root);
+        if elsewhere {
+            let mut fmstate_mut = fm_window.fmstate.borrow_mut();
+            files_panel::populate_files_list(
+                &fm_window.file_store,
+                &root,
+                &fmstate_mut.settings.show_hidden,
+            );
+            fmstate_mut.set_path(root.clone());
+            fmstate_mut.update_history(root);
+        }
+
+        self.load(saved);
+        self.open();
+        self.start(fm_window);
+    }
+
+    fn set_status(&self, text: &str, is_error: bool) {
+        self.status.set_text(text);
+        if is_error {
+            self.status.add_css_class("error");
+        } else {
+            self.status.remove_css_class("error");
+        }
+    }
+
+    /// Cancels the running search, keeping what it found so far.
+    fn stop(&self) {
+        if let Some(cancellable) = self.state.borrow_mut().running.take() {
+            cancellable.cancel();
+        }
+    }
+
+    /// Lists the current folder again in place of the results.
+    fn show_folder(&self, fm_window: &FmWindow) {
+        self.stop_following();
+        let mut state = self.state.borrow_mut();
+        state.showing_results = false;
+        state.criteria = None;
+        drop(state);
+        files_panel::set_location_column_visible(&fm_window.column_view, false);
+        fm_window.reload();
+    }
+
+    /// Searches again after the criteria were edited, unless they still
+    /// give the results shown.
+    fn restart(&self, fm_window: &FmWindow) {
+        if self.state.borrow().loading {
+            return;
+        }
+        if let Ok(criteria) = self.criteria(fm_window) {
+            let state = self.state.borrow();
+            if state.showing_results && state.criteria.as_ref() == Some(&criteria) {
+                return;
+            }
+        }
+        self.start(fm_window);
+    }
+
+    /// Starts searching with the current criteria, replacing the results of
+    /// any earlier search. The results then follow the files changing.
+    fn start(&self, fm_window: &FmWindow) {
+        self.run(fm_window, false);
+    }
+
+    /// Searches with the current criteria. With `update`, the results shown
+    /// are brought up to date rather than listed again: the files found
+    /// anew are added, the ones not found anymore removed.
+    fn run(&self, fm_window: &FmWindow, update: bool) {
+        self.stop();
+
+        let criteria = match self.criteria(fm_window) {
+            Ok(criteria) => criteria,
+            Err(problem) => {
+                self.set_status(&problem, true);
+                return;
+            }
+        };
+        if criteria.is_empty() {
+            self.set_status("", false);
+            if self.showing_results() {
+                self.show_folder(fm_window);
+            }
+            return;
+        }
+
+        let current_path = fm_window.fmstate.borrow().current_path.clone();
+        let Some(root) = current_path.path().filter(|path| path.is_dir()) else {
+            self.set_status("Only folders on this computer can be searched", true);
+            return;
+        };
+
+        let generation = {
+            let mut state = self.state.borrow_mut();
+            state.generation = state.generation.wrapping_add(1);
+            state.found = 0;
+            state.showing_results = true;
+            state.criteria = Some(criteria.clone());
+            state.generation
+        };
+        // Files already listed, and the ones found again, by their path as
+        // the items hold it
+        let mut listed = HashSet::new();
+        let found_again = Rc::new(RefCell::new(HashSet::new()));
+        if update {
+            listed = (0..fm_window.file_store.n_items())
+                .filter_map(|i| fm_window.file_store.item(i).and_downcast::<FileItem>())
+                .map(|item| item.path())
+                .collect();
+        } else {
+            fm_window.file_store.remove_all();
+            files_panel::set_location_column_visible(&fm_window.column_view, true);
+            self.set_status("Searching…", false);
+            self.follow_changes(fm_window, &root);
+        }
+
+        let name = current_path
+            .basename()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| root.display().to_string());
+        let title = if update {
+            format!("Updating the search in \"{}\"", name)
+        } else {
+            format!("Searching in \"{}\"", name)
+        };
+        let cancellable = fm_window.operations.run_streaming(
+            &title,
+            move |context, found| search::search(&root, &criteria, context, found),
+            glib::clone!(
+                #[strong(rename_to = search_bar)]
+                self,
+                #[strong]
+                found_again,
+                #[weak(rename_to = file_store)]
+                fm_window.file_store,
+                move |path: PathBuf| {
+                    if search_bar.state.borrow().generation != generation {
+                        return;
+                    }
+                    let shown = path.display().to_string();
+                    if listed.contains(&shown) {
+                        found_again.borrow_mut().insert(shown);
+                        search_bar.state.borrow_mut().found += 1;
+                    } else if let Some(item) = FileItem::from_file(&gio::File::for_path(path), true)
+                    {
+                        file_store.append(&item);
+                        search_bar.state.borrow_mut().found += 1;
+                    }
+                }
+            ),
+            glib::clone!(
+                #[strong(rename_to = search_bar)]
+                self,
+                #[weak(rename_to = file_store)]
+                fm_window.file_store,
+                move |_: usize| {
+                    let mut state = search_bar.state.borrow_mut();
+                    if state.generation != generation {
+                        return;
+                    }
+                    let stopped = state.running.take().is_none_or(|c| c.is_cancelled());
+                    let found = state.found;
+                    drop(state);
+
+                    // An update stopped halfway can't tell what is gone
+                    if update && !stopped {
+                        let found_again = found_again.borrow();
+                        let mut position = file_store.n_items();
+                        while position > 0 {
+                            position -= 1;
+                            let Some(item) = file_store.item(position).and_downcast::<FileItem>()
+                            else {
+                                continue;
+                            };
+                            if !found_again.contains(&item.path()) {
+                                file_store.remove(position);
+                            } else if let Some(fresh) = FileItem::from_file(&item.file(), true) {
+                                file_store.splice(position, 1, &[fresh]);
+                            }
+                        }
+                    }
+
+                    let text = match found {
+                        0 => "No files found".to_string(),
+                        1 => "1 file found".to_string(),
+                        count => format!("{} files found", count),
+                    };
+                    if stopped {
+                        search_bar.set_status(&format!("{} (stopped)", text), false);
+                    } else {
+                        search_bar.set_status(&text, false);
+                    }
+                }
+            ),
+        );
+        self.state.borrow_mut().running = Some(cancellable);
+    }
+
+    /// Updates the results once files under `root` change, as seen by the
+    /// search index when it covers `root`. Otherwise only `root` itself is
+    /// watched, changes in the folders below waiting for the next update.
+    fn follow_changes(&self, fm_window: &FmWindow, root: &Path) {
+        self.stop_following();
+        let searches = self.state.borrow().searches;
+        let window = fm_window.window.downgrade();
+        let on_change = move || {
+            let Some(fm_window) =
+                window.upgrade().and_then(|window| FmWindow::from_window(window.upcast_ref()))
+            else {
+                return glib::ControlFlow::Break;
+            };
+            let search_bar = &fm_window.search_bar;
+            if search_bar.state.borrow().searches != searches {
+                return glib::ControlFlow::Break;
+            }
+            search_bar.schedule_update(&fm_window);
+            glib::ControlFlow::Continue
+        };
+
+        if search_index::covers(root) {
+            let root = root.to_path_buf();
+            search_index::watch(move |path| {
+                if path.starts_with(&root) || root.starts_with(path) {
+                    on_change()
+                } else {
+                    glib::ControlFlow::Continue
+                }
+            });
+            return;
+        }
+
+        let monitor = gio::File::for_path(root)
+            .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE);
+        match monitor {
+            Ok(monitor) => {
+                monitor.connect_changed(move |monitor, _, _, event| {
+                    if matches!(
+                        event,
+                        gio::FileMonitorEvent::ChangesDoneHint
+                            | gio::FileMonitorEvent::Deleted
+                            | gio::FileMonitorEvent::Created
+                            | gio::FileMonitorEvent::MovedIn
+                            | gio::FileMonitorEvent::MovedOut
+                            | gio::FileMonitorEvent::Renamed
+                    ) && on_change().is_break()
+                    {
+                        monitor.cancel();
+                    }
+                });
+                self.state.borrow_mut().monitor = Some(monitor);
+            }
+            Err(e) => eprintln!("Failed to watch {}: {}", root.display(), e),
+        }
+    }
+
+    /// Stops updating the results shown, when they are left.
+    fn stop_following(&self) {
+        let mut state = self.state.borrow_mut();
+        state.searches = state.searches.wrapping_add(1);
+        if let Some(timer) = state.refresh_timer.take() {
+            timer.remove();
+        }
+        if let Some(monitor) = state.monitor.take() {
+            monitor.cancel();
+        }
+    }
+
+    /// Updates the results shortly, unless a search is already on its way.
+    fn schedule_update(&self, fm_window: &FmWindow) {
+        let mut state = self.state.borrow_mut();
+        if state.refresh_timer.is_some() || state.running.is_some() {
+            return;
+        }
+        let window = fm_window.window.downgrade();
+        state.refresh_timer = Some(glib::timeout_add_local_once(REFRESH_DELAY, move || {
+            let Some(fm_window) =
+                window.upgrade().and_then(|window| FmWindow::from_window(window.upcast_ref()))
+            else {
+                return;
+            };
+            let search_bar = &fm_window.search_bar;
+            let update = {
+                let mut state = search_bar.state.borrow_mut();
+                state.refresh_timer = None;
+                state.showing_results && state.running.is_none()
+            };
+            if update {
+                search_bar.run(&fm_window, true);
+            }
+        }));
+    }
+
+    /// Refreshes the results after files changed: the ones gone are dropped,
+    /// the others read again.
+    pub fn refresh_results(&self, file_store: &gio::ListStore) {
+        let mut position = 0;
+        while let Some(item) = file_store.item(position).and_downcast::<FileItem>() {
+            match FileItem::from_file(&item.file(), true) {
+                Some(fresh) => {
+                    file_store.splice(position, 1, &[fresh]);
+                    position += 1;
+                }
+                None => file_store.remove(position),
+            }
+        }
+    }
+}
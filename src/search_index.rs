@@ -0,0 +1,815 @@
+//! Optional index of the file names under a few folders, so the search bar
+//! can answer name queries without walking the disk.
+//!
+//! The folders are crawled on a worker thread and the result is saved under
+//! the XDG data folder, so the index is usable right away at the next start.
+//! Folder monitors keep it current between the periodic rescans; past
+//! `MAX_MONITORS` folders, only the rescans do. Search results shown follow
+//! the changes seen through `watch`.
+
+use crate::{search, utils::FMSettings};
+use gtk4::{gio, glib, prelude::*};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    ops::Bound,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    time::Duration,
+};
+
+const INDEX_FILE: &str = "search-index";
+const FORMAT_HEADER: &str = "axfm-search-index 1";
+
+const RESCAN_INTERVAL_SECS: u32 = 30 * 60;
+/// Changes seen by the monitors are saved together, this long after the
+/// first one.
+const SAVE_DELAY_SECS: u32 = 30;
+/// Each monitored folder takes an inotify watch, which are limited.
+const MAX_MONITORS: usize = 4096;
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// What the index covers, from the settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexConfig {
+    pub roots: Vec<PathBuf>,
+    pub exclusions: Vec<String>,
+}
+
+impl IndexConfig {
+    /// `None` when indexing is turned off.
+    pub fn from_settings(settings: &FMSettings) -> Option<IndexConfig> {
+        settings.index_files.then(|| IndexConfig {
+            roots: settings.index_roots.iter().map(PathBuf::from).collect(),
+            exclusions: settings.index_exclusions.clone(),
+        })
+    }
+
+    /// Whether everything under `dir` is indexed.
+    fn covers(&self, dir: &Path) -> bool {
+        Exclusions::new(self).covers(dir)
+    }
+}
+
+/// The exclusions of an `IndexConfig`, compiled. Exclusions starting with
+/// `/` leave out that path, the others any file whose name they match.
+struct Exclusions<'a> {
+    config: &'a IndexConfig,
+    paths: Vec<&'a Path>,
+    names: Vec<glib::Regex>,
+}
+
+impl<'a> Exclusions<'a> {
+    fn new(config: &'a IndexConfig) -> Self {
+        let (paths, names): (Vec<_>, Vec<_>) =
+            config.exclusions.iter().partition(|pattern| pattern.starts_with('/'));
+        Self {
+            config,
+            paths: paths.into_iter().map(Path::new).collect(),
+            names: names
+                .into_iter()
+                .filter_map(|pattern| {
+                    glib::Regex::new(
+                        &search::glob_to_regex(pattern),
+                        glib::RegexCompileFlags::empty(),
+                        glib::RegexMatchFlags::empty(),
+                    )
+                    .ok()
+                    .flatten()
+                })
+                .collect(),
+        }
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        if self.paths.iter().any(|excluded| path.starts_with(excluded)) {
+            return true;
+        }
+        let Some(name) = path.file_name() else {
+            return false;
+        };
+        let name = glib::GString::from(name.to_string_lossy().as_ref());
+        self.names
+            .iter()
+            .any(|regex| regex.match_(name.as_gstr(), glib::RegexMatchFlags::empty()).is_some())
+    }
+
+    /// Whether everything under `dir` is indexed.
+    fn covers(&self, dir: &Path) -> bool {
+        self.config.roots.iter().any(|root| {
+            dir.starts_with(root)
+                && !dir
+                    .ancestors()
+                    .take_while(|ancestor| *ancestor != root.as_path())
+                    .any(|ancestor| self.is_excluded(ancestor))
+        })
+    }
+}
+
+#[derive(Debug)]
+struct IndexedFile {
+    is_dir: bool,
+    /// Lowercase name, so queries don't convert every name again.
+    name_lower: Box<str>,
+}
+
+impl IndexedFile {
+    fn new(path: &Path, is_dir: bool) -> Self {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        Self { is_dir, name_lower: name.to_lowercase().into() }
+    }
+}
+
+type Files = BTreeMap<PathBuf, IndexedFile>;
+
+struct Index {
+    config: IndexConfig,
+    files: Files,
+    /// Set once the saved index is loaded or the first crawl is done.
+    ready: bool,
+    crawling: bool,
+}
+
+/// Read by search workers, updated on the main thread.
+static INDEX: RwLock<Option<Index>> = RwLock::new(None);
+
+/// The parts of the indexer living on the main thread.
+struct Indexer {
+    config: IndexConfig,
+    /// Tells the crawls started for this configuration to give up.
+    stop: Arc<AtomicBool>,
+    monitors: HashMap<PathBuf, gio::FileMonitor>,
+    rescan_timer: Option<glib::SourceId>,
+    save_timer: Option<glib::SourceId>,
+}
+
+type Watcher = Box<dyn Fn(&Path) -> glib::ControlFlow>;
+
+thread_local! {
+    static INDEXER: RefCell<Option<Indexer>> = const { RefCell::new(None) };
+    static WATCHERS: RefCell<Vec<Watcher>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Starts, stops or restarts the indexer to follow the settings.
+pub fn configure(settings: &FMSettings) {
+    let config = IndexConfig::from_settings(settings);
+    let unchanged =
+        INDEXER.with_borrow(|indexer| indexer.as_ref().map(|i| &i.config) == config.as_ref());
+    if unchanged {
+        return;
+    }
+
+    shut_down();
+    let Some(config) = config else {
+        return;
+    };
+
+    if let Ok(mut index) = INDEX.write() {
+        *index = Some(Index {
+            config: config.clone(),
+            files: Files::new(),
+            ready: false,
+            crawling: false,
+        });
+    }
+    let rescan_timer = glib::timeout_add_seconds_local(RESCAN_INTERVAL_SECS, || {
+        rescan(false);
+        glib::ControlFlow::Continue
+    });
+    INDEXER.with_borrow_mut(|indexer| {
+        *indexer = Some(Indexer {
+            config,
+            stop: Arc::new(AtomicBool::new(false)),
+            monitors: HashMap::new(),
+            rescan_timer: Some(rescan_timer),
+            save_timer: None,
+        })
+    });
+    rescan(true);
+}
+
+/// Stops indexing and forgets the index kept in memory.
+fn shut_down() {
+    let Some(indexer) = INDEXER.with_borrow_mut(Option::take) else {
+        return;
+    };
+    indexer.stop.store(true, Ordering::Relaxed);
+    for timer in [indexer.rescan_timer, indexer.save_timer].into_iter().flatten() {
+        timer.remove();
+    }
+    for monitor in indexer.monitors.values() {
+        monitor.cancel();
+    }
+    if let Ok(mut index) = INDEX.write() {
+        *index = None;
+    }
+}
+
+/// Saves the changes not saved yet, for when the application quits.
+pub fn save_pending() {
+    let pending = INDEXER
+        .with_borrow_mut(|indexer| indexer.as_mut().and_then(|indexer| indexer.save_timer.take()));
+    if let Some(timer) = pending {
+        timer.remove();
+        if let Err(e) = save() {
+            eprintln!("Failed to save the search index: {}", e);
+        }
+    }
+}
+
+/// A short description of what the indexer is doing.
+pub fn status() -> String {
+    let Ok(index) = INDEX.read() else {
+        return String::new();
+    };
+    match index.as_ref() {
+        None => "Off".to_string(),
+        Some(index) if !index.ready => "Indexing…".to_string(),
+        Some(index) => {
+            let count = index.files.len();
+            let text = if count == 1 {
+                "1 file indexed".to_string()
+            } else {
+                format!("{} files indexed", count)
+            };
+            if index.crawling { format!("{}, rescanning…", text) } else { text }
+        }
+    }
+}
+
+/// The files under `dir` whose name `matches`, given the name and its
+/// lowercase form. `None` when the index doesn't cover `dir` (yet), in which
+/// case the folder has to be walked.
+pub fn find(
+    dir: &Path,
+    show_hidden: bool,
+    matches: impl Fn(&str, &str) -> bool,
+) -> Option<Vec<PathBuf>> {
+    let index = INDEX.read().ok()?;
+    let index = index.as_ref().filter(|index| index.ready && index.config.covers(dir))?;
+
+    let found = index
+        .files
+        .range::<Path, _>((Bound::Excluded(dir), Bound::Unbounded))
+        .take_while(|(path, _)| path.starts_with(dir))
+        .filter(|(path, file)| {
+            let hidden = || {
+                path.strip_prefix(dir)
+                    .is_ok_and(|rest| rest.iter().any(|part| part.as_bytes().starts_with(b".")))
+            };
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            (show_hidden || !hidden()) && matches(&name, &file.name_lower)
+        })
+        .map(|(path, _)| path.clone())
+        .collect();
+    Some(found)
+}
+
+/// Whether the index is ready to answer for everything under `dir`.
+pub fn covers(dir: &Path) -> bool {
+    INDEX.read().is_ok_and(|index| {
+        index.as_ref().is_some_and(|index| index.ready && index.config.covers(dir))
+    })
+}
+
+/// Calls `on_change` with each file the index sees appear or go, and with
+/// the roots once they were crawled again, until it returns
+/// `ControlFlow::Break`.
+pub fn watch(on_change: impl Fn(&Path) -> glib::ControlFlow + 'static) {
+    WATCHERS.with_borrow_mut(|watchers| watchers.push(Box::new(on_change)));
+}
+
+fn notify(path: &Path) {
+    // Taken out, so the watchers can add others
+    let mut watchers = WATCHERS.take();
+    watchers.retain(|on_change| on_change(path).is_continue());
+    WATCHERS.with_borrow_mut(|added| watchers.append(added));
+    WATCHERS.set(watchers);
+}
+
+/// Runs `work` on a worker thread and hands what it sends to `on_message`
+/// on the main thread.
+fn in_background<T: Send + 'static>(
+    work: impl FnOnce(mpsc::Sender<T>) + Send + 'static,
+    mut on_message: impl FnMut(T) + 'static,
+) {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || work(sender));
+
+    glib::timeout_add_local(POLL_INTERVAL, move || {
+        loop {
+            match receiver.try_recv() {
+                Ok(message) => on_message(message),
+                Err(mpsc::TryRecvError::Empty) => return glib::ControlFlow::Continue,
+                Err(mpsc::TryRecvError::Disconnected) => return glib::ControlFlow::Break,
+            }
+        }
+    });
+}
+
+/// The stop flag of the running indexer, to tell whether work started for
+/// it is still wanted.
+fn current_stop() -> Option<Arc<AtomicBool>> {
+    INDEXER.with_borrow(|indexer| indexer.as_ref().map(|indexer| indexer.stop.clone()))
+}
+
+enum Crawled {
+    Saved(Files),
+    Fresh(Files),
+}
+
+/// Crawls all the roots again, first loading the saved index with
+/// `load_saved`. Does nothing while a crawl is running.
+fn rescan(load_saved: bool) {
+    let Some(stop) = current_stop() else {
+        return;
+    };
+    let config = {
+        let Ok(mut index) = INDEX.write() else {
+            return;
+        };
+        let Some(index) = index.as_mut().filter(|index| !index.crawling) else {
+            return;
+        };
+        index.crawling = true;
+        index.config.clone()
+    };
+
+    let roots = config.roots.clone();
+    let worker_stop = stop.clone();
+    in_background(
+        move |sender| {
+            if load_saved {
+                match load(&config) {
+                    Ok(files) => {
+                        let _ = sender.send(Crawled::Saved(files));
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => eprintln!("Failed to load the search index: {}", e),
+                }
+            }
+
+            let mut files = Files::new();
+            for root in &config.roots {
+                crawl(root, &config, &worker_stop, &mut files);
+            }
+            if !worker_stop.load(Ordering::Relaxed) {
+                let _ = sender.send(Crawled::Fresh(files));
+            }
+        },
+        move |crawled| {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let fresh = matches!(crawled, Crawled::Fresh(_));
+            if let Ok(mut index) = INDEX.write()
+                && let Some(index) = index.as_mut()
+            {
+                let (Crawled::Saved(files) | Crawled::Fresh(files)) = crawled;
+                index.files = files;
+                index.ready = true;
+                index.crawling &= !fresh;
+            }
+            update_monitors();
+            for root in &roots {
+                notify(root);
+            }
+            if fresh {
+                save_in_background();
+            }
+        },
+    );
+}
+
+/// Adds `dir` and everything below it to `files`, leaving out exclusions
+/// and not following links.
+fn crawl(dir: &Path, config: &IndexConfig, stop: &AtomicBool, files: &mut Files) {
+    let exclusions = Exclusions::new(config);
+    let Ok(meta) = fs::symlink_metadata(dir) else {
+        return;
+    };
+    files.insert(dir.to_path_buf(), IndexedFile::new(dir, meta.is_dir()));
+
+    let mut folders = vec![dir.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let Ok(entries) = fs::read_dir(&folder) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if exclusions.is_excluded(&path) {
+                continue;
+            }
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if is_dir {
+                folders.push(path.clone());
+            }
+            let file = IndexedFile::new(&path, is_dir);
+            files.insert(path, file);
+        }
+    }
+}
+
+/// Watches the indexed folders, the shallowest first, as far as
+/// `MAX_MONITORS` allows.
+fn update_monitors() {
+    let mut dirs: Vec<PathBuf> = match INDEX.read() {
+        Ok(index) => match index.as_ref() {
+            Some(index) => {
+                index.files.iter().filter(|(_, f)| f.is_dir).map(|(p, _)| p.clone()).collect()
+            }
+            None => return,
+        },
+        Err(_) => return,
+    };
+    dirs.sort_by_key(|dir| dir.components().count());
+    dirs.truncate(MAX_MONITORS);
+    let dirs: HashSet<PathBuf> = dirs.into_iter().collect();
+
+    INDEXER.with_borrow_mut(|indexer| {
+        let Some(indexer) = indexer.as_mut() else {
+            return;
+        };
+        indexer.monitors.retain(|dir, monitor| {
+            let keep = dirs.contains(dir);
+            if !keep {
+                monitor.cancel();
+            }
+            keep
+        });
+        for dir in dirs {
+            if !indexer.monitors.contains_key(&dir)
+                && let Some(monitor) = monitor(&dir)
+            {
+                indexer.monitors.insert(dir, monitor);
+            }
+        }
+    });
+}
+
+fn monitor(dir: &Path) -> Option<gio::FileMonitor> {
+    let monitor = gio::File::for_path(dir)
+        .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+        .ok()?;
+    monitor.connect_changed(|_, file, other, event| match event {
+        gio::FileMonitorEvent::Created | gio::FileMonitorEvent::MovedIn => {
+            if let Some(path) = file.path() {
+                added(&path);
+            }
+        }
+        gio::FileMonitorEvent::Deleted | gio::FileMonitorEvent::MovedOut => {
+            if let Some(path) = file.path() {
+                removed(&path);
+            }
+        }
+        gio::FileMonitorEvent::Renamed => {
+            if let Some(path) = file.path() {
+                removed(&path);
+            }
+            if let Some(path) = other.and_then(|other| other.path()) {
+                added(&path);
+            }
+        }
+        _ => {}
+    });
+    Some(monitor)
+}
+
+fn added(path: &Path) {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return;
+    };
+    {
+        let Ok(mut index) = INDEX.write() else {
+            return;
+        };
+        let Some(index) = index.as_mut() else {
+            return;
+        };
+        let parent = path.parent().unwrap_or(path);
+        let exclusions = Exclusions::new(&index.config);
+        if exclusions.is_excluded(path) || !exclusions.covers(parent) {
+            return;
+        }
+        index.files.insert(path.to_path_buf(), IndexedFile::new(path, meta.is_dir()));
+    }
+
+    // Folders moved in come with their contents
+    if meta.is_dir() {
+        crawl_folder(path.to_path_buf());
+    }
+    notify(path);
+    schedule_save();
+}
+
+fn removed(path: &Path) {
+    if let Ok(mut index) = INDEX.write()
+        && let Some(index) = index.as_mut()
+    {
+        remove_tree(&mut index.files, path);
+    }
+    INDEXER.with_borrow_mut(|indexer| {
+        if let Some(indexer) = indexer.as_mut() {
+            indexer.monitors.retain(|dir, monitor| {
+                let keep = !dir.starts_with(path);
+                if !keep {
+                    monitor.cancel();
+                }
+                keep
+            });
+        }
+    });
+    notify(path);
+    schedule_save();
+}
+
+fn remove_tree(files: &mut Files, path: &Path) {
+    let below: Vec<PathBuf> = files
+        .range::<Path, _>((Bound::Included(path), Bound::Unbounded))
+        .take_while(|(file, _)| file.starts_with(path))
+        .map(|(file, _)| file.clone())
+        .collect();
+    for file in below {
+        files.remove(&file);
+    }
+}
+
+/// Indexes a folder that just appeared, and watches it.
+fn crawl_folder(dir: PathBuf) {
+    let Some(stop) = current_stop() else {
+        return;
+    };
+    let Some(config) = INDEX.read().ok().and_then(|index| Some(index.as_ref()?.config.clone()))
+    else {
+        return;
+    };
+
+    let worker_stop = stop.clone();
+    in_background(
+        move |sender| {
+            let mut files = Files::new();
+            crawl(&dir, &config, &worker_stop, &mut files);
+            let _ = sender.send((dir, files));
+        },
+        move |(dir, files): (PathBuf, Files)| {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let new_dirs: Vec<PathBuf> =
+                files.iter().filter(|(_, f)| f.is_dir).map(|(p, _)| p.clone()).collect();
+            if let Ok(mut index) = INDEX.write()
+                && let Some(index) = index.as_mut()
+            {
+                remove_tree(&mut index.files, &dir);
+                index.files.extend(files);
+            }
+
+            INDEXER.with_borrow_mut(|indexer| {
+                let Some(indexer) = indexer.as_mut() else {
+                    return;
+                };
+                for dir in new_dirs {
+                    if indexer.monitors.len() >= MAX_MONITORS {
+                        break;
+                    }
+                    if !indexer.monitors.contains_key(&dir)
+                        && let Some(monitor) = monitor(&dir)
+                    {
+                        indexer.monitors.insert(dir, monitor);
+                    }
+                }
+            });
+            notify(&dir);
+            schedule_save();
+        },
+    );
+}
+
+fn schedule_save() {
+    INDEXER.with_borrow_mut(|indexer| {
+        let Some(indexer) = indexer.as_mut().filter(|indexer| indexer.save_timer.is_none()) else {
+            return;
+        };
+        indexer.save_timer = Some(glib::timeout_add_seconds_local_once(SAVE_DELAY_SECS, || {
+            INDEXER.with_borrow_mut(|indexer| {
+                if let Some(indexer) = indexer.as_mut() {
+                    indexer.save_timer = None;
+                }
+            });
+            save_in_background();
+        }));
+    });
+}
+
+fn index_path() -> PathBuf {
+    glib::user_data_dir().join("axfm").join(INDEX_FILE)
+}
+
+/// The index as written to disk: a header naming the configuration, then
+/// one record per file, a `d` or `f` followed by the path and a NUL byte.
+fn serialize(index: &Index) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(FORMAT_HEADER.as_bytes());
+    data.push(b'\n');
+    for root in &index.config.roots {
+        data.extend_from_slice(b"root\t");
+        data.extend_from_slice(root.as_os_str().as_bytes());
+        data.push(b'\n');
+    }
+    for pattern in &index.config.exclusions {
+        data.extend_from_slice(b"exclude\t");
+        data.extend_from_slice(pattern.as_bytes());
+        data.push(b'\n');
+    }
+    data.push(b'\n');
+
+    for (path, file) in &index.files {
+        data.push(if file.is_dir { b'd' } else { b'f' });
+        data.extend_from_slice(path.as_os_str().as_bytes());
+        data.push(0);
+    }
+    data
+}
+
+/// Writes the index, replacing the saved one only once it is complete.
+fn save() -> io::Result<()> {
+    let data = {
+        let index = INDEX.read().map_err(|_| io::Error::other("the index is poisoned"))?;
+        match index.as_ref().filter(|index| index.ready) {
+            Some(index) => serialize(index),
+            None => return Ok(()),
+        }
+    };
+    write_index(&data)
+}
+
+fn write_index(data: &[u8]) -> io::Result<()> {
+    let path = index_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let partial = path.with_extension("partial");
+    let mut file = fs::File::create(&partial)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&partial, &path)
+}
+
+fn save_in_background() {
+    // Copied out first, the lock isn't held while writing
+    let data = match INDEX.read() {
+        Ok(index) => match index.as_ref().filter(|index| index.ready) {
+            Some(index) => serialize(index),
+            None => return,
+        },
+        Err(_) => return,
+    };
+    std::thread::spawn(move || {
+        if let Err(e) = write_index(&data) {
+            eprintln!("Failed to save the search index: {}", e);
+        }
+    });
+}
+
+/// Reads the saved index, failing when it was made for other folders.
+fn load(config: &IndexConfig) -> io::Result<Files> {
+    read_index(BufReader::new(fs::File::open(index_path())?), config)
+}
+
+fn read_index(mut reader: impl BufRead, config: &IndexConfig) -> io::Result<Files> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim_end() != FORMAT_HEADER {
+        return Err(invalid("unknown index format"));
+    }
+
+    let mut saved = IndexConfig { roots: Vec::new(), exclusions: Vec::new() };
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("the index is truncated"));
+        }
+        match line.trim_end_matches('\n').split_once('\t') {
+            Some(("root", root)) => saved.roots.push(PathBuf::from(root)),
+            Some(("exclude", pattern)) => saved.exclusions.push(pattern.to_string()),
+            None if line == "\n" => break,
+            _ => return Err(invalid("unexpected line in the index header")),
+        }
+    }
+    if saved != *config {
+        return Err(invalid("the index was made for other folders"));
+    }
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut files = Files::new();
+    for record in data.split(|&byte| byte == 0).filter(|record| !record.is_empty()) {
+        let (kind, path) = record.split_at(1);
+        let path = PathBuf::from(OsStr::from_bytes(path));
+        let file = IndexedFile::new(&path, kind == b"d");
+        files.insert(path, file);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(roots: &[&str], exclusions: &[&str]) -> IndexConfig {
+        IndexConfig {
+            roots: roots.iter().map(PathBuf::from).collect(),
+            exclusions: exclusions.iter().map(|pattern| pattern.to_string()).collect(),
+        }
+    }
+
+    fn config_with(exclusion: &str) -> IndexConfig {
+        config(&["/"], &[exclusion])
+    }
+
+    #[test]
+    fn exclusions_match_names_or_paths() {
+        let config = config(&["/home/me"], &["*.tmp", "?ache", "node_modules", "/home/me/big"]);
+        let exclusions = Exclusions::new(&config);
+        let excluded = |path: &str| exclusions.is_excluded(Path::new(path));
+
+        assert!(excluded("/home/me/notes.tmp"));
+        assert!(excluded("/home/me/.tmp"));
+        assert!(!excluded("/home/me/notes.tmp.txt"));
+        assert!(excluded("/home/me/cache"));
+        assert!(!excluded("/home/me/.cache"));
+        assert!(excluded("/home/me/project/node_modules"));
+        assert!(!excluded("/home/me/node_modules_old"));
+        assert!(excluded("/home/me/big"));
+        assert!(excluded("/home/me/big/file"));
+        assert!(!excluded("/home/me/bigger"));
+        // The pattern is matched as it is, not as a regular expression
+        assert!(!Exclusions::new(&config_with("a.c")).is_excluded(Path::new("/abc")));
+        assert!(Exclusions::new(&config_with("[x]")).is_excluded(Path::new("/[x]")));
+        assert!(Exclusions::new(&config_with("é*")).is_excluded(Path::new("/été")));
+    }
+
+    #[test]
+    fn covers_only_the_roots() {
+        let config = config(&["/home/me/docs", "/srv"], &["build", "/srv/private"]);
+        assert!(config.covers(Path::new("/home/me/docs")));
+        assert!(config.covers(Path::new("/home/me/docs/letters")));
+        assert!(config.covers(Path::new("/srv/www")));
+        assert!(!config.covers(Path::new("/home/me")));
+        assert!(!config.covers(Path::new("/home/me/docs2")));
+        assert!(!config.covers(Path::new("/home/me/docs/build")));
+        assert!(!config.covers(Path::new("/home/me/docs/build/out")));
+        assert!(!config.covers(Path::new("/srv/private/keys")));
+    }
+
+    #[test]
+    fn saved_index_round_trip() {
+        let config = config(&["/home/me", "/srv/data"], &["*.tmp", "/home/me/big"]);
+        let mut files = Files::new();
+        for (path, is_dir) in
+            [("/home/me", true), ("/home/me/Notes.TXT", false), ("/srv/data/ünï", true)]
+        {
+            files.insert(PathBuf::from(path), IndexedFile::new(Path::new(path), is_dir));
+        }
+        // Names don't have to be UTF-8
+        let odd = PathBuf::from(OsStr::from_bytes(b"/srv/data/\xff\xfe"));
+        files.insert(odd.clone(), IndexedFile::new(&odd, false));
+
+        let index = Index { config: config.clone(), files, ready: true, crawling: false };
+        let data = serialize(&index);
+
+        let loaded = read_index(&data[..], &config).unwrap();
+        let summary = |files: &Files| -> Vec<(PathBuf, bool, String)> {
+            files
+                .iter()
+                .map(|(path, file)| (path.clone(), file.is_dir, file.name_lower.to_string()))
+                .collect()
+        };
+        assert_eq!(summary(&loaded), summary(&index.files));
+        assert_eq!(loaded[Path::new("/home/me/Notes.TXT")].name_lower.as_ref(), "notes.txt");
+
+        let other = self::config(&["/home/me"], &["*.tmp", "/home/me/big"]);
+        let error = read_index(&data[..], &other).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let header_end = data.windows(2).position(|pair| pair == b"\n\n").unwrap();
+        let error = read_index(&data[..header_end], &config).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let error = read_index(&b"something else\n"[..], &config).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
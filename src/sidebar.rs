@@ -1,15 +1,19 @@
-use crate::{glib::UserDirectory, state::FmState};
+use crate::{
+    glib::UserDirectory,
+    models::sidebar_item::{SidebarItem, SidebarItemKind},
+    state::FmState,
+};
 use gtk4::{
     Box as GtkBox, ListView, Orientation, ScrolledWindow, SignalListItemFactory, SingleSelection,
-    StringList, gdk, gio, glib, prelude::*,
+    gdk, gio, glib, prelude::*,
 };
 use std::{cell::RefCell, rc::Rc};
 
 pub fn build_sidebar(
     fmstate: Rc<RefCell<FmState>>,
     file_store: &gtk4::gio::ListStore,
-) -> (GtkBox, SingleSelection, StringList) {
-    let sidebar_list = StringList::new(&[]);
+) -> (GtkBox, SingleSelection, gio::ListStore) {
+    let sidebar_list = gio::ListStore::new::<SidebarItem>();
     let sidebar_selection = SingleSelection::new(Some(sidebar_list.clone()));
     sidebar_selection.set_can_unselect(true);
     sidebar_selection.set_autoselect(false);
@@ -21,6 +25,8 @@ pub fn build_sidebar(
     factory.connect_setup(glib::clone!(
         #[weak]
         file_store,
+        #[weak]
+        sidebar_list,
         #[strong]
         fmstate,
         move |_, item| {
@@ -43,46 +49,48 @@ pub fn build_sidebar(
             // add drop target
             let drop_target = gtk4::DropTarget::new(String::static_type(), gdk::DragAction::COPY);
             drop_target.connect_drop(glib::clone!(
-                #[weak_allow_none]
-                label,
+                #[weak]
+                item,
                 #[weak_allow_none]
                 file_store,
                 #[strong]
                 fmstate,
+                #[upgrade_or]
+                false,
                 move |_drop_target, value, _, _| {
-                    if let Some(label) = label.as_ref() {
-                        let label_text = label.text();
-
-                        let sidebar_items = get_sidebar_items();
-                        if let Some((_, target_path)) =
-                            sidebar_items.iter().find(|(n, _)| **n == label_text)
-                        {
-                            if let Ok(uri) = value.get::<glib::GString>() {
-                                let src_file = gio::File::for_uri(&uri);
-                                let src_filename =
-                                    src_file.basename().unwrap_or_else(|| "unknown".into());
-                                let dest_file =
-                                    target_path.child(src_filename.to_str().unwrap_or("unknown"));
-
-                                match src_file.move_(
-                                    &dest_file,
-                                    gio::FileCopyFlags::OVERWRITE,
-                                    None::<&gio::Cancellable>,
-                                    None::<&mut dyn FnMut(i64, i64)>,
-                                ) {
-                                    Ok(_) => {
-                                        if let Some(file_store) = &file_store {
-                                            let fmstate_ref = fmstate.borrow();
-                                            crate::files_panel::populate_files_list(
-                                                file_store,
-                                                &fmstate_ref.current_path,
-                                                &fmstate_ref.settings.show_hidden,
-                                            );
-                                        }
-                                    }
-                                    Err(e) => eprintln!("Error while moving file: {}", e),
+                    // Saved searches are no place to drop files
+                    let Some(target_path) = item
+                        .item()
+                        .and_downcast::<SidebarItem>()
+                        .filter(|i| i.kind() != SidebarItemKind::SavedSearch)
+                        .and_then(|i| i.file())
+                    else {
+                        return false;
+                    };
+
+                    if let Ok(uri) = value.get::<glib::GString>() {
+                        let src_file = gio::File::for_uri(&uri);
+                        let src_filename = src_file.basename().unwrap_or_else(|| "unknown".into());
+                        let dest_file =
+                            target_path.child(src_filename.to_str().unwrap_or("unknown"));
+
+                        match src_file.move_(
+                            &dest_file,
+                            gio::FileCopyFlags::OVERWRITE,
+                            None::<&gio::Cancellable>,
+                            None::<&mut dyn FnMut(i64, i64)>,
+                        ) {
+                            Ok(_) => {
+                                if let Some(file_store) = &file_store {
+                                    let fmstate_ref = fmstate.borrow();
+                                    crate::files_panel::populate_files_list(
+                                        file_store,
+                                        &fmstate_ref.current_path,
+                                        &fmstate_ref.settings.show_hidden,
+                                    );
                                 }
                             }
+                            Err(e) => eprintln!("Error while moving file: {}", e),
                         }
                     }
                     true
@@ -90,71 +98,116 @@ pub fn build_sidebar(
             ));
             hbox.add_controller(drop_target);
 
+            // Bookmarks can be dragged onto other bookmarks or groups to reorder them
+            let drag_source = gtk4::DragSource::new();
+            drag_source.set_actions(gdk::DragAction::MOVE);
+            drag_source.connect_prepare(glib::clone!(
+                #[weak]
+                item,
+                #[upgrade_or]
+                None,
+                move |_, _, _| {
+                    let sidebar_item = item.item().and_downcast::<SidebarItem>()?;
+                    (sidebar_item.kind() == SidebarItemKind::Bookmark)
+                        .then(|| gdk::ContentProvider::for_value(&sidebar_item.to_value()))
+                }
+            ));
+            hbox.add_controller(drag_source);
+
+            let reorder_target =
+                gtk4::DropTarget::new(SidebarItem::static_type(), gdk::DragAction::MOVE);
+            reorder_target.connect_drop(glib::clone!(
+                #[weak]
+                item,
+                #[weak]
+                sidebar_list,
+                #[strong]
+                fmstate,
+                #[upgrade_or]
+                false,
+                move |_, value, _, _| {
+                    let (Ok(dragged), Some(target)) =
+                        (value.get::<SidebarItem>(), item.item().and_downcast::<SidebarItem>())
+                    else {
+                        return false;
+                    };
+                    reorder_bookmark(&fmstate, &sidebar_list, &dragged, &target)
+                }
+            ));
+            hbox.add_controller(reorder_target);
+
+            // Saved searches are removed from their context menu
+            let right_click = gtk4::GestureClick::new();
+            right_click.set_button(gdk::BUTTON_SECONDARY);
+            right_click.connect_pressed(glib::clone!(
+                #[weak]
+                item,
+                #[weak]
+                hbox,
+                #[weak]
+                sidebar_list,
+                #[strong]
+                fmstate,
+                move |_, _, _, _| {
+                    if let Some(sidebar_item) = item
+                        .item()
+                        .and_downcast::<SidebarItem>()
+                        .filter(|i| i.kind() == SidebarItemKind::SavedSearch)
+                    {
+                        show_saved_search_menu(&hbox, &sidebar_item, &fmstate, &sidebar_list);
+                    }
+                }
+            ));
+            hbox.add_controller(right_click);
+
             item.set_child(Some(&hbox));
         }
     ));
 
-    factory.connect_bind(glib::clone!(
-        #[strong]
-        fmstate,
-        move |_, item| {
-            let hbox = item.child().and_downcast::<gtk4::Box>().unwrap();
-            let icon = hbox.first_child().and_downcast::<gtk4::Image>().unwrap();
-            let label = hbox.last_child().and_downcast::<gtk4::Label>().unwrap();
-
-            let obj = item.item().unwrap().downcast::<gtk4::StringObject>().unwrap();
-            let label_text = obj.string();
-            label.set_text(&label_text);
-
-            // Check if it's a heading
-            if label_text == "Places" || label_text == "Bookmarks" {
-                label.remove_css_class("sidebar-item");
-                label.add_css_class("sidebar-heading");
-                icon.set_visible(false);
-                label.set_tooltip_text(None);
-                return;
-            }
+    factory.connect_bind(move |_, item| {
+        let hbox = item.child().and_downcast::<gtk4::Box>().unwrap();
+        let icon = hbox.first_child().and_downcast::<gtk4::Image>().unwrap();
+        let label = hbox.last_child().and_downcast::<gtk4::Label>().unwrap();
+
+        let sidebar_item = item.item().and_downcast::<SidebarItem>().unwrap();
+        label.set_text(&sidebar_item.label());
 
-            // Regular item
+        let indent =
+            sidebar_item.kind() == SidebarItemKind::Bookmark && sidebar_item.group().is_some();
+        hbox.set_margin_start(if indent { 24 } else { 6 });
+
+        if sidebar_item.kind() == SidebarItemKind::Heading {
+            item.set_selectable(false);
+            item.set_activatable(false);
+            label.remove_css_class("sidebar-item");
+            label.add_css_class("sidebar-heading");
+            icon.set_visible(false);
+            label.set_tooltip_text(None);
+            return;
+        }
+
+        if sidebar_item.kind() == SidebarItemKind::Group {
+            let expander =
+                if sidebar_item.expanded() { "pan-down-symbolic" } else { "pan-end-symbolic" };
+            item.set_selectable(true);
+            item.set_activatable(true);
             label.remove_css_class("sidebar-heading");
             label.add_css_class("sidebar-item");
             icon.set_visible(true);
-
-            // Get sidebar items
-            let sidebar_items = get_sidebar_items();
-
-            if let Some((name, file)) = sidebar_items.iter().find(|(n, _)| *n == label_text) {
-                let tooltip = file
-                    .path()
-                    .map(|p| p.display().to_string())
-                    .unwrap_or_else(|| file.uri().to_string());
-                label.set_tooltip_text(Some(&tooltip));
-
-                let icon_name = match *name {
-                    "Home" => "user-home",
-                    "Documents" => "folder-documents",
-                    "Downloads" => "folder-download",
-                    "Music" => "folder-music",
-                    "Pictures" => "folder-pictures",
-                    "Videos" => "folder-videos",
-                    "Trash" => "user-trash",
-                    _ => "folder",
-                };
-                icon.set_icon_name(Some(icon_name));
-            } else {
-                // It's a bookmark
-                icon.set_icon_name(Some("starred"));
-
-                // Find the bookmark to get its path for tooltip
-                let bookmarks = fmstate.borrow().bookmarks.clone();
-                if let Some(bookmark) = bookmarks.iter().find(|b| b.name == label_text) {
-                    label.set_tooltip_text(Some(&bookmark.path));
-                } else {
-                    label.set_tooltip_text(None);
-                }
-            }
+            icon.set_icon_name(Some(expander));
+            label.set_tooltip_text(None);
+            return;
         }
-    ));
+
+        // Regular item
+        item.set_selectable(true);
+        item.set_activatable(true);
+        label.remove_css_class("sidebar-heading");
+        label.add_css_class("sidebar-item");
+        icon.set_visible(true);
+        icon.set_icon_name(sidebar_item.icon_name().as_deref());
+        label.set_tooltip_text(sidebar_item.location().as_deref());
+    });
 
     let list_view = ListView::new(Some(sidebar_selection.clone()), Some(factory));
     let scroll =
@@ -181,39 +234,126 @@ pub fn build_sidebar(
     (sidebar_box, sidebar_selection, sidebar_list)
 }
 
-pub fn get_sidebar_items() -> Vec<(&'static str, gio::File)> {
-    let home = gio::File::for_path(glib::home_dir());
-    let dirs = |d: UserDirectory| {
-        let path = glib::user_special_dir(d).unwrap();
-        gio::File::for_path(path)
+pub fn get_sidebar_items() -> Vec<SidebarItem> {
+    let place = |label: &str, file: gio::File, icon_name: &str| {
+        SidebarItem::new(SidebarItemKind::Place, label, &file, icon_name)
+    };
+    let special_dir = |label: &str, d: UserDirectory, icon_name: &str| {
+        glib::user_special_dir(d).map(|path| place(label, gio::File::for_path(path), icon_name))
+    };
+
+    let mut items = vec![place("Home", gio::File::for_path(glib::home_dir()), "user-home")];
+    items.extend(
+        [
+            special_dir("Documents", UserDirectory::Documents, "folder-documents"),
+            special_dir("Downloads", UserDirectory::Downloads, "folder-download"),
+            special_dir("Music", UserDirectory::Music, "folder-music"),
+            special_dir("Pictures", UserDirectory::Pictures, "folder-pictures"),
+            special_dir("Videos", UserDirectory::Videos, "folder-videos"),
+        ]
+        .into_iter()
+        .flatten(),
+    );
+    items.push(place("Trash", gio::File::for_uri("trash:///"), "user-trash"));
+
+    items
+}
+
+fn show_saved_search_menu(
+    row: &GtkBox,
+    sidebar_item: &SidebarItem,
+    fmstate: &Rc<RefCell<FmState>>,
+    sidebar_list: &gio::ListStore,
+) {
+    let remove = gtk4::Button::with_label("Remove Saved Search");
+    remove.add_css_class("flat");
+    let popover = gtk4::Popover::new();
+    popover.set_child(Some(&remove));
+    popover.set_parent(row);
+    popover.connect_closed(|popover| {
+        // The row may be rebuilt while the button handler still runs
+        glib::idle_add_local_once(glib::clone!(
+            #[weak]
+            popover,
+            move || popover.unparent()
+        ));
+    });
+
+    let name = sidebar_item.label();
+    remove.connect_clicked(glib::clone!(
+        #[weak]
+        popover,
+        #[weak]
+        sidebar_list,
+        #[strong]
+        fmstate,
+        move |_| {
+            popover.popdown();
+            let result = fmstate.borrow_mut().remove_saved_search(&name);
+            if let Err(e) = result {
+                eprintln!("Failed to save bookmarks: {}", e);
+            }
+            refresh_sidebar(&sidebar_list, &fmstate);
+        }
+    ));
+    popover.popup();
+}
+
+/// Drops the bookmark `dragged` before the bookmark `target`, or at the end
+/// of the group `target`.
+fn reorder_bookmark(
+    fmstate: &Rc<RefCell<FmState>>,
+    sidebar_list: &gio::ListStore,
+    dragged: &SidebarItem,
+    target: &SidebarItem,
+) -> bool {
+    let Some(path) = dragged.location() else {
+        return false;
+    };
+
+    let result = match target.kind() {
+        SidebarItemKind::Bookmark => {
+            let before = target.location();
+            fmstate.borrow_mut().move_bookmark(&path, before.as_deref(), target.group())
+        }
+        SidebarItemKind::Group => fmstate.borrow_mut().move_bookmark(&path, None, target.group()),
+        _ => return false,
     };
 
-    vec![
-        ("Home", home.clone()),
-        ("Documents", dirs(UserDirectory::Documents)),
-        ("Downloads", dirs(UserDirectory::Downloads)),
-        ("Music", dirs(UserDirectory::Music)),
-        ("Pictures", dirs(UserDirectory::Pictures)),
-        ("Videos", dirs(UserDirectory::Videos)),
-        ("Trash", gio::File::for_uri("trash:///")),
-    ]
+    if let Err(e) = result {
+        eprintln!("Failed to move bookmark: {}", e);
+    }
+    refresh_sidebar(sidebar_list, fmstate);
+    true
 }
 
-pub fn refresh_sidebar(sidebar_list: &StringList, fmstate: &Rc<RefCell<FmState>>) {
-    // Clear existing items
-    sidebar_list.splice(0, sidebar_list.n_items(), &[]);
+pub fn refresh_sidebar(sidebar_list: &gio::ListStore, fmstate: &Rc<RefCell<FmState>>) {
+    let mut items = vec![SidebarItem::heading("Places")];
+    items.extend(get_sidebar_items());
 
-    // Add Places section
-    sidebar_list.append("Places");
-    let places = get_sidebar_items();
-    for (name, _) in places.iter() {
-        sidebar_list.append(name);
+    items.push(SidebarItem::heading("Bookmarks"));
+    let fmstate_ref = fmstate.borrow();
+
+    // Bookmarks are kept in display order, each group is contiguous
+    let mut current_group = None;
+    for bookmark in fmstate_ref.bookmarks.iter() {
+        if let Some(group) = &bookmark.group {
+            let collapsed = fmstate_ref.is_bookmark_group_collapsed(group);
+            if current_group != Some(group) {
+                current_group = Some(group);
+                items.push(SidebarItem::group_row(group, !collapsed));
+            }
+            if collapsed {
+                continue;
+            }
+        }
+        items.push(SidebarItem::bookmark(bookmark));
     }
 
-    // Add Bookmarks section
-    sidebar_list.append("Bookmarks");
-    let bookmarks = fmstate.borrow().bookmarks.clone();
-    for bookmark in bookmarks.iter() {
-        sidebar_list.append(&bookmark.name);
+    if !fmstate_ref.saved_searches.is_empty() {
+        items.push(SidebarItem::heading("Saved Searches"));
+        items.extend(fmstate_ref.saved_searches.iter().map(SidebarItem::saved_search));
     }
+
+    sidebar_list.splice(0, sidebar_list.n_items(), &items);
 }
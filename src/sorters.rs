@@ -1,91 +1,179 @@
+//! Orderings of the file list. Names sort naturally (`file2` before
+//! `file10`) following the rules of the locale. Every column falls back on
+//! the name when its values are equal. Putting folders first is a sorter
+//! of its own, placed before the column ones, so folders stay on top
+//! whichever way a column is sorted.
+
 use crate::models::file_item::FileItem;
-use gtk4::{CustomSorter, Ordering, glib, prelude::*};
-use std::cmp::Ordering as StdOrdering;
+use gtk4::{CustomSorter, Ordering, prelude::*};
+use std::{cell::Cell, cmp::Ordering as StdOrdering, rc::Rc};
+
+/// Sorter comparing with `compare`, then by name.
+fn create_sorter(compare: impl Fn(&FileItem, &FileItem) -> StdOrdering + 'static) -> CustomSorter {
+    CustomSorter::new(move |obj1, obj2| {
+        let item1 = obj1.downcast_ref::<FileItem>().unwrap();
+        let item2 = obj2.downcast_ref::<FileItem>().unwrap();
 
-fn compare_with_folders_first(
+        then_by_name(&compare, item1, item2).into()
+    })
+}
+
+fn then_by_name(
+    compare: impl Fn(&FileItem, &FileItem) -> StdOrdering,
     item1: &FileItem,
     item2: &FileItem,
-    folders_first: bool,
-    compare_fn: impl FnOnce(&FileItem, &FileItem) -> StdOrdering,
 ) -> StdOrdering {
-    if folders_first {
-        let is_dir1 = item1.is_directory();
-        let is_dir2 = item2.is_directory();
-
-        if is_dir1 && !is_dir2 {
-            return StdOrdering::Less;
-        } else if !is_dir1 && is_dir2 {
-            return StdOrdering::Greater;
-        }
-    }
+    compare(item1, item2).then_with(|| item1.collate_name(item2))
+}
 
-    compare_fn(item1, item2)
+fn folders_first(item1: &FileItem, item2: &FileItem) -> StdOrdering {
+    item2.is_directory().cmp(&item1.is_directory())
 }
 
-pub fn create_name_sorter(folders_first: bool) -> CustomSorter {
+/// Puts folders before files while `enabled` is set, leaving the rest to
+/// the sorters after it.
+pub fn create_folders_first_sorter(enabled: Rc<Cell<bool>>) -> CustomSorter {
     CustomSorter::new(move |obj1, obj2| {
+        if !enabled.get() {
+            return Ordering::Equal;
+        }
         let item1 = obj1.downcast_ref::<FileItem>().unwrap();
         let item2 = obj2.downcast_ref::<FileItem>().unwrap();
 
-        let ordering = compare_with_folders_first(item1, item2, folders_first, |i1, i2| {
-            i1.display_name().to_lowercase().cmp(&i2.display_name().to_lowercase())
-        });
-
-        match ordering {
-            StdOrdering::Less => Ordering::Smaller,
-            StdOrdering::Equal => Ordering::Equal,
-            StdOrdering::Greater => Ordering::Larger,
-        }
+        folders_first(item1, item2).into()
     })
 }
 
-pub fn create_size_sorter(folders_first: bool) -> CustomSorter {
+pub fn create_name_sorter() -> CustomSorter {
     CustomSorter::new(move |obj1, obj2| {
         let item1 = obj1.downcast_ref::<FileItem>().unwrap();
         let item2 = obj2.downcast_ref::<FileItem>().unwrap();
 
-        let ordering = compare_with_folders_first(item1, item2, folders_first, |i1, i2| {
-            i1.size().cmp(&i2.size())
-        });
-
-        match ordering {
-            StdOrdering::Less => Ordering::Smaller,
-            StdOrdering::Equal => Ordering::Equal,
-            StdOrdering::Greater => Ordering::Larger,
-        }
+        item1.collate_name(item2).into()
     })
 }
 
-pub fn create_date_sorter(folders_first: bool) -> CustomSorter {
-    CustomSorter::new(move |obj1, obj2| {
-        let item1 = obj1.downcast_ref::<FileItem>().unwrap();
-        let item2 = obj2.downcast_ref::<FileItem>().unwrap();
+pub fn create_size_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.size().cmp(&i2.size()))
+}
 
-        let ordering = compare_with_folders_first(item1, item2, folders_first, |i1, i2| {
-            i1.modified().cmp(&i2.modified())
-        });
+pub fn create_date_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.modified().cmp(&i2.modified()))
+}
 
-        match ordering {
-            StdOrdering::Less => Ordering::Smaller,
-            StdOrdering::Equal => Ordering::Equal,
-            StdOrdering::Greater => Ordering::Larger,
-        }
-    })
+pub fn create_type_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.mime_type().cmp(&i2.mime_type()))
 }
 
-pub fn create_type_sorter(folders_first: bool) -> CustomSorter {
-    CustomSorter::new(move |obj1, obj2| {
-        let item1 = obj1.downcast_ref::<FileItem>().unwrap();
-        let item2 = obj2.downcast_ref::<FileItem>().unwrap();
+/// Orders search results by the folder they are in.
+pub fn create_location_sorter() -> CustomSorter {
+    create_sorter(FileItem::collate_location)
+}
 
-        let ordering = compare_with_folders_first(item1, item2, folders_first, |i1, i2| {
-            i1.mime_type().cmp(&i2.mime_type())
-        });
+pub fn create_permissions_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| (i1.mode() & 0o7777).cmp(&(i2.mode() & 0o7777)))
+}
 
-        match ordering {
-            StdOrdering::Less => Ordering::Smaller,
-            StdOrdering::Equal => Ordering::Equal,
-            StdOrdering::Greater => Ordering::Larger,
-        }
-    })
+pub fn create_owner_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.owner().cmp(&i2.owner()))
+}
+
+pub fn create_group_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.group().cmp(&i2.group()))
+}
+
+pub fn create_created_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.created().cmp(&i2.created()))
+}
+
+pub fn create_accessed_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.accessed().cmp(&i2.accessed()))
+}
+
+pub fn create_extension_sorter() -> CustomSorter {
+    create_sorter(FileItem::compare_extension)
+}
+
+pub fn create_mime_type_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.content_type().cmp(&i2.content_type()))
+}
+
+pub fn create_link_target_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.symlink_target().cmp(&i2.symlink_target()))
+}
+
+pub fn create_inode_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.inode().cmp(&i2.inode()))
+}
+
+pub fn create_links_sorter() -> CustomSorter {
+    create_sorter(|i1, i2| i1.nlink().cmp(&i2.nlink()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64) -> FileItem {
+        FileItem::new(
+            format!("/tmp/{}", name),
+            name.to_string(),
+            size,
+            0,
+            String::new(),
+            false,
+            None,
+        )
+    }
+
+    fn folder(name: &str) -> FileItem {
+        FileItem::new(format!("/tmp/{}", name), name.to_string(), 0, 0, String::new(), true, None)
+    }
+
+    fn names(items: &[FileItem]) -> Vec<String> {
+        items.iter().map(|item| item.display_name()).collect()
+    }
+
+    #[test]
+    fn names_sort_naturally() {
+        let mut items = vec![file("file10", 0), file("file2", 0), file("file1", 0)];
+        items.sort_by(|a, b| a.collate_name(b));
+        assert_eq!(names(&items), ["file1", "file2", "file10"]);
+    }
+
+    #[test]
+    fn renaming_refreshes_the_name_key() {
+        let a = file("a", 0);
+        let b = file("b", 0);
+        assert_eq!(a.collate_name(&b), StdOrdering::Less);
+
+        a.set_display_name("c");
+        assert_eq!(a.collate_name(&b), StdOrdering::Greater);
+    }
+
+    #[test]
+    fn equal_keys_fall_back_on_the_name() {
+        let mut items = vec![file("b", 5), file("c", 1), file("a", 5)];
+        items.sort_by(|a, b| then_by_name(|a, b| a.size().cmp(&b.size()), a, b));
+        assert_eq!(names(&items), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn extensions_ignore_case() {
+        let mut items = vec![file("b.TXT", 0), file("a.png", 0), file("c.txt", 0)];
+        items.sort_by(|a, b| then_by_name(FileItem::compare_extension, a, b));
+        assert_eq!(names(&items), ["a.png", "b.TXT", "c.txt"]);
+    }
+
+    #[test]
+    fn folders_stay_first_in_descending_order() {
+        // As in the list: folders first, then the column, which alone is
+        // reversed when sorting down
+        let mut items = vec![file("a", 3), folder("x"), file("b", 1), folder("y")];
+        items.sort_by(|a, b| {
+            folders_first(a, b)
+                .then_with(|| then_by_name(|a, b| a.size().cmp(&b.size()), a, b).reverse())
+        });
+        assert_eq!(names(&items), ["y", "x", "a", "b"]);
+    }
 }
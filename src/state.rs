@@ -1,11 +1,18 @@
-use crate::{bookmarks::Bookmark, utils::FMSettings};
+use crate::{
+    bookmarks::{self, Bookmark, BookmarksError, BookmarksFile, SavedSearch},
+    frecency, gtk_bookmarks,
+    undo::{self, UndoEntry},
+    utils::FMSettings,
+};
 use gtk4::{gio, glib::GString};
 use std::path::PathBuf;
 
 pub struct FmState {
     pub current_path: gio::File,
     pub on_path_changed: Vec<Box<dyn Fn(&gio::File)>>,
+    pub on_settings_changed: Vec<Box<dyn Fn(&FmState)>>,
     pub settings: FMSettings,
+    pub settings_monitor: Option<gio::FileMonitor>,
     pub hovered_file: Option<GString>,
     pub popup_focused_file: Option<GString>,
     pub clipboard: Vec<PathBuf>,
@@ -13,24 +20,38 @@ pub struct FmState {
     pub history: Vec<gio::File>,
     pub history_index: usize,
     pub bookmarks: Vec<Bookmark>,
+    pub collapsed_bookmark_groups: Vec<String>,
+    pub saved_searches: Vec<SavedSearch>,
+    /// The GTK bookmarks as last read or written, see `sync_gtk_bookmarks`.
+    pub gtk_bookmarks: Vec<Bookmark>,
+    pub gtk_bookmarks_monitor: Option<gio::FileMonitor>,
+    /// Changes to files that can be undone, most recent last.
+    pub undo_history: Vec<UndoEntry>,
 }
 
 impl FmState {
-    pub fn new(current_path: gio::File) -> Self {
+    pub fn new(current_path: gio::File, settings: FMSettings, bookmarks: BookmarksFile) -> Self {
         let mut history = Vec::new();
         history.push(current_path.clone());
 
         Self {
             current_path,
             on_path_changed: Vec::new(),
-            settings: FMSettings::new(),
+            on_settings_changed: Vec::new(),
+            settings,
+            settings_monitor: None,
             hovered_file: None,
             popup_focused_file: None,
             clipboard: Vec::new(),
             clipboard_is_cut: false,
             history,
             history_index: 0,
-            bookmarks: crate::bookmarks::load_bookmarks(),
+            bookmarks: bookmarks.bookmarks,
+            collapsed_bookmark_groups: bookmarks.collapsed_groups,
+            saved_searches: bookmarks.saved_searches,
+            gtk_bookmarks: Vec::new(),
+            gtk_bookmarks_monitor: None,
+            undo_history: Vec::new(),
         }
     }
 
@@ -45,11 +66,34 @@ impl FmState {
         self.on_path_changed.push(Box::new(f));
     }
 
+    pub fn connect_settings_changed<F: Fn(&FmState) + 'static>(&mut self, f: F) {
+        self.on_settings_changed.push(Box::new(f));
+    }
+
+    /// Replaces the settings and notifies listeners, unless nothing changed.
+    pub fn apply_settings(&mut self, settings: FMSettings) {
+        if self.settings == settings {
+            return;
+        }
+
+        self.settings = settings;
+        for cb in self.on_settings_changed.iter() {
+            cb(self);
+        }
+    }
+
+    pub fn save_settings(&self) {
+        if let Err(e) = self.settings.save() {
+            eprintln!("{}", e);
+        }
+    }
+
     pub fn update_history(&mut self, file: gio::File) {
         if self.history_index + 1 < self.history.len() {
             self.history.truncate(self.history_index + 1);
         }
 
+        frecency::record(&file);
         self.history.push(file);
         self.history_index = self.history.len() - 1;
     }
@@ -76,22 +120,157 @@ impl FmState {
         Some(file)
     }
 
-    pub fn add_bookmark(&mut self, bookmark: Bookmark) -> Result<(), std::io::Error> {
+    pub fn push_undo(&mut self, entry: UndoEntry) {
+        if self.undo_history.len() >= undo::MAX_UNDO_ENTRIES {
+            self.undo_history.remove(0);
+        }
+        self.undo_history.push(entry);
+    }
+
+    pub fn pop_undo(&mut self) -> Option<UndoEntry> {
+        self.undo_history.pop()
+    }
+
+    pub fn add_bookmark(&mut self, bookmark: Bookmark) -> Result<(), BookmarksError> {
         // Check for duplicates
         if !self.bookmarks.iter().any(|b| b.path == bookmark.path) {
             self.bookmarks.push(bookmark);
-            crate::bookmarks::save_bookmarks(&self.bookmarks)
+            self.save_bookmarks()
         } else {
             Ok(()) // Already exists
         }
     }
 
-    pub fn remove_bookmark(&mut self, index: usize) -> Result<(), std::io::Error> {
+    pub fn remove_bookmark(&mut self, index: usize) -> Result<(), BookmarksError> {
         if index < self.bookmarks.len() {
             self.bookmarks.remove(index);
-            crate::bookmarks::save_bookmarks(&self.bookmarks)
+            self.save_bookmarks()
         } else {
             Ok(()) // Index out of bounds, ignore
         }
     }
+
+    /// Applies `change` to the bookmark pointing at `path` and saves.
+    pub fn update_bookmark(
+        &mut self,
+        path: &str,
+        change: impl FnOnce(&mut Bookmark),
+    ) -> Result<(), BookmarksError> {
+        let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.path == path) else {
+            return Ok(());
+        };
+
+        change(bookmark);
+        bookmarks::normalize_order(&mut self.bookmarks);
+        self.save_bookmarks()
+    }
+
+    /// Moves the bookmark pointing at `path` before the one pointing at
+    /// `before` (or to the end of `group`), and into `group`.
+    pub fn move_bookmark(
+        &mut self,
+        path: &str,
+        before: Option<&str>,
+        group: Option<String>,
+    ) -> Result<(), BookmarksError> {
+        let position = |path: &str| self.bookmarks.iter().position(|b| b.path == path);
+
+        let Some(from) = position(path) else {
+            return Ok(());
+        };
+        let before = match before {
+            Some(before) => position(before),
+            None => self.bookmarks.iter().rposition(|b| b.group == group).map(|i| i + 1),
+        };
+
+        bookmarks::move_bookmark(&mut self.bookmarks, from, before, group);
+        self.save_bookmarks()
+    }
+
+    pub fn toggle_bookmark_group(&mut self, group: &str) -> Result<(), BookmarksError> {
+        match self.collapsed_bookmark_groups.iter().position(|g| g == group) {
+            Some(index) => {
+                self.collapsed_bookmark_groups.remove(index);
+            }
+            None => self.collapsed_bookmark_groups.push(group.to_string()),
+        }
+        self.save_bookmarks()
+    }
+
+    pub fn is_bookmark_group_collapsed(&self, group: &str) -> bool {
+        self.collapsed_bookmark_groups.iter().any(|g| g == group)
+    }
+
+    /// Saves `search`, replacing the saved search of the same name.
+    pub fn add_saved_search(&mut self, search: SavedSearch) -> Result<(), BookmarksError> {
+        match self.saved_searches.iter_mut().find(|s| s.name == search.name) {
+            Some(existing) => *existing = search,
+            None => self.saved_searches.push(search),
+        }
+        self.save_bookmarks()
+    }
+
+    pub fn remove_saved_search(&mut self, name: &str) -> Result<(), BookmarksError> {
+        self.saved_searches.retain(|s| s.name != name);
+        self.save_bookmarks()
+    }
+
+    pub fn saved_search(&self, name: &str) -> Option<SavedSearch> {
+        self.saved_searches.iter().find(|s| s.name == name).cloned()
+    }
+
+    fn bookmarks_file(&self) -> BookmarksFile {
+        // Forget the state of groups that no longer exist
+        let collapsed_groups = self
+            .collapsed_bookmark_groups
+            .iter()
+            .filter(|group| self.bookmarks.iter().any(|b| b.group.as_ref() == Some(*group)))
+            .cloned()
+            .collect();
+
+        BookmarksFile {
+            bookmarks: self.bookmarks.clone(),
+            collapsed_groups,
+            saved_searches: self.saved_searches.clone(),
+        }
+    }
+
+    /// Saves the bookmarks, and mirrors them to the GTK bookmarks file when
+    /// sharing is enabled.
+    pub fn save_bookmarks(&mut self) -> Result<(), BookmarksError> {
+        bookmarks::save_bookmarks(&self.bookmarks_file())?;
+
+        if self.settings.sync_gtk_bookmarks
+            && !gtk_bookmarks::same_gtk_bookmarks(&self.gtk_bookmarks, &self.bookmarks)
+        {
+            gtk_bookmarks::save_gtk_bookmarks(&self.bookmarks)?;
+            self.gtk_bookmarks = self.bookmarks.clone();
+        }
+        Ok(())
+    }
+
+    /// Merges the current GTK bookmarks into ours and writes back whichever
+    /// side is out of date. Returns `true` when our bookmarks changed.
+    pub fn sync_gtk_bookmarks(&mut self, external: Vec<Bookmark>) -> Result<bool, BookmarksError> {
+        if !self.settings.sync_gtk_bookmarks {
+            return Ok(false);
+        }
+
+        let mut merged =
+            gtk_bookmarks::merge_gtk_bookmarks(&self.bookmarks, &self.gtk_bookmarks, &external);
+        bookmarks::normalize_order(&mut merged);
+        self.gtk_bookmarks = external;
+
+        let changed = merged != self.bookmarks;
+        if changed {
+            self.bookmarks = merged;
+            bookmarks::save_bookmarks(&self.bookmarks_file())?;
+        }
+        if !gtk_bookmarks::same_gtk_bookmarks(&self.gtk_bookmarks, &self.bookmarks) {
+            gtk_bookmarks::save_gtk_bookmarks(&self.bookmarks)?;
+            self.gtk_bookmarks = self.bookmarks.clone();
+        }
+
+        Ok(changed)
+    }
 }
@@ -24,6 +24,14 @@ pub fn load_css() {
         .footer-label {
             margin: 0 10px;
         }
+        .operations-panel {
+            border-top: 1px solid #d0d0d0;
+            padding: 6px 10px;
+        }
+        .broken-link {
+            color: #c01c28;
+            opacity: 0.7;
+        }
 	",
     );
 
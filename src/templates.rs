@@ -0,0 +1,123 @@
+//! New documents, empty or copied from the XDG Templates folder.
+
+use gtk4::{gio, glib, prelude::*};
+
+/// Name given to new empty files.
+const EMPTY_FILE_NAME: &str = "New File";
+
+/// The Templates folder, unless it is unset or points at the home folder,
+/// which some setups do when the folder doesn't exist.
+pub fn templates_dir() -> Option<gio::File> {
+    let dir = glib::user_special_dir(glib::UserDirectory::Templates)?;
+    if dir == glib::home_dir() || !dir.is_dir() {
+        return None;
+    }
+    Some(gio::File::for_path(dir))
+}
+
+/// Menu with an "Empty File" entry and one entry per template, subfolders
+/// becoming submenus. Entries activate `win.new_document` with the template
+/// path, or an empty string for an empty file.
+pub fn new_document_menu() -> gio::Menu {
+    let menu = gio::Menu::new();
+
+    let empty = gio::Menu::new();
+    empty.append_item(&document_item("Empty File", ""));
+    menu.append_section(None, &empty);
+
+    if let Some(dir) = templates_dir() {
+        let templates = gio::Menu::new();
+        append_templates(&templates, &dir);
+        if templates.n_items() > 0 {
+            menu.append_section(None, &templates);
+        }
+    }
+
+    menu
+}
+
+fn append_templates(menu: &gio::Menu, dir: &gio::File) {
+    let Ok(enumerator) = dir.enumerate_children(
+        "standard::name,standard::display-name,standard::type,standard::is-hidden,standard::is-backup",
+        gio::FileQueryInfoFlags::NONE,
+        gio::Cancellable::NONE,
+    ) else {
+        return;
+    };
+
+    let mut entries = Vec::new();
+    while let Ok(Some(info)) = enumerator.next_file(gio::Cancellable::NONE) {
+        if !info.is_hidden() && !info.is_backup() {
+            entries.push(info);
+        }
+    }
+    entries.sort_by_cached_key(|info| glib::FilenameCollationKey::from(info.display_name()));
+
+    for info in entries {
+        let file = dir.child(info.name());
+        let display_name = info.display_name();
+
+        if info.file_type() == gio::FileType::Directory {
+            let submenu = gio::Menu::new();
+            append_templates(&submenu, &file);
+            if submenu.n_items() > 0 {
+                menu.append_submenu(Some(&display_name), &submenu);
+            }
+        } else if let Some(path) = file.path() {
+            // Like other file managers, the extension is left out
+            let (label, _) = crate::batch_rename::split_extension(&display_name, false);
+            menu.append_item(&document_item(label, &path.to_string_lossy()));
+        }
+    }
+}
+
+fn document_item(label: &str, template: &str) -> gio::MenuItem {
+    let item = gio::MenuItem::new(Some(label), None);
+    item.set_action_and_target_value(Some("win.new_document"), Some(&template.to_variant()));
+    item
+}
+
+/// Creates a new document in `dir`, a copy of `template` or an empty file,
+/// under a name that isn't taken yet.
+pub fn create_document(
+    dir: &gio::File,
+    template: Option<&gio::File>,
+) -> Result<gio::File, glib::Error> {
+    let name = template
+        .and_then(|t| t.basename())
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| EMPTY_FILE_NAME.to_string());
+    let target = dir.child(unique_name(dir, &name, false));
+
+    match template {
+        Some(template) => {
+            template.copy(&target, gio::FileCopyFlags::NONE, gio::Cancellable::NONE, None)?
+        }
+        None => {
+            target.create(gio::FileCreateFlags::NONE, gio::Cancellable::NONE)?;
+        }
+    }
+    Ok(target)
+}
+
+/// `name`, or `name (2)`, `name (3)`, ... with the extension kept last,
+/// unless `is_dir`. Broken links count as taken.
+pub fn unique_name(dir: &gio::File, name: &str, is_dir: bool) -> String {
+    let taken = |name: &str| {
+        dir.child(name)
+            .query_file_type(gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS, gio::Cancellable::NONE)
+            != gio::FileType::Unknown
+    };
+    if !taken(name) {
+        return name.to_string();
+    }
+
+    let (stem, extension) = crate::batch_rename::split_extension(name, is_dir);
+    (2..)
+        .map(|n| match extension {
+            Some(extension) => format!("{} ({}).{}", stem, n, extension),
+            None => format!("{} ({})", stem, n),
+        })
+        .find(|candidate| !taken(candidate))
+        .unwrap_or_else(|| name.to_string())
+}
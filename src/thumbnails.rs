@@ -0,0 +1,589 @@
+//! Thumbnails, following the freedesktop Thumbnail Managing Standard.
+//!
+//! Thumbnails live in `~/.cache/thumbnails/normal` and `large`, named after
+//! the MD5 sum of the file URI, and are shared with other applications: one
+//! made elsewhere is used as long as its `Thumb::MTime` matches the file.
+//! Missing ones are made with gdk-pixbuf for images, or by the thumbnailers
+//! other programs install as `.thumbnailer` files. Files that can't be
+//! thumbnailed get a marker under `fail/`, so they aren't tried again until
+//! they change.
+//!
+//! The work runs on a few worker threads. Requests wait on the main thread,
+//! the latest first, and are dropped when their row is no longer shown by
+//! the time their turn comes, so the rows in view are done first.
+
+use crate::models::file_item::FileItem;
+use gtk4::{
+    gdk,
+    gdk_pixbuf::{Colorspace, Pixbuf},
+    gio, glib,
+    prelude::*,
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const WORKERS: usize = 3;
+
+/// Requests waiting beyond this many drop the oldest, long scrolled away.
+const MAX_WAITING: usize = 500;
+
+/// Thumbnails kept in memory, per file and size.
+const MAX_TEXTURES: usize = 2000;
+
+/// Larger images are left alone, decoding them takes too long.
+const MAX_IMAGE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// A thumbnailer taking longer is stopped.
+const THUMBNAILER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Our folder under `fail/`, as the standard asks for.
+const FAIL_FOLDER: &str = concat!("axfm-", env!("CARGO_PKG_VERSION"));
+
+const THUMBNAILER_GROUP: &str = "Thumbnailer Entry";
+
+/// Counts the thumbnails written, to give each one its own partial file.
+static PARTIAL_WRITES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThumbnailSize {
+    /// Up to 128 pixels.
+    Normal,
+    /// Up to 256 pixels.
+    Large,
+}
+
+impl ThumbnailSize {
+    pub fn pixels(self) -> i32 {
+        match self {
+            ThumbnailSize::Normal => 128,
+            ThumbnailSize::Large => 256,
+        }
+    }
+
+    fn folder(self) -> &'static str {
+        match self {
+            ThumbnailSize::Normal => "normal",
+            ThumbnailSize::Large => "large",
+        }
+    }
+}
+
+/// A program making thumbnails, from its `.thumbnailer` file.
+struct Thumbnailer {
+    exec: String,
+    mime_types: Vec<String>,
+}
+
+/// The thumbnailers installed. Those of the user come first and hide
+/// system ones with the same file name.
+fn thumbnailers() -> &'static [Thumbnailer] {
+    static THUMBNAILERS: OnceLock<Vec<Thumbnailer>> = OnceLock::new();
+    THUMBNAILERS.get_or_init(|| {
+        let mut seen = HashSet::new();
+        let mut found = Vec::new();
+        let dirs = std::iter::once(glib::user_data_dir()).chain(glib::system_data_dirs());
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(dir.join("thumbnailers")) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "thumbnailer")
+                    && seen.insert(entry.file_name())
+                    && let Some(thumbnailer) = read_thumbnailer(&path)
+                {
+                    found.push(thumbnailer);
+                }
+            }
+        }
+        found
+    })
+}
+
+fn read_thumbnailer(path: &Path) -> Option<Thumbnailer> {
+    let key_file = glib::KeyFile::new();
+    key_file.load_from_file(path, glib::KeyFileFlags::NONE).ok()?;
+
+    // Thumbnailers whose program is missing don't count
+    if let Ok(try_exec) = key_file.string(THUMBNAILER_GROUP, "TryExec")
+        && glib::find_program_in_path(try_exec.as_str()).is_none()
+    {
+        return None;
+    }
+    Some(Thumbnailer {
+        exec: key_file.string(THUMBNAILER_GROUP, "Exec").ok()?.to_string(),
+        mime_types: key_file
+            .string_list(THUMBNAILER_GROUP, "MimeType")
+            .ok()?
+            .iter()
+            .map(|mime_type| mime_type.to_string())
+            .collect(),
+    })
+}
+
+fn thumbnailer_for(content_type: &str) -> Option<&'static Thumbnailer> {
+    thumbnailers().iter().find(|t| t.mime_types.iter().any(|m| m == content_type))
+}
+
+/// Content types gdk-pixbuf can read.
+fn image_types() -> &'static [String] {
+    static IMAGE_TYPES: OnceLock<Vec<String>> = OnceLock::new();
+    IMAGE_TYPES.get_or_init(|| {
+        Pixbuf::formats()
+            .iter()
+            .flat_map(|format| format.mime_types())
+            .map(|mime_type| mime_type.to_string())
+            .collect()
+    })
+}
+
+fn is_image(content_type: &str) -> bool {
+    image_types().iter().any(|t| t == content_type)
+}
+
+fn cache_root() -> PathBuf {
+    glib::user_cache_dir().join("thumbnails")
+}
+
+/// What the workers are asked to do.
+struct Job {
+    key: Key,
+    uri: String,
+    content_type: String,
+    file_size: u64,
+}
+
+/// A file as of its modification time, and the size wanted.
+type Key = (PathBuf, i64, ThumbnailSize);
+
+type OnReady = Box<dyn FnOnce(&gdk::Texture)>;
+
+struct Request {
+    job: Job,
+    wanted: Box<dyn Fn() -> bool>,
+    on_ready: OnReady,
+}
+
+struct Pool {
+    jobs: mpsc::Sender<Job>,
+    results: mpsc::Receiver<(Key, Option<PathBuf>)>,
+}
+
+#[derive(Default)]
+struct Thumbnails {
+    pool: Option<Pool>,
+    /// Newest last, it goes first.
+    waiting: Vec<Request>,
+    /// Jobs given to the workers, with who waits for them.
+    running: HashMap<Key, Vec<OnReady>>,
+    /// `None` for files without a thumbnail.
+    textures: HashMap<Key, Option<gdk::Texture>>,
+    poll: Option<glib::SourceId>,
+}
+
+thread_local! {
+    static THUMBNAILS: RefCell<Thumbnails> = RefCell::new(Thumbnails::default());
+}
+
+/// Calls `on_ready` with the thumbnail of `item` once there is one, right
+/// away when it is at hand. Nothing is called for files without one.
+/// `wanted` tells whether the thumbnail is still worth making when its turn
+/// comes, like while the row showing it is in view.
+pub fn request(
+    item: &FileItem,
+    size: ThumbnailSize,
+    wanted: impl Fn() -> bool + 'static,
+    on_ready: impl FnOnce(&gdk::Texture) + 'static,
+) {
+    let content_type = item.content_type();
+    if item.is_directory()
+        || item.archive_member()
+        || (!is_image(&content_type) && thumbnailer_for(&content_type).is_none())
+    {
+        return;
+    }
+    // Thumbnails of thumbnails would never end
+    let path = PathBuf::from(item.path());
+    if !path.is_absolute() || path.starts_with(cache_root()) {
+        return;
+    }
+
+    let key = (path, item.modified(), size);
+    match THUMBNAILS.with_borrow(|thumbnails| thumbnails.textures.get(&key).cloned()) {
+        Some(Some(texture)) => on_ready(&texture),
+        Some(None) => {}
+        None => {
+            let uri = gio::File::for_path(&key.0).uri().to_string();
+            let job = Job { key, uri, content_type, file_size: item.size() };
+            THUMBNAILS.with_borrow_mut(|thumbnails| {
+                if thumbnails.waiting.len() >= MAX_WAITING {
+                    thumbnails.waiting.remove(0);
+                }
+                thumbnails.waiting.push(Request {
+                    job,
+                    wanted: Box::new(wanted),
+                    on_ready: Box::new(on_ready),
+                });
+            });
+            pump();
+        }
+    }
+}
+
+/// Hands waiting requests to idle workers, and makes sure their results
+/// are picked up.
+fn pump() {
+    loop {
+        let request = THUMBNAILS.with_borrow_mut(|thumbnails| {
+            if thumbnails.running.len() >= WORKERS { None } else { thumbnails.waiting.pop() }
+        });
+        let Some(request) = request else {
+            break;
+        };
+        if !(request.wanted)() {
+            continue;
+        }
+
+        // Made for an earlier request in the meantime
+        let key = &request.job.key;
+        let done = THUMBNAILS.with_borrow(|thumbnails| thumbnails.textures.get(key).cloned());
+        if let Some(texture) = done {
+            if let Some(texture) = texture {
+                (request.on_ready)(&texture);
+            }
+            continue;
+        }
+
+        THUMBNAILS.with_borrow_mut(|thumbnails| {
+            if let Some(waiting) = thumbnails.running.get_mut(&request.job.key) {
+                waiting.push(request.on_ready);
+                return;
+            }
+            let pool = thumbnails.pool.get_or_insert_with(start_pool);
+            let key = request.job.key.clone();
+            if pool.jobs.send(request.job).is_ok() {
+                thumbnails.running.insert(key, vec![request.on_ready]);
+            }
+        });
+    }
+
+    THUMBNAILS.with_borrow_mut(|thumbnails| {
+        if thumbnails.poll.is_none() && !thumbnails.running.is_empty() {
+            thumbnails.poll =
+                Some(glib::timeout_add_local(Duration::from_millis(50), poll_results));
+        }
+    });
+}
+
+fn poll_results() -> glib::ControlFlow {
+    let results: Vec<(Key, Option<PathBuf>)> = THUMBNAILS.with_borrow(|thumbnails| {
+        thumbnails.pool.as_ref().map(|pool| pool.results.try_iter().collect()).unwrap_or_default()
+    });
+
+    for (key, thumbnail) in results {
+        let texture = thumbnail
+            .and_then(|path| Pixbuf::from_file(path).ok())
+            .map(|pixbuf| gdk::Texture::for_pixbuf(&pixbuf));
+        let waiting = THUMBNAILS.with_borrow_mut(|thumbnails| {
+            if thumbnails.textures.len() >= MAX_TEXTURES {
+                thumbnails.textures.clear();
+            }
+            thumbnails.textures.insert(key.clone(), texture.clone());
+            thumbnails.running.remove(&key).unwrap_or_default()
+        });
+        if let Some(texture) = &texture {
+            for on_ready in waiting {
+                on_ready(texture);
+            }
+        }
+    }
+
+    pump();
+    THUMBNAILS.with_borrow_mut(|thumbnails| {
+        if thumbnails.running.is_empty() {
+            thumbnails.poll = None;
+            glib::ControlFlow::Break
+        } else {
+            glib::ControlFlow::Continue
+        }
+    })
+}
+
+fn start_pool() -> Pool {
+    let (jobs, job_receiver) = mpsc::channel::<Job>();
+    let (result_sender, results) = mpsc::channel();
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+    for _ in 0..WORKERS {
+        let job_receiver = job_receiver.clone();
+        let result_sender = result_sender.clone();
+        thread::spawn(move || {
+            loop {
+                let job = match job_receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => return,
+                };
+                let Ok(job) = job else {
+                    return;
+                };
+                let thumbnail = thumbnail(&job);
+                if result_sender.send((job.key, thumbnail)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    Pool { jobs, results }
+}
+
+/// Finds or makes the thumbnail for `job`, returning where it is.
+fn thumbnail(job: &Job) -> Option<PathBuf> {
+    let (path, mtime, size) = &job.key;
+    let name = thumbnail_name(&job.uri)?;
+
+    // A larger thumbnail does as well
+    let sizes: &[ThumbnailSize] = match size {
+        ThumbnailSize::Normal => &[ThumbnailSize::Normal, ThumbnailSize::Large],
+        ThumbnailSize::Large => &[ThumbnailSize::Large],
+    };
+    for size in sizes {
+        let existing = cache_root().join(size.folder()).join(&name);
+        if is_current(&existing, &job.uri, *mtime) {
+            return Some(existing);
+        }
+    }
+    let failure = cache_root().join("fail").join(FAIL_FOLDER).join(&name);
+    if is_current(&failure, &job.uri, *mtime) {
+        return None;
+    }
+
+    let target = cache_root().join(size.folder()).join(&name);
+    let made = if is_image(&job.content_type) && job.file_size <= MAX_IMAGE_SIZE {
+        scale_image(path, size.pixels())
+    } else {
+        match thumbnailer_for(&job.content_type) {
+            Some(thumbnailer) => run_thumbnailer(thumbnailer, job),
+            None => Err("no thumbnailer".to_string()),
+        }
+    };
+    match made.and_then(|pixbuf| save(&pixbuf, &target, job)) {
+        Ok(()) => Some(target),
+        Err(_) => {
+            let marker = Pixbuf::new(Colorspace::Rgb, true, 8, 1, 1);
+            if let Some(marker) = marker {
+                marker.fill(0);
+                if let Err(e) = save(&marker, &failure, job) {
+                    eprintln!("Failed to record the failure: {}", e);
+                }
+            }
+            None
+        }
+    }
+}
+
+/// The file name of the thumbnail for `uri`, the MD5 of the URI as the
+/// standard asks.
+fn thumbnail_name(uri: &str) -> Option<String> {
+    Some(format!("{}.png", glib::compute_checksum_for_string(glib::ChecksumType::Md5, uri)?))
+}
+
+/// Whether the thumbnail at `path` was made from the current file.
+fn is_current(path: &Path, uri: &str, mtime: i64) -> bool {
+    if !path.exists() {
+        return false;
+    }
+    Pixbuf::from_file(path).is_ok_and(|pixbuf| {
+        pixbuf.option("tEXt::Thumb::URI").as_deref() == Some(uri)
+            && pixbuf.option("tEXt::Thumb::MTime").as_deref() == Some(mtime.to_string().as_str())
+    })
+}
+
+/// Reads the image at `path` to fit in `pixels`, never enlarging it.
+pub fn scale_image(path: &Path, pixels: i32) -> Result<Pixbuf, String> {
+    let (_, width, height) = Pixbuf::file_info(path).ok_or("unknown image format")?;
+    let pixbuf = if width <= pixels && height <= pixels {
+        Pixbuf::from_file(path)
+    } else {
+        Pixbuf::from_file_at_scale(path, pixels, pixels, true)
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(pixbuf.apply_embedded_orientation().unwrap_or(pixbuf))
+}
+
+fn run_thumbnailer(thumbnailer: &Thumbnailer, job: &Job) -> Result<Pixbuf, String> {
+    // A folder only we can write to, so the thumbnailer can't be made to
+    // write over a file planted under a name known in advance
+    let dir = glib::mkdtemp(glib::tmp_dir().join("axfm-thumbnail-XXXXXX"))
+        .ok_or("could not create a temporary folder")?;
+    let output = dir.join("thumbnail.png");
+    let pixbuf = run_thumbnailer_into(thumbnailer, job, &output);
+    let _ = fs::remove_dir_all(&dir);
+    pixbuf
+}
+
+fn run_thumbnailer_into(
+    thumbnailer: &Thumbnailer,
+    job: &Job,
+    output: &Path,
+) -> Result<Pixbuf, String> {
+    let (path, _, size) = &job.key;
+
+    let argv = glib::shell_parse_argv(&thumbnailer.exec).map_err(|e| e.to_string())?;
+    let args: Vec<String> = argv
+        .iter()
+        .map(|arg| expand_field_codes(&arg.to_string_lossy(), path, &job.uri, output, *size))
+        .collect();
+    let (program, args) = args.split_first().ok_or("the thumbnailer has no command")?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("{}: {}", program, e))?;
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() > THUMBNAILER_TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("{} took too long", program));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(20)),
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+
+    if status.success() {
+        scale_image(output, size.pixels())
+    } else {
+        Err(format!("{} failed ({})", program, status))
+    }
+}
+
+/// Fills in the `%i`, `%u`, `%o` and `%s` codes of a thumbnailer command.
+fn expand_field_codes(
+    arg: &str,
+    path: &Path,
+    uri: &str,
+    output: &Path,
+    size: ThumbnailSize,
+) -> String {
+    let mut expanded = String::new();
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('i') => expanded.push_str(&path.to_string_lossy()),
+            Some('u') => expanded.push_str(uri),
+            Some('o') => expanded.push_str(&output.to_string_lossy()),
+            Some('s') => expanded.push_str(&size.pixels().to_string()),
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+/// Writes `pixbuf` to `target` with the metadata the standard asks for.
+/// The file is written aside and renamed, so other readers never see half
+/// of it.
+fn save(pixbuf: &Pixbuf, target: &Path, job: &Job) -> Result<(), String> {
+    let folder = target.parent().ok_or("no thumbnail folder")?;
+    fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+    let _ = fs::set_permissions(folder, fs::Permissions::from_mode(0o700));
+
+    // Named after the write too, as the workers may save the same thumbnail
+    let write = PARTIAL_WRITES.fetch_add(1, Ordering::Relaxed);
+    let partial = target.with_extension(format!("png.{}-{}.partial", std::process::id(), write));
+    let mtime = job.key.1.to_string();
+    let file_size = job.file_size.to_string();
+    pixbuf
+        .savev(
+            &partial,
+            "png",
+            &[
+                ("tEXt::Thumb::URI", &job.uri),
+                ("tEXt::Thumb::MTime", &mtime),
+                ("tEXt::Thumb::Size", &file_size),
+                ("tEXt::Thumb::Mimetype", &job.content_type),
+                ("tEXt::Software", "AxFM"),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    let _ = fs::set_permissions(&partial, fs::Permissions::from_mode(0o600));
+    fs::rename(&partial, target).map_err(|e| {
+        let _ = fs::remove_file(&partial);
+        e.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(arg: &str) -> String {
+        expand_field_codes(
+            arg,
+            Path::new("/home/me/a b.pdf"),
+            "file:///home/me/a%20b.pdf",
+            Path::new("/tmp/out.png"),
+            ThumbnailSize::Large,
+        )
+    }
+
+    #[test]
+    fn field_codes_are_expanded() {
+        assert_eq!(expand("%i"), "/home/me/a b.pdf");
+        assert_eq!(expand("%u"), "file:///home/me/a%20b.pdf");
+        assert_eq!(expand("%o"), "/tmp/out.png");
+        assert_eq!(expand("%s"), "256");
+        assert_eq!(expand("--size=%sx%s"), "--size=256x256");
+        assert_eq!(expand("100%%"), "100%");
+        assert_eq!(expand("plain"), "plain");
+    }
+
+    #[test]
+    fn unknown_field_codes_are_kept() {
+        assert_eq!(expand("%f"), "%f");
+        assert_eq!(expand("%x%i"), "%x/home/me/a b.pdf");
+        assert_eq!(expand("50%"), "50%");
+        assert_eq!(expand("%%i"), "%i");
+    }
+
+    #[test]
+    fn thumbnails_are_named_after_the_uri() {
+        // The example from the thumbnail standard
+        assert_eq!(
+            thumbnail_name("file:///home/jens/photos/me.png").as_deref(),
+            Some("c6ee772d9e49320e97ec29a7eb5b1697.png")
+        );
+        // Of the escaped URI, not the path
+        let uri = gio::File::for_path("/tmp/a b.png").uri();
+        assert_eq!(uri, "file:///tmp/a%20b.png");
+        assert_eq!(thumbnail_name(&uri).as_deref(), Some("f2584ab78dd95a88bd0d3f0ecaee7a8c.png"));
+    }
+}
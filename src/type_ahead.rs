@@ -0,0 +1,116 @@
+//! Jumping to a file by typing the start of its name in the file list.
+//! Typing the same letter again goes on to the next name starting with it.
+
+use crate::{files_panel, fm_window::FmWindow, models::file_item::FileItem};
+use gtk4::{gdk, glib, prelude::*};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// A pause this long starts a new name.
+const RESET_AFTER: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct Typed {
+    text: String,
+    last_key: Option<Instant>,
+}
+
+/// Lets typing in the file list of `fm_window` select the matching name.
+/// A `/` typed first opens the filter bar instead.
+pub fn attach(fm_window: &FmWindow) {
+    let typed = Rc::new(RefCell::new(Typed::default()));
+
+    let keys = gtk4::EventControllerKey::new();
+    keys.connect_key_pressed(glib::clone!(
+        #[weak(rename_to = window)]
+        fm_window.window,
+        #[strong]
+        typed,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, modifiers| {
+            let shortcut_modifiers = gdk::ModifierType::CONTROL_MASK
+                | gdk::ModifierType::ALT_MASK
+                | gdk::ModifierType::SUPER_MASK;
+            let Some(c) = key.to_unicode().filter(|c| !c.is_control()) else {
+                return glib::Propagation::Proceed;
+            };
+            // Keys typed in an entry, like a rename, are its own
+            if modifiers.intersects(shortcut_modifiers)
+                || GtkWindowExt::focus(&window).is_some_and(|w| w.is::<gtk4::Text>())
+            {
+                return glib::Propagation::Proceed;
+            }
+            let Some(fm_window) = FmWindow::from_window(window.upcast_ref()) else {
+                return glib::Propagation::Proceed;
+            };
+
+            let mut typed = typed.borrow_mut();
+            let now = Instant::now();
+            if typed.last_key.is_none_or(|last| now - last > RESET_AFTER) {
+                typed.text.clear();
+            }
+            if typed.text.is_empty() {
+                match c {
+                    '/' => {
+                        fm_window.filter_bar.open();
+                        return glib::Propagation::Stop;
+                    }
+                    // Space keeps its own meaning
+                    ' ' => return glib::Propagation::Proceed,
+                    _ => {}
+                }
+            }
+            typed.last_key = Some(now);
+            typed.text.extend(c.to_lowercase());
+            let text = typed.text.clone();
+            drop(typed);
+
+            jump(&fm_window, &text);
+            glib::Propagation::Stop
+        }
+    ));
+    // On the stack, so the keys typed in either view get here
+    if let Some(files_view) = files_panel::files_view(&fm_window.column_view) {
+        files_view.add_controller(keys);
+    }
+}
+
+/// Selects the first name starting with `typed`, which is lowercase. A
+/// letter typed over and over moves on to the next name starting with it.
+fn jump(fm_window: &FmWindow, typed: &str) {
+    let selection = &fm_window.files_selection;
+    let count = selection.n_items();
+    if count == 0 {
+        return;
+    }
+
+    let mut chars = typed.chars();
+    let first = chars.next().unwrap_or_default();
+    let repeated = typed.len() > first.len_utf8() && chars.all(|c| c == first);
+
+    let (prefix, start) = if repeated {
+        let selected = selection.selection();
+        let next = if selected.is_empty() { 0 } else { selected.minimum() + 1 };
+        (first.to_string(), next)
+    } else {
+        (typed.to_string(), 0)
+    };
+
+    let found = (0..count).map(|offset| (start + offset) % count).find(|&position| {
+        selection
+            .item(position)
+            .and_downcast::<FileItem>()
+            .is_some_and(|item| item.display_name().to_lowercase().starts_with(&prefix))
+    });
+    match found {
+        Some(position) => {
+            selection.select_item(position, true);
+            files_panel::scroll_to_position(&fm_window.column_view, position);
+        }
+        None => fm_window.column_view.error_bell(),
+    }
+}
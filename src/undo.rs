@@ -0,0 +1,68 @@
+//! Undo history for changes made to files from the window.
+
+use crate::{
+    batch_rename::{self, Renames},
+    file_operations,
+    fm_window::FmWindow,
+};
+use gtk4::{glib, prelude::*};
+
+/// How many changes can be undone.
+pub const MAX_UNDO_ENTRIES: usize = 50;
+
+pub enum UndoAction {
+    /// Files renamed, as `(old, new)` pairs.
+    Rename(Renames),
+}
+
+pub struct UndoEntry {
+    /// Shown in the progress panel while undoing, e.g. "Rename 3 items".
+    pub label: String,
+    pub action: UndoAction,
+}
+
+/// Reverts the most recent change of `fm_window`, if any.
+pub fn undo_last(fm_window: &FmWindow) {
+    let entry = fm_window.fmstate.borrow_mut().pop_undo();
+    let Some(entry) = entry else {
+        return;
+    };
+
+    match entry.action {
+        UndoAction::Rename(renames) => {
+            let reverse: Vec<_> =
+                renames.iter().map(|(old, new)| (new.clone(), old.clone())).collect();
+            let restored: Vec<_> = renames.iter().map(|(old, _)| old.clone()).collect();
+            let label = entry.label;
+
+            fm_window.operations.run(
+                &format!("Undo: {}", label),
+                move |context| batch_rename::rename_files(&reverse, context),
+                glib::clone!(
+                    #[strong]
+                    fm_window,
+                    move |result: Result<(), String>| {
+                        fm_window.reload();
+                        match result {
+                            Ok(()) => {
+                                fm_window.select_files(&restored);
+                            }
+                            Err(e) => {
+                                // Nothing changed, so the entry can be tried again
+                                fm_window.fmstate.borrow_mut().push_undo(UndoEntry {
+                                    label,
+                                    action: UndoAction::Rename(renames),
+                                });
+                                file_operations::show_errors(
+                                    fm_window.window.upcast_ref(),
+                                    "The rename could not be undone",
+                                    &[e],
+                                );
+                            }
+                        }
+                    }
+                ),
+            );
+        }
+    }
+}
@@ -0,0 +1,511 @@
+//! A small JSON reader/writer for AxFM's data files.
+//!
+//! It implements the full JSON grammar (including `\uXXXX` escapes and
+//! surrogate pairs) and reports the line and column of malformed input, so a
+//! damaged file can be explained to the user instead of silently ignored.
+
+use std::fmt;
+
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Members keep their order so files stay stable across saves.
+    Object(Vec<(String, JsonValue)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl JsonValue {
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the number if it is a non-negative integer.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonValue::Number(n) if *n >= 0.0 && n.fract() == 0.0 && *n <= u64::MAX as f64 => {
+                Some(*n as u64)
+            }
+            _ => None,
+        }
+    }
+
+    /// Serializes the value with two-space indentation.
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out.push('\n');
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, indent: usize) {
+        match self {
+            JsonValue::Null => out.push_str("null"),
+            JsonValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            JsonValue::Number(n) => {
+                if n.is_finite() {
+                    out.push_str(&n.to_string());
+                } else {
+                    out.push_str("null");
+                }
+            }
+            JsonValue::String(s) => write_string(out, s),
+            JsonValue::Array(items) => {
+                if items.is_empty() {
+                    out.push_str("[]");
+                    return;
+                }
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, indent + 1);
+                    item.write_pretty(out, indent + 1);
+                }
+                newline(out, indent);
+                out.push(']');
+            }
+            JsonValue::Object(members) => {
+                if members.is_empty() {
+                    out.push_str("{}");
+                    return;
+                }
+                out.push('{');
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, indent + 1);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write_pretty(out, indent + 1);
+                }
+                newline(out, indent);
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn newline(out: &mut String, indent: usize) {
+    out.push('\n');
+    for _ in 0..indent {
+        out.push_str("  ");
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Parses a complete JSON document.
+pub fn parse(input: &str) -> Result<JsonValue, JsonError> {
+    let mut parser = Parser { chars: input.chars().collect(), pos: 0 };
+
+    parser.skip_whitespace();
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespace();
+
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected data after the end of the document"));
+    }
+
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> JsonError {
+        let consumed = &self.chars[..self.pos.min(self.chars.len())];
+        let line = consumed.iter().filter(|c| **c == '\n').count() + 1;
+        let column = consumed.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        JsonError { line, column, message: message.to_string() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek();
+        if ch.is_some() {
+            self.pos += 1;
+        }
+        ch
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("document is nested too deeply"));
+        }
+
+        match self.peek() {
+            Some('{') => self.parse_object(depth),
+            Some('[') => self.parse_array(depth),
+            Some('"') => self.parse_string().map(JsonValue::String),
+            Some('t') => self.parse_literal("true", JsonValue::Bool(true)),
+            Some('f') => self.parse_literal("false", JsonValue::Bool(false)),
+            Some('n') => self.parse_literal("null", JsonValue::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of document")),
+        }
+    }
+
+    fn parse_literal(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, JsonError> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(&format!("invalid literal, expected '{}'", word)));
+            }
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, JsonError> {
+        let start = self.pos;
+
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+
+        match self.peek() {
+            Some('0') => self.pos += 1,
+            Some(c) if c.is_ascii_digit() => self.skip_digits(),
+            _ => return Err(self.error("invalid number")),
+        }
+
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("expected digits after the decimal point"));
+            }
+            self.skip_digits();
+        }
+
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("expected digits in the exponent"));
+            }
+            self.skip_digits();
+        }
+
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>().map(JsonValue::Number).map_err(|_| self.error("invalid number"))
+    }
+
+    fn skip_digits(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut value = String::new();
+
+        loop {
+            match self.next() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(value),
+                Some('\\') => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{08}',
+                        Some('f') => '\u{0c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    value.push(escaped);
+                }
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("control characters must be escaped in strings"));
+                }
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("invalid \\u escape"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    /// Decodes the part after `\u`, combining UTF-16 surrogate pairs.
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let first = self.parse_hex4()?;
+
+        let code = if (0xD800..0xDC00).contains(&first) {
+            if self.next() != Some('\\') || self.next() != Some('u') {
+                return Err(self.error("unpaired surrogate in \\u escape"));
+            }
+            let second = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&second) {
+                return Err(self.error("invalid low surrogate in \\u escape"));
+            }
+            0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+        } else if (0xDC00..0xE000).contains(&first) {
+            return Err(self.error("unpaired surrogate in \\u escape"));
+        } else {
+            first
+        };
+
+        char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(items));
+        }
+
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value(depth + 1)?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a member name"));
+            }
+            let key = self.parse_string()?;
+
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+
+            let value = self.parse_value(depth + 1)?;
+            members.push((key, value));
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> JsonValue {
+        JsonValue::String(s.to_string())
+    }
+
+    fn error_message(input: &str) -> String {
+        parse(input).unwrap_err().message
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(
+            parse(r#""quote \" slash \\ \/ \b\f\n\r\t end""#).unwrap(),
+            string("quote \" slash \\ / \u{08}\u{0c}\n\r\t end")
+        );
+        assert_eq!(parse(r#""caf\u00e9 \u00C9""#).unwrap(), string("café É"));
+        assert_eq!(parse("\"raw ünïcödé 📁\"").unwrap(), string("raw ünïcödé 📁"));
+
+        assert_eq!(error_message(r#""\x""#), "invalid escape sequence");
+        assert_eq!(error_message(r#""\u12G4""#), "invalid \\u escape");
+        assert_eq!(error_message("\"tab\there\""), "control characters must be escaped in strings");
+        assert_eq!(error_message(r#""open"#), "unterminated string");
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        assert_eq!(parse(r#""\ud83d\ude00""#).unwrap(), string("😀"));
+        assert_eq!(parse(r#""\uD834\uDD1E""#).unwrap(), string("𝄞"));
+
+        assert_eq!(error_message(r#""\ud83d""#), "unpaired surrogate in \\u escape");
+        assert_eq!(error_message(r#""\ud83dx""#), "unpaired surrogate in \\u escape");
+        assert_eq!(error_message(r#""\ude00""#), "unpaired surrogate in \\u escape");
+        assert_eq!(error_message(r#""\ud83d\u0041""#), "invalid low surrogate in \\u escape");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse("0").unwrap(), JsonValue::Number(0.0));
+        assert_eq!(parse("-0").unwrap(), JsonValue::Number(-0.0));
+        assert_eq!(parse("12.5").unwrap(), JsonValue::Number(12.5));
+        assert_eq!(parse("1.5e-3").unwrap(), JsonValue::Number(0.0015));
+        assert_eq!(parse("-2E+2").unwrap(), JsonValue::Number(-200.0));
+
+        for invalid in ["-", "+1", ".5", "1.", "1e", "1e+", "--1", "01", "0x10", "NaN", "1.2.3"] {
+            assert!(parse(invalid).is_err(), "{} was accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn trailing_garbage() {
+        assert_eq!(error_message("{} x"), "unexpected data after the end of the document");
+        assert_eq!(error_message("[1] ]"), "unexpected data after the end of the document");
+        assert_eq!(error_message("nullx"), "unexpected data after the end of the document");
+        assert_eq!(error_message("[1,]"), "unexpected character");
+        assert_eq!(error_message(r#"{"a": 1,}"#), "expected a member name");
+        assert_eq!(error_message(""), "unexpected end of document");
+        assert!(parse(" \n\t[1]\r\n ").is_ok());
+    }
+
+    #[test]
+    fn errors_tell_where() {
+        let error = parse("{\n  \"a\": tru\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 11));
+        assert_eq!(error.to_string(), "line 2, column 11: invalid literal, expected 'true'");
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(error_message(&nested(MAX_DEPTH + 2)), "document is nested too deeply");
+        // Refused long before the stack runs out
+        assert!(parse(&nested(100_000)).is_err());
+    }
+
+    #[test]
+    fn pretty_round_trip() {
+        let value = JsonValue::Object(vec![
+            ("name".to_string(), string("Quote \" \\ \u{01} \n ünïcödé 😀")),
+            (
+                "numbers".to_string(),
+                JsonValue::Array(vec![
+                    JsonValue::Number(0.1),
+                    JsonValue::Number(-3.0),
+                    JsonValue::Number(1e21),
+                    JsonValue::Number(9007199254740992.0),
+                ]),
+            ),
+            ("flags".to_string(), JsonValue::Array(vec![JsonValue::Bool(true), JsonValue::Null])),
+            ("empty".to_string(), JsonValue::Object(Vec::new())),
+            ("none".to_string(), JsonValue::Array(Vec::new())),
+        ]);
+
+        let text = value.to_pretty_string();
+        assert_eq!(parse(&text).unwrap(), value);
+        assert!(text.contains("\\u0001"));
+
+        let small = JsonValue::Object(vec![(
+            "a".to_string(),
+            JsonValue::Array(vec![JsonValue::Number(1.0), JsonValue::Number(2.0)]),
+        )]);
+        assert_eq!(small.to_pretty_string(), "{\n  \"a\": [\n    1,\n    2\n  ]\n}\n");
+    }
+}
@@ -1,3 +1,4 @@
+pub mod json;
 mod settings;
 mod widget_data;
 
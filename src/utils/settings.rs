@@ -1,9 +1,33 @@
+//! Persistent file manager settings.
+//!
+//! Settings live in `$XDG_CONFIG_HOME/axfm/settings.conf` as a glib key file.
+//! Missing keys fall back to their defaults, so older files keep loading after
+//! new options are added. Values that cannot be parsed are reported instead of
+//! silently replaced.
+
+use gtk4::{gio, glib, prelude::*};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+const SETTINGS_FILE: &str = "settings.conf";
+
+const GROUP_VIEW: &str = "View";
+const GROUP_WINDOW: &str = "Window";
+const GROUP_GENERAL: &str = "General";
+const GROUP_BEHAVIOR: &str = "Behavior";
+const GROUP_SEARCH: &str = "Search";
+
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FileView {
     IconView,
     ListView,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortColumn {
     Name,
     Size,
@@ -11,28 +35,746 @@ pub enum SortColumn {
     Type,
 }
 
+/// A column of the file list. The name always comes first, the others can
+/// be hidden and moved around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListColumn {
+    Size,
+    Modified,
+    Type,
+    Permissions,
+    Owner,
+    Group,
+    Created,
+    Accessed,
+    Extension,
+    MimeType,
+    LinkTarget,
+    Inode,
+    Links,
+}
+
+/// A column shown, and how wide.
 #[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColumnLayout {
+    pub column: ListColumn,
+    pub width: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortOrder {
     Ascending,
     Descending,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeUnits {
+    /// Powers of 1024 (KiB, MiB, ...)
+    Iec,
+    /// Powers of 1000 (kB, MB, ...)
+    Si,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClickActivation {
+    Single,
+    Double,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct FMSettings {
     pub show_hidden: bool,
     pub file_view: FileView,
     pub sort_column: SortColumn,
     pub sort_order: SortOrder,
     pub folders_first: bool,
+    /// The columns shown after the name, in order.
+    pub columns: Vec<ColumnLayout>,
+    pub window_width: i32,
+    pub window_height: i32,
+    pub pane_position: i32,
+    pub show_preview: bool,
+    /// Width of the preview pane.
+    pub preview_width: i32,
+    pub terminal_command: String,
+    pub date_format: String,
+    pub size_units: SizeUnits,
+    pub click_activation: ClickActivation,
+    pub confirm_trash: bool,
+    pub confirm_delete: bool,
+    /// Keep the GTK bookmarks file (used by file choosers) in sync with ours.
+    pub sync_gtk_bookmarks: bool,
+    /// Keep an index of file names for instant search.
+    pub index_files: bool,
+    /// Folders covered by the index.
+    pub index_roots: Vec<String>,
+    /// Names (wildcards allowed) or absolute paths left out of the index.
+    pub index_exclusions: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Read { path: PathBuf, source: glib::Error },
+    Invalid { path: PathBuf, group: String, key: String, value: String },
+    Write { path: PathBuf, message: String },
+    UnknownKey(String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Read { path, source } => {
+                write!(f, "Could not read settings from {}: {}", path.display(), source)
+            }
+            SettingsError::Invalid { path, group, key, value } => write!(
+                f,
+                "Invalid value '{}' for '{}' in section [{}] of {}",
+                value,
+                key,
+                group,
+                path.display()
+            ),
+            SettingsError::Write { path, message } => {
+                write!(f, "Could not save settings to {}: {}", path.display(), message)
+            }
+            SettingsError::UnknownKey(key) => write!(f, "Unknown setting '{}'", key),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl FileView {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FileView::IconView => "icons",
+            FileView::ListView => "list",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "icons" => Some(FileView::IconView),
+            "list" => Some(FileView::ListView),
+            _ => None,
+        }
+    }
+}
+
+impl SortColumn {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortColumn::Name => "name",
+            SortColumn::Size => "size",
+            SortColumn::ModifiedDate => "modified",
+            SortColumn::Type => "type",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "name" => Some(SortColumn::Name),
+            "size" => Some(SortColumn::Size),
+            "modified" => Some(SortColumn::ModifiedDate),
+            "type" => Some(SortColumn::Type),
+            _ => None,
+        }
+    }
+}
+
+impl ListColumn {
+    pub const ALL: [ListColumn; 13] = [
+        ListColumn::Size,
+        ListColumn::Modified,
+        ListColumn::Type,
+        ListColumn::Permissions,
+        ListColumn::Owner,
+        ListColumn::Group,
+        ListColumn::Created,
+        ListColumn::Accessed,
+        ListColumn::Extension,
+        ListColumn::MimeType,
+        ListColumn::LinkTarget,
+        ListColumn::Inode,
+        ListColumn::Links,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ListColumn::Size => "Size",
+            ListColumn::Modified => "Modified",
+            ListColumn::Type => "Type",
+            ListColumn::Permissions => "Permissions",
+            ListColumn::Owner => "Owner",
+            ListColumn::Group => "Group",
+            ListColumn::Created => "Created",
+            ListColumn::Accessed => "Accessed",
+            ListColumn::Extension => "Extension",
+            ListColumn::MimeType => "MIME Type",
+            ListColumn::LinkTarget => "Link Target",
+            ListColumn::Inode => "Inode",
+            ListColumn::Links => "Links",
+        }
+    }
+
+    pub fn default_width(&self) -> i32 {
+        match self {
+            ListColumn::Size | ListColumn::Permissions => 100,
+            ListColumn::Modified | ListColumn::Created | ListColumn::Accessed => 150,
+            ListColumn::Type | ListColumn::MimeType => 120,
+            ListColumn::LinkTarget => 200,
+            ListColumn::Owner | ListColumn::Group | ListColumn::Inode => 90,
+            ListColumn::Extension | ListColumn::Links => 70,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListColumn::Size => "size",
+            ListColumn::Modified => "modified",
+            ListColumn::Type => "type",
+            ListColumn::Permissions => "permissions",
+            ListColumn::Owner => "owner",
+            ListColumn::Group => "group",
+            ListColumn::Created => "created",
+            ListColumn::Accessed => "accessed",
+            ListColumn::Extension => "extension",
+            ListColumn::MimeType => "mime-type",
+            ListColumn::LinkTarget => "link-target",
+            ListColumn::Inode => "inode",
+            ListColumn::Links => "links",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        ListColumn::ALL.into_iter().find(|column| column.as_str() == value)
+    }
+}
+
+impl SortOrder {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Ascending => "ascending",
+            SortOrder::Descending => "descending",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ascending" => Some(SortOrder::Ascending),
+            "descending" => Some(SortOrder::Descending),
+            _ => None,
+        }
+    }
+}
+
+impl SizeUnits {
+    pub fn base(&self) -> f64 {
+        match self {
+            SizeUnits::Iec => 1024.0,
+            SizeUnits::Si => 1000.0,
+        }
+    }
+
+    /// Unit suffixes from bytes up to terabytes.
+    pub fn suffixes(&self) -> [&'static str; 5] {
+        match self {
+            SizeUnits::Iec => ["B", "KiB", "MiB", "GiB", "TiB"],
+            SizeUnits::Si => ["B", "kB", "MB", "GB", "TB"],
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SizeUnits::Iec => "iec",
+            SizeUnits::Si => "si",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "iec" => Some(SizeUnits::Iec),
+            "si" => Some(SizeUnits::Si),
+            _ => None,
+        }
+    }
+}
+
+impl ClickActivation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ClickActivation::Single => "single",
+            ClickActivation::Double => "double",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "single" => Some(ClickActivation::Single),
+            "double" => Some(ClickActivation::Double),
+            _ => None,
+        }
+    }
 }
 
 impl FMSettings {
     pub fn new() -> Self {
         Self {
             show_hidden: false,
-            file_view: FileView::IconView,
+            file_view: FileView::ListView,
             sort_column: SortColumn::Name,
             sort_order: SortOrder::Ascending,
             folders_first: true,
+            columns: [ListColumn::Size, ListColumn::Modified, ListColumn::Type]
+                .map(|column| ColumnLayout { column, width: column.default_width() })
+                .to_vec(),
+            window_width: 800,
+            window_height: 500,
+            pane_position: 200,
+            show_preview: false,
+            preview_width: 300,
+            terminal_command: std::env::var("TERMINAL").unwrap_or_else(|_| "xterm".to_string()),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            size_units: SizeUnits::Iec,
+            click_activation: ClickActivation::Double,
+            confirm_trash: false,
+            confirm_delete: true,
+            sync_gtk_bookmarks: true,
+            index_files: false,
+            index_roots: vec![glib::home_dir().display().to_string()],
+            index_exclusions: [".git", "node_modules", ".cache", "__pycache__"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+
+    pub fn config_dir() -> PathBuf {
+        glib::user_config_dir().join("axfm")
+    }
+
+    pub fn config_path() -> PathBuf {
+        Self::config_dir().join(SETTINGS_FILE)
+    }
+
+    /// Loads the settings file, returning the defaults when it does not exist yet.
+    pub fn load() -> Result<Self, SettingsError> {
+        let path = Self::config_path();
+
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+
+        let key_file = glib::KeyFile::new();
+        key_file
+            .load_from_file(&path, glib::KeyFileFlags::NONE)
+            .map_err(|source| SettingsError::Read { path: path.clone(), source })?;
+
+        Self::from_key_file(&key_file, &path)
+    }
+
+    fn from_key_file(key_file: &glib::KeyFile, path: &Path) -> Result<Self, SettingsError> {
+        let mut settings = Self::new();
+
+        let reader = KeyReader { key_file, path };
+
+        if let Some(value) = reader.parsed(GROUP_VIEW, "show-hidden", parse_bool)? {
+            settings.show_hidden = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_VIEW, "view-mode", FileView::parse)? {
+            settings.file_view = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_VIEW, "sort-column", SortColumn::parse)? {
+            settings.sort_column = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_VIEW, "sort-order", SortOrder::parse)? {
+            settings.sort_order = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_VIEW, "folders-first", parse_bool)? {
+            settings.folders_first = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_VIEW, "columns", parse_columns)? {
+            settings.columns = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_WINDOW, "width", parse_dimension)? {
+            settings.window_width = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_WINDOW, "height", parse_dimension)? {
+            settings.window_height = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_WINDOW, "pane-position", parse_dimension)? {
+            settings.pane_position = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_VIEW, "show-preview", parse_bool)? {
+            settings.show_preview = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_WINDOW, "preview-width", parse_dimension)? {
+            settings.preview_width = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_VIEW, "date-format", parse_date_format)? {
+            settings.date_format = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_VIEW, "size-units", SizeUnits::parse)? {
+            settings.size_units = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_GENERAL, "terminal", parse_command)? {
+            settings.terminal_command = value;
+        }
+        if let Some(value) =
+            reader.parsed(GROUP_BEHAVIOR, "click-activation", ClickActivation::parse)?
+        {
+            settings.click_activation = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_BEHAVIOR, "confirm-trash", parse_bool)? {
+            settings.confirm_trash = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_BEHAVIOR, "confirm-delete", parse_bool)? {
+            settings.confirm_delete = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_BEHAVIOR, "sync-gtk-bookmarks", parse_bool)? {
+            settings.sync_gtk_bookmarks = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_SEARCH, "index-files", parse_bool)? {
+            settings.index_files = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_SEARCH, "index-roots", parse_list)? {
+            settings.index_roots = value;
+        }
+        if let Some(value) = reader.parsed(GROUP_SEARCH, "index-exclusions", parse_list)? {
+            settings.index_exclusions = value;
+        }
+
+        Ok(settings)
+    }
+
+    fn to_key_file(&self) -> glib::KeyFile {
+        let key_file = glib::KeyFile::new();
+
+        key_file.set_boolean(GROUP_VIEW, "show-hidden", self.show_hidden);
+        key_file.set_string(GROUP_VIEW, "view-mode", self.file_view.as_str());
+        key_file.set_string(GROUP_VIEW, "sort-column", self.sort_column.as_str());
+        key_file.set_string(GROUP_VIEW, "sort-order", self.sort_order.as_str());
+        key_file.set_boolean(GROUP_VIEW, "folders-first", self.folders_first);
+        key_file.set_string(GROUP_VIEW, "columns", &join_columns(&self.columns));
+        key_file.set_integer(GROUP_WINDOW, "width", self.window_width);
+        key_file.set_integer(GROUP_WINDOW, "height", self.window_height);
+        key_file.set_integer(GROUP_WINDOW, "pane-position", self.pane_position);
+        key_file.set_boolean(GROUP_VIEW, "show-preview", self.show_preview);
+        key_file.set_integer(GROUP_WINDOW, "preview-width", self.preview_width);
+        key_file.set_string(GROUP_VIEW, "date-format", &self.date_format);
+        key_file.set_string(GROUP_VIEW, "size-units", self.size_units.as_str());
+        key_file.set_string(GROUP_GENERAL, "terminal", &self.terminal_command);
+        key_file.set_string(GROUP_BEHAVIOR, "click-activation", self.click_activation.as_str());
+        key_file.set_boolean(GROUP_BEHAVIOR, "confirm-trash", self.confirm_trash);
+        key_file.set_boolean(GROUP_BEHAVIOR, "confirm-delete", self.confirm_delete);
+        key_file.set_boolean(GROUP_BEHAVIOR, "sync-gtk-bookmarks", self.sync_gtk_bookmarks);
+        key_file.set_boolean(GROUP_SEARCH, "index-files", self.index_files);
+        key_file.set_string(GROUP_SEARCH, "index-roots", &join_list(&self.index_roots));
+        key_file.set_string(GROUP_SEARCH, "index-exclusions", &join_list(&self.index_exclusions));
+
+        key_file
+    }
+
+    /// Lists every setting as `(key, value)` pairs, with keys written as
+    /// `section.name` (e.g. `view.show-hidden`).
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let key_file = self.to_key_file();
+        let mut entries = Vec::new();
+
+        for group in key_file.groups().iter() {
+            let Ok(keys) = key_file.keys(group.as_str()) else {
+                continue;
+            };
+            for key in keys.iter() {
+                if let Ok(value) = key_file.string(group.as_str(), key.as_str()) {
+                    let name = format!("{}.{}", group.as_str().to_lowercase(), key.as_str());
+                    entries.push((name, value.to_string()));
+                }
+            }
+        }
+
+        entries
+    }
+
+    pub fn value_of(&self, key: &str) -> Result<String, SettingsError> {
+        self.entries()
+            .into_iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+            .ok_or_else(|| SettingsError::UnknownKey(key.to_string()))
+    }
+
+    /// Returns a copy of the settings with `key` set to `value`, validated
+    /// the same way as when loading the file.
+    pub fn with_value(&self, key: &str, value: &str) -> Result<FMSettings, SettingsError> {
+        let unknown = || SettingsError::UnknownKey(key.to_string());
+
+        let (section, name) = key.split_once('.').ok_or_else(unknown)?;
+        let key_file = self.to_key_file();
+        let group = key_file
+            .groups()
+            .iter()
+            .map(|g| g.as_str().to_string())
+            .find(|g| g.eq_ignore_ascii_case(section))
+            .ok_or_else(unknown)?;
+
+        if !key_file.has_key(&group, name).unwrap_or(false) {
+            return Err(unknown());
+        }
+
+        key_file.set_string(&group, name, value);
+        Self::from_key_file(&key_file, &Self::config_path())
+    }
+
+    /// Writes the settings atomically: the data goes to a temporary file that
+    /// then replaces the old one, so a crash never leaves a truncated config.
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let path = Self::config_path();
+
+        std::fs::create_dir_all(Self::config_dir())
+            .map_err(|e| SettingsError::Write { path: path.clone(), message: e.to_string() })?;
+
+        let data = self.to_key_file().to_data();
+        glib::file_set_contents(&path, data.as_bytes())
+            .map_err(|e| SettingsError::Write { path: path.clone(), message: e.to_string() })
+    }
+}
+
+struct KeyReader<'a> {
+    key_file: &'a glib::KeyFile,
+    path: &'a Path,
+}
+
+impl KeyReader<'_> {
+    /// Returns `None` for absent keys and an error for values `parse` rejects.
+    fn parsed<T>(
+        &self,
+        group: &str,
+        key: &str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Result<Option<T>, SettingsError> {
+        let Ok(raw) = self.key_file.string(group, key) else {
+            return Ok(None);
+        };
+
+        let raw = raw.trim();
+        parse(raw).map(Some).ok_or_else(|| SettingsError::Invalid {
+            path: self.path.to_path_buf(),
+            group: group.to_string(),
+            key: key.to_string(),
+            value: raw.to_string(),
+        })
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_dimension(value: &str) -> Option<i32> {
+    value.parse::<i32>().ok().filter(|v| *v > 0)
+}
+
+/// Reads a `;`-separated list, ignoring empty items.
+pub fn parse_list(value: &str) -> Option<Vec<String>> {
+    Some(
+        value.split(';').map(str::trim).filter(|item| !item.is_empty()).map(String::from).collect(),
+    )
+}
+
+pub fn join_list(items: &[String]) -> String {
+    items.join(";")
+}
+
+/// Reads columns written as `name:width`, like `size:100;modified:150`.
+/// The width may be left out.
+fn parse_columns(value: &str) -> Option<Vec<ColumnLayout>> {
+    let mut columns: Vec<ColumnLayout> = Vec::new();
+    for item in parse_list(value)? {
+        let (name, width) = match item.split_once(':') {
+            Some((name, width)) => (name, Some(parse_dimension(width.trim())?)),
+            None => (item.as_str(), None),
+        };
+        let column = ListColumn::parse(name.trim())?;
+        if columns.iter().any(|layout| layout.column == column) {
+            return None;
+        }
+        columns.push(ColumnLayout { column, width: width.unwrap_or(column.default_width()) });
+    }
+    Some(columns)
+}
+
+fn join_columns(columns: &[ColumnLayout]) -> String {
+    let items: Vec<String> = columns
+        .iter()
+        .map(|layout| format!("{}:{}", layout.column.as_str(), layout.width))
+        .collect();
+    join_list(&items)
+}
+
+fn parse_command(value: &str) -> Option<String> {
+    if value.is_empty() { None } else { Some(value.to_string()) }
+}
+
+/// Accepts any strftime-style format glib can render.
+pub fn parse_date_format(value: &str) -> Option<String> {
+    let now = glib::DateTime::now_local().ok()?;
+    match now.format(value) {
+        Ok(_) if !value.is_empty() => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Watches the settings file and calls `on_change` with the new settings
+/// whenever it is modified, including by an external editor.
+///
+/// The returned monitor must be kept alive for the watch to stay active.
+pub fn watch_settings<F>(on_change: F) -> Option<gio::FileMonitor>
+where
+    F: Fn(Result<FMSettings, SettingsError>) + 'static,
+{
+    if let Err(e) = std::fs::create_dir_all(FMSettings::config_dir()) {
+        eprintln!("Failed to create config directory: {}", e);
+        return None;
+    }
+
+    let file = gio::File::for_path(FMSettings::config_path());
+    let monitor = match file.monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            eprintln!("Failed to watch settings file: {}", e);
+            return None;
+        }
+    };
+
+    monitor.connect_changed(move |_, _, _, event| {
+        if matches!(event, gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::Created)
+        {
+            on_change(FMSettings::load());
+        }
+    });
+
+    Some(monitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_data(data: &str) -> Result<FMSettings, SettingsError> {
+        let key_file = glib::KeyFile::new();
+        key_file.load_from_data(data, glib::KeyFileFlags::NONE).unwrap();
+        FMSettings::from_key_file(&key_file, Path::new("settings.conf"))
+    }
+
+    fn sample() -> FMSettings {
+        FMSettings {
+            show_hidden: true,
+            file_view: FileView::IconView,
+            sort_column: SortColumn::ModifiedDate,
+            sort_order: SortOrder::Descending,
+            columns: vec![
+                ColumnLayout { column: ListColumn::Permissions, width: 80 },
+                ColumnLayout { column: ListColumn::Size, width: 120 },
+            ],
+            window_width: 1024,
+            terminal_command: "foot -e".to_string(),
+            date_format: "%d/%m/%Y".to_string(),
+            size_units: SizeUnits::Si,
+            click_activation: ClickActivation::Single,
+            index_roots: vec!["/home/me".to_string(), "/srv/shared files".to_string()],
+            index_exclusions: Vec::new(),
+            ..FMSettings::new()
+        }
+    }
+
+    fn assert_invalid(result: Result<FMSettings, SettingsError>, group: &str, key: &str) {
+        match result {
+            Err(SettingsError::Invalid { group: g, key: k, .. }) => {
+                assert_eq!((g.as_str(), k.as_str()), (group, key));
+            }
+            other => panic!("expected {}.{} to be rejected, got {:?}", group, key, other),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = sample().to_key_file().to_data();
+        assert_eq!(from_data(&data).unwrap(), sample());
+    }
+
+    #[test]
+    fn missing_keys_keep_their_defaults() {
+        assert_eq!(from_data("").unwrap(), FMSettings::new());
+
+        let settings = from_data("[View]\nshow-hidden=true\n[Unknown]\nkey=value\n").unwrap();
+        assert_eq!(settings, FMSettings { show_hidden: true, ..FMSettings::new() });
+    }
+
+    #[test]
+    fn values_are_trimmed() {
+        let settings = from_data("[View]\nsort-order= descending \ncolumns=size ; type:90\n");
+        let settings = settings.unwrap();
+        assert_eq!(settings.sort_order, SortOrder::Descending);
+        assert_eq!(
+            settings.columns,
+            [
+                ColumnLayout { column: ListColumn::Size, width: ListColumn::Size.default_width() },
+                ColumnLayout { column: ListColumn::Type, width: 90 },
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_values_are_reported() {
+        assert_invalid(from_data("[View]\nshow-hidden=yes\n"), GROUP_VIEW, "show-hidden");
+        assert_invalid(from_data("[View]\nview-mode=grid\n"), GROUP_VIEW, "view-mode");
+        assert_invalid(from_data("[View]\ncolumns=size;size\n"), GROUP_VIEW, "columns");
+        assert_invalid(from_data("[View]\ncolumns=size:wide\n"), GROUP_VIEW, "columns");
+        assert_invalid(from_data("[View]\ncolumns=colour\n"), GROUP_VIEW, "columns");
+        assert_invalid(from_data("[Window]\nwidth=-5\n"), GROUP_WINDOW, "width");
+        assert_invalid(from_data("[Window]\nheight=tall\n"), GROUP_WINDOW, "height");
+        assert_invalid(from_data("[General]\nterminal=\n"), GROUP_GENERAL, "terminal");
+        assert_invalid(
+            from_data("[Behavior]\nclick-activation=triple\n"),
+            GROUP_BEHAVIOR,
+            "click-activation",
+        );
+    }
+
+    #[test]
+    fn entries_use_lowercase_sections() {
+        let settings = sample();
+        let entries = settings.entries();
+
+        assert!(entries.contains(&("view.show-hidden".to_string(), "true".to_string())));
+        assert!(entries.contains(&("window.width".to_string(), "1024".to_string())));
+        assert!(
+            entries.contains(&("view.columns".to_string(), "permissions:80;size:120".to_string()))
+        );
+        assert_eq!(settings.value_of("general.terminal").unwrap(), "foot -e");
+        assert!(matches!(settings.value_of("general.shell"), Err(SettingsError::UnknownKey(_))));
+    }
+
+    #[test]
+    fn values_are_set_and_validated() {
+        let settings = FMSettings::new();
+
+        let changed = settings.with_value("view.sort-column", "size").unwrap();
+        assert_eq!(changed, FMSettings { sort_column: SortColumn::Size, ..FMSettings::new() });
+        let changed = settings.with_value("Window.width", "640").unwrap();
+        assert_eq!(changed.window_width, 640);
+
+        assert_invalid(
+            settings.with_value("view.sort-column", "colour"),
+            GROUP_VIEW,
+            "sort-column",
+        );
+        for key in ["view.nothing", "nowhere.width", "width", ""] {
+            assert!(matches!(settings.with_value(key, "1"), Err(SettingsError::UnknownKey(_))));
         }
     }
 }
@@ -0,0 +1,161 @@
+//! Calls the `org.freedesktop.FileManager1` interface of a running axfm on a
+//! private session bus.
+//!
+//! Needs `dbus-daemon` and a display to open windows on; the test is skipped
+//! when either is missing.
+
+use gtk4::{gio, glib, prelude::*};
+use std::{
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+const BUS_NAME: &str = "org.freedesktop.FileManager1";
+const OBJECT_PATH: &str = "/org/freedesktop/FileManager1";
+
+/// Kills the process when the test ends, passed or not.
+struct Killed(Child);
+
+impl Drop for Killed {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("axfm-dbus-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Starts a bus of its own, returning it with its address.
+fn start_bus() -> Option<(Killed, String)> {
+    let mut daemon = Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let stdout = daemon.stdout.take()?;
+    let daemon = Killed(daemon);
+
+    let mut address = String::new();
+    BufReader::new(stdout).read_line(&mut address).ok()?;
+    let address = address.trim().to_string();
+    (!address.is_empty()).then_some((daemon, address))
+}
+
+fn name_has_owner(connection: &gio::DBusConnection, name: &str) -> bool {
+    connection
+        .call_sync(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameHasOwner",
+            Some(&(name,).to_variant()),
+            Some(glib::VariantTy::new("(b)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+            gio::Cancellable::NONE,
+        )
+        .ok()
+        .and_then(|reply| reply.get::<(bool,)>())
+        .is_some_and(|(owned,)| owned)
+}
+
+fn call(
+    connection: &gio::DBusConnection,
+    method: &str,
+    uris: &[String],
+) -> Result<glib::Variant, glib::Error> {
+    connection.call_sync(
+        Some(BUS_NAME),
+        OBJECT_PATH,
+        BUS_NAME,
+        method,
+        Some(&(uris.to_vec(), "").to_variant()),
+        None,
+        gio::DBusCallFlags::NONE,
+        10_000,
+        gio::Cancellable::NONE,
+    )
+}
+
+#[test]
+fn file_manager_interface_answers() {
+    if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        eprintln!("No display, skipping");
+        return;
+    }
+    let Some((_bus, address)) = start_bus() else {
+        eprintln!("dbus-daemon is not available, skipping");
+        return;
+    };
+
+    // Settings and bookmarks go to a home of its own
+    let home = temp_dir("home");
+    let folder = home.join("folder");
+    std::fs::create_dir_all(&folder).unwrap();
+    let file = folder.join("file.txt");
+    std::fs::write(&file, "text").unwrap();
+
+    let _app = Killed(
+        Command::new(env!("CARGO_BIN_EXE_axfm"))
+            .env("DBUS_SESSION_BUS_ADDRESS", &address)
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .env("XDG_CACHE_HOME", home.join(".cache"))
+            .spawn()
+            .expect("axfm could not be started"),
+    );
+
+    let connection = gio::DBusConnection::for_address_sync(
+        &address,
+        gio::DBusConnectionFlags::AUTHENTICATION_CLIENT
+            | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+        None,
+        gio::Cancellable::NONE,
+    )
+    .expect("the private bus could not be reached");
+
+    let started = Instant::now();
+    while !name_has_owner(&connection, BUS_NAME) {
+        assert!(started.elapsed() < Duration::from_secs(20), "{} was never owned", BUS_NAME);
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let folder_uri = gio::File::for_path(&folder).uri().to_string();
+    let file_uri = gio::File::for_path(&file).uri().to_string();
+    for (method, uris) in [
+        ("ShowFolders", vec![folder_uri]),
+        ("ShowItems", vec![file_uri.clone()]),
+        ("ShowItemProperties", vec![file_uri]),
+    ] {
+        let reply =
+            call(&connection, method, &uris).unwrap_or_else(|e| panic!("{} failed: {}", method, e));
+        assert_eq!(reply.type_().as_str(), "()", "{} replied {}", method, reply);
+    }
+
+    // Wrong arguments are turned down rather than ignored
+    let error = connection
+        .call_sync(
+            Some(BUS_NAME),
+            OBJECT_PATH,
+            BUS_NAME,
+            "ShowFolders",
+            Some(&("not a list",).to_variant()),
+            None,
+            gio::DBusCallFlags::NONE,
+            10_000,
+            gio::Cancellable::NONE,
+        )
+        .unwrap_err();
+    assert!(error.matches(gio::DBusError::InvalidArgs), "{}", error);
+
+    let _ = std::fs::remove_dir_all(&home);
+}